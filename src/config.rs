@@ -1,28 +1,185 @@
 use std::{
-    convert::TryFrom, fmt::Display, fs::OpenOptions, io::Read as _, path::PathBuf, str::FromStr,
+    convert::TryFrom,
+    fmt::Display,
+    fs::OpenOptions,
+    io::Read as _,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
 };
 
+use ckb_types::H256;
+use tempfile::TempDir;
+
 use crate::{
     error::{Error, Result},
-    fuzzer::Storage,
-    types::{MetaData, RunEnv},
-    utils,
+    fuzzer::{MockedStore, Storage},
+    types::{
+        ChainFormat, ExportFormat, GraphDirection, GraphFormat, MatrixSpec, MetaData,
+        PowEngineKind, Problems, Profile, RunEnv, Script,
+    },
+    utils::{self, lock::DataDirLock},
 };
 
+// The directory in the data directory to store the snapshots.
+const SNAPSHOTS_DIR: &str = "snapshots";
+
+// The directories which are copied into the ephemeral data directory and the data directories of
+// the parallel instances, the snapshots are not.
+const EPHEMERAL_DIRS: &[&str] = &["chain", "storage", "tx_pool", "network"];
+// The shared memory on Linux, the ephemeral data directory is put in it if it exists.
+const SHARED_MEMORY_DIR: &str = "/dev/shm";
+// The directory in the data directory to store the data directories of the parallel instances.
+const INSTANCES_DIR: &str = "instances";
+
 pub(crate) enum AppConfig {
     Init(InitConfig),
     Run(RunConfig),
+    Parallel(ParallelConfig),
+    Report(ReportConfig),
+    History(HistoryConfig),
+    Inspect(InspectConfig),
+    Verify(VerifyConfig),
+    Snapshot(SnapshotConfig),
+    Restore(RestoreConfig),
+    Clean(CleanConfig),
+    Prune(PruneConfig),
+    Graph(GraphConfig),
+    Export(ExportConfig),
+    DumpChain(DumpChainConfig),
+    NewConfig(NewConfigConfig),
+    Matrix(MatrixConfig),
+}
+
+pub struct InitConfig {
+    pub data_dir: PathBuf,
+    pub storage: Storage,
+    pub meta_data: MetaData,
+    // Import the chain from a CKB node instead of building the genesis block.
+    pub from_chain: Option<ChainSource>,
+    // Held until the config is dropped, none if the data directory is not shared.
+    pub lock: Option<DataDirLock>,
+}
+
+// The chain of a CKB node which is imported when initializing.
+pub struct ChainSource {
+    // The data directory of the node, the store is in its "db" directory.
+    pub data_dir: PathBuf,
+    // Import the main chain up to the height, or up to the tip.
+    pub to_height: Option<u64>,
 }
 
-pub(crate) struct InitConfig {
+pub struct RunConfig {
+    pub data_dir: PathBuf,
+    pub storage: Storage,
+    pub run_env: RunEnv,
+    pub script: Option<Script>,
+    pub metrics_listen: Option<SocketAddr>,
+    pub resume: bool,
+    pub baselines: Option<PathBuf>,
+    // Held until the config is dropped, none if the data directory is not shared.
+    pub lock: Option<DataDirLock>,
+    // The temporary copy of the data directory which is used instead, it's removed when the
+    // config is dropped.
+    pub ephemeral: Option<TempDir>,
+}
+
+// Run the instances in their own data directories concurrently, in one process.
+pub(crate) struct ParallelConfig {
     pub(crate) data_dir: PathBuf,
+    pub(crate) run_env: RunEnv,
+    pub(crate) script: Option<Script>,
+    pub(crate) metrics_listen: Option<SocketAddr>,
+    pub(crate) resume: bool,
+    pub(crate) instance_dirs: Vec<PathBuf>,
+    // The data directories of the instances are in the data directory, so they are locked too.
+    pub(crate) lock: DataDirLock,
+}
+
+pub(crate) struct ReportConfig {
     pub(crate) storage: Storage,
-    pub(crate) meta_data: MetaData,
+    pub(crate) output: Option<PathBuf>,
+}
+
+pub(crate) struct HistoryConfig {
+    pub(crate) storage: Storage,
+    pub(crate) run_id: Option<u32>,
+}
+
+pub(crate) struct InspectConfig {
+    pub(crate) storage: Storage,
+    pub(crate) stats: bool,
+    pub(crate) list: bool,
+    pub(crate) tx_hash: Option<H256>,
+    pub(crate) cells: bool,
+}
+
+pub(crate) struct VerifyConfig {
+    pub(crate) data_dir: PathBuf,
+    pub(crate) storage: Storage,
+    pub(crate) store: MockedStore,
 }
 
-pub(crate) struct RunConfig {
+pub(crate) struct SnapshotConfig {
     pub(crate) data_dir: PathBuf,
     pub(crate) storage: Storage,
+    pub(crate) store: MockedStore,
+    pub(crate) snapshot_dir: PathBuf,
+}
+
+pub(crate) struct RestoreConfig {
+    pub(crate) data_dir: PathBuf,
+    pub(crate) snapshot_dir: PathBuf,
+    pub(crate) lock: DataDirLock,
+}
+
+pub(crate) struct CleanConfig {
+    pub(crate) data_dir: PathBuf,
+    pub(crate) lock: DataDirLock,
+}
+
+pub(crate) struct PruneConfig {
+    pub(crate) storage: Storage,
+    pub(crate) lock: DataDirLock,
+}
+
+pub(crate) struct GraphConfig {
+    pub(crate) storage: Storage,
+    pub(crate) lock: DataDirLock,
+    // Export all transactions if it's not set.
+    pub(crate) tx_hash: Option<H256>,
+    pub(crate) direction: GraphDirection,
+    pub(crate) depth: Option<usize>,
+    pub(crate) format: GraphFormat,
+    // Write into the standard output if it's not set.
+    pub(crate) output: Option<PathBuf>,
+}
+
+pub(crate) struct ExportConfig {
+    pub(crate) storage: Storage,
+    pub(crate) store: MockedStore,
+    pub(crate) output_dir: PathBuf,
+    pub(crate) format: ExportFormat,
+}
+
+pub(crate) struct DumpChainConfig {
+    pub(crate) store: MockedStore,
+    // At least one of the output and the RPC address is set.
+    pub(crate) output: Option<PathBuf>,
+    pub(crate) format: ChainFormat,
+    pub(crate) rpc: Option<SocketAddr>,
+}
+
+pub(crate) struct NewConfigConfig {
+    pub(crate) output_dir: PathBuf,
+    pub(crate) profile: Profile,
+}
+
+pub(crate) struct MatrixConfig {
+    pub(crate) output_dir: PathBuf,
+    pub(crate) spec: MatrixSpec,
+    // The base configs, which are overridden by the combinations.
+    pub(crate) meta_data: MetaData,
     pub(crate) run_env: RunEnv,
 }
 
@@ -42,6 +199,20 @@ impl AppConfig {
         match self {
             Self::Init(cfg) => cfg.execute(),
             Self::Run(cfg) => cfg.execute(),
+            Self::Parallel(cfg) => cfg.execute(),
+            Self::Report(cfg) => cfg.execute(),
+            Self::History(cfg) => cfg.execute(),
+            Self::Inspect(cfg) => cfg.execute(),
+            Self::Verify(cfg) => cfg.execute(),
+            Self::Snapshot(cfg) => cfg.execute(),
+            Self::Restore(cfg) => cfg.execute(),
+            Self::Clean(cfg) => cfg.execute(),
+            Self::Prune(cfg) => cfg.execute(),
+            Self::Graph(cfg) => cfg.execute(),
+            Self::Export(cfg) => cfg.execute(),
+            Self::DumpChain(cfg) => cfg.execute(),
+            Self::NewConfig(cfg) => cfg.execute(),
+            Self::Matrix(cfg) => cfg.execute(),
         }
     }
 }
@@ -51,7 +222,43 @@ impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for AppConfig {
     fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
         match matches.subcommand() {
             ("init", Some(submatches)) => InitConfig::try_from(submatches).map(AppConfig::Init),
+            ("run", Some(submatches)) if submatches.is_present("parallel") => {
+                ParallelConfig::try_from(submatches).map(AppConfig::Parallel)
+            }
             ("run", Some(submatches)) => RunConfig::try_from(submatches).map(AppConfig::Run),
+            ("report", Some(submatches)) => {
+                ReportConfig::try_from(submatches).map(AppConfig::Report)
+            }
+            ("history", Some(submatches)) => {
+                HistoryConfig::try_from(submatches).map(AppConfig::History)
+            }
+            ("inspect", Some(submatches)) => {
+                InspectConfig::try_from(submatches).map(AppConfig::Inspect)
+            }
+            ("verify", Some(submatches)) => {
+                VerifyConfig::try_from(submatches).map(AppConfig::Verify)
+            }
+            ("snapshot", Some(submatches)) => {
+                SnapshotConfig::try_from(submatches).map(AppConfig::Snapshot)
+            }
+            ("restore", Some(submatches)) => {
+                RestoreConfig::try_from(submatches).map(AppConfig::Restore)
+            }
+            ("clean", Some(submatches)) => CleanConfig::try_from(submatches).map(AppConfig::Clean),
+            ("prune", Some(submatches)) => PruneConfig::try_from(submatches).map(AppConfig::Prune),
+            ("graph", Some(submatches)) => GraphConfig::try_from(submatches).map(AppConfig::Graph),
+            ("export", Some(submatches)) => {
+                ExportConfig::try_from(submatches).map(AppConfig::Export)
+            }
+            ("dump-chain", Some(submatches)) => {
+                DumpChainConfig::try_from(submatches).map(AppConfig::DumpChain)
+            }
+            ("new-config", Some(submatches)) => {
+                NewConfigConfig::try_from(submatches).map(AppConfig::NewConfig)
+            }
+            ("matrix", Some(submatches)) => {
+                MatrixConfig::try_from(submatches).map(AppConfig::Matrix)
+            }
             (subcmd, _) => Err(Error::config(format!("subcommand {}", subcmd))),
         }
     }
@@ -61,14 +268,48 @@ impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for InitConfig {
     type Error = Error;
     fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
         let data_dir = parse_from_str::<PathBuf>(matches, "data-dir")?;
+        let force_unlock = matches.is_present("force-unlock");
+        if matches.is_present("force") && data_dir.exists() {
+            let lock = DataDirLock::acquire(&data_dir, force_unlock)?;
+            check_data_dir(&data_dir)?;
+            log::warn!(
+                "Remove the data directory {} to re-initialize it",
+                data_dir.display()
+            );
+            utils::fs::remove_directory(&data_dir)?;
+            drop(lock);
+        }
         utils::fs::check_directory(&data_dir, false)?;
         utils::fs::create_directory(&data_dir)?;
-        let meta_data = parse_from_file::<MetaData>(matches, "config-file")?;
+        let lock = DataDirLock::acquire(&data_dir, force_unlock)?;
+        let mut meta_data = parse_from_file::<MetaData>(matches, "config-file")?;
+        if let Some(spec) = matches.value_of("chain-spec") {
+            meta_data.chain_spec.import(spec)?;
+            // The imported params are checked as the config file.
+            meta_data = MetaData::from_str(&meta_data.to_string()).map_err(Error::config)?;
+        }
+        let from_chain = parse_from_str_opt::<PathBuf>(matches, "from-chain")?
+            .map(|data_dir| {
+                let to_height = parse_from_str_opt::<u64>(matches, "to-height")?;
+                Ok(ChainSource {
+                    data_dir,
+                    to_height,
+                })
+            })
+            .transpose()?;
+        check_from_chain(&meta_data, from_chain.is_some())?;
+        if from_chain.is_some() && meta_data.chain_spec.pow != Some(PowEngineKind::Dummy) {
+            // The blocks could not be sealed in the difficulty of a real chain.
+            log::warn!("Use the dummy PoW engine for the imported chain");
+            meta_data.chain_spec.pow = Some(PowEngineKind::Dummy);
+        }
         let storage = Storage::init(data_dir.join("storage"))?;
         Ok(Self {
             data_dir,
             storage,
             meta_data,
+            from_chain,
+            lock: Some(lock),
         })
     }
 }
@@ -76,14 +317,300 @@ impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for InitConfig {
 impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for RunConfig {
     type Error = Error;
     fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
-        let data_dir = parse_from_str::<PathBuf>(matches, "data-dir")?;
+        let mut data_dir = parse_from_str::<PathBuf>(matches, "data-dir")?;
         utils::fs::check_directory(&data_dir, true)?;
+        let lock = DataDirLock::acquire(&data_dir, matches.is_present("force-unlock"))?;
+        let ephemeral = if matches.is_present("ephemeral") {
+            let temp_dir = copy_into_temp_dir(&data_dir)?;
+            data_dir = temp_dir.path().to_owned();
+            Some(temp_dir)
+        } else {
+            None
+        };
         let run_env = parse_from_file::<RunEnv>(matches, "config-file")?;
+        let script = parse_from_file_opt::<Script>(matches, "script-file")?;
         let storage = Storage::load(data_dir.join("storage"))?;
+        let metrics_listen = parse_from_str_opt::<SocketAddr>(matches, "metrics-listen")?;
+        let resume = matches.is_present("resume");
+        let baselines = parse_from_str_opt::<PathBuf>(matches, "baselines")?;
         Ok(Self {
             data_dir,
             storage,
             run_env,
+            script,
+            metrics_listen,
+            resume,
+            baselines,
+            lock: Some(lock),
+            ephemeral,
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for ParallelConfig {
+    type Error = Error;
+    fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
+        let data_dir = parse_from_str::<PathBuf>(matches, "data-dir")?;
+        utils::fs::check_directory(&data_dir, true)?;
+        for name in &["ephemeral", "baselines"] {
+            if matches.is_present(name) {
+                let errmsg = format!("argument {} could not be used with parallel", name);
+                return Err(Error::config(errmsg));
+            }
+        }
+        let count = parse_from_str::<u32>(matches, "parallel")?;
+        if count == 0 {
+            return Err(Error::config("argument parallel should be positive"));
+        }
+        let lock = DataDirLock::acquire(&data_dir, matches.is_present("force-unlock"))?;
+        check_data_dir(&data_dir)?;
+        let instance_dirs = (0..count)
+            .map(|index| {
+                let instance_dir = data_dir.join(INSTANCES_DIR).join(index.to_string());
+                if !instance_dir.exists() {
+                    copy_into_instance_dir(&data_dir, &instance_dir)?;
+                }
+                Ok(instance_dir)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let run_env = parse_from_file::<RunEnv>(matches, "config-file")?;
+        let mut problems = Problems::default();
+        run_env.validate_parallel(&mut problems);
+        if !problems.is_empty() {
+            return Err(Error::config(problems));
+        }
+        let script = parse_from_file_opt::<Script>(matches, "script-file")?;
+        let metrics_listen = parse_from_str_opt::<SocketAddr>(matches, "metrics-listen")?;
+        let resume = matches.is_present("resume");
+        Ok(Self {
+            data_dir,
+            run_env,
+            script,
+            metrics_listen,
+            resume,
+            instance_dirs,
+            lock,
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for ReportConfig {
+    type Error = Error;
+    fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
+        let data_dir = parse_from_str::<PathBuf>(matches, "data-dir")?;
+        utils::fs::check_directory(&data_dir, true)?;
+        let storage = Storage::load_read_only(data_dir.join("storage"))?;
+        let output = parse_from_str_opt::<PathBuf>(matches, "output")?;
+        Ok(Self { storage, output })
+    }
+}
+
+impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for HistoryConfig {
+    type Error = Error;
+    fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
+        let data_dir = parse_from_str::<PathBuf>(matches, "data-dir")?;
+        utils::fs::check_directory(&data_dir, true)?;
+        let storage = Storage::load_read_only(data_dir.join("storage"))?;
+        let run_id = parse_from_str_opt::<u32>(matches, "run")?;
+        Ok(Self { storage, run_id })
+    }
+}
+
+impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for InspectConfig {
+    type Error = Error;
+    fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
+        let data_dir = parse_from_str::<PathBuf>(matches, "data-dir")?;
+        utils::fs::check_directory(&data_dir, true)?;
+        let storage = Storage::load_read_only(data_dir.join("storage"))?;
+        let list = matches.is_present("list");
+        let tx_hash = parse_tx_hash_opt(matches, "tx")?;
+        let cells = matches.is_present("cells");
+        // Show the statistics if nothing is chosen.
+        let stats = matches.is_present("stats") || (!list && tx_hash.is_none() && !cells);
+        Ok(Self {
+            storage,
+            stats,
+            list,
+            tx_hash,
+            cells,
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for VerifyConfig {
+    type Error = Error;
+    fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
+        let data_dir = parse_from_str::<PathBuf>(matches, "data-dir")?;
+        utils::fs::check_directory(&data_dir, true)?;
+        let storage = Storage::load_read_only(data_dir.join("storage"))?;
+        let store_dir = data_dir.join("chain");
+        utils::fs::check_directory(&store_dir, true)?;
+        let store = MockedStore::init(store_dir);
+        Ok(Self {
+            data_dir,
+            storage,
+            store,
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for SnapshotConfig {
+    type Error = Error;
+    fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
+        let data_dir = parse_from_str::<PathBuf>(matches, "data-dir")?;
+        utils::fs::check_directory(&data_dir, true)?;
+        let name = parse_from_str::<String>(matches, "name")?;
+        let snapshot_dir = data_dir.join(SNAPSHOTS_DIR).join(name);
+        utils::fs::check_directory(&snapshot_dir, false)?;
+        let storage = Storage::load_current(data_dir.join("storage"))?;
+        let store_dir = data_dir.join("chain");
+        utils::fs::check_directory(&store_dir, true)?;
+        let store = MockedStore::init(store_dir);
+        Ok(Self {
+            data_dir,
+            storage,
+            store,
+            snapshot_dir,
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for RestoreConfig {
+    type Error = Error;
+    fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
+        let data_dir = parse_from_str::<PathBuf>(matches, "data-dir")?;
+        utils::fs::check_directory(&data_dir, true)?;
+        let name = parse_from_str::<String>(matches, "name")?;
+        let snapshot_dir = data_dir.join(SNAPSHOTS_DIR).join(name);
+        utils::fs::check_directory(&snapshot_dir, true)?;
+        // The directories are replaced, so no other process should use them.
+        let lock = DataDirLock::acquire(&data_dir, matches.is_present("force-unlock"))?;
+        Ok(Self {
+            data_dir,
+            snapshot_dir,
+            lock,
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for CleanConfig {
+    type Error = Error;
+    fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
+        let data_dir = parse_from_str::<PathBuf>(matches, "data-dir")?;
+        utils::fs::check_directory(&data_dir, true)?;
+        let lock = DataDirLock::acquire(&data_dir, matches.is_present("force-unlock"))?;
+        check_data_dir(&data_dir)?;
+        Ok(Self { data_dir, lock })
+    }
+}
+
+impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for PruneConfig {
+    type Error = Error;
+    fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
+        let data_dir = parse_from_str::<PathBuf>(matches, "data-dir")?;
+        utils::fs::check_directory(&data_dir, true)?;
+        let lock = DataDirLock::acquire(&data_dir, matches.is_present("force-unlock"))?;
+        let storage = Storage::load(data_dir.join("storage"))?;
+        Ok(Self { storage, lock })
+    }
+}
+
+impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for GraphConfig {
+    type Error = Error;
+    fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
+        let data_dir = parse_from_str::<PathBuf>(matches, "data-dir")?;
+        utils::fs::check_directory(&data_dir, true)?;
+        let lock = DataDirLock::acquire(&data_dir, matches.is_present("force-unlock"))?;
+        let storage = Storage::load_current(data_dir.join("storage"))?;
+        let tx_hash = parse_tx_hash_opt(matches, "tx")?;
+        let direction = parse_from_str::<GraphDirection>(matches, "direction")?;
+        let depth = parse_from_str_opt::<usize>(matches, "depth")?;
+        let format = parse_from_str::<GraphFormat>(matches, "format")?;
+        let output = parse_from_str_opt::<PathBuf>(matches, "output")?;
+        Ok(Self {
+            storage,
+            lock,
+            tx_hash,
+            direction,
+            depth,
+            format,
+            output,
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for ExportConfig {
+    type Error = Error;
+    fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
+        let format = parse_from_str::<ExportFormat>(matches, "format")?;
+        if format == ExportFormat::Sqlite && !cfg!(feature = "sqlite") {
+            let errmsg = "the format sqlite requires the feature \"sqlite\"";
+            return Err(Error::config(errmsg));
+        }
+        let data_dir = parse_from_str::<PathBuf>(matches, "data-dir")?;
+        utils::fs::check_directory(&data_dir, true)?;
+        let storage = Storage::load_read_only(data_dir.join("storage"))?;
+        let store_dir = data_dir.join("chain");
+        utils::fs::check_directory(&store_dir, true)?;
+        let store = MockedStore::init(store_dir);
+        let output_dir = parse_from_str::<PathBuf>(matches, "output-dir")?;
+        Ok(Self {
+            storage,
+            store,
+            output_dir,
+            format,
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for DumpChainConfig {
+    type Error = Error;
+    fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
+        let data_dir = parse_from_str::<PathBuf>(matches, "data-dir")?;
+        utils::fs::check_directory(&data_dir, true)?;
+        let store_dir = data_dir.join("chain");
+        utils::fs::check_directory(&store_dir, true)?;
+        let store = MockedStore::init(store_dir);
+        let output = parse_from_str_opt::<PathBuf>(matches, "output")?;
+        let format = parse_from_str::<ChainFormat>(matches, "format")?;
+        let rpc = parse_from_str_opt::<SocketAddr>(matches, "rpc")?;
+        if output.is_none() && rpc.is_none() {
+            return Err(Error::argument_should_exist("output"));
+        }
+        Ok(Self {
+            store,
+            output,
+            format,
+            rpc,
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for NewConfigConfig {
+    type Error = Error;
+    fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
+        let output_dir = parse_from_str::<PathBuf>(matches, "output-dir")?;
+        let profile =
+            parse_from_str_opt::<Profile>(matches, "profile")?.unwrap_or(Profile::Default);
+        Ok(Self {
+            output_dir,
+            profile,
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a clap::ArgMatches<'a>> for MatrixConfig {
+    type Error = Error;
+    fn try_from(matches: &'a clap::ArgMatches) -> Result<Self> {
+        let output_dir = parse_from_str::<PathBuf>(matches, "output-dir")?;
+        let spec = parse_from_file::<MatrixSpec>(matches, "config-file")?;
+        let meta_data = parse_from_file::<MetaData>(matches, "init-config-file")?;
+        let run_env = parse_from_file::<RunEnv>(matches, "run-config-file")?;
+        Ok(Self {
+            output_dir,
+            spec,
+            meta_data,
+            run_env,
         })
     }
 }
@@ -99,7 +626,123 @@ where
         .ok_or_else(|| Error::argument_should_exist(name))
 }
 
+// Copy the data directory into a temporary directory, the runs in it never change the original
+// one, and all results are dropped with it.
+fn copy_into_temp_dir(data_dir: &Path) -> Result<TempDir> {
+    let mut builder = tempfile::Builder::new();
+    builder.prefix("ckb-txpool-fuzzer-");
+    let shared_memory = Path::new(SHARED_MEMORY_DIR);
+    let temp_dir = if shared_memory.is_dir() {
+        builder.tempdir_in(shared_memory)
+    } else {
+        builder.tempdir()
+    }
+    .map_err(|err| {
+        let errmsg = format!("failed to create temporary directory since {}", err);
+        Error::runtime(errmsg)
+    })?;
+    for name in EPHEMERAL_DIRS {
+        let source = data_dir.join(name);
+        if source.exists() {
+            utils::fs::copy_directory(&source, temp_dir.path().join(name))?;
+        }
+    }
+    log::info!(
+        "[Ephemeral] run in {}, copied from {}, all results will be dropped",
+        temp_dir.path().display(),
+        data_dir.display()
+    );
+    Ok(temp_dir)
+}
+
+// Initialize the data directory of an instance by copying the data directory, so all instances
+// start from the same chain, then they go on separately.
+fn copy_into_instance_dir(data_dir: &Path, instance_dir: &Path) -> Result<()> {
+    utils::fs::create_directory(instance_dir)?;
+    for name in EPHEMERAL_DIRS {
+        let source = data_dir.join(name);
+        if source.exists() {
+            utils::fs::copy_directory(&source, instance_dir.join(name))?;
+        }
+    }
+    log::info!(
+        "[Parallel] initialize the instance {}, copied from {}",
+        instance_dir.display(),
+        data_dir.display()
+    );
+    Ok(())
+}
+
+// The imported chain is recorded by the init, and the genesis block of the node doesn't deploy
+// the secp256k1 locks or the script binaries.
+fn check_from_chain(meta_data: &MetaData, from_chain: bool) -> Result<()> {
+    let genesis = &meta_data.chain_spec.genesis;
+    if meta_data.chain_spec.imported.is_some() {
+        Err(Error::config(
+            "chain_spec.imported should not be set, use --from-chain instead",
+        ))
+    } else if from_chain && (genesis.secp256k1_keys.is_some() || genesis.scripts.is_some()) {
+        Err(Error::config(
+            "chain_spec.genesis.secp256k1_keys and scripts are not supported with --from-chain",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+// Check that the directory is a data directory of this tool, before removing it.
+fn check_data_dir(data_dir: &Path) -> Result<()> {
+    let storage_dir = data_dir.join("storage");
+    if !storage_dir.is_dir() {
+        let errmsg = format!(
+            "the directory {} is not a data directory, no storage in it",
+            data_dir.display()
+        );
+        return Err(Error::config(errmsg));
+    }
+    Storage::read_meta_data(&storage_dir).map_err(|err| {
+        let errmsg = format!(
+            "the directory {} is not a data directory since {}",
+            data_dir.display(),
+            err
+        );
+        Error::config(errmsg)
+    })?;
+    Ok(())
+}
+
+fn parse_from_str_opt<T: FromStr>(matches: &clap::ArgMatches, name: &str) -> Result<Option<T>>
+where
+    <T as FromStr>::Err: Display,
+{
+    matches
+        .value_of(name)
+        .map(|index| T::from_str(index).map_err(Error::config))
+        .transpose()
+}
+
+// The hash of a transaction, with or without the "0x" prefix.
+fn parse_tx_hash_opt(matches: &clap::ArgMatches, name: &str) -> Result<Option<H256>> {
+    matches
+        .value_of(name)
+        .map(|hash| {
+            let hex = hash.trim_start_matches("0x");
+            H256::from_str(hex).map_err(|err| {
+                let errmsg = format!("failed to parse tx hash {} since {}", hash, err);
+                Error::config(errmsg)
+            })
+        })
+        .transpose()
+}
+
 fn parse_from_file<T: FromStr>(matches: &clap::ArgMatches, name: &str) -> Result<T>
+where
+    <T as FromStr>::Err: Display,
+{
+    parse_from_file_opt(matches, name)?.ok_or_else(|| Error::argument_should_exist(name))
+}
+
+fn parse_from_file_opt<T: FromStr>(matches: &clap::ArgMatches, name: &str) -> Result<Option<T>>
 where
     <T as FromStr>::Err: Display,
 {
@@ -118,8 +761,11 @@ where
                         })
                         .map(|_| buffer)
                 })
-                .and_then(|data| T::from_str(&data).map_err(Error::config))
+                .and_then(|data| {
+                    T::from_str(&data).map_err(|err| {
+                        Error::config(format!("failed to parse {} since {}", file, err))
+                    })
+                })
         })
-        .transpose()?
-        .ok_or_else(|| Error::argument_should_exist(name))
+        .transpose()
 }
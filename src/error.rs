@@ -10,6 +10,8 @@ pub enum Error {
     Storage(String),
     #[error("runtime error: {0}")]
     Runtime(String),
+    #[error("mismatched: {0}")]
+    Mismatch(String),
 
     #[error("data error: {0}")]
     Data(String),
@@ -33,6 +35,9 @@ impl Error {
     pub(crate) fn runtime<T: fmt::Display>(inner: T) -> Self {
         Self::Runtime(inner.to_string())
     }
+    pub(crate) fn mismatch<T: fmt::Display>(inner: T) -> Self {
+        Self::Mismatch(inner.to_string())
+    }
     pub(crate) fn broken_since<T: fmt::Display>(name: T, reason: T) -> Self {
         let errmsg = format!("broken {} since {}", name, reason);
         Self::Data(errmsg)
@@ -0,0 +1,402 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+use rocksdb::ops::{
+    Delete as _, DeleteCF as _, Get as _, GetCF as _, GetColumnFamilys as _, IterateCF as _,
+    OpenCF as _, Put as _, PutCF as _, WriteOps as _,
+};
+
+use crate::{
+    error::{Error, Result},
+    utils,
+};
+
+// The column family for the keys which are not in any named column family.
+pub(crate) const CF_DEFAULT: &str = "default";
+
+// The key-value store under the storage.
+//
+// All data are organized in column families, and all column families should be known when the
+// backend is opened.
+//
+// The storage is moved into the blocking threads with the steppers, so the backends should be
+// sendable.
+pub(crate) trait Backend: Send {
+    fn name(&self) -> &'static str;
+
+    fn get(&self, cf_name: &str, key: &[u8]) -> Result<Option<Vec<u8>>>;
+
+    fn put(&self, cf_name: &str, key: &[u8], value: &[u8]) -> Result<()>;
+
+    fn delete(&self, cf_name: &str, key: &[u8]) -> Result<()>;
+
+    // All key-value pairs of a column family, in the order of the keys.
+    fn pairs(&self, cf_name: &str) -> Result<Vec<(Vec<u8>, Vec<u8>)>>;
+
+    // Write all changes in the batch atomically.
+    fn write(&self, batch: WriteBatch) -> Result<()>;
+
+    // Create a consistent copy of the store, which could be opened by `RocksDbBackend`.
+    fn create_checkpoint(&self, path: &Path) -> Result<()>;
+}
+
+// How the persistent store is opened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OpenMode {
+    // Create the store if it doesn't exist.
+    Create,
+    // Open the existing store, and create the column families which are added in newer versions,
+    // so it could be upgraded.
+    Upgrade,
+    // Open the existing store as it is, the column families which are added in newer versions are
+    // missing from an outdated store.
+    Existing,
+}
+
+// The changes which are written together, a value of none means a deletion.
+#[derive(Default)]
+pub(crate) struct WriteBatch {
+    changes: Vec<(&'static str, Vec<u8>, Option<Vec<u8>>)>,
+}
+
+// The persistent backend.
+pub(crate) struct RocksDbBackend {
+    db: rocksdb::DB,
+}
+
+// The persistent backend in the read-only mode of RocksDB, all writes are refused.
+pub(crate) struct ReadOnlyRocksDbBackend {
+    db: rocksdb::ReadOnlyDB,
+}
+
+// The backend which only keeps data in memory, for tests and benchmarks.
+pub(crate) struct MemoryBackend {
+    cfs: RefCell<HashMap<String, BTreeMap<Vec<u8>, Vec<u8>>>>,
+}
+
+impl WriteBatch {
+    pub(crate) fn put<K: AsRef<[u8]>, V: AsRef<[u8]>>(
+        &mut self,
+        cf_name: &'static str,
+        key: K,
+        value: V,
+    ) {
+        let change = (
+            cf_name,
+            key.as_ref().to_vec(),
+            Some(value.as_ref().to_vec()),
+        );
+        self.changes.push(change);
+    }
+
+    pub(crate) fn delete<K: AsRef<[u8]>>(&mut self, cf_name: &'static str, key: K) {
+        self.changes.push((cf_name, key.as_ref().to_vec(), None));
+    }
+}
+
+impl RocksDbBackend {
+    pub(crate) fn open<P: AsRef<Path>>(path: P, mode: OpenMode, cf_names: &[&str]) -> Result<Self> {
+        let create = mode == OpenMode::Create;
+        utils::fs::check_directory(&path, !create)?;
+        let opts = Self::default_dboptions(mode);
+        let cfs = if mode == OpenMode::Existing {
+            let cf_names = existing_column_families(&opts, &path, cf_names)?;
+            Self::default_column_family_descriptors(&cf_names)
+        } else {
+            Self::default_column_family_descriptors(cf_names)
+        };
+        let db = rocksdb::DB::open_cf_descriptors(&opts, &path, cfs)?;
+        Ok(Self { db })
+    }
+
+    fn default_dboptions(mode: OpenMode) -> rocksdb::Options {
+        let mut opts = rocksdb::Options::default();
+        opts.create_if_missing(mode == OpenMode::Create);
+        // Column families which are added in newer versions are created only for upgrading.
+        opts.create_missing_column_families(mode != OpenMode::Existing);
+        // DBOptions
+        opts.set_bytes_per_sync(1 << 20);
+        // TODO RocksDB API
+        opts.set_max_background_compactions(2);
+        opts.set_max_background_flushes(2);
+        // opts.set_max_background_jobs(4);
+        opts.set_max_total_wal_size((1 << 20) * 64);
+        opts.set_keep_log_file_num(64);
+        opts.set_max_open_files(64);
+        // CFOptions "default"
+        opts.set_level_compaction_dynamic_level_bytes(true);
+        opts.set_write_buffer_size((1 << 20) * 8);
+        opts.set_min_write_buffer_number_to_merge(1);
+        opts.set_max_write_buffer_number(2);
+        // TODO RocksDB API
+        // opts.set_max_write_buffer_size_to_maintain(-1);
+        // [TableOptions/BlockBasedTable "default"]
+        let block_opts = {
+            let mut block_opts = rocksdb::BlockBasedOptions::default();
+            block_opts.set_cache_index_and_filter_blocks(true);
+            block_opts.set_pin_l0_filter_and_index_blocks_in_cache(true);
+            block_opts
+        };
+
+        opts.set_block_based_table_factory(&block_opts);
+
+        opts
+    }
+
+    fn default_cfoptions() -> rocksdb::Options {
+        let mut opts = rocksdb::Options::default();
+        opts.set_level_compaction_dynamic_level_bytes(true);
+        opts.set_write_buffer_size((1 << 20) * 8);
+        opts.set_min_write_buffer_number_to_merge(1);
+        opts.set_max_write_buffer_number(2);
+        // TODO RocksDB API
+        // opts.set_max_write_buffer_size_to_maintain(-1);
+        opts
+    }
+
+    fn default_column_family_descriptors(
+        cf_names: &[&str],
+    ) -> Vec<rocksdb::ColumnFamilyDescriptor> {
+        let cfopts = Self::default_cfoptions();
+        cf_names
+            .iter()
+            .map(|name| rocksdb::ColumnFamilyDescriptor::new(name.to_owned(), cfopts.clone()))
+            .collect()
+    }
+
+    fn cf_handle(&self, cf_name: &str) -> Result<&rocksdb::ColumnFamily> {
+        self.db.cf_handle(cf_name).ok_or_else(|| {
+            let errmsg = format!("column family {} should exists", cf_name);
+            Error::storage(errmsg)
+        })
+    }
+}
+
+impl Backend for RocksDbBackend {
+    fn name(&self) -> &'static str {
+        "rocksdb"
+    }
+
+    fn get(&self, cf_name: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let value_opt = if cf_name == CF_DEFAULT {
+            self.db.get(key)?
+        } else {
+            let cf = self.cf_handle(cf_name)?;
+            self.db.get_cf(cf, key)?
+        };
+        Ok(value_opt.map(|slice| slice.to_vec()))
+    }
+
+    fn put(&self, cf_name: &str, key: &[u8], value: &[u8]) -> Result<()> {
+        if cf_name == CF_DEFAULT {
+            self.db.put(key, value)?;
+        } else {
+            let cf = self.cf_handle(cf_name)?;
+            self.db.put_cf(cf, key, value)?;
+        }
+        Ok(())
+    }
+
+    fn delete(&self, cf_name: &str, key: &[u8]) -> Result<()> {
+        if cf_name == CF_DEFAULT {
+            self.db.delete(key)?;
+        } else {
+            let cf = self.cf_handle(cf_name)?;
+            self.db.delete_cf(cf, key)?;
+        }
+        Ok(())
+    }
+
+    fn pairs(&self, cf_name: &str) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let cf = self.cf_handle(cf_name)?;
+        let pairs = self
+            .db
+            .full_iterator_cf(cf, rocksdb::IteratorMode::Start)?
+            .map(|(key, value)| (key.into_vec(), value.into_vec()))
+            .collect();
+        Ok(pairs)
+    }
+
+    fn write(&self, batch: WriteBatch) -> Result<()> {
+        let mut inner = rocksdb::WriteBatch::default();
+        for (cf_name, key, value_opt) in batch.changes {
+            match (cf_name == CF_DEFAULT, value_opt) {
+                (true, Some(value)) => inner.put(key, value)?,
+                (true, None) => inner.delete(key)?,
+                (false, Some(value)) => inner.put_cf(self.cf_handle(cf_name)?, key, value)?,
+                (false, None) => inner.delete_cf(self.cf_handle(cf_name)?, key)?,
+            }
+        }
+        self.db.write(&inner)?;
+        Ok(())
+    }
+
+    fn create_checkpoint(&self, path: &Path) -> Result<()> {
+        let checkpoint = rocksdb::checkpoint::Checkpoint::new(&self.db)?;
+        checkpoint.create_checkpoint(path)?;
+        Ok(())
+    }
+}
+
+impl ReadOnlyRocksDbBackend {
+    pub(crate) fn open<P: AsRef<Path>>(path: P, cf_names: &[&str]) -> Result<Self> {
+        utils::fs::check_directory(&path, true)?;
+        let opts = rocksdb::Options::default();
+        let cf_names = existing_column_families(&opts, &path, cf_names)?;
+        let db = rocksdb::ReadOnlyDB::open_cf(&opts, &path, cf_names)?;
+        Ok(Self { db })
+    }
+
+    fn cf_handle(&self, cf_name: &str) -> Result<&rocksdb::ColumnFamily> {
+        self.db.cf_handle(cf_name).ok_or_else(|| {
+            let errmsg = format!("column family {} should exists", cf_name);
+            Error::storage(errmsg)
+        })
+    }
+
+    fn refuse<T>(&self, operation: &str) -> Result<T> {
+        let errmsg = format!("failed to {} since the storage is read-only", operation);
+        Err(Error::storage(errmsg))
+    }
+}
+
+impl Backend for ReadOnlyRocksDbBackend {
+    fn name(&self) -> &'static str {
+        "read-only rocksdb"
+    }
+
+    fn get(&self, cf_name: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let value_opt = if cf_name == CF_DEFAULT {
+            self.db.get(key)?
+        } else {
+            let cf = self.cf_handle(cf_name)?;
+            self.db.get_cf(cf, key)?
+        };
+        Ok(value_opt.map(|slice| slice.to_vec()))
+    }
+
+    fn put(&self, _cf_name: &str, _key: &[u8], _value: &[u8]) -> Result<()> {
+        self.refuse("put")
+    }
+
+    fn delete(&self, _cf_name: &str, _key: &[u8]) -> Result<()> {
+        self.refuse("delete")
+    }
+
+    fn pairs(&self, cf_name: &str) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let cf = self.cf_handle(cf_name)?;
+        let pairs = self
+            .db
+            .full_iterator_cf(cf, rocksdb::IteratorMode::Start)?
+            .map(|(key, value)| (key.into_vec(), value.into_vec()))
+            .collect();
+        Ok(pairs)
+    }
+
+    fn write(&self, _batch: WriteBatch) -> Result<()> {
+        self.refuse("write")
+    }
+
+    fn create_checkpoint(&self, _path: &Path) -> Result<()> {
+        self.refuse("create checkpoint")
+    }
+}
+
+impl MemoryBackend {
+    pub(crate) fn new(cf_names: &[&str]) -> Self {
+        let cfs = cf_names
+            .iter()
+            .chain(&[CF_DEFAULT])
+            .map(|name| ((*name).to_owned(), BTreeMap::new()))
+            .collect();
+        Self {
+            cfs: RefCell::new(cfs),
+        }
+    }
+
+    fn with_cf<T, F>(&self, cf_name: &str, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut BTreeMap<Vec<u8>, Vec<u8>>) -> T,
+    {
+        let mut cfs = self.cfs.borrow_mut();
+        let cf = cfs.get_mut(cf_name).ok_or_else(|| {
+            let errmsg = format!("column family {} should exists", cf_name);
+            Error::storage(errmsg)
+        })?;
+        Ok(f(cf))
+    }
+}
+
+impl Backend for MemoryBackend {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn get(&self, cf_name: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.with_cf(cf_name, |cf| cf.get(key).cloned())
+    }
+
+    fn put(&self, cf_name: &str, key: &[u8], value: &[u8]) -> Result<()> {
+        self.with_cf(cf_name, |cf| {
+            cf.insert(key.to_vec(), value.to_vec());
+        })
+    }
+
+    fn delete(&self, cf_name: &str, key: &[u8]) -> Result<()> {
+        self.with_cf(cf_name, |cf| {
+            cf.remove(key);
+        })
+    }
+
+    fn pairs(&self, cf_name: &str) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        self.with_cf(cf_name, |cf| {
+            cf.iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect()
+        })
+    }
+
+    fn write(&self, batch: WriteBatch) -> Result<()> {
+        // Check all column families first, so the batch is never partially written.
+        for (cf_name, _, _) in &batch.changes {
+            self.with_cf(cf_name, |_| ())?;
+        }
+        for (cf_name, key, value_opt) in batch.changes {
+            self.with_cf(cf_name, |cf| {
+                if let Some(value) = value_opt {
+                    cf.insert(key, value);
+                } else {
+                    cf.remove(&key);
+                }
+            })?;
+        }
+        Ok(())
+    }
+
+    fn create_checkpoint(&self, path: &Path) -> Result<()> {
+        let errmsg = format!(
+            "failed to create checkpoint at {} since the storage is in memory",
+            path.display()
+        );
+        Err(Error::storage(errmsg))
+    }
+}
+
+// The column families which exist in the store, the ones which are added in newer versions are
+// missing from an outdated store.
+fn existing_column_families<'a, P: AsRef<Path>>(
+    opts: &rocksdb::Options,
+    path: P,
+    cf_names: &[&'a str],
+) -> Result<Vec<&'a str>> {
+    let existing = rocksdb::DB::list_cf(opts, path)?;
+    let cf_names = cf_names
+        .iter()
+        .copied()
+        .filter(|name| existing.iter().any(|cf_name| cf_name == name))
+        .collect();
+    Ok(cf_names)
+}
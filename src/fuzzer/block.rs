@@ -0,0 +1,360 @@
+use std::{
+    collections::{HashMap, HashSet},
+    result::Result as StdResult,
+};
+
+use ckb_chain_spec::consensus::ProposalWindow;
+use ckb_store::ChainStore as _;
+use ckb_types::{
+    core::{tx_pool::TxPoolEntryInfo, BlockView, Version},
+    packed,
+    prelude::*,
+};
+
+use super::{reward, BlockProducers, MockedChain, Storage};
+use crate::{
+    error::{Error, Result},
+    types::{BlockDelta, MismatchKind, RandomGenerator, TemplateLimits, TxStage},
+};
+
+// A broken block, the kind tells whether it's broken by the assembler of the tx-pool or by the
+// producer which edits the template.
+pub(crate) struct BrokenBlock {
+    pub(crate) kind: MismatchKind,
+    pub(crate) reason: String,
+}
+
+// Build the next block from the block template by a producer, return the block and the
+// differences between them.
+//
+// A mismatch error is returned if the block is broken by the assembler or by the producer.
+pub fn build_block(
+    rg: &RandomGenerator,
+    chain: &MockedChain,
+    producers: &BlockProducers,
+) -> Result<(BlockView, BlockDelta)> {
+    try_build_block(rg, chain, producers)?.map_err(|broken| Error::mismatch(broken.reason))
+}
+
+// The same as `build_block`, but the broken block is returned with its attribution.
+pub(crate) fn try_build_block(
+    rg: &RandomGenerator,
+    chain: &MockedChain,
+    producers: &BlockProducers,
+) -> Result<StdResult<(BlockView, BlockDelta), BrokenBlock>> {
+    let consensus = chain.consensus();
+    let limits = rg.template_limits(
+        consensus.max_block_bytes(),
+        consensus.max_block_proposals_limit(),
+        consensus.block_version(),
+    );
+    let limited = if let Some(limits) = limits {
+        get_limited_template(chain, &limits)?
+    } else {
+        Ok(chain.get_block_template()?.into())
+    };
+    let template_view = match limited {
+        Ok(block) => stamp_block(chain, block).into_view(),
+        Err(reason) => {
+            return Ok(Err(BrokenBlock {
+                kind: MismatchKind::TemplateBroken,
+                reason,
+            }));
+        }
+    };
+    let cellbase_checked = reward::check_cellbase(chain, &template_view)?;
+    let checked = check_epoch(chain, &template_view)
+        .and_then(|_| check_dependencies(chain, &template_view))
+        .and_then(|_| cellbase_checked);
+    if let Err(reason) = checked {
+        let reason = format!(
+            "the block template from the assembler is broken since {}",
+            reason
+        );
+        return Ok(Err(BrokenBlock {
+            kind: MismatchKind::TemplateBroken,
+            reason,
+        }));
+    }
+    let (name, producer) = producers.choose(rg);
+    log::trace!("[BuildBlock] produce the block by {}", name);
+    let block_view = producer.produce(rg, chain, &template_view)?;
+    let delta = BlockDelta::new(&template_view, &block_view);
+    if !delta.is_empty() {
+        if let Err(reason) = check_dependencies(chain, &block_view) {
+            let reason = format!(
+                "the block edited by the producer {} is broken since {}",
+                name, reason
+            );
+            return Ok(Err(BrokenBlock {
+                kind: MismatchKind::ProducedBlockBroken,
+                reason,
+            }));
+        }
+    }
+    // Seal the block at last, since the producers change the header.
+    let block_view = chain.seal_block(block_view)?;
+    Ok(Ok((block_view, delta)))
+}
+
+// The tx-pool stamps the template by the process-global fake time, which could be ahead of the
+// clock of the chain when other chains run in the same process, so restamp it by the clock.
+fn stamp_block(chain: &MockedChain, block: packed::Block) -> packed::Block {
+    let timestamp = chain.clock().block_timestamp(&chain.chain_tip_header());
+    let header = block.header();
+    let raw = header
+        .raw()
+        .as_builder()
+        .timestamp(timestamp.pack())
+        .build();
+    let header = header.as_builder().raw(raw).build();
+    block.as_builder().header(header).build()
+}
+
+// Get the block template with the limits, and check that the template honors them.
+//
+// The limits beyond the consensus limits are capped by them. The tx-pool could only refuse a
+// bytes limit which couldn't hold the block without any transactions but the cellbase, then the
+// template with the consensus limits is used.
+//
+// The reason is returned if the template is wrongly refused or doesn't honor the limits.
+fn get_limited_template(
+    chain: &MockedChain,
+    limits: &TemplateLimits,
+) -> Result<StdResult<packed::Block, String>> {
+    let consensus = chain.consensus();
+    let bytes_limit = limits
+        .bytes_limit
+        .map_or(consensus.max_block_bytes(), |limit| {
+            limit.min(consensus.max_block_bytes())
+        });
+    let proposals_limit = limits
+        .proposals_limit
+        .map_or(consensus.max_block_proposals_limit(), |limit| {
+            limit.min(consensus.max_block_proposals_limit())
+        });
+    let max_version = limits
+        .max_version
+        .map_or(consensus.block_version(), |version| {
+            version.min(consensus.block_version())
+        });
+    let result = chain.get_block_template_with(
+        limits.bytes_limit,
+        limits.proposals_limit,
+        limits.max_version,
+    )?;
+    let block: packed::Block = match result {
+        Ok(template) => template.into(),
+        Err(reason) => {
+            let block: packed::Block = chain.get_block_template()?.into();
+            // The smallest block which the tx-pool could assemble with the limits.
+            let cellbase = block.transactions().get(0);
+            let basic_size = block
+                .clone()
+                .as_builder()
+                .transactions(cellbase.into_iter().collect::<Vec<_>>().pack())
+                .proposals(
+                    block
+                        .proposals()
+                        .into_iter()
+                        .take(proposals_limit as usize)
+                        .collect::<Vec<_>>()
+                        .pack(),
+                )
+                .build()
+                .serialized_size_without_uncle_proposals() as u64;
+            if bytes_limit >= basic_size {
+                let reason = format!(
+                    "the block template is refused with {:?}, \
+                    but the smallest block is {} bytes, since {}",
+                    limits, basic_size, reason
+                );
+                return Ok(Err(reason));
+            }
+            log::trace!(
+                "[BuildBlock] the block template is refused with {:?} as expected, \
+                the smallest block is {} bytes",
+                limits,
+                basic_size
+            );
+            return Ok(Ok(block));
+        }
+    };
+    let size = block.serialized_size_without_uncle_proposals() as u64;
+    let proposals_count = block.proposals().len() as u64;
+    let version: Version = block.header().raw().version().unpack();
+    let mut violations = Vec::new();
+    if size > bytes_limit {
+        violations.push(format!("size {} > {}", size, bytes_limit));
+    }
+    if proposals_count > proposals_limit {
+        violations.push(format!(
+            "proposals {} > {}",
+            proposals_count, proposals_limit
+        ));
+    }
+    if version > max_version {
+        violations.push(format!("version {} > {}", version, max_version));
+    }
+    if !violations.is_empty() {
+        let reason = format!(
+            "the block template doesn't honor {:?}: {}",
+            limits,
+            violations.join(", ")
+        );
+        return Ok(Err(reason));
+    }
+    log::trace!(
+        "[BuildBlock] the block template with {:?}: {} bytes, {} proposals",
+        limits,
+        size,
+        proposals_count
+    );
+    Ok(Ok(block))
+}
+
+// Check the proposals of the block template against the tx-pool before it and the storage,
+// returns the reasons of the stale proposals.
+//
+// All proposals should be pending in both the tx-pool and the storage, and the transactions
+// which are proposed in the window by the previous blocks should not be proposed again.
+pub(crate) fn check_template_proposals(
+    pool_before: &TxPoolEntryInfo,
+    storage: &Storage,
+    block: &BlockView,
+    delta: &BlockDelta,
+    window: &ProposalWindow,
+) -> Result<Vec<String>> {
+    let pending = pool_before
+        .pending
+        .keys()
+        .map(|tx_hash| (packed::ProposalShortId::from_tx_hash(tx_hash), tx_hash))
+        .collect::<HashMap<_, _>>();
+    let proposals = block
+        .data()
+        .proposals()
+        .into_iter()
+        .chain(delta.dropped_proposals.iter().cloned())
+        .filter(|id| !delta.added_proposals.contains(id));
+    let mut reasons = Vec::new();
+    for id in proposals {
+        let tx_hash = if let Some(tx_hash) = pending.get(&id) {
+            *tx_hash
+        } else {
+            reasons.push(format!("proposal {:#x} is not pending in the tx-pool", id));
+            continue;
+        };
+        match storage.get_tx_status(tx_hash)? {
+            // The transactions which are not tracked, for example, removed as invalid.
+            Some(ref tx_status) if tx_status.is_uncommitted() => {}
+            None => {}
+            Some(tx_status) => {
+                reasons.push(format!(
+                    "proposal {:#x} of tx {:#x} is {} in the storage",
+                    id, tx_hash, tx_status
+                ));
+                continue;
+            }
+        }
+        let stage = storage.tx_stage(tx_hash, block.number(), window)?;
+        if let Some(stage) = stage.filter(|stage| *stage != TxStage::Pending) {
+            let proposed_in = storage.get_proposals(tx_hash)?.unwrap_or_default();
+            reasons.push(format!(
+                "proposal {:#x} of tx {:#x} is repeated, it's {} (proposed in: {})",
+                id, tx_hash, stage, proposed_in
+            ));
+        }
+    }
+    Ok(reasons)
+}
+
+// Check that the block template is in the next epoch of the chain, with its difficulty, so the
+// snapshot of the tx-pool is refreshed, especially after an epoch boundary.
+fn check_epoch(chain: &MockedChain, block: &BlockView) -> StdResult<(), String> {
+    let next_epoch_ext = chain.next_epoch_ext();
+    let expected = next_epoch_ext.number_with_fraction(block.number());
+    if block.epoch() != expected {
+        return Err(format!(
+            "block {} is in epoch {} but {} is expected",
+            block.number(),
+            block.epoch(),
+            expected
+        ));
+    }
+    let compact_target = block.header().compact_target();
+    if compact_target != next_epoch_ext.compact_target() {
+        return Err(format!(
+            "block {} has compact target {:#x} but {:#x} is expected",
+            block.number(),
+            compact_target,
+            next_epoch_ext.compact_target()
+        ));
+    }
+    Ok(())
+}
+
+// Check that all inputs and cell deps of the committed transactions could be resolved, either
+// from the live cells in the chain, or from the transactions before them in the same block.
+// The timestamp-based since of the inputs should be satisfied by the median time of the tip,
+// which could go backwards after the blocks with anomalous timestamps.
+fn check_dependencies(chain: &MockedChain, block: &BlockView) -> StdResult<(), String> {
+    let store = chain.store();
+    let median_time = chain.tip_median_time();
+    let mut created = HashSet::new();
+    let mut spent = HashSet::new();
+    for (index, tx) in block.transactions().into_iter().enumerate() {
+        let tx_hash = tx.hash();
+        if index > 0 {
+            for out_point in tx.input_pts_iter() {
+                let is_live = created.contains(&out_point) || store.get_cell(&out_point).is_some();
+                if !is_live {
+                    return Err(format!(
+                        "input {} of tx {:#x} could not be resolved",
+                        display_out_point(&out_point),
+                        tx_hash
+                    ));
+                }
+                if !spent.insert(out_point.clone()) {
+                    return Err(format!(
+                        "input {} of tx {:#x} is spent twice in the block",
+                        display_out_point(&out_point),
+                        tx_hash
+                    ));
+                }
+            }
+            for input in tx.inputs() {
+                match chain.since_median_time(&input) {
+                    Some(required) if required > median_time => {
+                        return Err(format!(
+                            "input {} of tx {:#x} is locked until the median time {} (now: {})",
+                            display_out_point(&input.previous_output()),
+                            tx_hash,
+                            required,
+                            median_time
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+            for out_point in tx.cell_deps_iter().map(|cell_dep| cell_dep.out_point()) {
+                let is_live = created.contains(&out_point) || store.get_cell(&out_point).is_some();
+                if !is_live || spent.contains(&out_point) {
+                    return Err(format!(
+                        "cell dep {} of tx {:#x} could not be resolved",
+                        display_out_point(&out_point),
+                        tx_hash
+                    ));
+                }
+            }
+        }
+        for index in 0..tx.outputs().len() {
+            created.insert(packed::OutPoint::new(tx_hash.clone(), index as u32));
+        }
+    }
+    Ok(())
+}
+
+pub(super) fn display_out_point(out_point: &packed::OutPoint) -> String {
+    let index: u32 = out_point.index().unpack();
+    format!("{:#x},{}", out_point.tx_hash(), index)
+}
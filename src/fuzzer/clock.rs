@@ -0,0 +1,46 @@
+use std::cell::Cell;
+
+use ckb_types::core::HeaderView;
+
+use crate::{error::Result, utils};
+
+// The virtual time of a mocked chain, in milliseconds.
+//
+// The blocks of a chain are stamped by its own clock, so the chains which run in the same
+// process don't disturb the timestamps of each other. The tx-pool still reads the
+// process-global fake time, so the clock pushes the fake time forward as it ticks, and that is
+// the only place where the fake time is written.
+pub struct Clock {
+    now: Cell<u64>,
+}
+
+impl Clock {
+    // Start the clock at the timestamp of the tip.
+    pub(crate) fn start(tip_timestamp: u64) -> Result<Self> {
+        let clock = Self {
+            now: Cell::new(tip_timestamp),
+        };
+        clock.sync()?;
+        Ok(clock)
+    }
+
+    pub fn now(&self) -> u64 {
+        self.now.get()
+    }
+
+    // Move the clock forward, by a block interval or a time jump.
+    pub fn increase(&self, millis: u32) -> Result<()> {
+        self.now.set(self.now.get() + u64::from(millis));
+        self.sync()
+    }
+
+    // The timestamp of the block after the parent, a block is never older than its parent even
+    // if the clock doesn't tick between them.
+    pub(crate) fn block_timestamp(&self, parent: &HeaderView) -> u64 {
+        self.now().max(parent.timestamp() + 1)
+    }
+
+    fn sync(&self) -> Result<()> {
+        utils::faketime::update(self.now())
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    fs,
+    io::{BufWriter, Write as _},
+    path::Path,
+};
+
+use ckb_jsonrpc_types::{Block as JsonBlock, BlockView as JsonBlockView};
+use ckb_store::{ChainDB, ChainStore as _};
+use ckb_types::{
+    core::{BlockNumber, BlockView},
+    prelude::*,
+};
+use serde_json::{json, Value};
+
+use crate::{
+    error::{Error, Result},
+    types::ChainFormat,
+    utils::rpc::RpcClient,
+};
+
+// The work ID is not checked when submitting the blocks which are not from the block assembler.
+const SUBMIT_WORK_ID: &str = "ckb-txpool-fuzzer";
+
+// Write all blocks of the main chain in order, from the genesis block to the tip.
+//
+// The genesis block is included, `ckb import` skips it, but it should be the same as the
+// genesis block of the node, so the node should be initialized by the same chain spec.
+pub(crate) fn dump_chain(store: &ChainDB, format: ChainFormat, path: &Path) -> Result<()> {
+    let write_error = |err: std::io::Error| {
+        let errmsg = format!("failed to write {} since {}", path.display(), err);
+        Error::runtime(errmsg)
+    };
+    let file = fs::File::create(path).map_err(write_error)?;
+    let mut writer = BufWriter::new(file);
+    let tip_number = tip_number(store)?;
+    for number in 0..=tip_number {
+        let block = block_by_number(store, number)?;
+        match format {
+            ChainFormat::Molecule => writer
+                .write_all(block.data().as_slice())
+                .map_err(write_error)?,
+            ChainFormat::Json => {
+                let json = serde_json::to_string(&JsonBlockView::from(block)).map_err(|err| {
+                    let errmsg = format!("failed to serialize block {} since {}", number, err);
+                    Error::runtime(errmsg)
+                })?;
+                writeln!(writer, "{}", json).map_err(write_error)?;
+            }
+        }
+    }
+    writer.flush().map_err(write_error)?;
+    log::info!(
+        "[DumpChain] dump {} blocks into {}",
+        tip_number + 1,
+        path.display()
+    );
+    Ok(())
+}
+
+// Submit the blocks which the node doesn't have by the RPC `submit_block`, so the node verifies
+// them by `process_block` as the blocks from the miners.
+//
+// The node should have the same genesis block, and its chain should be a prefix of the mocked
+// chain, then a rejected block is a divergence between the mocked chain and a real node.
+//
+// Returns how many blocks are submitted.
+pub(crate) fn submit_chain(store: &ChainDB, client: &mut RpcClient) -> Result<u64> {
+    let node_tip = client
+        .call("get_tip_block_number", json!([]))
+        .and_then(|result| parse_number(&result))?;
+    let tip_number = tip_number(store)?;
+    if node_tip > tip_number {
+        let errmsg = format!(
+            "the tip {} of the node {} is higher than the tip {} of the mocked chain",
+            node_tip,
+            client.addr(),
+            tip_number
+        );
+        return Err(Error::runtime(errmsg));
+    }
+    // Both the genesis blocks and the tips should be the same.
+    for number in [0, node_tip] {
+        let expected = format!("{:#x}", block_by_number(store, number)?.hash());
+        let actual = client.call("get_block_hash", json!([format!("{:#x}", number)]))?;
+        if actual.as_str() != Some(expected.as_str()) {
+            let errmsg = format!(
+                "the block {} of the node {} is {} but it's {} in the mocked chain",
+                number,
+                client.addr(),
+                actual,
+                expected
+            );
+            return Err(Error::runtime(errmsg));
+        }
+    }
+    log::info!(
+        "[DumpChain] submit blocks from {} to {} into the node {}",
+        node_tip + 1,
+        tip_number,
+        client.addr()
+    );
+    for number in node_tip + 1..=tip_number {
+        let block = block_by_number(store, number)?;
+        let expected = format!("{:#x}", block.hash());
+        let params = json!([SUBMIT_WORK_ID, JsonBlock::from(block.data())]);
+        let actual = client.call("submit_block", params).map_err(|err| {
+            let errmsg = format!("the node rejects block {} since {}", number, err);
+            Error::runtime(errmsg)
+        })?;
+        if actual.as_str() != Some(expected.as_str()) {
+            let errmsg = format!(
+                "the node accepts block {} as {} but it's {} in the mocked chain",
+                number, actual, expected
+            );
+            return Err(Error::runtime(errmsg));
+        }
+        log::trace!("[DumpChain] block {} is accepted by the node", number);
+    }
+    Ok(tip_number - node_tip)
+}
+
+fn tip_number(store: &ChainDB) -> Result<BlockNumber> {
+    store
+        .get_tip_header()
+        .map(|header| header.number())
+        .ok_or_else(|| Error::runtime("the tip of the chain is not found"))
+}
+
+fn block_by_number(store: &ChainDB, number: BlockNumber) -> Result<BlockView> {
+    store
+        .get_block_hash(number)
+        .and_then(|block_hash| store.get_block(&block_hash))
+        .ok_or_else(|| {
+            let errmsg = format!("block {} is not found", number);
+            Error::runtime(errmsg)
+        })
+}
+
+// The numbers are hex strings in the RPC.
+fn parse_number(value: &Value) -> Result<BlockNumber> {
+    value
+        .as_str()
+        .and_then(|hex| BlockNumber::from_str_radix(hex.trim_start_matches("0x"), 16).ok())
+        .ok_or_else(|| {
+            let errmsg = format!("failed to parse the block number {}", value);
+            Error::runtime(errmsg)
+        })
+}
@@ -0,0 +1,165 @@
+use std::collections::{HashMap, HashSet};
+
+use ckb_store::ChainStore as _;
+use ckb_types::{
+    core::{Capacity, FeeRate, TransactionView},
+    packed,
+    prelude::*,
+};
+
+use super::{MockedChain, Storage};
+use crate::error::{Error, Result};
+
+// Find the pending transactions which were evicted from the tx-pool, check the eviction keys
+// of them and remove them from the storage.
+//
+// Returns the mismatches.
+pub(crate) fn check_evicted(chain: &MockedChain, storage: &Storage) -> Result<Vec<String>> {
+    let entries = chain.txpool_entries()?;
+    let (mut evicted, kept): (Vec<_>, Vec<_>) =
+        storage.pending_transactions()?.into_iter().partition(|tx| {
+            let tx_hash = tx.hash();
+            !entries.pending.contains_key(&tx_hash) && !entries.proposed.contains_key(&tx_hash)
+        });
+    if evicted.is_empty() {
+        return Ok(Vec::new());
+    }
+    log::trace!("[Eviction] {} transactions were evicted", evicted.len());
+    let keys = evict_keys(chain, storage, evicted.iter().chain(kept.iter()))?;
+    let min_key_in_pool = kept
+        .iter()
+        .filter_map(|tx| keys.get(&tx.hash()))
+        .min()
+        .copied();
+    let evicted_hashes = evicted.iter().map(|tx| tx.hash()).collect::<HashSet<_>>();
+    let mut mismatches = Vec::new();
+    for tx in &evicted {
+        let depends_on_evicted = tx
+            .input_pts_iter()
+            .any(|out_point| evicted_hashes.contains(&out_point.tx_hash()));
+        if depends_on_evicted {
+            // The descendants are evicted with their ancestors, whatever the keys are.
+            continue;
+        }
+        if let (Some(key), Some(min_key)) = (keys.get(&tx.hash()), min_key_in_pool) {
+            if *key > min_key {
+                mismatches.push(format!(
+                    "evict {:#x} (evict key: {}) but the lowest evict key in pool is {}",
+                    tx.hash(),
+                    key,
+                    min_key
+                ));
+            }
+        }
+    }
+    // Remove the descendants before their ancestors.
+    while !evicted.is_empty() {
+        let spent = evicted
+            .iter()
+            .flat_map(|tx| tx.input_pts_iter().map(|out_point| out_point.tx_hash()))
+            .collect::<HashSet<_>>();
+        let (leaves, rest): (Vec<_>, Vec<_>) = evicted
+            .into_iter()
+            .partition(|tx| !spent.contains(&tx.hash()));
+        if leaves.is_empty() {
+            return Err(Error::runtime(
+                "evicted transactions have circular dependencies",
+            ));
+        }
+        for tx in &leaves {
+            log::info!("[Eviction] >>> remove evicted {:#x}", tx.hash());
+            storage.evict_tx(tx)?;
+        }
+        evicted = rest;
+    }
+    Ok(mismatches)
+}
+
+// The tx-pool evicts the transaction which has the lowest key first, the key is the higher one
+// of the fee rate of the transaction itself and the fee rate of the transaction with all its
+// descendants, so a transaction is not evicted for its low fee children, and a low fee parent
+// is kept for its high fee children.
+//
+// The transactions which have unknown inputs have no keys.
+fn evict_keys<'a>(
+    chain: &MockedChain,
+    storage: &Storage,
+    txs: impl Iterator<Item = &'a TransactionView>,
+) -> Result<HashMap<packed::Byte32, FeeRate>> {
+    let mut fees = HashMap::new();
+    let mut children: HashMap<_, Vec<_>> = HashMap::new();
+    for tx in txs {
+        let tx_hash = tx.hash();
+        for out_point in tx.input_pts_iter() {
+            children
+                .entry(out_point.tx_hash())
+                .or_default()
+                .push(tx_hash.clone());
+        }
+        if let Some(fee) = fee(chain, storage, tx)? {
+            let size = tx.data().serialized_size_in_block() as u64;
+            fees.insert(tx_hash, (fee.as_u64(), size));
+        }
+    }
+    let mut keys = HashMap::new();
+    for (tx_hash, (fee, size)) in &fees {
+        let mut visited = HashSet::new();
+        let mut stack = vec![tx_hash.clone()];
+        let (mut total_fee, mut total_size) = (0u64, 0u64);
+        let mut is_known = true;
+        while let Some(current) = stack.pop() {
+            if !visited.insert(current.clone()) {
+                continue;
+            }
+            if let Some((fee, size)) = fees.get(&current) {
+                total_fee = total_fee.saturating_add(*fee);
+                total_size = total_size.saturating_add(*size);
+            } else {
+                is_known = false;
+                break;
+            }
+            if let Some(tx_children) = children.get(&current) {
+                stack.extend(tx_children.iter().cloned());
+            }
+        }
+        if !is_known {
+            continue;
+        }
+        let fee_rate = FeeRate::calculate(Capacity::shannons(*fee), *size as usize);
+        let descendants_fee_rate =
+            FeeRate::calculate(Capacity::shannons(total_fee), total_size as usize);
+        keys.insert(tx_hash.clone(), fee_rate.max(descendants_fee_rate));
+    }
+    Ok(keys)
+}
+
+fn fee(chain: &MockedChain, storage: &Storage, tx: &TransactionView) -> Result<Option<Capacity>> {
+    let mut inputs_capacity = Capacity::zero();
+    for out_point in tx.input_pts_iter() {
+        let output = input_tx(chain, storage, &out_point.tx_hash())?
+            .and_then(|input_tx| input_tx.output(out_point.index().unpack()));
+        if let Some(output) = output {
+            inputs_capacity = inputs_capacity
+                .safe_add(output.capacity().unpack())
+                .map_err(Error::runtime)?;
+        } else {
+            return Ok(None);
+        }
+    }
+    let fee = inputs_capacity
+        .safe_sub(tx.outputs_capacity().map_err(Error::runtime)?)
+        .map_err(Error::runtime)?;
+    Ok(Some(fee))
+}
+
+fn input_tx(
+    chain: &MockedChain,
+    storage: &Storage,
+    tx_hash: &packed::Byte32,
+) -> Result<Option<TransactionView>> {
+    if let Some(tx) = storage.get_transaction(tx_hash)? {
+        Ok(Some(tx))
+    } else {
+        Ok(chain.store().get_transaction(tx_hash).map(|(tx, _)| tx))
+    }
+}
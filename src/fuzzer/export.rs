@@ -0,0 +1,236 @@
+use std::path::Path;
+
+use ckb_store::{ChainDB, ChainStore as _};
+use ckb_types::{core::BlockNumber, prelude::*};
+
+use super::Storage;
+use crate::{
+    error::{Error, Result},
+    types::{CellStatus, ExportFormat, ExportTable},
+    utils,
+};
+
+// The database which the format "sqlite" writes into the output directory.
+pub(crate) const SQLITE_FILE: &str = "export.sqlite";
+
+// Collect the tables from the storage and the chain, they are flat, so they could be joined by
+// the hashes and the block numbers.
+pub(crate) fn collect_tables(storage: &Storage, store: &ChainDB) -> Result<Vec<ExportTable>> {
+    let tables = vec![
+        transactions_table(storage)?,
+        tx_statuses_table(storage)?,
+        blocks_table(storage, store)?,
+        rejects_table(storage)?,
+    ];
+    for table in &tables {
+        log::info!("[Export] table {}: {} rows", table.name, table.rows.len());
+    }
+    Ok(tables)
+}
+
+pub(crate) fn write_tables(
+    format: ExportFormat,
+    output_dir: &Path,
+    tables: &[ExportTable],
+) -> Result<()> {
+    utils::fs::need_directory(output_dir)?;
+    log::info!(
+        "[Export] write {} tables as {} into {}",
+        tables.len(),
+        format.name(),
+        output_dir.display()
+    );
+    match format {
+        ExportFormat::Csv => {
+            for table in tables {
+                let path = output_dir.join(format!("{}.csv", table.name));
+                utils::fs::write_file(&path, table.to_csv())?;
+            }
+            Ok(())
+        }
+        ExportFormat::Sqlite => write_sqlite(&output_dir.join(SQLITE_FILE), tables),
+    }
+}
+
+fn transactions_table(storage: &Storage) -> Result<ExportTable> {
+    let mut table = ExportTable::new(
+        "transactions",
+        &[
+            "tx_hash",
+            "run",
+            "step",
+            "seq_index",
+            "inputs",
+            "outputs",
+            "cell_deps",
+            "size",
+        ],
+    );
+    for tx in storage.transactions()? {
+        let tx_hash = tx.hash();
+        let seq_id = storage.get_tx_seq_id(&tx_hash)?;
+        table.push(vec![
+            format!("{:#x}", tx_hash).into(),
+            seq_id.map(|seq_id| u64::from(seq_id.run)).into(),
+            seq_id.map(|seq_id| seq_id.step).into(),
+            seq_id.map(|seq_id| u64::from(seq_id.index)).into(),
+            (tx.inputs().len() as u64).into(),
+            (tx.outputs().len() as u64).into(),
+            (tx.cell_deps().len() as u64).into(),
+            (tx.data().serialized_size_in_block() as u64).into(),
+        ]);
+    }
+    Ok(table)
+}
+
+// The counts of the cells are null if the transaction has no statuses of its outputs.
+fn tx_statuses_table(storage: &Storage) -> Result<ExportTable> {
+    let mut table = ExportTable::new(
+        "tx_statuses",
+        &[
+            "tx_hash",
+            "status",
+            "live_cells",
+            "dead_cells",
+            "burn_cells",
+        ],
+    );
+    for (tx_hash, tx_status) in storage.tx_statuses()? {
+        let counts = tx_status.outputs().map(|inner| {
+            let count = |expected: CellStatus| {
+                inner
+                    .statuses
+                    .iter()
+                    .filter(|status| **status == expected)
+                    .count() as u64
+            };
+            (
+                count(CellStatus::Live),
+                count(CellStatus::Dead),
+                count(CellStatus::Burn),
+            )
+        });
+        table.push(vec![
+            format!("{:#x}", tx_hash).into(),
+            tx_status.name().to_owned().into(),
+            counts.map(|(live, _, _)| live).into(),
+            counts.map(|(_, dead, _)| dead).into(),
+            counts.map(|(_, _, burn)| burn).into(),
+        ]);
+    }
+    Ok(table)
+}
+
+// The blocks of the main chain, with the differences from their templates if they are
+// recorded, the cellbases are not counted as transactions.
+fn blocks_table(storage: &Storage, store: &ChainDB) -> Result<ExportTable> {
+    let mut table = ExportTable::new(
+        "blocks",
+        &[
+            "number",
+            "block_hash",
+            "timestamp",
+            "transactions",
+            "proposals",
+            "uncles",
+            "size",
+            "dropped_txs",
+            "added_txs",
+            "dropped_proposals",
+            "added_proposals",
+        ],
+    );
+    let tip_number = store
+        .get_tip_header()
+        .map(|header| header.number())
+        .ok_or_else(|| Error::runtime("the tip of the chain is not found"))?;
+    for number in 0..=tip_number {
+        let block = store
+            .get_block_hash(number)
+            .and_then(|block_hash| store.get_block(&block_hash))
+            .ok_or_else(|| {
+                let errmsg = format!("block {} is not found", number);
+                Error::runtime(errmsg)
+            })?;
+        let delta = block_delta_counts(storage, number)?;
+        table.push(vec![
+            number.into(),
+            format!("{:#x}", block.hash()).into(),
+            block.timestamp().into(),
+            (block.transactions().len().saturating_sub(1) as u64).into(),
+            (block.union_proposal_ids().len() as u64).into(),
+            (block.uncles().data().len() as u64).into(),
+            (block.data().serialized_size_without_uncle_proposals() as u64).into(),
+            delta.map(|counts| counts[0]).into(),
+            delta.map(|counts| counts[1]).into(),
+            delta.map(|counts| counts[2]).into(),
+            delta.map(|counts| counts[3]).into(),
+        ]);
+    }
+    Ok(table)
+}
+
+fn block_delta_counts(storage: &Storage, number: BlockNumber) -> Result<Option<[u64; 4]>> {
+    let counts = storage.get_block_delta(number)?.map(|delta| {
+        [
+            delta.dropped_txs.len() as u64,
+            delta.added_txs.len() as u64,
+            delta.dropped_proposals.len() as u64,
+            delta.added_proposals.len() as u64,
+        ]
+    });
+    Ok(counts)
+}
+
+fn rejects_table(storage: &Storage) -> Result<ExportTable> {
+    let mut table = ExportTable::new("rejects", &["tx_hash", "reason"]);
+    for (tx_hash, reason) in storage.rejects()? {
+        table.push(vec![format!("{:#x}", tx_hash).into(), reason.into()]);
+    }
+    Ok(table)
+}
+
+// The tables are replaced, so the database could be exported again after more runs.
+#[cfg(feature = "sqlite")]
+fn write_sqlite(path: &Path, tables: &[ExportTable]) -> Result<()> {
+    use rusqlite::{params_from_iter, types::Value, Connection};
+
+    use crate::types::ExportValue;
+
+    let sqlite_error = |err: rusqlite::Error| {
+        let errmsg = format!("failed to write {} since {}", path.display(), err);
+        Error::runtime(errmsg)
+    };
+    let mut conn = Connection::open(path).map_err(sqlite_error)?;
+    let db_tx = conn.transaction().map_err(sqlite_error)?;
+    for table in tables {
+        let sql = format!(
+            "DROP TABLE IF EXISTS {name}; CREATE TABLE {name} ({columns});",
+            name = table.name,
+            columns = table.columns.join(", ")
+        );
+        db_tx.execute_batch(&sql).map_err(sqlite_error)?;
+        let placeholders = vec!["?"; table.columns.len()].join(", ");
+        let sql = format!("INSERT INTO {} VALUES ({})", table.name, placeholders);
+        let mut stmt = db_tx.prepare(&sql).map_err(sqlite_error)?;
+        for row in &table.rows {
+            let values = row.iter().map(|value| match value {
+                ExportValue::Null => Value::Null,
+                ExportValue::Integer(value) => Value::Integer(*value),
+                ExportValue::Text(value) => Value::Text(value.to_owned()),
+            });
+            stmt.execute(params_from_iter(values))
+                .map_err(sqlite_error)?;
+        }
+    }
+    db_tx.commit().map_err(sqlite_error)
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn write_sqlite(path: &Path, _tables: &[ExportTable]) -> Result<()> {
+    let errmsg = format!(
+        "failed to write {} since the feature \"sqlite\" is not enabled",
+        path.display()
+    );
+    Err(Error::config(errmsg))
+}
@@ -0,0 +1,42 @@
+use std::{cell::RefCell, thread, time};
+
+use rand::{rngs::StdRng, Rng as _, SeedableRng as _};
+
+use crate::{
+    error::{Error, Result},
+    types::StorageFaults,
+};
+
+// Fail or delay the write operations of the storage randomly, to test the error handling and
+// the atomicity of the storage.
+//
+// It has its own random generator, so the generated transactions are not affected by it.
+pub(crate) struct FaultInjector {
+    config: StorageFaults,
+    rng: RefCell<StdRng>,
+}
+
+impl FaultInjector {
+    pub(crate) fn new(config: &StorageFaults, seed: u64) -> Self {
+        Self {
+            config: config.clone(),
+            rng: RefCell::new(StdRng::seed_from_u64(seed)),
+        }
+    }
+
+    // Returns an error if a failure is injected into the operation.
+    pub(crate) fn before(&self, operation: &str) -> Result<()> {
+        let mut rng = self.rng.borrow_mut();
+        if rng.gen_range::<u32, _>(0..100) < self.config.delay_chance {
+            let millis = rng.gen_range(0..=self.config.max_delay_millis);
+            log::trace!("[Fault] delay {} for {} ms", operation, millis);
+            thread::sleep(time::Duration::from_millis(millis));
+        }
+        if rng.gen_range::<u64, _>(0..self.config.fail_one_in) == 0 {
+            log::warn!("[Fault] >>> inject a failure into {}", operation);
+            let errmsg = format!("injected failure of {}", operation);
+            return Err(Error::storage(errmsg));
+        }
+        Ok(())
+    }
+}
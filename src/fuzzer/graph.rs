@@ -0,0 +1,141 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use ckb_types::packed;
+
+use super::Storage;
+use crate::{
+    error::{Error, Result},
+    types::{GraphDirection, TxEdge, TxEventKind, TxGraph, TxNode},
+};
+
+// Build the dependency graph of all transactions, or only of the transactions which are
+// related to the chosen one, in the direction and up to the depth.
+pub(crate) fn build_graph(
+    storage: &Storage,
+    root: Option<&packed::Byte32>,
+    direction: GraphDirection,
+    depth: Option<usize>,
+) -> Result<TxGraph> {
+    let all_edges = storage.tx_edges()?;
+    let (tx_hashes, edges) = if let Some(root) = root {
+        log::info!("[Graph] export the {} of {:#x}", direction, root);
+        let tx_hashes = related_txs(&all_edges, root, direction, depth);
+        let edges = all_edges
+            .into_iter()
+            .filter(|edge| tx_hashes.contains(&edge.parent) && tx_hashes.contains(&edge.child))
+            .collect::<Vec<_>>();
+        let mut tx_hashes = tx_hashes.into_iter().collect::<Vec<_>>();
+        tx_hashes.sort_by(|a, b| a.as_slice().cmp(b.as_slice()));
+        (tx_hashes, edges)
+    } else {
+        let mut visited = HashSet::new();
+        let mut tx_hashes = Vec::new();
+        let statuses = storage.tx_statuses()?;
+        let endpoints = all_edges
+            .iter()
+            .flat_map(|edge| vec![edge.parent.clone(), edge.child.clone()]);
+        for tx_hash in statuses
+            .into_iter()
+            .map(|(tx_hash, _)| tx_hash)
+            .chain(endpoints)
+        {
+            if visited.insert(tx_hash.clone()) {
+                tx_hashes.push(tx_hash);
+            }
+        }
+        (tx_hashes, all_edges)
+    };
+    if let Some(root) = root {
+        if edges.is_empty() && !is_known(storage, root)? {
+            let errmsg = format!("can not found the tx {:#x}", root);
+            return Err(Error::storage(errmsg));
+        }
+    }
+    let nodes = tx_hashes
+        .into_iter()
+        .map(|tx_hash| build_node(storage, tx_hash))
+        .collect::<Result<Vec<_>>>()?;
+    log::info!(
+        "[Graph] {} transactions and {} edges",
+        nodes.len(),
+        edges.len()
+    );
+    Ok(TxGraph { nodes, edges })
+}
+
+// Walk the edges from the root breadth first, so the depth is the shortest distance.
+fn related_txs(
+    edges: &[TxEdge],
+    root: &packed::Byte32,
+    direction: GraphDirection,
+    depth: Option<usize>,
+) -> HashSet<packed::Byte32> {
+    let mut parents: HashMap<&packed::Byte32, Vec<&packed::Byte32>> = HashMap::new();
+    let mut children: HashMap<&packed::Byte32, Vec<&packed::Byte32>> = HashMap::new();
+    for edge in edges {
+        parents.entry(&edge.child).or_default().push(&edge.parent);
+        children.entry(&edge.parent).or_default().push(&edge.child);
+    }
+    let mut related = HashSet::new();
+    related.insert(root.clone());
+    let mut walks = Vec::new();
+    if direction != GraphDirection::Descendants {
+        walks.push(&parents);
+    }
+    if direction != GraphDirection::Ancestors {
+        walks.push(&children);
+    }
+    for neighbours in walks {
+        let mut visited = HashSet::new();
+        visited.insert(root);
+        let mut queue = VecDeque::new();
+        queue.push_back((root, 0));
+        while let Some((tx_hash, distance)) = queue.pop_front() {
+            if depth.map(|depth| distance >= depth).unwrap_or(false) {
+                continue;
+            }
+            for next in neighbours.get(tx_hash).into_iter().flatten() {
+                if visited.insert(*next) {
+                    related.insert((*next).clone());
+                    queue.push_back((*next, distance + 1));
+                }
+            }
+        }
+    }
+    related
+}
+
+fn is_known(storage: &Storage, tx_hash: &packed::Byte32) -> Result<bool> {
+    let known = storage.get_tx_status(tx_hash)?.is_some()
+        || storage.get_transaction(tx_hash)?.is_some()
+        || storage.get_tx_history(tx_hash)?.is_some();
+    Ok(known)
+}
+
+fn build_node(storage: &Storage, tx_hash: packed::Byte32) -> Result<TxNode> {
+    let status = if let Some(tx_status) = storage.get_tx_status(&tx_hash)? {
+        tx_status.name()
+    } else {
+        // Only the histories of the pruned or removed transactions are kept.
+        storage
+            .get_tx_history(&tx_hash)?
+            .and_then(|history| {
+                history
+                    .events
+                    .iter()
+                    .rev()
+                    .find_map(|event| match event.kind {
+                        TxEventKind::Pruned => Some("pruned"),
+                        TxEventKind::Removed => Some("removed"),
+                        _ => None,
+                    })
+            })
+            .unwrap_or("untracked")
+    };
+    let seq_id = storage.get_tx_seq_id(&tx_hash)?;
+    Ok(TxNode {
+        tx_hash,
+        status: status.to_owned(),
+        seq_id,
+    })
+}
@@ -0,0 +1,238 @@
+use std::result::Result as StdResult;
+
+use ckb_store::ChainStore as _;
+use ckb_types::core::{tx_pool::TxPoolEntryInfo, BlockView, FeeRate};
+
+use super::{MockedChain, Storage};
+use crate::{
+    error::{Error, Result},
+    types::{TxStage, TxStatus},
+};
+
+// The data which is passed to the post-block hooks.
+pub(crate) struct HookContext<'a> {
+    pub(crate) block: &'a BlockView,
+    pub(crate) chain: &'a MockedChain,
+    pub(crate) storage: &'a Storage,
+    pub(crate) pool: &'a TxPoolEntryInfo,
+}
+
+// A check which is run after each block is committed, returns the reason if failed.
+pub(crate) type PostBlockHook = fn(&HookContext) -> StdResult<(), String>;
+
+// All available hooks, new hooks should be registered here.
+const REGISTRY: &[(&str, PostBlockHook)] = &[
+    ("committed_left_pool", committed_left_pool),
+    ("committed_in_storage", committed_in_storage),
+    ("min_fee_rate", min_fee_rate),
+    ("proposal_stages", proposal_stages),
+    ("epoch_transition", epoch_transition),
+];
+
+// The names of all available post-block hooks.
+pub(crate) fn hook_names() -> Vec<&'static str> {
+    REGISTRY.iter().map(|(name, _)| *name).collect()
+}
+
+#[derive(Default)]
+pub(crate) struct PostBlockHooks {
+    hooks: Vec<(&'static str, PostBlockHook)>,
+}
+
+impl PostBlockHooks {
+    pub(crate) fn from_names(names: &[String]) -> Result<Self> {
+        let mut hooks = Vec::new();
+        for name in names {
+            let hook = REGISTRY
+                .iter()
+                .find(|(registered, _)| *registered == name.as_str())
+                .ok_or_else(|| {
+                    let available = hook_names().join(", ");
+                    let errmsg = format!(
+                        "post-block hook {} is unknown (available: {})",
+                        name, available
+                    );
+                    Error::config(errmsg)
+                })?;
+            hooks.push(*hook);
+        }
+        Ok(Self { hooks })
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    // Run all hooks, return the names and the reasons of the failed hooks.
+    pub(crate) fn run(&self, ctx: &HookContext) -> Vec<(&'static str, String)> {
+        self.hooks
+            .iter()
+            .filter_map(|(name, hook)| hook(ctx).err().map(|reason| (*name, reason)))
+            .collect()
+    }
+}
+
+// The committed transactions should be removed from the tx-pool.
+fn committed_left_pool(ctx: &HookContext) -> StdResult<(), String> {
+    for tx_hash in ctx.block.tx_hashes().iter().skip(1) {
+        if ctx.pool.pending.contains_key(tx_hash) || ctx.pool.proposed.contains_key(tx_hash) {
+            return Err(format!(
+                "committed tx {:#x} is still in the tx-pool",
+                tx_hash
+            ));
+        }
+    }
+    Ok(())
+}
+
+// The committed transactions should be committed in the storage, if they are known.
+fn committed_in_storage(ctx: &HookContext) -> StdResult<(), String> {
+    for tx_hash in ctx.block.tx_hashes().iter().skip(1) {
+        match ctx.storage.get_tx_status(tx_hash) {
+            Ok(Some(TxStatus::Committed(_))) | Ok(None) => {}
+            Ok(Some(tx_status)) => {
+                return Err(format!(
+                    "committed tx {:#x} is {} in the storage",
+                    tx_hash, tx_status
+                ));
+            }
+            Err(err) => {
+                return Err(format!("failed to load tx {:#x} since {}", tx_hash, err));
+            }
+        }
+    }
+    Ok(())
+}
+
+// All transactions in the tx-pool should pay at least the min fee rate.
+fn min_fee_rate(ctx: &HookContext) -> StdResult<(), String> {
+    let min_fee_rate = ctx.chain.tx_pool_config().min_fee_rate;
+    let entries = ctx.pool.pending.iter().chain(ctx.pool.proposed.iter());
+    for (tx_hash, info) in entries {
+        let fee_rate = FeeRate::calculate(info.fee, info.size as usize);
+        if fee_rate < min_fee_rate {
+            return Err(format!(
+                "tx {:#x} in the tx-pool has fee rate {} which is lower than {}",
+                tx_hash, fee_rate, min_fee_rate
+            ));
+        }
+    }
+    Ok(())
+}
+
+// The pending transactions in the storage should be in the same stages as in the tx-pool, by
+// the proposals of them.
+fn proposal_stages(ctx: &HookContext) -> StdResult<(), String> {
+    let window = ctx.chain.proposal_window();
+    let next_number = ctx.block.number() + 1;
+    let pending_txs = ctx
+        .storage
+        .pending_transactions()
+        .map_err(|err| format!("failed to load pending txs since {}", err))?;
+    for tx in pending_txs {
+        let tx_hash = tx.hash();
+        let stage = ctx
+            .storage
+            .tx_stage(&tx_hash, next_number, &window)
+            .map_err(|err| format!("failed to load tx {:#x} since {}", tx_hash, err))?;
+        let (mismatched, in_pool) = match stage {
+            Some(TxStage::Proposed) => (ctx.pool.pending.contains_key(&tx_hash), "pending"),
+            Some(_) => (ctx.pool.proposed.contains_key(&tx_hash), "proposed"),
+            // The proposals are not fully tracked yet.
+            None => (false, ""),
+        };
+        if mismatched {
+            return Err(format!(
+                "tx {:#x} is {} in the storage but {} in the tx-pool",
+                tx_hash,
+                stage.unwrap_or(TxStage::Pending),
+                in_pool
+            ));
+        }
+    }
+    Ok(())
+}
+
+// At the first block of an epoch, the epoch should follow the last one, its reward should be
+// the primary epoch reward after the halvings, the block should use its difficulty, and the
+// tx-pool should have moved to the block.
+fn epoch_transition(ctx: &HookContext) -> StdResult<(), String> {
+    let number = ctx.block.number();
+    let epoch = ctx.block.epoch();
+    if number == 0 || epoch.index() != 0 {
+        return Ok(());
+    }
+    let store = ctx.chain.store();
+    let current = store
+        .get_block_epoch(&ctx.block.hash())
+        .ok_or_else(|| format!("the epoch of block {} is not found", number))?;
+    let last = store
+        .get_block_epoch(&ctx.block.parent_hash())
+        .ok_or_else(|| format!("the epoch of block {} is not found", number - 1))?;
+    if current.number() != last.number() + 1 || current.number() != epoch.number() {
+        return Err(format!(
+            "block {} starts epoch {} but the last epoch is {} and the header has {}",
+            number,
+            current.number(),
+            last.number(),
+            epoch
+        ));
+    }
+    if current.start_number() != number || last.start_number() + last.length() != number {
+        return Err(format!(
+            "block {} starts epoch {} at {}, the last epoch starts at {} with {} blocks",
+            number,
+            current.number(),
+            current.start_number(),
+            last.start_number(),
+            last.length()
+        ));
+    }
+    if current.length() != epoch.length() {
+        return Err(format!(
+            "epoch {} has {} blocks but the header has {}",
+            current.number(),
+            current.length(),
+            epoch
+        ));
+    }
+    let reward = current.base_block_reward().as_u64() * current.length()
+        + current.remainder_reward().as_u64();
+    let expected_reward = ctx
+        .chain
+        .consensus()
+        .primary_epoch_reward(current.number())
+        .as_u64();
+    if reward != expected_reward {
+        return Err(format!(
+            "epoch {} rewards {} shannons but {} is expected",
+            current.number(),
+            reward,
+            expected_reward
+        ));
+    }
+    let compact_target = ctx.block.header().compact_target();
+    if compact_target != current.compact_target() {
+        return Err(format!(
+            "block {} has compact target {:#x} but epoch {} has {:#x}",
+            number,
+            compact_target,
+            current.number(),
+            current.compact_target()
+        ));
+    }
+    let info = ctx
+        .chain
+        .txpool_info()
+        .map_err(|err| format!("failed to get the tx-pool info since {}", err))?;
+    if info.tip_hash != ctx.block.hash() {
+        return Err(format!(
+            "the tx-pool is at {:#x} ({}) but block {} is {:#x}",
+            info.tip_hash,
+            info.tip_number,
+            number,
+            ctx.block.hash()
+        ));
+    }
+    Ok(())
+}
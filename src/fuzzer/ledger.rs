@@ -0,0 +1,209 @@
+use std::{collections::HashMap, result::Result as StdResult};
+
+use ckb_db::iter::IteratorMode;
+use ckb_db_schema::COLUMN_CELL;
+use ckb_store::ChainStore as _;
+use ckb_types::{
+    core::{BlockView, Capacity},
+    packed,
+    prelude::*,
+};
+
+use super::{
+    strategy::{dao_maximum_withdraw, has_type_script, DAO_DATA_SIZE},
+    MockedChain, Storage,
+};
+use crate::{
+    error::{Error, Result},
+    types::{CellStatus, Ledger, TxStatus},
+};
+
+// The capacity of a committed transaction, in shannons.
+pub(crate) struct TxCapacity {
+    pub(crate) inputs: u64,
+    // The interest of the withdrawn DAO cells, which is issued when they are withdrawn.
+    pub(crate) interest: u64,
+    pub(crate) outputs: u64,
+}
+
+impl TxCapacity {
+    // Nothing if the transaction creates capacity.
+    pub(crate) fn fee(&self) -> Option<u64> {
+        (self.inputs + self.interest).checked_sub(self.outputs)
+    }
+}
+
+// Load the ledger from the storage, or start a new ledger from the live cells in the chain.
+pub(crate) fn load_or_start(chain: &MockedChain, storage: &Storage) -> Result<Ledger> {
+    if let Some(ledger) = storage.get_ledger()? {
+        return Ok(ledger);
+    }
+    let live_capacity = live_capacity(chain)?;
+    log::info!(
+        "[Ledger] start the ledger from the live capacity {}",
+        live_capacity
+    );
+    Ok(Ledger {
+        issued: live_capacity,
+        ..Default::default()
+    })
+}
+
+// Record the capacity changes of a block which is already submitted to the chain, and before
+// it's confirmed in the storage.
+pub(crate) fn record_block(
+    ledger: &mut Ledger,
+    chain: &MockedChain,
+    storage: &Storage,
+    block: &BlockView,
+) -> Result<()> {
+    let cellbase_capacity = block
+        .transaction(0)
+        .map(|cellbase| cellbase.outputs_capacity())
+        .transpose()
+        .map_err(Error::runtime)?
+        .unwrap_or_else(Capacity::zero);
+    ledger.issued = checked_add(ledger.issued, cellbase_capacity.as_u64())?;
+    let capacities = committed_capacities(chain, block)?;
+    for (tx, tx_capacity) in block.transactions().into_iter().skip(1).zip(capacities) {
+        let fee = tx_capacity.fee().ok_or_else(|| {
+            let errmsg = format!("committed tx {:#x} creates capacity", tx.hash());
+            Error::runtime(errmsg)
+        })?;
+        ledger.issued = checked_add(ledger.issued, tx_capacity.interest)?;
+        ledger.fees = checked_add(ledger.fees, fee)?;
+        // The untracked transactions are treated as no burned cells.
+        let tx_status = storage.get_tx_status(&tx.hash())?;
+        if let Some(inner) = tx_status
+            .as_ref()
+            .filter(|tx_status| tx_status.is_uncommitted())
+            .and_then(TxStatus::outputs)
+        {
+            for (index, output) in tx.outputs().into_iter().enumerate() {
+                if index < inner.count() && *inner.status(index) == CellStatus::Burn {
+                    let capacity: Capacity = output.capacity().unpack();
+                    ledger.burned = checked_add(ledger.burned, capacity.as_u64())?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// The capacity of the committed transactions in the block, except the cellbase.
+//
+// The inputs are found in the chain or in the block, so it works either before or after the
+// block is submitted to the chain.
+pub(crate) fn committed_capacities(
+    chain: &MockedChain,
+    block: &BlockView,
+) -> Result<Vec<TxCapacity>> {
+    let store = chain.store();
+    let dao_type_hash = chain.dao_script().type_hash();
+    let in_block = block
+        .transactions()
+        .into_iter()
+        .map(|tx| (tx.hash(), tx))
+        .collect::<HashMap<_, _>>();
+    let mut capacities = Vec::with_capacity(in_block.len());
+    for tx in block.transactions().into_iter().skip(1) {
+        let outputs = tx.outputs_capacity().map_err(Error::runtime)?.as_u64();
+        let mut capacity = TxCapacity {
+            inputs: 0,
+            interest: 0,
+            outputs,
+        };
+        for out_point in tx.input_pts_iter() {
+            let tx_hash = out_point.tx_hash();
+            let index: u32 = out_point.index().unpack();
+            let (output, data) = in_block
+                .get(&tx_hash)
+                .cloned()
+                .or_else(|| {
+                    store
+                        .get_transaction(&tx_hash)
+                        .map(|(input_tx, _)| input_tx)
+                })
+                .and_then(|input_tx| input_tx.output_with_data(index as usize))
+                .ok_or_else(|| {
+                    let errmsg = format!(
+                        "failed to find the input {:#x} of committed tx {:#x}",
+                        tx_hash,
+                        tx.hash()
+                    );
+                    Error::runtime(errmsg)
+                })?;
+            let input_capacity: Capacity = output.capacity().unpack();
+            capacity.inputs = checked_add(capacity.inputs, input_capacity.as_u64())?;
+            // A withdrawing DAO cell keeps the number of the deposit block as its data.
+            let is_withdrawing = has_type_script(&output, &dao_type_hash)
+                && data.len() == DAO_DATA_SIZE
+                && data.iter().any(|byte| *byte != 0);
+            if is_withdrawing {
+                let maximum = dao_withdraw_capacity(chain, &tx_hash, &output, &data)?;
+                let interest = maximum.saturating_sub(input_capacity.as_u64());
+                capacity.interest = checked_add(capacity.interest, interest)?;
+            }
+        }
+        capacities.push(capacity);
+    }
+    Ok(capacities)
+}
+
+// The capacity of a withdrawing DAO cell with the interest, from the deposit to the prepare.
+fn dao_withdraw_capacity(
+    chain: &MockedChain,
+    tx_hash: &packed::Byte32,
+    output: &packed::CellOutput,
+    data: &[u8],
+) -> Result<u64> {
+    let store = chain.store();
+    let mut bytes = [0u8; DAO_DATA_SIZE];
+    bytes.copy_from_slice(data);
+    let deposit_number = u64::from_le_bytes(bytes);
+    let deposit_header = store
+        .get_block_hash(deposit_number)
+        .and_then(|block_hash| store.get_block_header(&block_hash));
+    let prepare_header = store
+        .get_transaction_info(tx_hash)
+        .and_then(|info| store.get_block_header(&info.block_hash));
+    if let (Some(deposit_header), Some(prepare_header)) = (deposit_header, prepare_header) {
+        dao_maximum_withdraw(output, &deposit_header, &prepare_header)
+    } else {
+        let errmsg = format!(
+            "the blocks of the DAO cell {:#x} are not found (deposit: {})",
+            tx_hash, deposit_number
+        );
+        Err(Error::runtime(errmsg))
+    }
+}
+
+// Invariant: the capacity of all live cells in the chain is the issued capacity minus the
+// collected fees.
+pub(crate) fn verify(ledger: &Ledger, chain: &MockedChain) -> Result<StdResult<(), String>> {
+    let actual = live_capacity(chain)?;
+    let result = match ledger.expected_live_capacity() {
+        Some(expected) if expected == actual => Ok(()),
+        Some(expected) => Err(format!(
+            "the live capacity in chain is {} but the ledger expects {} ({})",
+            actual, expected, ledger
+        )),
+        None => Err(format!("the fees are more than the issued ({})", ledger)),
+    };
+    Ok(result)
+}
+
+fn live_capacity(chain: &MockedChain) -> Result<u64> {
+    let mut total = 0;
+    for (_, value) in chain.store().get_iter(COLUMN_CELL, IteratorMode::Start) {
+        let entry = packed::CellEntryReader::from_slice(&value).map_err(Error::storage)?;
+        let capacity: Capacity = entry.output().capacity().unpack();
+        total = checked_add(total, capacity.as_u64())?;
+    }
+    Ok(total)
+}
+
+fn checked_add(lhs: u64, rhs: u64) -> Result<u64> {
+    lhs.checked_add(rhs)
+        .ok_or_else(|| Error::runtime("the capacity in the ledger overflows"))
+}
@@ -0,0 +1,165 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    str::FromStr as _,
+};
+
+use super::{watchdog::HANG_EXIT_CODE, REPORT_FILE};
+use crate::{
+    error::{Error, Result},
+    types::{apply_combination, Combination, MetaData, RunEnv, RunReport, SessionOutcome, Verdict},
+    utils,
+};
+
+// The files in the directory of a session.
+const SESSION_INIT_CONFIG_FILE: &str = "init.yaml";
+const SESSION_RUN_CONFIG_FILE: &str = "run.yaml";
+const SESSION_LOG_FILE: &str = "session.log";
+const SESSION_DATA_DIR: &str = "data";
+
+// Override the base configs by the combination, the combination is invalid if the overridden
+// configs are rejected, for example, it conflicts with the base run config.
+pub(crate) fn build_session_configs(
+    combination: &Combination,
+    meta_data: &MetaData,
+    run_env: &RunEnv,
+) -> Result<(MetaData, RunEnv)> {
+    let mut meta_data = meta_data.clone();
+    let mut run_env = run_env.clone();
+    apply_combination(combination, &mut meta_data, &mut run_env);
+    // Check the overridden configs as the subcommands will do.
+    let meta_data = MetaData::from_str(&meta_data.to_string()).map_err(Error::config)?;
+    let run_env = RunEnv::from_str(&run_env.to_string()).map_err(Error::config)?;
+    Ok((meta_data, run_env))
+}
+
+// Run a session in its own temporary directory, by the subcommands "init" and "run" of the
+// current executable, since a mismatch or a hang exits the process.
+//
+// The directory is removed if the session is passed, otherwise it's kept as "session-<index>"
+// in the output directory.
+pub(crate) fn run_session(
+    index: usize,
+    combination: Combination,
+    meta_data: &MetaData,
+    run_env: &RunEnv,
+    output_dir: &Path,
+) -> Result<SessionOutcome> {
+    let temp_dir = tempfile::Builder::new()
+        .prefix("session-")
+        .tempdir_in(output_dir)
+        .map_err(|err| {
+            let errmsg = format!("failed to create temporary directory since {}", err);
+            Error::runtime(errmsg)
+        })?;
+    let init_file = temp_dir.path().join(SESSION_INIT_CONFIG_FILE);
+    let run_file = temp_dir.path().join(SESSION_RUN_CONFIG_FILE);
+    let data_dir = temp_dir.path().join(SESSION_DATA_DIR);
+    utils::fs::write_file(&init_file, meta_data.to_string())?;
+    utils::fs::write_file(&run_file, run_env.to_string())?;
+    let log_file = temp_dir.path().join(SESSION_LOG_FILE);
+
+    let mut exit_code = run_subcommand("init", &data_dir, &init_file, &log_file)?;
+    if exit_code == Some(0) {
+        exit_code = run_subcommand("run", &data_dir, &run_file, &log_file)?;
+    }
+    let report = utils::fs::read_file_opt(data_dir.join(REPORT_FILE))?
+        .map(|data| {
+            RunReport::from_str(&data).map_err(|err| {
+                let errmsg = format!(
+                    "failed to parse the report of session {} since {}",
+                    index, err
+                );
+                Error::runtime(errmsg)
+            })
+        })
+        .transpose()?;
+    let (mismatches, divergence) = report
+        .map(|report| (report.mismatches.total(), report.divergence))
+        .unwrap_or_default();
+    // The mismatches which are only logged by the policies don't fail the run.
+    let verdict = match exit_code {
+        Some(HANG_EXIT_CODE) => Verdict::Hung,
+        Some(0) if mismatches == 0 => Verdict::Passed,
+        _ if mismatches > 0 || divergence.is_some() => Verdict::Mismatched,
+        _ => Verdict::Failed,
+    };
+
+    let kept_dir = if verdict == Verdict::Passed {
+        None
+    } else {
+        let kept_dir = output_dir.join(format!("session-{}", index));
+        let temp_path = temp_dir.into_path();
+        fs::rename(&temp_path, &kept_dir).map_err(|err| {
+            let errmsg = format!(
+                "failed to keep {} as {} since {}",
+                temp_path.display(),
+                kept_dir.display(),
+                err
+            );
+            Error::runtime(errmsg)
+        })?;
+        Some(kept_dir)
+    };
+    Ok(SessionOutcome {
+        index,
+        combination,
+        verdict,
+        exit_code,
+        mismatches,
+        divergence,
+        data_dir: kept_dir,
+    })
+}
+
+// Run a subcommand of the current executable, its outputs are appended into the log file.
+//
+// The exit code is none if the process is terminated by a signal.
+fn run_subcommand(
+    subcmd: &str,
+    data_dir: &Path,
+    config_file: &Path,
+    log_file: &Path,
+) -> Result<Option<i32>> {
+    let exe = current_exe()?;
+    let open_log = || {
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_file)
+            .map_err(|err| {
+                let errmsg = format!("failed to open {} since {}", log_file.display(), err);
+                Error::runtime(errmsg)
+            })
+    };
+    let stdout = open_log()?;
+    let stderr = open_log()?;
+    let status = Command::new(&exe)
+        .arg(subcmd)
+        .arg("--data-dir")
+        .arg(data_dir)
+        .arg("--config-file")
+        .arg(config_file)
+        .stdin(Stdio::null())
+        .stdout(stdout)
+        .stderr(stderr)
+        .status()
+        .map_err(|err| {
+            let errmsg = format!(
+                "failed to execute {} {} since {}",
+                exe.display(),
+                subcmd,
+                err
+            );
+            Error::runtime(errmsg)
+        })?;
+    Ok(status.code())
+}
+
+fn current_exe() -> Result<PathBuf> {
+    env::current_exe().map_err(|err| {
+        let errmsg = format!("failed to find the current executable since {}", err);
+        Error::runtime(errmsg)
+    })
+}
@@ -1,19 +1,22 @@
 use std::{
-    collections::{HashSet, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     path::{Path, PathBuf},
-    sync::Arc,
+    result::Result as StdResult,
+    sync::{Arc, Mutex},
+    time,
 };
 
 use ckb_app_config::{BlockAssemblerConfig, NetworkConfig, TxPoolConfig};
 use ckb_async_runtime::{new_global_runtime, Handle};
 use ckb_chain_spec::{
     build_genesis_type_id_script, calculate_block_reward,
-    consensus::{build_genesis_epoch_ext, Consensus, ConsensusBuilder},
+    consensus::{build_genesis_epoch_ext, Consensus, ConsensusBuilder, ProposalWindow},
     OUTPUT_INDEX_DAO,
 };
 use ckb_channel::Receiver;
-use ckb_dao_utils::genesis_dao_data_with_satoshi_gift;
-use ckb_network::{DefaultExitHandler, NetworkController, NetworkService, NetworkState};
+use ckb_dao_utils::{extract_dao_data, genesis_dao_data_with_satoshi_gift, pack_dao_data};
+use ckb_hash::blake2b_256;
+use ckb_network::{DefaultExitHandler, NetworkController, NetworkService, NetworkState, PeerIndex};
 use ckb_pow::Pow;
 use ckb_proposal_table::{ProposalTable, ProposalView};
 use ckb_script::mock::MockedScripts;
@@ -27,47 +30,92 @@ use ckb_tx_pool::{
     TxPoolServiceBuilder,
 };
 use ckb_types::{
+    bytes::Bytes,
     core::{
-        capacity_bytes, hardfork::HardForkSwitch, tx_pool::Reject, BlockView, Capacity, DepType,
-        EpochExt, EpochNumber, EpochNumberWithFraction, FeeRate, HeaderView, ScriptHashType,
-        TransactionView,
+        capacity_bytes,
+        hardfork::HardForkSwitch,
+        tx_pool::{Reject, TxPoolEntryInfo, TxPoolInfo},
+        BlockNumber, BlockView, Capacity, Cycle, DepType, EpochExt, EpochNumber,
+        EpochNumberWithFraction, FeeRate, HeaderView, ScriptHashType, TransactionView, Version,
     },
     packed,
     prelude::*,
+    H256,
 };
 use ckb_verification::cache::init_cache;
 use ckb_verification_traits::Verifier;
 
-use super::MockedStore;
+use super::{ledger, Clock, MockedStore, WatchGuard, Watchdog};
 use crate::{
     error::{Error, Result},
-    types::{ChainSpec, Params, ScriptAnchor},
-    utils,
+    types::{
+        CellStatus, CellbaseOrigin, ChainSpec, ImportedChain, IssuedLock, Keyring, Params,
+        PowEngineKind, RunEnv, ScriptAnchor, Signer, TxOutputsStatus, TxStatus,
+    },
+    utils::{self, metrics::Operation},
 };
 
 const CONSENSUS_ID: &str = "ckb-txpool-fuzzer";
 const NETWORK_NAME: &str = "CKB Mocked Network";
+// Give up sealing a block after that many nonces, the difficulty is too high for a fuzzer then.
+const MAX_SEAL_ATTEMPTS: u128 = 1 << 24;
+
+// The transactions which are passed to the reject callback, and whether they could be found in
+// the recent rejects of the tx-pool just after they are put.
+type RecentRejects = Arc<Mutex<HashMap<packed::Byte32, bool>>>;
+
+// The outputs of the genesis cellbase which deploy the secp256k1 locks, if they are enabled.
+const OUTPUT_INDEX_SECP256K1: usize = 4;
+const OUTPUT_INDEX_SECP256K1_DATA: usize = 5;
+const OUTPUT_INDEX_SECP256K1_MULTISIG: usize = 6;
+const CELL_SECP256K1: &str = "specs/cells/secp256k1_blake160_sighash_all";
+const CELL_SECP256K1_DATA: &str = "specs/cells/secp256k1_data";
+const CELL_SECP256K1_MULTISIG: &str = "specs/cells/secp256k1_blake160_multisig_all";
+
+// The since of an input: the relative flag, the metric flags and the value.
+const SINCE_RELATIVE_FLAG: u64 = 0x8000_0000_0000_0000;
+const SINCE_METRIC_MASK: u64 = 0x6000_0000_0000_0000;
+const SINCE_METRIC_TIMESTAMP: u64 = 0x4000_0000_0000_0000;
+const SINCE_VALUE_MASK: u64 = 0x00ff_ffff_ffff_ffff;
 
-pub(crate) struct MockedChain {
+// Log the progress of importing a chain after that many blocks.
+const IMPORT_LOG_INTERVAL: BlockNumber = 10_000;
+
+pub struct MockedChain {
     consensus: Arc<Consensus>,
     store: MockedStore,
     current_snapshot: Arc<Snapshot>,
+    clock: Clock,
     _handle: Handle,
     _stop_handler: StopHandler<()>,
     tx_pool_controller: TxPoolController,
+    tx_pool_config: TxPoolConfig,
+    max_tx_size: u64,
+    always_sucess: ScriptAnchor,
+    dao_script: ScriptAnchor,
+    custom_scripts: Vec<(ScriptAnchor, bool)>,
+    keyring: Option<Keyring>,
     _network_controller: NetworkController,
-    _tx_relay_receiver: Receiver<TxVerificationResult>,
+    tx_relay_receiver: Receiver<TxVerificationResult>,
     proposal_table: ProposalTable,
+    recent_rejects: RecentRejects,
+    request_timeout: Option<time::Duration>,
+    watchdog: Option<Watchdog>,
 }
 
 // Init
 impl MockedChain {
-    pub(crate) fn init<P: AsRef<Path>>(data_dir: P, cfg: &ChainSpec) -> Result<()> {
+    // Returns the status of the genesis cellbase if there are issued cells, which are the live
+    // cells since the beginning.
+    pub(crate) fn init<P: AsRef<Path>>(
+        data_dir: P,
+        cfg: &ChainSpec,
+    ) -> Result<Option<(packed::Byte32, TxStatus)>> {
         let store_dir = data_dir.as_ref().join("chain");
         utils::fs::check_directory(&store_dir, false)?;
         let store = MockedStore::init(store_dir);
 
-        let consensus = Arc::new(Self::build_consensus(cfg)?);
+        let consensus = Arc::new(Self::build_consensus(cfg, &store)?);
         ckb_verification::GenesisVerifier::new()
             .verify(&consensus)
             .map_err(|err| {
@@ -76,20 +124,77 @@ impl MockedChain {
             })?;
 
         store.store().init(&consensus).map_err(Error::runtime)?;
-        Ok(())
+        Ok(Self::genesis_cellbase_status(
+            consensus.genesis_block(),
+            cfg,
+        ))
+    }
+
+    fn genesis_cellbase_status(
+        genesis_block: &BlockView,
+        cfg: &ChainSpec,
+    ) -> Option<(packed::Byte32, TxStatus)> {
+        let issued_count = cfg
+            .genesis
+            .issued_cells
+            .as_deref()
+            .unwrap_or(&[])
+            .iter()
+            .map(|cells| cells.count as usize)
+            .sum::<usize>();
+        if issued_count == 0 {
+            return None;
+        }
+        let binaries_count = cfg.genesis.scripts.as_deref().unwrap_or(&[]).len();
+        let cellbase = genesis_block.transaction(0).unwrap();
+        let outputs_count = cellbase.outputs().len();
+        // The issued cells are just before the script binaries.
+        let issued_end = outputs_count - binaries_count;
+        let issued_start = issued_end - issued_count;
+        let statuses = (0..outputs_count)
+            .map(|index| {
+                if (issued_start..issued_end).contains(&index) {
+                    CellStatus::Live
+                } else {
+                    CellStatus::Burn
+                }
+            })
+            .collect();
+        let tx_status = TxStatus::Committed(TxOutputsStatus {
+            statuses,
+            cellbase: None,
+        });
+        Some((cellbase.hash(), tx_status))
+    }
+
+    // The genesis block is built from the config, or it's the genesis block of the imported
+    // chain, which is already in the store.
+    fn build_consensus(cfg: &ChainSpec, store: &MockedStore) -> Result<Consensus> {
+        let (genesis_block, genesis_epoch_ext) = if cfg.imported.is_some() {
+            Self::genesis_from_store(store.store())?
+        } else {
+            let genesis_epoch_ext = build_genesis_epoch_ext(
+                cfg.params.initial_primary_epoch_reward(),
+                cfg.genesis.compact_target,
+                cfg.params.genesis_epoch_length(),
+                cfg.params.epoch_duration_target(),
+                cfg.params.orphan_rate_target(),
+            );
+            (Self::build_genesis_block(cfg)?, genesis_epoch_ext)
+        };
+        Self::build_consensus_with(cfg, genesis_block, genesis_epoch_ext)
     }
 
-    fn build_consensus(cfg: &ChainSpec) -> Result<Consensus> {
+    fn build_consensus_with(
+        cfg: &ChainSpec,
+        genesis_block: BlockView,
+        genesis_epoch_ext: EpochExt,
+    ) -> Result<Consensus> {
         let hardfork_switch = Self::build_hardfork_switch(&cfg.params)?;
-        let genesis_epoch_ext = build_genesis_epoch_ext(
-            cfg.params.initial_primary_epoch_reward(),
-            cfg.genesis.compact_target,
-            cfg.params.genesis_epoch_length(),
-            cfg.params.epoch_duration_target(),
-            cfg.params.orphan_rate_target(),
-        );
-        let genesis_block = Self::build_genesis_block(cfg)?;
-        let pow = Pow::Dummy;
+        let pow = match cfg.pow.unwrap_or(PowEngineKind::Dummy) {
+            PowEngineKind::Dummy => Pow::Dummy,
+            PowEngineKind::Eaglesong => Pow::Eaglesong,
+        };
         let consensus = ConsensusBuilder::new(genesis_block, genesis_epoch_ext)
             .id(CONSENSUS_ID.to_owned())
             .cellbase_maturity(EpochNumberWithFraction::from_full_value(
@@ -126,6 +231,9 @@ impl MockedChain {
     //   - Deploy always success script.
     //   - Burned cell.
     //   - Input cell for tx1.
+    //   - Deploy secp256k1 locks and their data, if the keyring is enabled.
+    //   - Issued cells, if any.
+    //   - Deploy the script binaries of the users, if any.
     // - tx1: Deploy always success script again with type script.
     fn build_genesis_block(cfg: &ChainSpec) -> Result<BlockView> {
         let (_, script_data, _) = always_success_cell();
@@ -173,6 +281,41 @@ impl MockedChain {
                     .build()
             };
 
+            let secp256k1_names = if cfg.genesis.secp256k1_keys.is_some() {
+                vec![CELL_SECP256K1, CELL_SECP256K1_DATA, CELL_SECP256K1_MULTISIG]
+            } else {
+                Vec::new()
+            };
+            let (outputs_secp256k1, outputs_data_secp256k1): (Vec<_>, Vec<_>) = secp256k1_names
+                .into_iter()
+                .map(|name| {
+                    let data = BUNDLED_CELL.get(name).unwrap().into_owned();
+                    let output = packed::CellOutput::new_builder()
+                        .build_exact_capacity(Capacity::bytes(data.len()).unwrap())
+                        .unwrap();
+                    (output, data.pack())
+                })
+                .unzip();
+            let outputs_issued =
+                Self::build_issued_cells(cfg, &script_as_data_hash_type.calc_script_hash())?;
+            let outputs_data_issued = vec![packed::Bytes::default(); outputs_issued.len()];
+            // The type IDs of the script binaries are derived from their output indexes.
+            let binaries_start =
+                OUTPUT_INDEX_SECP256K1 + outputs_secp256k1.len() + outputs_issued.len();
+            let (outputs_binaries, outputs_data_binaries): (Vec<_>, Vec<_>) =
+                Self::load_script_binaries(cfg)?
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, data)| {
+                        let type_id = build_genesis_type_id_script((binaries_start + index) as u64);
+                        let output = packed::CellOutput::new_builder()
+                            .type_(Some(type_id).pack())
+                            .build_exact_capacity(Capacity::bytes(data.len()).unwrap())
+                            .unwrap();
+                        (output, data.pack())
+                    })
+                    .unzip();
+
             TransactionView::new_advanced_builder()
                 .input(input)
                 // Cell 0: always success script
@@ -188,6 +331,16 @@ impl MockedChain {
                 // Cell 3: burned
                 .output(output_burned)
                 .output_data(Default::default())
+                // Cell 4, 5 and 6: secp256k1 lock, its data and multisig lock, if the keyring
+                // is enabled.
+                .outputs(outputs_secp256k1)
+                .outputs_data(outputs_data_secp256k1)
+                // Then the issued cells, if any.
+                .outputs(outputs_issued)
+                .outputs_data(outputs_data_issued)
+                // The rest cells: the script binaries of the users.
+                .outputs(outputs_binaries)
+                .outputs_data(outputs_data_binaries)
                 .witness(script_as_data_hash_type.clone().into_witness())
                 .build()
         };
@@ -241,45 +394,441 @@ impl MockedChain {
             .build();
         Ok(genesis_block)
     }
+
+    // The issued cells are locked by the mocked script in the type hash type, or by the secp256k1
+    // locks of the keyring.
+    fn build_issued_cells(
+        cfg: &ChainSpec,
+        mocked_type_hash: &packed::Byte32,
+    ) -> Result<Vec<packed::CellOutput>> {
+        let issued_cells = cfg.genesis.issued_cells.as_deref().unwrap_or(&[]);
+        // Only the lock scripts are used, so the cell deps are not required.
+        let keyring = cfg
+            .genesis
+            .secp256k1_keys
+            .filter(|_| !issued_cells.is_empty())
+            .map(|keys_count| {
+                let code_hash = |name: &str| {
+                    let data = BUNDLED_CELL.get(name).unwrap();
+                    packed::CellOutput::calc_data_hash(&data)
+                };
+                Keyring::new(
+                    Vec::new(),
+                    code_hash(CELL_SECP256K1),
+                    code_hash(CELL_SECP256K1_MULTISIG),
+                    keys_count,
+                )
+            })
+            .transpose()?;
+        let mocked_lock = packed::Script::new_builder()
+            .code_hash(mocked_type_hash.to_owned())
+            .hash_type(ScriptHashType::Type.into())
+            .args(Self::always_success_args())
+            .build();
+        let mut outputs = Vec::new();
+        let mut key_index = 0;
+        for cells in issued_cells {
+            let capacity = Capacity::bytes(cells.capacity as usize).map_err(|err| {
+                let errmsg = format!(
+                    "the capacity {} of the issued cells is overflow since {}",
+                    cells.capacity, err
+                );
+                Error::config(errmsg)
+            })?;
+            for _ in 0..cells.count {
+                let lock = match (cells.lock, keyring.as_ref()) {
+                    (IssuedLock::Secp256k1, Some(keyring)) => {
+                        let signer = Signer::Single(key_index % keyring.keys_count());
+                        key_index += 1;
+                        keyring.lock_script(signer)
+                    }
+                    _ => mocked_lock.clone(),
+                };
+                let output = packed::CellOutput::new_builder()
+                    .capacity(capacity.pack())
+                    .lock(lock)
+                    .build();
+                let occupied = output.occupied_capacity(Capacity::zero()).unwrap();
+                if occupied > capacity {
+                    let errmsg = format!(
+                        "the capacity {} of the issued cells is less than the occupied capacity {}",
+                        capacity, occupied
+                    );
+                    return Err(Error::config(errmsg));
+                }
+                outputs.push(output);
+            }
+        }
+        Ok(outputs)
+    }
+
+    // The args of the mocked script which passes with a few cycles.
+    fn always_success_args() -> packed::Bytes {
+        let mut args = vec![0u8; 32];
+        let result_bytes = 0u64.to_le_bytes();
+        let cycles_bytes = 500u64.to_le_bytes();
+        (&mut args[0..8]).copy_from_slice(&result_bytes);
+        (&mut args[8..16]).copy_from_slice(&cycles_bytes);
+        (&mut args[16..24]).copy_from_slice(&result_bytes);
+        (&mut args[24..32]).copy_from_slice(&cycles_bytes);
+        args.pack()
+    }
+
+    // Read the script binaries of the users, and check them by their hashes.
+    fn load_script_binaries(cfg: &ChainSpec) -> Result<Vec<Bytes>> {
+        let binaries = cfg.genesis.scripts.as_deref().unwrap_or(&[]);
+        let mut loaded = Vec::with_capacity(binaries.len());
+        for binary in binaries {
+            let data = utils::fs::read_binary(&binary.path)?;
+            let hash = H256::from(blake2b_256(&data));
+            if hash != binary.hash {
+                let errmsg = format!(
+                    "the hash of the script binary {} is {:#x} but {:#x} is expected",
+                    binary.path.display(),
+                    hash,
+                    binary.hash
+                );
+                return Err(Error::config(errmsg));
+            }
+            loaded.push(Bytes::from(data));
+        }
+        Ok(loaded)
+    }
+}
+
+// Import
+impl MockedChain {
+    // Copy the main chain of a CKB node up to the height, then append a bootstrap block which
+    // deploys the mocked script and the issued cells, since the fuzzer could not unlock any cell
+    // of the node.
+    //
+    // The node should be stopped, and its store should be in the same schema as the CKB which
+    // the fuzzer is built with. The imported blocks are not verified again, and the bootstrap
+    // block is not verified at all, the following blocks are verified by the params of the
+    // config, so they should be the same as the params of the node.
+    //
+    // Returns the imported chain, and the statuses of the imported transactions which have any
+    // unspent outputs, they are burned since they are locked by others, except the issued cells.
+    pub(crate) fn import<P: AsRef<Path>>(
+        data_dir: P,
+        cfg: &ChainSpec,
+        source: &Path,
+        to_height: Option<BlockNumber>,
+    ) -> Result<(ImportedChain, Vec<(packed::Byte32, TxStatus)>)> {
+        let source_dir = source.join("db");
+        utils::fs::check_directory(&source_dir, true)?;
+        let source_store = MockedStore::init(&source_dir);
+        let source_db = source_store.store();
+        let source_tip = source_db
+            .get_tip_header()
+            .map(|header| header.number())
+            .ok_or_else(|| Error::runtime("the tip of the imported chain is not found"))?;
+        let tip_number = to_height.unwrap_or(source_tip);
+        if tip_number > source_tip {
+            let errmsg = format!(
+                "the height {} is higher than the tip {} of {}",
+                tip_number,
+                source_tip,
+                source.display()
+            );
+            return Err(Error::config(errmsg));
+        }
+
+        let (genesis_block, genesis_epoch_ext) = Self::genesis_from_store(source_db)?;
+        let consensus = Self::build_consensus_with(cfg, genesis_block, genesis_epoch_ext)?;
+        let store_dir = data_dir.as_ref().join("chain");
+        utils::fs::check_directory(&store_dir, false)?;
+        let store = MockedStore::init(store_dir);
+        store.store().init(&consensus).map_err(Error::runtime)?;
+
+        log::info!(
+            "[Import] import {} blocks from {}",
+            tip_number,
+            source.display()
+        );
+        // The outputs of all transactions, including the genesis transactions.
+        let mut tx_hashes = Vec::new();
+        let mut outputs: HashMap<packed::Byte32, Vec<CellStatus>> = HashMap::new();
+        for number in 0..=tip_number {
+            let block = Self::block_from_store(source_db, number)?;
+            let block_hash = block.hash();
+            if number > 0 {
+                let block_ext = source_db.get_block_ext(&block_hash).ok_or_else(|| {
+                    let errmsg = format!("the ext of block {} is not found", number);
+                    Error::runtime(errmsg)
+                })?;
+                let epoch_ext = Self::epoch_ext_from_store(source_db, &block_hash)?;
+                store.insert_block(
+                    &block,
+                    &epoch_ext,
+                    block_ext.received_at,
+                    block_ext.txs_fees,
+                );
+                store.attach_block(&block_hash);
+            }
+            for tx in block.transactions() {
+                for out_point in tx.input_pts_iter() {
+                    let index: u32 = out_point.index().unpack();
+                    if let Some(status) = outputs
+                        .get_mut(&out_point.tx_hash())
+                        .and_then(|statuses| statuses.get_mut(index as usize))
+                    {
+                        *status = CellStatus::Dead;
+                    }
+                }
+                outputs.insert(tx.hash(), vec![CellStatus::Burn; tx.outputs().len()]);
+                tx_hashes.push(tx.hash());
+            }
+            if number % IMPORT_LOG_INTERVAL == 0 {
+                log::info!("[Import] block {} / {}", number, tip_number);
+            }
+        }
+
+        let parent = Self::block_from_store(store.store(), tip_number)?.header();
+        let epoch_ext = consensus
+            .next_epoch_ext(&parent, &store.store().as_data_provider())
+            .ok_or_else(|| Error::runtime("the epoch of the bootstrap block is not found"))?
+            .epoch();
+        let bootstrap_block = Self::build_bootstrap_block(cfg, &parent, &epoch_ext)?;
+        let bootstrap_hash = bootstrap_block.hash();
+        let txs_fees = vec![Capacity::zero(); bootstrap_block.transactions().len() - 1];
+        store.insert_block(
+            &bootstrap_block,
+            &epoch_ext,
+            bootstrap_block.timestamp(),
+            txs_fees,
+        );
+        store.attach_block(&bootstrap_hash);
+        // Only set the tip at last, an interrupted import should be initialized again.
+        store.set_block_as_tip(&bootstrap_hash);
+
+        let mut tx_statuses = tx_hashes
+            .into_iter()
+            .filter_map(|tx_hash| {
+                let statuses = outputs.remove(&tx_hash)?;
+                if statuses.contains(&CellStatus::Burn) {
+                    let tx_status = TxStatus::Committed(TxOutputsStatus {
+                        statuses,
+                        cellbase: None,
+                    });
+                    Some((tx_hash, tx_status))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        // The issued cells are just after the mocked script.
+        let bootstrap_tx = bootstrap_block.transaction(1).unwrap();
+        let statuses = (0..bootstrap_tx.outputs().len())
+            .map(|index| {
+                if index == 0 {
+                    CellStatus::Burn
+                } else {
+                    CellStatus::Live
+                }
+            })
+            .collect();
+        tx_statuses.push((
+            bootstrap_tx.hash(),
+            TxStatus::Committed(TxOutputsStatus {
+                statuses,
+                cellbase: None,
+            }),
+        ));
+        log::info!(
+            "[Import] {} transactions have unspent cells, bootstrap block {:#x}",
+            tx_statuses.len(),
+            bootstrap_hash
+        );
+        let imported = ImportedChain {
+            source: source.to_owned(),
+            tip_number,
+            bootstrap_block: bootstrap_hash.unpack(),
+        };
+        Ok((imported, tx_statuses))
+    }
+
+    // Transactions in the Bootstrap Block:
+    // - tx0: Cellbase, without any outputs.
+    // - tx1: Deploy always success script with type script, then the issued cells, if any.
+    //
+    // The capacity of tx1 is created from nothing, it's added into the DAO field, so the DAO
+    // field is still consistent with the live cells.
+    fn build_bootstrap_block(
+        cfg: &ChainSpec,
+        parent: &HeaderView,
+        epoch_ext: &EpochExt,
+    ) -> Result<BlockView> {
+        let number = parent.number() + 1;
+        let (_, script_data, _) = always_success_cell();
+        let script_data_capacity = Capacity::bytes(script_data.len()).unwrap();
+        let script_data_hash = packed::CellOutput::calc_data_hash(script_data);
+        let script_as_data_hash_type = packed::Script::new_builder()
+            .hash_type(ScriptHashType::Data.into())
+            .code_hash(script_data_hash)
+            .build();
+        let script_hash = script_as_data_hash_type.calc_script_hash();
+
+        // The rewards of the bootstrap block are paid to the block assembler.
+        let cellbase = {
+            let lock = packed::Script::new_builder()
+                .code_hash(script_hash.clone())
+                .hash_type(ScriptHashType::Type.into())
+                .args(Self::always_success_args())
+                .build();
+            TransactionView::new_advanced_builder()
+                .input(packed::CellInput::new_cellbase_input(number))
+                .witness(lock.into_witness())
+                .build()
+        };
+
+        let tx1 = {
+            let output_script = packed::CellOutput::new_builder()
+                .type_(Some(script_as_data_hash_type).pack())
+                .build_exact_capacity(script_data_capacity)
+                .unwrap();
+            let outputs_issued = Self::build_issued_cells(cfg, &script_hash)?;
+            let outputs_data_issued = vec![packed::Bytes::default(); outputs_issued.len()];
+            TransactionView::new_advanced_builder()
+                .output(output_script)
+                .output_data(script_data.pack())
+                .outputs(outputs_issued)
+                .outputs_data(outputs_data_issued)
+                .build()
+        };
+
+        let dao = {
+            let (ar, total, secondary, occupied) =
+                extract_dao_data(parent.dao()).map_err(Error::runtime)?;
+            let issued = tx1.outputs_capacity().map_err(Error::runtime)?;
+            let mut issued_occupied = Capacity::zero();
+            for (output, data) in tx1.outputs_with_data_iter() {
+                let data_capacity = Capacity::bytes(data.len()).unwrap();
+                let occupied = output
+                    .occupied_capacity(data_capacity)
+                    .map_err(Error::runtime)?;
+                issued_occupied = issued_occupied.safe_add(occupied).map_err(Error::runtime)?;
+            }
+            let total = total.safe_add(issued).map_err(Error::runtime)?;
+            let occupied = occupied.safe_add(issued_occupied).map_err(Error::runtime)?;
+            pack_dao_data(ar, total, secondary, occupied)
+        };
+
+        let bootstrap_block = packed::Block::new_advanced_builder()
+            .parent_hash(parent.hash())
+            .number(number.pack())
+            .epoch(epoch_ext.number_with_fraction(number).pack())
+            .timestamp((parent.timestamp() + 1).pack())
+            .compact_target(epoch_ext.compact_target().pack())
+            .dao(dao)
+            .transaction(cellbase)
+            .transaction(tx1)
+            .build();
+        Ok(bootstrap_block)
+    }
+
+    fn genesis_from_store(store: &ChainDB) -> Result<(BlockView, EpochExt)> {
+        let genesis_block = Self::block_from_store(store, 0)?;
+        let genesis_epoch_ext = Self::epoch_ext_from_store(store, &genesis_block.hash())?;
+        Ok((genesis_block, genesis_epoch_ext))
+    }
+
+    fn block_from_store(store: &ChainDB, number: BlockNumber) -> Result<BlockView> {
+        store
+            .get_block_hash(number)
+            .and_then(|block_hash| store.get_block(&block_hash))
+            .ok_or_else(|| {
+                let errmsg = format!("block {} is not found", number);
+                Error::runtime(errmsg)
+            })
+    }
+
+    fn epoch_ext_from_store(store: &ChainDB, block_hash: &packed::Byte32) -> Result<EpochExt> {
+        store
+            .get_block_epoch_index(block_hash)
+            .and_then(|index| store.get_epoch_ext(&index))
+            .ok_or_else(|| {
+                let errmsg = format!("the epoch of block {:#x} is not found", block_hash);
+                Error::runtime(errmsg)
+            })
+    }
 }
 
 // Load
 impl MockedChain {
-    pub(crate) fn load<P: AsRef<Path>>(data_dir: P, cfg: &ChainSpec) -> Result<Self> {
+    pub(crate) fn load<P: AsRef<Path>>(
+        data_dir: P,
+        cfg: &ChainSpec,
+        run_env: &RunEnv,
+    ) -> Result<Self> {
         let store_dir = data_dir.as_ref().join("chain");
         utils::fs::check_directory(&store_dir, true)?;
         let store = MockedStore::init(store_dir);
 
-        let consensus = Arc::new(Self::build_consensus(cfg)?);
+        let consensus = Arc::new(Self::build_consensus(cfg, &store)?);
 
         let (current_snapshot, proposal_table) =
             Self::initialize_current_snapshot(&consensus, &store);
+        // Start the clock before the tx-pool, which reads the fake time since it starts.
+        let clock = Clock::start(current_snapshot.tip_header().timestamp())?;
         let (handle, stop_handler) = new_global_runtime();
         let network_dir = data_dir.as_ref().join("network");
         let network_controller = Self::dummy_network(network_dir, &handle)?;
         let tx_pool_dir = data_dir.as_ref().join("tx_pool");
         utils::fs::need_directory(&tx_pool_dir)?;
-        let always_sucess = Self::always_sucess_from_genesis_block(consensus.genesis_block());
+        let always_sucess = Self::always_sucess_from_chain(&consensus, &store, cfg)?;
         MockedScripts::insert_data_hash(always_sucess.data_hash());
         MockedScripts::insert_type_hash(always_sucess.type_hash());
+        let tx_pool_config = Self::build_tx_pool_config(&tx_pool_dir, run_env);
+        let max_tx_size = run_env
+            .tx_size_limit
+            .unwrap_or_else(|| consensus.max_block_bytes());
+        let dao_script = Self::dao_from_genesis_block(consensus.genesis_block());
+        let custom_scripts =
+            Self::custom_scripts_from_genesis_block(consensus.genesis_block(), cfg);
+        let keyring = cfg
+            .genesis
+            .secp256k1_keys
+            .map(|keys_count| {
+                Self::keyring_from_genesis_block(consensus.genesis_block(), keys_count)
+            })
+            .transpose()?;
+        let recent_rejects = RecentRejects::default();
         let (tx_pool_controller, tx_relay_receiver) = Self::build_tx_pool(
-            tx_pool_dir,
+            tx_pool_config.clone(),
             &handle,
             &current_snapshot,
             &network_controller,
             &always_sucess,
+            &recent_rejects,
         )?;
+        let request_timeout = run_env
+            .request_timeout_millis
+            .map(time::Duration::from_millis);
+        let watchdog = request_timeout
+            .map(|timeout| Watchdog::spawn(timeout, tx_pool_controller.clone()))
+            .transpose()?;
 
         Ok(Self {
             consensus,
             store,
             current_snapshot,
+            clock,
             _handle: handle,
             _stop_handler: stop_handler,
             tx_pool_controller,
+            tx_pool_config,
+            max_tx_size,
+            always_sucess,
+            dao_script,
+            custom_scripts,
+            keyring,
             _network_controller: network_controller,
-            _tx_relay_receiver: tx_relay_receiver,
+            tx_relay_receiver,
             proposal_table,
+            recent_rejects,
+            request_timeout,
+            watchdog,
         })
     }
 
@@ -310,21 +859,39 @@ impl MockedChain {
         (Arc::new(snapshot), proposal_table)
     }
 
+    // The network which never connects to any peers, it only exists since the tx-pool requires a
+    // network controller to start.
+    //
+    // The controller is a concrete type instead of a trait, so it could not be replaced by a
+    // stub, the service is started offline instead: it doesn't listen on any addresses, never
+    // dials or discovers the peers, and refuses all inbound connections, so the runs never
+    // bind any ports or conflict with other processes.
     fn dummy_network(network_dir: PathBuf, handle: &Handle) -> Result<NetworkController> {
         let exit_handler = DefaultExitHandler::default();
         let config = NetworkConfig {
-            max_peers: 20,
-            max_outbound_peers: 5,
+            max_peers: 1,
+            max_outbound_peers: 0,
             path: network_dir,
+            listen_addresses: Vec::new(),
+            public_addresses: Vec::new(),
+            bootnodes: Vec::new(),
+            dns_seeds: Vec::new(),
+            whitelist_only: true,
+            whitelist_peers: Vec::new(),
             ping_interval_secs: 15,
             ping_timeout_secs: 20,
-            connect_outbound_interval_secs: 1,
-            discovery_local_address: true,
+            // The outbound service has nothing to dial, check it rarely.
+            connect_outbound_interval_secs: 3600,
+            discovery_local_address: false,
             bootnode_mode: true,
-            reuse_port_on_linux: true,
+            reuse_port_on_linux: false,
             ..Default::default()
         };
-        let network_state = Arc::new(NetworkState::from_config(config).unwrap());
+        let network_state = NetworkState::from_config(config).map_err(|err| {
+            let errmsg = format!("failed to initialize network since {}", err);
+            Error::runtime(errmsg)
+        })?;
+        let network_state = Arc::new(network_state);
         NetworkService::new(
             network_state,
             vec![],
@@ -340,31 +907,54 @@ impl MockedChain {
         })
     }
 
+    fn build_tx_pool_config(tx_pool_dir: &Path, run_env: &RunEnv) -> TxPoolConfig {
+        let mut tx_pool_config = TxPoolConfig {
+            min_fee_rate: FeeRate(0),
+            persisted_data: tx_pool_dir.join("persisted_data"),
+            // The recent rejects are disabled if the path is empty.
+            recent_reject: tx_pool_dir.join("recent_reject"),
+            ..Default::default()
+        };
+        if let Some(ref options) = run_env.tx_pool {
+            if let Some(max_mem_size) = options.max_mem_size {
+                tx_pool_config.max_mem_size = max_mem_size;
+            }
+            if let Some(max_cycles) = options.max_cycles {
+                tx_pool_config.max_cycles = max_cycles;
+            }
+            if let Some(max_tx_verify_cycles) = options.max_tx_verify_cycles {
+                tx_pool_config.max_tx_verify_cycles = max_tx_verify_cycles;
+            }
+            if let Some(max_ancestors_count) = options.max_ancestors_count {
+                tx_pool_config.max_ancestors_count = max_ancestors_count;
+            }
+            if let Some(days) = options.keep_rejected_tx_hashes_days {
+                tx_pool_config.keep_rejected_tx_hashes_days = days;
+            }
+            if let Some(count) = options.keep_rejected_tx_hashes_count {
+                tx_pool_config.keep_rejected_tx_hashes_count = count;
+            }
+        }
+        if let Some(ref eviction) = run_env.eviction {
+            tx_pool_config.max_mem_size = eviction.max_mem_size;
+        }
+        if let Some(ref large_cycles) = run_env.large_cycles {
+            tx_pool_config.max_tx_verify_cycles = large_cycles.chunk_limit;
+        }
+        tx_pool_config
+    }
+
     fn build_tx_pool(
-        tx_pool_dir: PathBuf,
+        tx_pool_config: TxPoolConfig,
         handle: &Handle,
         current_snapshot: &Arc<Snapshot>,
         network_controller: &NetworkController,
         always_sucess: &ScriptAnchor,
+        recent_rejects: &RecentRejects,
     ) -> Result<(TxPoolController, Receiver<TxVerificationResult>)> {
-        let tx_pool_config = TxPoolConfig {
-            min_fee_rate: FeeRate(0),
-            persisted_data: tx_pool_dir.join("persisted_data"),
-            ..Default::default()
-        };
-        let args = {
-            let mut tmp = vec![0u8; 32];
-            let result_bytes = 0u64.to_le_bytes();
-            let cycles_bytes = 500u64.to_le_bytes();
-            (&mut tmp[0..8]).copy_from_slice(&result_bytes);
-            (&mut tmp[8..16]).copy_from_slice(&cycles_bytes);
-            (&mut tmp[16..24]).copy_from_slice(&result_bytes);
-            (&mut tmp[24..32]).copy_from_slice(&cycles_bytes);
-            tmp
-        };
         let block_assembler_config = BlockAssemblerConfig {
             code_hash: always_sucess.type_hash().unpack(),
-            args: args.pack().into(),
+            args: Self::always_success_args().into(),
             hash_type: ScriptHashType::Type.into(),
             message: Default::default(),
             use_binary_version_as_message_prefix: false,
@@ -383,7 +973,7 @@ impl MockedChain {
             handle,
             tx_relay_sender,
         );
-        Self::register_tx_pool_callback(&mut tx_pool_builder);
+        Self::register_tx_pool_callback(&mut tx_pool_builder, Arc::clone(recent_rejects));
         tx_pool_builder.start(network_controller.clone());
         if tx_pool_controller.service_started() {
             Ok((tx_pool_controller, tx_relay_receiver))
@@ -426,7 +1016,13 @@ impl MockedChain {
     }
 
     // Copy from ckb/util/launcher/src/shared_builder.rs
-    fn register_tx_pool_callback(tx_pool_builder: &mut TxPoolServiceBuilder) {
+    //
+    // The reject callback also records the rejected transactions, as a debug hook to check the
+    // recent rejects, which couldn't be queried outside of the tx-pool.
+    fn register_tx_pool_callback(
+        tx_pool_builder: &mut TxPoolServiceBuilder,
+        recent_rejects: RecentRejects,
+    ) {
         tx_pool_builder.register_pending(Box::new(move |tx_pool: &mut TxPool, entry: &TxEntry| {
             tx_pool.update_statics_for_add_tx(entry.size, entry.cycles);
         }));
@@ -450,9 +1046,16 @@ impl MockedChain {
                 tx_pool.update_statics_for_remove_tx(entry.size, entry.cycles);
                 let tx_hash = entry.transaction().hash();
                 if matches!(reject, Reject::Resolve(..)) {
-                    if let Some(ref mut recent_reject) = tx_pool.recent_reject {
+                    let is_recorded = if let Some(ref mut recent_reject) = tx_pool.recent_reject {
                         let _ = recent_reject.put(&tx_hash, reject);
-                    }
+                        matches!(recent_reject.get(&tx_hash), Ok(Some(_)))
+                    } else {
+                        false
+                    };
+                    recent_rejects
+                        .lock()
+                        .expect("the lock of recent rejects is poisoned")
+                        .insert(tx_hash, is_recorded);
                 }
             },
         ));
@@ -460,17 +1063,41 @@ impl MockedChain {
 }
 
 impl MockedChain {
-    pub(crate) fn store(&self) -> &ChainDB {
+    pub fn store(&self) -> &ChainDB {
         self.store.store()
     }
 
     pub(crate) fn mocked_script(&self) -> ScriptAnchor {
-        let genesis_block = self.consensus.genesis_block();
-        Self::always_sucess_from_genesis_block(genesis_block)
+        self.always_sucess.clone()
     }
 
-    fn always_sucess_from_genesis_block(genesis_block: &BlockView) -> ScriptAnchor {
-        let tx1 = genesis_block.transaction(1).unwrap();
+    // The mocked script is deployed by tx1 of the genesis block, or by tx1 of the bootstrap
+    // block if the chain is imported.
+    fn always_sucess_from_chain(
+        consensus: &Consensus,
+        store: &MockedStore,
+        cfg: &ChainSpec,
+    ) -> Result<ScriptAnchor> {
+        let tx1 = if let Some(ref imported) = cfg.imported {
+            let block_hash = imported.bootstrap_block.pack();
+            store
+                .store()
+                .get_block(&block_hash)
+                .and_then(|block| block.transaction(1))
+                .ok_or_else(|| {
+                    let errmsg = format!(
+                        "the bootstrap block {:#x} is not found",
+                        imported.bootstrap_block
+                    );
+                    Error::runtime(errmsg)
+                })?
+        } else {
+            consensus.genesis_block().transaction(1).unwrap()
+        };
+        Ok(Self::always_sucess_from_tx(&tx1))
+    }
+
+    fn always_sucess_from_tx(tx1: &TransactionView) -> ScriptAnchor {
         let index: usize = 0;
         let cell_dep = {
             let out_point = packed::OutPoint::new(tx1.hash(), index as u32);
@@ -492,6 +1119,100 @@ impl MockedChain {
         ScriptAnchor::new(cell_dep, data_hash, type_hash)
     }
 
+    // The DAO script, it's not mocked, the DAO transactions are verified by the real script.
+    pub(crate) fn dao_script(&self) -> &ScriptAnchor {
+        &self.dao_script
+    }
+
+    fn dao_from_genesis_block(genesis_block: &BlockView) -> ScriptAnchor {
+        Self::anchor_from_genesis_cellbase(genesis_block, OUTPUT_INDEX_DAO as usize)
+    }
+
+    // The script binaries of the users, and whether they pass with empty args.
+    pub(crate) fn custom_scripts(&self) -> &[(ScriptAnchor, bool)] {
+        &self.custom_scripts
+    }
+
+    fn custom_scripts_from_genesis_block(
+        genesis_block: &BlockView,
+        cfg: &ChainSpec,
+    ) -> Vec<(ScriptAnchor, bool)> {
+        let binaries = cfg.genesis.scripts.as_deref().unwrap_or(&[]);
+        let cellbase = genesis_block.transaction(0).unwrap();
+        // The script binaries are the last outputs of the genesis cellbase.
+        let start = cellbase.outputs().len() - binaries.len();
+        binaries
+            .iter()
+            .enumerate()
+            .map(|(index, binary)| {
+                let anchor = Self::anchor_from_genesis_cellbase(genesis_block, start + index);
+                (anchor, binary.passes)
+            })
+            .collect()
+    }
+
+    // A script which is deployed by the genesis cellbase, with a type ID.
+    fn anchor_from_genesis_cellbase(genesis_block: &BlockView, index: usize) -> ScriptAnchor {
+        let cellbase = genesis_block.transaction(0).unwrap();
+        let cell_dep = {
+            let out_point = packed::OutPoint::new(cellbase.hash(), index as u32);
+            packed::CellDep::new_builder()
+                .out_point(out_point)
+                .dep_type(DepType::Code.into())
+                .build()
+        };
+        let data_hash = cellbase
+            .outputs_data()
+            .get(index)
+            .map(|data| packed::CellOutput::calc_data_hash(&data.raw_data()))
+            .unwrap();
+        let type_hash = cellbase
+            .output(index)
+            .and_then(|output| output.type_().to_opt())
+            .map(|script| script.calc_script_hash())
+            .unwrap();
+        ScriptAnchor::new(cell_dep, data_hash, type_hash)
+    }
+
+    // The keyring to sign the cells which are locked by the secp256k1 lock, if it's enabled.
+    pub(crate) fn keyring(&self) -> Option<&Keyring> {
+        self.keyring.as_ref()
+    }
+
+    fn keyring_from_genesis_block(genesis_block: &BlockView, keys_count: u32) -> Result<Keyring> {
+        let cellbase = genesis_block.transaction(0).unwrap();
+        let cell_deps = [
+            OUTPUT_INDEX_SECP256K1,
+            OUTPUT_INDEX_SECP256K1_DATA,
+            OUTPUT_INDEX_SECP256K1_MULTISIG,
+        ]
+        .iter()
+        .map(|index| {
+            let out_point = packed::OutPoint::new(cellbase.hash(), *index as u32);
+            packed::CellDep::new_builder()
+                .out_point(out_point)
+                .dep_type(DepType::Code.into())
+                .build()
+        })
+        .collect();
+        let code_hash = |index: usize| {
+            cellbase
+                .outputs_data()
+                .get(index)
+                .map(|data| packed::CellOutput::calc_data_hash(&data.raw_data()))
+                .ok_or_else(|| {
+                    let errmsg = "the secp256k1 locks are not deployed in the genesis block";
+                    Error::config(errmsg)
+                })
+        };
+        Keyring::new(
+            cell_deps,
+            code_hash(OUTPUT_INDEX_SECP256K1)?,
+            code_hash(OUTPUT_INDEX_SECP256K1_MULTISIG)?,
+            keys_count,
+        )
+    }
+
     fn current_snapshot(&self) -> Arc<Snapshot> {
         Arc::clone(&self.current_snapshot)
     }
@@ -500,6 +1221,143 @@ impl MockedChain {
         &self.tx_pool_controller
     }
 
+    // Watch a request to the tx-pool until the returned guard is dropped.
+    fn watch(&self, request: &'static str, hash: Option<packed::Byte32>) -> Option<WatchGuard<'_>> {
+        self.watchdog
+            .as_ref()
+            .map(|watchdog| watchdog.watch(request, hash))
+    }
+
+    pub fn consensus(&self) -> &Consensus {
+        &self.consensus
+    }
+
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    pub fn tx_pool_config(&self) -> &TxPoolConfig {
+        &self.tx_pool_config
+    }
+
+    // The max size of a transaction which the tx-pool accepts, in bytes.
+    pub(crate) fn max_tx_size(&self) -> u64 {
+        self.max_tx_size
+    }
+
+    // Whether the outputs of the cellbase which is committed at the origin couldn't be spent in
+    // the next block.
+    pub(crate) fn is_immature_cellbase(&self, origin: &CellbaseOrigin) -> bool {
+        let maturity = self.consensus.cellbase_maturity();
+        // The outputs of the genesis cellbase are always mature.
+        if maturity.full_value() == 0 || origin.block_number == 0 {
+            return false;
+        }
+        let next_number = self.chain_tip_header().number() + 1;
+        let next_epoch = self.next_epoch_ext().number_with_fraction(next_number);
+        next_epoch.to_rational() < origin.epoch.to_rational() + maturity.to_rational()
+    }
+
+    // The origins of the immature cellbases in chain, from the newest one.
+    pub(crate) fn immature_cellbase_origins(&self) -> Vec<(packed::Byte32, CellbaseOrigin)> {
+        let store = self.store.store();
+        let mut origins = Vec::new();
+        for number in (1..=self.chain_tip_header().number()).rev() {
+            let cellbase_and_epoch = store.get_block_hash(number).and_then(|block_hash| {
+                let cellbase = store.get_cellbase(&block_hash)?;
+                let header = store.get_block_header(&block_hash)?;
+                Some((cellbase, header.epoch()))
+            });
+            let (cellbase, epoch) = if let Some(cellbase_and_epoch) = cellbase_and_epoch {
+                cellbase_and_epoch
+            } else {
+                break;
+            };
+            let origin = CellbaseOrigin {
+                block_number: number,
+                epoch,
+            };
+            if !self.is_immature_cellbase(&origin) {
+                // The older cellbases are mature too.
+                break;
+            }
+            origins.push((cellbase.hash(), origin));
+        }
+        origins
+    }
+
+    // The median time of the block and its ancestors, the timestamp-based since of a transaction
+    // is compared with the median time of the parent of the block which commits it.
+    pub(crate) fn block_median_time(&self, block_hash: &packed::Byte32) -> u64 {
+        let store = self.store.store();
+        let count = self.consensus.median_time_block_count();
+        let mut timestamps = Vec::with_capacity(count);
+        let mut block_hash = block_hash.clone();
+        while timestamps.len() < count {
+            let header = if let Some(header) = store.get_block_header(&block_hash) {
+                header
+            } else {
+                break;
+            };
+            timestamps.push(header.timestamp());
+            if header.number() == 0 {
+                break;
+            }
+            block_hash = header.parent_hash();
+        }
+        timestamps.sort_unstable();
+        timestamps.get(timestamps.len() / 2).copied().unwrap_or(0)
+    }
+
+    // The median time which the transactions in the next block are compared with.
+    pub(crate) fn tip_median_time(&self) -> u64 {
+        self.block_median_time(&self.chain_tip_header().hash())
+    }
+
+    // The median time which the relative timestamp-based since of the outputs of a committed
+    // transaction starts from.
+    pub(crate) fn committed_median_time(&self, tx_hash: &packed::Byte32) -> Option<u64> {
+        let store = self.store.store();
+        store
+            .get_transaction_info(tx_hash)
+            .and_then(|info| store.get_block_header(&info.block_hash))
+            .map(|header| self.block_median_time(&header.parent_hash()))
+    }
+
+    // The median time which the input requires, or nothing if its since is not timestamp-based,
+    // or it's relative to a cell which is not committed yet.
+    pub(crate) fn since_median_time(&self, input: &packed::CellInput) -> Option<u64> {
+        let since: u64 = input.since().unpack();
+        if since & SINCE_METRIC_MASK != SINCE_METRIC_TIMESTAMP {
+            return None;
+        }
+        let millis = (since & SINCE_VALUE_MASK).saturating_mul(1000);
+        if since & SINCE_RELATIVE_FLAG == 0 {
+            Some(millis)
+        } else {
+            self.committed_median_time(&input.previous_output().tx_hash())
+                .map(|base| base.saturating_add(millis))
+        }
+    }
+
+    // Whether the data1 hash type is enabled for the next block, or nothing if it's never enabled
+    // by the hardfork switch.
+    pub(crate) fn is_data1_enabled(&self) -> Option<bool> {
+        let hardfork_switch = self.consensus.hardfork_switch();
+        if hardfork_switch.rfc_0032() == EpochNumber::MAX {
+            return None;
+        }
+        let epoch_number = self
+            .chain_tip_header()
+            .epoch()
+            .minimum_epoch_number_after_n_blocks(1);
+        Some(hardfork_switch.is_vm_version_1_and_syscalls_2_enabled(epoch_number))
+    }
+
+    pub fn proposal_window(&self) -> ProposalWindow {
+        self.consensus.tx_proposal_window()
+    }
+
     pub(crate) fn next_epoch_ext(&self) -> EpochExt {
         self.consensus
             .next_epoch_ext(
@@ -513,13 +1371,51 @@ impl MockedChain {
 
 // Chain
 impl MockedChain {
-    pub(crate) fn chain_tip_header(&self) -> HeaderView {
+    pub fn chain_tip_header(&self) -> HeaderView {
         self.current_snapshot().tip_header().to_owned()
     }
 
-    pub(crate) fn chain_submit_block(&mut self, block: &BlockView) {
+    // Find a nonce which passes the PoW engine, the block is not changed if it already passes,
+    // which is always the case for the dummy PoW.
+    pub fn seal_block(&self, block: BlockView) -> Result<BlockView> {
+        let pow_engine = self.consensus.pow_engine();
+        let header = block.header().data();
+        if pow_engine.verify(&header) {
+            return Ok(block);
+        }
+        for nonce in 0..MAX_SEAL_ATTEMPTS {
+            let sealed = header.clone().as_builder().nonce(nonce.pack()).build();
+            if pow_engine.verify(&sealed) {
+                log::trace!("[Chain] seal block {} by nonce {}", block.number(), nonce);
+                return Ok(block.as_advanced_builder().nonce(nonce.pack()).build());
+            }
+        }
+        let errmsg = format!(
+            "failed to seal block {} in {} attempts, the compact target is {:#x}",
+            block.number(),
+            MAX_SEAL_ATTEMPTS,
+            block.compact_target()
+        );
+        Err(Error::runtime(errmsg))
+    }
+
+    pub fn chain_submit_block(&mut self, block: &BlockView) {
         let next_epoch_ext = self.next_epoch_ext();
-        self.store.insert_block(block, &next_epoch_ext);
+        // The block assembler rewards the fees by them. The broken fees are reported by the
+        // ledger later, so they are just left out here.
+        let txs_fees = ledger::committed_capacities(self, block)
+            .map(|capacities| {
+                capacities
+                    .iter()
+                    .map(|capacity| Capacity::shannons(capacity.fee().unwrap_or(0)))
+                    .collect()
+            })
+            .unwrap_or_else(|err| {
+                log::error!("[Chain] failed to calculate the fees since {}", err);
+                Vec::new()
+            });
+        self.store
+            .insert_block(block, &next_epoch_ext, self.clock.now(), txs_fees);
         self.store.attach_block(&block.hash());
         self.store.set_block_as_tip(&block.hash());
         let (current_snapshot, proposal_table) =
@@ -531,11 +1427,15 @@ impl MockedChain {
 
 // TxPool
 impl MockedChain {
-    pub(crate) fn txpool_trace(&self) -> Result<()> {
-        let info = self
-            .tx_pool_controller()
+    pub fn txpool_info(&self) -> Result<TxPoolInfo> {
+        let _watch = self.watch("get_tx_pool_info", None);
+        self.tx_pool_controller()
             .get_tx_pool_info()
-            .map_err(Error::runtime)?;
+            .map_err(Error::runtime)
+    }
+
+    pub fn txpool_trace(&self) -> Result<()> {
+        let info = self.txpool_info()?;
         log::trace!(
             "[TxPool] tip: {}, hash: {:#x}, ts: {}, \
             pending: {}, proposed: {}, orphan: {}, \
@@ -549,28 +1449,88 @@ impl MockedChain {
             info.total_tx_size,
             info.total_tx_cycles,
         );
+        utils::metrics::global().set_pool_sizes(info.pending_size, info.proposed_size);
         Ok(())
     }
 
-    pub(crate) fn txpool_save_pool(&self) -> Result<()> {
+    pub fn txpool_tip_hash(&self) -> Result<packed::Byte32> {
+        self.txpool_info().map(|info| info.tip_hash)
+    }
+
+    pub fn txpool_save_pool(&self) -> Result<()> {
+        let _watch = self.watch("save_pool", None);
         self.tx_pool_controller()
             .save_pool()
             .map_err(Error::runtime)
     }
 
-    pub(crate) fn get_block_template(&self) -> Result<BlockTemplate> {
+    // Save the tx-pool, then replace it by a new one which loads the persisted data, the chain
+    // and the network are kept.
+    //
+    // The old tx-pool stops when its controller is dropped, and the watchdog is replaced, since
+    // it holds a controller to dump the old tx-pool.
+    pub fn txpool_reload(&mut self) -> Result<()> {
+        self.txpool_save_pool()?;
+        self.watchdog = None;
+        let (tx_pool_controller, tx_relay_receiver) = Self::build_tx_pool(
+            self.tx_pool_config.clone(),
+            &self._handle,
+            &self.current_snapshot,
+            &self._network_controller,
+            &self.always_sucess,
+            &self.recent_rejects,
+        )?;
+        self.tx_pool_controller = tx_pool_controller;
+        self.tx_relay_receiver = tx_relay_receiver;
+        self.watchdog = self
+            .request_timeout
+            .map(|timeout| Watchdog::spawn(timeout, self.tx_pool_controller.clone()))
+            .transpose()?;
+        Ok(())
+    }
+
+    pub fn get_block_template(&self) -> Result<BlockTemplate> {
+        self.get_block_template_with(None, None, None)?
+            .map_err(Error::runtime)
+    }
+
+    // Get the block template with the limits, the refusals of the tx-pool are returned as the
+    // inner errors, none means the consensus limit.
+    pub fn get_block_template_with(
+        &self,
+        bytes_limit: Option<u64>,
+        proposals_limit: Option<u64>,
+        max_version: Option<Version>,
+    ) -> Result<StdResult<BlockTemplate, String>> {
         let snapshot = self.current_snapshot();
-        self.tx_pool_controller()
-            .get_block_template(None, None, None, snapshot)
+        let _watch = self.watch("get_block_template", None);
+        let started_at = time::Instant::now();
+        let template = match self
+            .tx_pool_controller()
+            .get_block_template(bytes_limit, proposals_limit, max_version, snapshot)
             .map_err(Error::runtime)?
-            .map_err(Error::runtime)
+        {
+            Ok(template) => template,
+            Err(err) => return Ok(Err(err.to_string())),
+        };
+        let elapsed = started_at.elapsed();
+        if utils::metrics::global().observe_latency(Operation::Template, elapsed) {
+            log::warn!(
+                "[Latency] >>> get the block template on tip {} took {:?}",
+                self.chain_tip_header().number(),
+                elapsed
+            );
+        }
+        Ok(Ok(template))
     }
 
-    pub(crate) fn txpool_submit_block(&self, block: &BlockView) -> Result<()> {
+    pub fn txpool_submit_block(&self, block: &BlockView) -> Result<()> {
         let snapshot = self.current_snapshot();
         let detached_blocks = VecDeque::default();
         let attached_blocks = vec![block.to_owned()].into_iter().collect();
         let detached_proposal_id = HashSet::default();
+        let _watch = self.watch("update_tx_pool_for_reorg", Some(block.hash()));
+        let started_at = time::Instant::now();
         self.tx_pool_controller()
             .update_tx_pool_for_reorg(
                 detached_blocks,
@@ -578,13 +1538,72 @@ impl MockedChain {
                 detached_proposal_id,
                 snapshot,
             )
-            .map_err(Error::runtime)
+            .map_err(Error::runtime)?;
+        let elapsed = started_at.elapsed();
+        if utils::metrics::global().observe_latency(Operation::Reorg, elapsed) {
+            log::warn!(
+                "[Latency] >>> update the tx-pool with block {} {:#x} took {:?}",
+                block.number(),
+                block.hash(),
+                elapsed
+            );
+        }
+        Ok(())
     }
 
-    pub(crate) fn txpool_submit_local_tx(&self, tx: &TransactionView) -> Result<()> {
+    pub fn txpool_entries(&self) -> Result<TxPoolEntryInfo> {
+        let _watch = self.watch("get_all_entry_info", None);
         self.tx_pool_controller()
-            .submit_local_tx(tx.clone())
-            .map_err(Error::runtime)?
+            .get_all_entry_info()
             .map_err(Error::runtime)
     }
+
+    // Take the transactions which are passed to the reject callback since the last call, and
+    // whether they are in the recent rejects.
+    pub fn txpool_take_recent_rejects(&self) -> HashMap<packed::Byte32, bool> {
+        let mut recent_rejects = self
+            .recent_rejects
+            .lock()
+            .expect("the lock of recent rejects is poisoned");
+        std::mem::take(&mut *recent_rejects)
+    }
+
+    pub fn txpool_submit_local_tx(&self, tx: &TransactionView) -> Result<StdResult<(), Reject>> {
+        let _watch = self.watch("submit_local_tx", Some(tx.hash()));
+        let started_at = time::Instant::now();
+        let result = self
+            .tx_pool_controller()
+            .submit_local_tx(tx.clone())
+            .map_err(Error::runtime)?;
+        observe_submit_latency(tx, started_at.elapsed());
+        Ok(result)
+    }
+
+    // Submit a transaction as it's relayed from a peer, with the declared cycles.
+    pub fn txpool_submit_remote_tx(
+        &self,
+        tx: &TransactionView,
+        declared_cycles: Cycle,
+        peer: PeerIndex,
+    ) -> Result<StdResult<(), Reject>> {
+        let _watch = self.watch("submit_remote_tx", Some(tx.hash()));
+        let started_at = time::Instant::now();
+        let result = self
+            .tx_pool_controller()
+            .submit_remote_tx(tx.clone(), declared_cycles, peer)
+            .map_err(Error::runtime)?;
+        observe_submit_latency(tx, started_at.elapsed());
+        Ok(result)
+    }
+
+    // Take all results which are sent to the relayer since the last call.
+    pub fn txpool_take_relay_results(&self) -> Vec<TxVerificationResult> {
+        self.tx_relay_receiver.try_iter().collect()
+    }
+}
+
+fn observe_submit_latency(tx: &TransactionView, elapsed: time::Duration) {
+    if utils::metrics::global().observe_latency(Operation::Submit, elapsed) {
+        log::warn!("[Latency] >>> submit {:#x} took {:?}", tx.hash(), elapsed);
+    }
 }
@@ -7,12 +7,11 @@ use ckb_types::{
     core::{
         cell::{CellMetaBuilder, CellProvider, CellStatus, HeaderChecker},
         error::OutPointError,
-        BlockExt, BlockView, EpochExt,
+        BlockExt, BlockView, Capacity, EpochExt,
     },
     packed,
     prelude::*,
 };
-use faketime::unix_time_as_millis;
 
 #[derive(Clone)]
 pub(crate) struct MockedStore {
@@ -31,20 +30,34 @@ impl MockedStore {
         &self.inner
     }
 
-    pub(crate) fn insert_block(&self, block: &BlockView, epoch_ext: &EpochExt) {
+    // Create a consistent copy of the chain store, by a RocksDB checkpoint.
+    pub(crate) fn create_checkpoint<P: AsRef<Path>>(&self, path: P) -> crate::error::Result<()> {
+        let db = self.store().db().inner();
+        let checkpoint = rocksdb::checkpoint::Checkpoint::new(&*db)?;
+        checkpoint.create_checkpoint(path.as_ref())?;
+        Ok(())
+    }
+
+    pub(crate) fn insert_block(
+        &self,
+        block: &BlockView,
+        epoch_ext: &EpochExt,
+        received_at: u64,
+        txs_fees: Vec<Capacity>,
+    ) {
         let db_txn = self.store().begin_transaction();
         let last_block_hash_in_previous_epoch = epoch_ext.last_block_hash_in_previous_epoch();
         db_txn.insert_block(block).unwrap();
         {
             let parent_block_ext = self.store().get_block_ext(&block.parent_hash()).unwrap();
             let block_ext = BlockExt {
-                received_at: unix_time_as_millis(),
+                received_at,
                 total_difficulty: parent_block_ext.total_difficulty.to_owned()
                     + block.header().difficulty(),
                 total_uncles_count: parent_block_ext.total_uncles_count
                     + block.data().uncles().len() as u64,
                 verified: Some(true),
-                txs_fees: vec![],
+                txs_fees,
             };
             db_txn.insert_block_ext(&block.hash(), &block_ext).unwrap();
         }
@@ -1,129 +1,904 @@
-use std::{process, sync::atomic::Ordering, thread, time};
+use std::{path::Path, str::FromStr as _, thread, time};
 
-use ckb_types::packed;
+use ckb_types::core::{tx_pool::Reject, BlockNumber};
 
 use crate::{
     config::{InitConfig, RunConfig},
-    error::Result,
-    types::RandomGenerator,
-    utils,
+    error::{Error, Result},
+    types::{
+        reject_reason, Baselines, BiasAdapter, Checkpoint, LongChain, MetaData, MismatchKind,
+        PartialCommit, RandomGenerator, ResolvedMismatchPolicies, RunEnv, RunRecord, RunReport,
+        RunSummary, ScriptStep, TxSeqId,
+    },
+    utils::{self, signals::Signals},
 };
 
+mod backend;
+mod block;
+mod clock;
+mod dump;
+mod eviction;
+mod export;
+mod faults;
+mod graph;
+mod hooks;
+mod ledger;
+mod matrix;
 mod mocked_chain;
 mod mocked_store;
 mod overlay;
+mod package;
+mod parallel;
+mod producer;
+mod provenance;
+mod relay;
+mod reload;
+mod remote;
+mod resume;
+mod reward;
+mod scenario;
+mod stepper;
 mod storage;
 mod strategy;
+mod stuck;
+mod verify;
+mod watchdog;
 
-pub(crate) use mocked_chain::MockedChain;
+pub use block::build_block;
+pub use clock::Clock;
+pub(crate) use dump::{dump_chain, submit_chain};
+pub(crate) use export::{
+    collect_tables as collect_export_tables, write_tables as write_export_tables,
+};
+pub(crate) use graph::build_graph;
+pub(crate) use hooks::{hook_names, HookContext, PostBlockHooks};
+pub(crate) use matrix::{build_session_configs, run_session};
+pub use mocked_chain::MockedChain;
 pub(crate) use mocked_store::MockedStore;
-pub(crate) use overlay::{Overlay, TxOverlay, TxOverlayChanges};
-pub(crate) use storage::Storage;
+pub use overlay::TxOverlay;
+pub(crate) use overlay::{FailReason, Overlay, Package, TxOverlayChanges};
+use package::Packages;
+pub(crate) use parallel::run_parallel;
+pub(crate) use producer::producer_names;
+pub use producer::BlockProducers;
+pub(crate) use provenance::{export as export_provenance, PROVENANCE_FILE};
+use relay::RelayOracle;
+use remote::RemoteSubmitter;
+use scenario::Scenario;
+#[cfg(feature = "async-api")]
+pub use stepper::AsyncStepper;
+pub use stepper::Stepper;
+pub use storage::Storage;
+pub(crate) use strategy::strategy_names;
+pub use strategy::{build_transactions, TxStrategies};
+use stuck::StuckTxs;
+pub(crate) use verify::verify_storage;
+use watchdog::{WatchGuard, Watchdog};
+
+pub(crate) const REPORT_FILE: &str = "report.yaml";
+
+// Only measure the throughput of the large batches, the small ones are too noisy.
+const THROUGHPUT_MIN_BATCH: usize = 500;
+
+// The initialization parameters and the count of blocks for each input of the fuzzers.
+const FUZZ_INIT_CONFIG: &str = include_str!("../../configs/init.yaml.sample");
+const FUZZ_CHAIN_BLOCKS: BlockNumber = 10;
 
-pub(crate) struct Fuzzer {
+pub struct Fuzzer {
     chain: MockedChain,
     config: RunConfig,
 }
 
 impl Fuzzer {
-    pub(crate) fn init(cfg: InitConfig) -> Result<()> {
-        MockedChain::init(&cfg.data_dir, &cfg.meta_data.chain_spec)?;
+    pub fn init(mut cfg: InitConfig) -> Result<()> {
+        if let Some(ref source) = cfg.from_chain {
+            let (imported, txs) = MockedChain::import(
+                &cfg.data_dir,
+                &cfg.meta_data.chain_spec,
+                &source.data_dir,
+                source.to_height,
+            )?;
+            cfg.storage.put_initial_txs(txs)?;
+            cfg.meta_data.chain_spec.imported = Some(imported);
+        } else {
+            let genesis_cellbase = MockedChain::init(&cfg.data_dir, &cfg.meta_data.chain_spec)?;
+            if let Some(genesis_cellbase) = genesis_cellbase {
+                cfg.storage.put_initial_txs(vec![genesis_cellbase])?;
+            }
+        }
         cfg.storage.put_meta_data(&cfg.meta_data)?;
         Ok(())
     }
 
-    pub(crate) fn load(cfg: RunConfig) -> Result<Self> {
+    // Initialize a temporary data directory and run a few blocks with the bytes.
+    pub fn fuzz_one(data: &[u8]) -> Result<()> {
+        let temp_dir = tempfile::tempdir().map_err(|err| {
+            let errmsg = format!("failed to create temporary directory since {}", err);
+            Error::runtime(errmsg)
+        })?;
+        let data_dir = temp_dir.path().join("data");
+        utils::fs::create_directory(&data_dir)?;
+        let meta_data = MetaData::from_str(FUZZ_INIT_CONFIG).map_err(Error::config)?;
+        let storage = Storage::init(data_dir.join("storage"))?;
+        Self::init(InitConfig {
+            data_dir: data_dir.clone(),
+            storage,
+            meta_data,
+            from_chain: None,
+            lock: None,
+        })?;
+        let run_env = RunEnv {
+            chain_blocks: FUZZ_CHAIN_BLOCKS,
+            block_interval: 8000,
+            verify_every_n_blocks: Some(1),
+            verify_ledger_every_n_blocks: Some(1),
+            post_block_hooks: Some(vec![
+                "committed_left_pool".to_owned(),
+                "committed_in_storage".to_owned(),
+            ]),
+            partial_commit: Some(PartialCommit {
+                block_chance: 10,
+                keep_chance: 50,
+            }),
+            long_chain: Some(LongChain {
+                chance: 2,
+                extra_depth: 3,
+            }),
+            ..Default::default()
+        };
+        let storage = Storage::load(data_dir.join("storage"))?;
+        let cfg = RunConfig {
+            data_dir,
+            storage,
+            run_env,
+            script: None,
+            metrics_listen: None,
+            resume: false,
+            baselines: None,
+            lock: None,
+            ephemeral: None,
+        };
+        Self::load(cfg)?.run_with_bytes(data.to_vec())
+    }
+
+    pub fn load(cfg: RunConfig) -> Result<Self> {
         let meta_data = cfg.storage.get_meta_data()?;
         utils::faketime::enable()?;
-        let chain = MockedChain::load(&cfg.data_dir, &meta_data.chain_spec)?;
+        let chain = MockedChain::load(&cfg.data_dir, &meta_data.chain_spec, &cfg.run_env)?;
+        // The cellbases which were committed before their origins are tracked.
+        cfg.storage
+            .put_cellbase_origins(chain.immature_cellbase_origins())?;
         Ok(Self { chain, config: cfg })
     }
 
-    pub(crate) fn run(self) -> Result<()> {
+    pub fn chain(&self) -> &MockedChain {
+        &self.chain
+    }
+
+    pub fn chain_mut(&mut self) -> &mut MockedChain {
+        &mut self.chain
+    }
+
+    pub fn storage(&self) -> &Storage {
+        &self.config.storage
+    }
+
+    pub fn run_env(&self) -> &RunEnv {
+        &self.config.run_env
+    }
+
+    pub fn run(self) -> Result<()> {
+        let signals = Signals::capture()?;
+        self.run_with(None, signals)
+    }
+
+    // Consume all random decisions from the bytes, for coverage-guided fuzzers.
+    pub fn run_with_bytes(self, data: Vec<u8>) -> Result<()> {
+        self.run_with(Some(data), Signals::default())
+    }
+
+    fn run_with(self, bytes: Option<Vec<u8>>, signals: Signals) -> Result<()> {
         let Self { mut chain, config } = self;
         let RunConfig {
-            data_dir: _,
-            storage,
+            data_dir,
+            mut storage,
             run_env,
+            script,
+            metrics_listen,
+            resume,
+            baselines,
+            // Keep the data directory locked until the run is finished.
+            lock: _lock,
+            ephemeral: _ephemeral,
         } = config;
 
-        let tip_header = chain.chain_tip_header();
-        let tip_timestamp = tip_header.timestamp();
-        utils::faketime::update(tip_timestamp)?;
+        if let Some(listen) = metrics_listen {
+            utils::metrics::serve(listen)?;
+        }
+        let metrics = utils::metrics::global();
+        if let Some(millis) = run_env.slow_call_millis {
+            metrics.set_slow_call_threshold(time::Duration::from_millis(millis));
+        }
+        if let Some(capacity) = run_env.cache_capacity {
+            storage.set_cache_capacity(capacity);
+        }
+
+        let resumed_seed = if resume {
+            Some(resume::resume(&chain, &storage)?.rng_seed)
+        } else {
+            None
+        };
+        let mut ledger = ledger::load_or_start(&chain, &storage)?;
+
+        let start_number = chain.chain_tip_header().number();
+
+        let random_generator = if let Some(data) = bytes {
+            RandomGenerator::from_bytes(&run_env, data)?
+        } else {
+            RandomGenerator::new(&run_env, resumed_seed)?
+        };
+
+        let run_id = storage.next_run_id()?;
+        log::info!("Run {} ...", run_id);
+        let mut record = RunRecord::start(run_id, random_generator.seed(), resume, &run_env);
+        storage.put_run_record(&record)?;
+
+        // Inject the faults after resuming, so an interrupted run could always be resumed.
+        if let Some(ref faults) = run_env.storage_faults {
+            log::warn!("[Fault] inject faults into the storage: {:?}", faults);
+            storage.inject_faults(faults, random_generator.seed());
+        }
+
+        let started_at = time::Instant::now();
+        let mismatch_handler = MismatchHandler {
+            policies: run_env.resolved_mismatch_policies(),
+            data_dir: &data_dir,
+            started_at,
+        };
+        let mut report = RunReport {
+            seed: random_generator.seed(),
+            start_number,
+            tip_number: start_number,
+            ..Default::default()
+        };
+
+        let post_block_hooks = run_env
+            .post_block_hooks
+            .as_ref()
+            .map(|names| PostBlockHooks::from_names(names))
+            .transpose()?
+            .unwrap_or_default();
+
+        if let Some(profile) = run_env.profile {
+            log::info!("[Random] workload profile: {}", profile);
+        }
+        let strategies = TxStrategies::from_run_env(&run_env)?;
+        let block_producers = BlockProducers::from_run_env(&run_env)?;
 
-        let start_number = tip_header.number();
+        let mut bias_adapter = run_env.adaptive.as_ref().map(BiasAdapter::new);
 
-        let random_generator = RandomGenerator::new(&run_env)?;
+        let max_generation = run_env
+            .max_generation_millis
+            .map(time::Duration::from_millis);
 
-        let ctrlc_pressed = utils::ctrlc::capture()?;
+        // Follow the script if it's provided, otherwise run randomly.
+        let mut script_steps = if let Some(script) = script {
+            for name in script.strategies() {
+                strategy::find_strategy(name)?;
+            }
+            let steps = script.steps();
+            log::info!("[Script] {} steps", steps.len());
+            Some(steps.into_iter())
+        } else {
+            None
+        };
 
-        // Run randomly.
-        while !ctrlc_pressed.load(Ordering::SeqCst) {
-            utils::faketime::increase(random_generator.block_interval())?;
+        let mut packages = Packages::default();
+        let mut stuck_txs = StuckTxs::default();
+        let mut remote_submitter = RemoteSubmitter::new(&run_env);
+        let mut relay_oracle = RelayOracle::default();
+        let mut scenario = Scenario::default();
+        let mut step = 0;
+        // Why the run stops, none if it's interrupted.
+        let mut termination = None;
+        while !signals.is_shutdown() {
+            if signals.take_dump() {
+                log::info!("[Signal] dump the states as requested");
+                storage.trace();
+                chain.txpool_trace()?;
+                report.bias.trace();
+                chain.txpool_save_pool()?;
+                finish_report(&mut report, started_at, &storage);
+                save_report(&data_dir, &storage, &report)?;
+            }
+            let script_step = if let Some(steps) = script_steps.as_mut() {
+                if let Some(script_step) = steps.next() {
+                    Some(script_step)
+                } else {
+                    log::info!("[Script] all actions are done");
+                    termination = Some("all actions of the script are done");
+                    break;
+                }
+            } else {
+                None
+            };
+            if let Some(ScriptStep::Expect(ref expect)) = script_step {
+                if let Err(reason) = scenario.check(expect, &chain, &storage)? {
+                    return mismatch_handler.abort(
+                        MismatchKind::ScriptExpectFailed,
+                        format!("[Script] >>> expect {} failed: {}", expect, reason),
+                        &mut report,
+                        &storage,
+                        &mut record,
+                    );
+                }
+                log::info!("[Script] >>> expect {} passed", expect);
+                continue;
+            }
+            step += 1;
+            chain.clock().increase(random_generator.block_interval())?;
 
-            let txs = strategy::build_transactions(&random_generator, &chain, &storage)?;
+            let txs = if let Some(ScriptStep::Submit(submits)) = script_step {
+                let (txs, submitted) = strategy::build_scripted_transactions(
+                    &random_generator,
+                    &chain,
+                    &storage,
+                    &submits,
+                    max_generation,
+                )?;
+                scenario.add_submitted(&submits, submitted);
+                txs
+            } else {
+                build_transactions(
+                    &random_generator,
+                    &chain,
+                    &storage,
+                    &strategies,
+                    max_generation,
+                )?
+            };
+            metrics.add_txs_generated(txs.len());
+            report.txs.generated += txs.len() as u64;
+            // The transactions which should be in the recent rejects of the tx-pool.
+            let mut expected_recent_rejects = Vec::new();
             log::trace!("[SendTxs] try to send transactions");
-            for tx in &txs {
+            // Build the block template after all transactions, or before the rest of them in the
+            // interleaved mode, so they arrive while the block is being built.
+            let template_index = random_generator.template_index(txs.len());
+            let mut built = None;
+            let mut discard_rest = false;
+            let mut submitted = 0;
+            let mut submit_elapsed = time::Duration::ZERO;
+            for index in 0..=txs.len() {
+                if index == template_index {
+                    if index < txs.len() {
+                        log::trace!(
+                            "[BuildBlock] build the block template before {} transactions",
+                            txs.len() - index
+                        );
+                    }
+                    let pool_before = chain.txpool_entries()?;
+                    let built_block =
+                        block::try_build_block(&random_generator, &chain, &block_producers)?;
+                    let (block_view, block_delta) = match built_block {
+                        Ok(built_block) => built_block,
+                        Err(broken) => {
+                            return mismatch_handler.abort(
+                                broken.kind,
+                                format!("[BuildBlock] >>> {}", broken.reason),
+                                &mut report,
+                                &storage,
+                                &mut record,
+                            );
+                        }
+                    };
+                    if !packages.is_empty() {
+                        for reason in
+                            packages.check_template(&pool_before, &block_view, &block_delta)
+                        {
+                            mismatch_handler.handle(
+                                MismatchKind::PackageSkipped,
+                                format!("[Package] >>> {}", reason),
+                                &mut report,
+                                &storage,
+                                &mut record,
+                            )?;
+                        }
+                    }
+                    let stale_proposals = block::check_template_proposals(
+                        &pool_before,
+                        &storage,
+                        &block_view,
+                        &block_delta,
+                        &chain.proposal_window(),
+                    )?;
+                    for reason in stale_proposals {
+                        mismatch_handler.handle(
+                            MismatchKind::StaleProposals,
+                            format!("[BuildBlock] >>> {}", reason),
+                            &mut report,
+                            &storage,
+                            &mut record,
+                        )?;
+                    }
+                    log::trace!(
+                        "new block: num: {}, ts: {}, txs: {}, proposals: {}",
+                        block_view.number(),
+                        block_view.timestamp(),
+                        block_view.transactions().len(),
+                        block_view.data().proposals().len(),
+                    );
+                    built = Some((block_view, block_delta));
+                }
+                let tx = match txs.get(index) {
+                    Some(tx) if !discard_rest => tx,
+                    _ => continue,
+                };
+                if let Some(millis) = random_generator.time_jump() {
+                    log::trace!("[SendTxs] advance the time by {} ms", millis);
+                    chain.clock().increase(millis)?;
+                }
                 let tx_view = tx.view();
                 let tx_hash = tx_view.hash();
+                let seq_id = TxSeqId {
+                    run: run_id,
+                    step,
+                    index: index as u32,
+                };
+                storage.put_tx_seq_id(&tx_hash, &seq_id)?;
                 let changes = tx.changes();
-                let result = chain.txpool_submit_local_tx(tx_view);
+                let submit_start = time::Instant::now();
+                let result = if remote_submitter.is_enabled() {
+                    remote_submitter.submit(
+                        &random_generator,
+                        &chain,
+                        &storage,
+                        &mut relay_oracle,
+                        tx_view,
+                    )?
+                } else {
+                    let result = chain.txpool_submit_local_tx(tx_view)?;
+                    relay_oracle.submitted(tx_hash.clone(), None, result.is_ok());
+                    result
+                };
+                submitted += 1;
+                submit_elapsed += submit_start.elapsed();
+                match result {
+                    Ok(()) => {
+                        metrics.inc_txs_accepted();
+                        report.txs.accepted += 1;
+                        if let Some(package) = tx.package() {
+                            packages.add(tx_hash.clone(), package.clone());
+                        }
+                        stuck_txs.add(tx_hash.clone(), chain.chain_tip_header().number());
+                    }
+                    Err(ref reject) => {
+                        metrics.inc_txs_rejected();
+                        report.txs.rejected += 1;
+                        report.add_reject(reject);
+                        storage.put_reject(&tx_hash, reject)?;
+                    }
+                }
+                report.bias.add_tx(tx.branches(), result.is_ok());
                 match (changes, result) {
                     (Ok((tx_status, updates)), Ok(())) => {
-                        log::info!("[SendTxs] >>> send {:#x} passed", tx_hash);
+                        log::info!("[SendTxs] >>> send {} {:#x} passed", seq_id, tx_hash);
                         storage.submit_tx(tx_view, tx_status, updates)?;
                     }
-                    (Err(updates), Err(_)) => {
-                        log::info!("[SendTxs] >>> send {:#x} failed", tx_hash);
-                        storage.submit_invalid_tx(tx_view)?;
+                    (Err(updates), Err(reject)) => {
+                        log::info!(
+                            "[SendTxs] >>> send {} {:#x} failed ({})",
+                            seq_id,
+                            tx_hash,
+                            reject
+                        );
+                        if let Some(rival) = tx.rival() {
+                            if !matches!(reject, Reject::Resolve(..)) {
+                                let msg = format!(
+                                    "[SendTxs] >>> send {} {:#x} is a double spend of {:#x}, \
+                                    but it's rejected by {} instead of the conflict",
+                                    seq_id, tx_hash, rival, reject
+                                );
+                                mismatch_handler.handle(
+                                    MismatchKind::DoubleSpendMisjudged,
+                                    msg,
+                                    &mut report,
+                                    &storage,
+                                    &mut record,
+                                )?;
+                            }
+                        }
+                        // A full tx-pool rejects the transactions before checking them.
+                        let reasons = tx.fail_reasons();
+                        if !reasons.is_empty()
+                            && !matches!(reject, Reject::Full(..))
+                            && !FailReason::is_expected(reasons, &reject_reason(&reject))
+                        {
+                            let expected = reasons
+                                .iter()
+                                .map(ToString::to_string)
+                                .collect::<Vec<_>>()
+                                .join(", ");
+                            let msg = format!(
+                                "[SendTxs] >>> send {} {:#x} expect failed since {}, \
+                                but it's rejected by {}",
+                                seq_id, tx_hash, expected, reject
+                            );
+                            mismatch_handler.handle(
+                                MismatchKind::RejectReasonMismatched,
+                                msg,
+                                &mut report,
+                                &storage,
+                                &mut record,
+                            )?;
+                        }
+                        // The transactions with unknown inputs are kept as orphans instead.
+                        if tx.is_orphan(&reject) {
+                            storage.submit_orphan_tx(tx_view, chain.clock().now())?;
+                        } else {
+                            if matches!(reject, Reject::Resolve(..)) {
+                                expected_recent_rejects.push((seq_id, tx_hash.clone()));
+                            }
+                            storage.submit_invalid_tx(tx_view)?;
+                        }
                         for (tx_hash, tx_status) in updates {
                             storage.remove_invalid_tx(&tx_hash, &tx_status)?;
                         }
                     }
-                    (Ok(_), Err(errmsg)) => {
-                        log::error!(
-                            "[SendTxs] >>> send {:#x} expect passed but got {}",
+                    (Ok(_), Err(Reject::Full(..))) if run_env.eviction.is_some() => {
+                        log::info!(
+                            "[SendTxs] >>> send {} {:#x} failed since tx-pool is full, \
+                            discard the rest transactions",
+                            seq_id,
+                            tx_hash
+                        );
+                        report.txs.discarded += (txs.len() - index - 1) as u64;
+                        discard_rest = true;
+                    }
+                    (Ok(_), Err(reject)) => {
+                        let msg = format!(
+                            "[SendTxs] >>> send {} {:#x} expect passed but got {}",
+                            seq_id, tx_hash, reject
+                        );
+                        mismatch_handler.handle(
+                            MismatchKind::ExpectPassedButFailed,
+                            msg,
+                            &mut report,
+                            &storage,
+                            &mut record,
+                        )?;
+                        // Follow the tx-pool, the transactions which spend its outputs are
+                        // still expected to pass, they will be mismatched too.
+                        storage.submit_invalid_tx(tx_view)?;
+                    }
+                    (Err(_), Ok(())) if tx.rival().is_some() => {
+                        let msg = format!(
+                            "[SendTxs] >>> send {} {:#x} passed, but it's a double spend of {:#x}",
+                            seq_id,
                             tx_hash,
-                            errmsg
+                            tx.rival().expect("the rival exists")
                         );
-                        process::exit(1);
+                        mismatch_handler.handle(
+                            MismatchKind::DoubleSpendAccepted,
+                            msg,
+                            &mut report,
+                            &storage,
+                            &mut record,
+                        )?;
                     }
                     (Err(_), Ok(())) => {
-                        log::warn!("[SendTxs] >>> send {:#x} expect failed but passed", tx_hash);
+                        let msg = format!(
+                            "[SendTxs] >>> send {} {:#x} expect failed but passed",
+                            seq_id, tx_hash
+                        );
+                        mismatch_handler.handle(
+                            MismatchKind::ExpectFailedButPassed,
+                            msg,
+                            &mut report,
+                            &storage,
+                            &mut record,
+                        )?;
                     }
                 };
             }
 
-            let block_template = chain.get_block_template()?;
-
-            let block: packed::Block = block_template.into();
-            let block_view = block.into_view();
-            log::trace!(
-                "new block: num: {}, ts: {}, txs: {}, proposals: {}",
-                block_view.number(),
-                block_view.timestamp(),
-                block_view.transactions().len(),
-                block_view.data().proposals().len(),
-            );
+            if submitted >= THROUGHPUT_MIN_BATCH {
+                let txs_per_sec = (submitted as f64 / submit_elapsed.as_secs_f64()) as u64;
+                let info = chain.txpool_info()?;
+                log::info!(
+                    "[SendTxs] submit {} transactions in {:?} ({} txs/sec), \
+                    pending: {}, total_size: {}",
+                    submitted,
+                    submit_elapsed,
+                    txs_per_sec,
+                    info.pending_size,
+                    info.total_tx_size,
+                );
+                report.throughput.observe(txs_per_sec, info.total_tx_size);
+            }
 
+            let (block_view, block_delta) = built.expect("the block is always built");
             chain.chain_submit_block(&block_view);
             chain.txpool_submit_block(&block_view)?;
-            storage.confirm_block(&block_view)?;
+            let recent_rejects = chain.txpool_take_recent_rejects();
+            for (seq_id, tx_hash) in expected_recent_rejects {
+                let reason = match recent_rejects.get(&tx_hash) {
+                    Some(true) => continue,
+                    Some(false) => "it's passed to the reject callback but not recorded",
+                    None => "it's never passed to the reject callback",
+                };
+                let msg = format!(
+                    "[SendTxs] >>> send {} {:#x} is rejected by resolving, \
+                    but it's not in the recent rejects of the tx-pool: {}",
+                    seq_id, tx_hash, reason
+                );
+                mismatch_handler.handle(
+                    MismatchKind::RecentRejectMissed,
+                    msg,
+                    &mut report,
+                    &storage,
+                    &mut record,
+                )?;
+            }
+            remote_submitter.check_chunked(&chain, &storage)?;
+            for reason in remote_submitter.take_mismatches() {
+                mismatch_handler.handle(
+                    MismatchKind::RemoteSubmit,
+                    format!("[SendTxs] >>> {}", reason),
+                    &mut report,
+                    &storage,
+                    &mut record,
+                )?;
+            }
+            for reason in relay_oracle.check(&chain, block_view.number()) {
+                mismatch_handler.handle(
+                    MismatchKind::RelayMismatched,
+                    format!("[Relay] >>> {}", reason),
+                    &mut report,
+                    &storage,
+                    &mut record,
+                )?;
+            }
+            let checkpoint = Checkpoint {
+                block_number: block_view.number(),
+                block_hash: block_view.hash(),
+                rng_seed: random_generator.checkpoint_seed(),
+            };
+            let confirmed = ledger::record_block(&mut ledger, &chain, &storage, &block_view)
+                .and_then(|()| {
+                    let window = chain.proposal_window();
+                    storage.confirm_block(&block_view, &checkpoint, &ledger, &window)
+                });
+            if let Err(err) = confirmed {
+                let msg = format!(
+                    "[Storage] >>> failed to confirm block {} ({:#x}): {}",
+                    block_view.number(),
+                    block_view.hash(),
+                    err
+                );
+                return mismatch_handler.abort(
+                    MismatchKind::BlockConfirmFailed,
+                    msg,
+                    &mut report,
+                    &storage,
+                    &mut record,
+                );
+            }
+            if !block_delta.is_empty() {
+                log::trace!(
+                    "[Storage] block {} differs from its template: \
+                    txs: -{} +{}, proposals: -{} +{}",
+                    block_view.number(),
+                    block_delta.dropped_txs.len(),
+                    block_delta.added_txs.len(),
+                    block_delta.dropped_proposals.len(),
+                    block_delta.added_proposals.len(),
+                );
+                storage.put_block_delta(&block_view, &block_delta)?;
+            }
+            metrics.inc_blocks_mined();
+            report.blocks_mined += 1;
+            report.tip_number = block_view.number();
+            if run_env.eviction.is_some() {
+                for reason in eviction::check_evicted(&chain, &storage)? {
+                    mismatch_handler.handle(
+                        MismatchKind::EvictionMisordered,
+                        format!("[Eviction] >>> {}", reason),
+                        &mut report,
+                        &storage,
+                        &mut record,
+                    )?;
+                }
+            }
+            if !packages.is_empty() {
+                packages.retain_in_pool(&chain.txpool_entries()?);
+            }
+            if let Some(limit) = run_env.stuck_after_n_blocks {
+                let stuck = stuck_txs.check(&chain, &storage, block_view.number(), limit)?;
+                for stuck_tx in &stuck {
+                    let msg = format!(
+                        "[Stuck] >>> tx {:#x} is submitted at block {} but still not committed \
+                        after {} blocks: {}",
+                        stuck_tx.tx_hash, stuck_tx.submitted_at, limit, stuck_tx.diagnostic
+                    );
+                    mismatch_handler.handle(
+                        MismatchKind::StuckTxs,
+                        msg,
+                        &mut report,
+                        &storage,
+                        &mut record,
+                    )?;
+                }
+            }
+
+            if !post_block_hooks.is_empty() {
+                let pool = chain.txpool_entries()?;
+                let ctx = HookContext {
+                    block: &block_view,
+                    chain: &chain,
+                    storage: &storage,
+                    pool: &pool,
+                };
+                let failures = post_block_hooks.run(&ctx);
+                if !failures.is_empty() {
+                    let msg = failures
+                        .iter()
+                        .map(|(name, reason)| format!("[Hook] >>> {} failed: {}", name, reason))
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    return mismatch_handler.abort(
+                        MismatchKind::HookFailed,
+                        msg,
+                        &mut report,
+                        &storage,
+                        &mut record,
+                    );
+                }
+            }
+
+            if let Some(interval) = run_env.verify_every_n_blocks {
+                if interval > 0 && (block_view.number() - start_number) % interval == 0 {
+                    let divergences = verify::verify_running(&chain, &storage)?;
+                    if !divergences.is_empty() {
+                        provenance::export(
+                            &data_dir.join(PROVENANCE_FILE),
+                            chain.store(),
+                            &storage,
+                            &divergences,
+                        )?;
+                        let msg = divergences
+                            .iter()
+                            .map(|divergence| format!("[Verify] >>> {}", divergence))
+                            .collect::<Vec<_>>()
+                            .join("\n");
+                        return mismatch_handler.abort(
+                            MismatchKind::StorageDiverged,
+                            msg,
+                            &mut report,
+                            &storage,
+                            &mut record,
+                        );
+                    }
+                    log::trace!("[Verify] block {} is consistent", block_view.number());
+                }
+            }
+
+            if let Some(interval) = run_env.verify_ledger_every_n_blocks {
+                if interval > 0 && (block_view.number() - start_number) % interval == 0 {
+                    if let Err(reason) = ledger::verify(&ledger, &chain)? {
+                        return mismatch_handler.abort(
+                            MismatchKind::LedgerMismatched,
+                            format!("[Ledger] >>> {}", reason),
+                            &mut report,
+                            &storage,
+                            &mut record,
+                        );
+                    }
+                    log::trace!("[Ledger] block {}: {}", block_view.number(), ledger);
+                }
+            }
+
+            if let Some(interval) = run_env.reload_pool_every_n_blocks {
+                if interval > 0 && (block_view.number() - start_number) % interval == 0 {
+                    for reason in reload::reload_pool(&mut chain, &storage)? {
+                        mismatch_handler.handle(
+                            MismatchKind::PoolReload,
+                            format!("[Reload] >>> {}", reason),
+                            &mut report,
+                            &storage,
+                            &mut record,
+                        )?;
+                    }
+                }
+            }
+
+            if let Some(interval) = run_env.bias_report_every_n_blocks {
+                if interval > 0 && (block_view.number() - start_number) % interval == 0 {
+                    report.bias.trace();
+                }
+            }
+            if let Some(adapter) = bias_adapter.as_mut() {
+                if adapter.is_due(block_view.number() - start_number) {
+                    adapter.adapt(&report.bias, &random_generator);
+                }
+            }
+
+            if let Some(interval) = run_env.prune_every_n_blocks {
+                if interval > 0 && (block_view.number() - start_number) % interval == 0 {
+                    let pruned = storage.prune()?;
+                    log::debug!(
+                        "[Storage] prune {} transactions at block {}",
+                        pruned,
+                        block_view.number()
+                    );
+                }
+            }
 
             storage.trace();
             chain.txpool_trace()?;
+            let latencies = metrics
+                .take_block_latency_percentiles()
+                .into_iter()
+                .map(|(operation, [p50, p95, p99])| {
+                    format!("{}: {}/{}/{}", operation.name(), p50, p95, p99)
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            log::trace!(
+                "[Latency] block {}: p50/p95/p99 (us) {}",
+                block_view.number(),
+                latencies
+            );
 
             if run_env.chain_blocks > 0
                 && block_view.number() - start_number >= run_env.chain_blocks
             {
+                termination = Some("chain_blocks is reached");
                 break;
             }
+            if let Some(secs) = run_env.max_duration_secs {
+                if started_at.elapsed() >= time::Duration::from_secs(secs) {
+                    log::info!("Stop since the run lasts for {} seconds", secs);
+                    termination = Some("max_duration_secs is reached");
+                    break;
+                }
+            }
+            if let Some(count) = run_env.max_txs {
+                let submitted = report.txs.accepted + report.txs.rejected;
+                if submitted >= count {
+                    log::info!("Stop since {} transactions are submitted", submitted);
+                    termination = Some("max_txs is reached");
+                    break;
+                }
+            }
 
             sleep_millis(run_env.step_interval);
         }
 
+        if signals.is_shutdown() {
+            log::info!("[Signal] shutdown is requested");
+        }
         log::info!("Finishing work, please wait...");
         chain.txpool_save_pool()?;
+        if let Some(checkpoint) = storage.get_checkpoint()? {
+            log::info!(
+                "[Storage] the final checkpoint is block {} {:#x}",
+                checkpoint.block_number,
+                checkpoint.block_hash
+            );
+        }
+        report.bias.trace();
+        finish_report(&mut report, started_at, &storage);
+        if let Some(ref path) = baselines {
+            let latency = metrics.submit_latency_percentiles(
+                RunSummary::LATENCY_PERCENTILES.map(|(_, quantile)| quantile),
+            );
+            let summary = RunSummary::new(run_env.profile, &report, latency);
+            report.baseline_shifts = compare_with_baselines(path, summary)?;
+        }
+        save_report(&data_dir, &storage, &report)?;
+        let termination = termination.unwrap_or("interrupted by a signal");
+        finish_record(&storage, &mut record, termination)?;
 
         drop(chain);
         drop(storage);
@@ -132,6 +907,121 @@ impl Fuzzer {
     }
 }
 
+// Decide what to do with the mismatches by the policies of their kinds.
+struct MismatchHandler<'a> {
+    policies: ResolvedMismatchPolicies,
+    data_dir: &'a Path,
+    started_at: time::Instant,
+}
+
+impl MismatchHandler<'_> {
+    // Count the mismatch and log it as the policy of its kind, the run is stopped with the
+    // report and a mismatch error if the policy is fatal.
+    fn handle(
+        &self,
+        kind: MismatchKind,
+        msg: String,
+        report: &mut RunReport,
+        storage: &Storage,
+        record: &mut RunRecord,
+    ) -> Result<()> {
+        utils::metrics::global().inc_oracle_mismatches();
+        report.mismatches.count(kind);
+        if self.policies.get(kind).log(&msg) {
+            return self.stop(kind, msg, report, storage, record);
+        }
+        Ok(())
+    }
+
+    // Count the mismatch which the run couldn't go on after, then stop the run with the report
+    // and a mismatch error.
+    fn abort(
+        &self,
+        kind: MismatchKind,
+        msg: String,
+        report: &mut RunReport,
+        storage: &Storage,
+        record: &mut RunRecord,
+    ) -> Result<()> {
+        log::error!("{}", msg);
+        utils::metrics::global().inc_oracle_mismatches();
+        report.mismatches.count(kind);
+        self.stop(kind, msg, report, storage, record)
+    }
+
+    fn stop(
+        &self,
+        kind: MismatchKind,
+        msg: String,
+        report: &mut RunReport,
+        storage: &Storage,
+        record: &mut RunRecord,
+    ) -> Result<()> {
+        report.divergence = Some(msg);
+        finish_report(report, self.started_at, storage);
+        save_report(self.data_dir, storage, report)?;
+        finish_record(storage, record, &format!("mismatched: {}", kind.name()))?;
+        Err(Error::mismatch(kind.name()))
+    }
+}
+
+fn finish_report(report: &mut RunReport, started_at: time::Instant, storage: &Storage) {
+    report.runtime_millis = started_at.elapsed().as_millis() as u64;
+    report.storage = (&storage.stats()).into();
+    report.latencies = utils::metrics::global()
+        .latency_percentiles()
+        .into_iter()
+        .map(|(operation, percentiles)| (operation.name().to_owned(), percentiles.into()))
+        .collect();
+}
+
+fn finish_record(storage: &Storage, record: &mut RunRecord, termination: &str) -> Result<()> {
+    log::info!("Run {} finished: {}", record.run_id, termination);
+    record.finish(termination);
+    storage.put_run_record(record)
+}
+
+fn save_report(data_dir: &Path, storage: &Storage, report: &RunReport) -> Result<()> {
+    log::info!(
+        "Save the report into {}",
+        data_dir.join(REPORT_FILE).display()
+    );
+    storage.put_report(report)?;
+    utils::fs::write_file(data_dir.join(REPORT_FILE), report.to_string())
+}
+
+// Compare the summary with the latest baseline of the same profile, then append it into the index.
+fn compare_with_baselines(path: &Path, summary: RunSummary) -> Result<Vec<String>> {
+    let mut baselines = utils::fs::read_file_opt(path)?
+        .map(|data| {
+            Baselines::from_str(&data).map_err(|err| {
+                let errmsg = format!("failed to parse {} since {}", path.display(), err);
+                Error::config(errmsg)
+            })
+        })
+        .transpose()?
+        .unwrap_or_default();
+    let shifts = if let Some(baseline) = baselines.latest(&summary.profile) {
+        let shifts = summary.compare(baseline);
+        log::info!(
+            "[Baseline] compare with the run (seed: {}) of profile {}: {} shift(s)",
+            baseline.seed,
+            summary.profile,
+            shifts.len()
+        );
+        for shift in &shifts {
+            log::warn!("[Baseline] >>> significant shift of {}", shift);
+        }
+        shifts
+    } else {
+        log::info!("[Baseline] no baseline of profile {}", summary.profile);
+        Vec::new()
+    };
+    baselines.add(summary);
+    utils::fs::write_file(path, baselines.to_string())?;
+    Ok(shifts)
+}
+
 fn sleep_millis(interval: u64) {
     thread::sleep(time::Duration::from_millis(interval));
 }
@@ -1,25 +1,39 @@
-use std::{collections::HashMap, result::Result as StdResult};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    result::Result as StdResult,
+};
 
-use ckb_types::{core::TransactionView, packed, prelude::*};
+use ckb_types::{
+    core::{tx_pool::Reject, FeeRate, TransactionView},
+    packed,
+};
 use indexmap::IndexMap;
 
 use super::Storage;
 use crate::{
     error::{Error, Result},
-    types::{RandomGenerator, TxOutputsStatus, TxStatus},
+    types::{Branch, CellStage, RandomGenerator, TxOutputsStatus, TxStatus},
 };
 
 type TxUpdates = HashMap<packed::Byte32, TxStatus>;
 
-pub(crate) struct TxOverlay {
+// How many times to pick a live cell again, when the picked one is spent in the overlay.
+const LIVE_CELL_RETRIES: usize = 30;
+
+pub struct TxOverlay {
     view: TransactionView,
     changes: TxOverlayChanges,
+    // The generator branches which were exercised to build this transaction.
+    branches: Vec<Branch>,
 }
 
 pub(crate) enum TxOverlayChanges {
     Pending {
         new: TxOutputsStatus,
         updates: TxUpdates,
+        // The package which this transaction pays for, as the child.
+        package: Option<Package>,
     },
     Committed {
         new: TxOutputsStatus,
@@ -27,20 +41,78 @@ pub(crate) enum TxOverlayChanges {
     },
     Failed {
         updates: TxUpdates,
+        // Why it should be rejected, the tx-pool could reject it by any of them.
+        reasons: Vec<FailReason>,
+    },
+    // Spend a cell which is spent by the rival in the same batch, so it should be rejected as a
+    // conflict, and nothing is changed.
+    DoubleSpent {
+        rival: packed::Byte32,
     },
 }
 
+// Why a transaction is expected to be rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FailReason {
+    EmptyInputs,
+    EmptyOutputs,
+    CapacityOverflow,
+    // The capacity of an output is less than its occupied capacity.
+    InsufficientCapacity,
+    // Spend a dead cell.
+    DeadCell,
+    // Spend a cell of a failed transaction.
+    UnknownCell,
+    // Spend a cell of a cellbase before it's mature.
+    ImmatureCell,
+    // Spend a cell before the median time satisfies the timestamp-based since.
+    ImmatureSince,
+    DuplicateInput,
+    // A lock script or a type script fails.
+    ScriptFailure,
+    TooManyAncestors,
+    // A script uses a hash type which is not enabled by the hardfork switch yet.
+    InactiveHashType,
+    // The version is not the default version.
+    WrongVersion,
+    // The transaction is larger than the size limit.
+    TooLarge,
+}
+
+// A parent which pays a low fee, and its child pays for both of them.
+#[derive(Debug, Clone)]
+pub(crate) struct Package {
+    pub(crate) parent: packed::Byte32,
+    // The fee rate of the parent and the child together.
+    pub(crate) fee_rate: FeeRate,
+}
+
 pub(crate) struct Overlay<'a> {
     storage: &'a Storage,
     pub(crate) txs: IndexMap<packed::Byte32, TxOverlay>,
+    // The cells which are spent by the transactions in the overlay, and their spenders.
+    spent: IndexMap<packed::OutPoint, packed::Byte32>,
 }
 
 impl TxOverlay {
     pub(crate) fn new(view: TransactionView, changes: TxOverlayChanges) -> Self {
-        Self { view, changes }
+        Self {
+            view,
+            changes,
+            branches: Vec::new(),
+        }
+    }
+
+    pub(crate) fn with_branches(mut self, branches: Vec<Branch>) -> Self {
+        self.branches = branches;
+        self
     }
 
-    pub(crate) fn is_failed(&self) -> bool {
+    pub(crate) fn branches(&self) -> &[Branch] {
+        &self.branches
+    }
+
+    pub fn is_failed(&self) -> bool {
         self.changes.is_failed()
     }
 
@@ -48,18 +120,106 @@ impl TxOverlay {
         self.changes.to_res()
     }
 
-    pub(crate) fn view(&self) -> &TransactionView {
+    pub fn view(&self) -> &TransactionView {
         &self.view
     }
 
     pub(crate) fn status(&self) -> TxStatus {
         self.changes.to_status()
     }
+
+    pub(crate) fn rival(&self) -> Option<&packed::Byte32> {
+        if let TxOverlayChanges::DoubleSpent { ref rival } = self.changes {
+            Some(rival)
+        } else {
+            None
+        }
+    }
+
+    // The reasons why it should be rejected, empty if it should be accepted or the reasons are
+    // unknown.
+    pub(crate) fn fail_reasons(&self) -> &[FailReason] {
+        if let TxOverlayChanges::Failed { ref reasons, .. } = self.changes {
+            reasons
+        } else {
+            &[]
+        }
+    }
+
+    // The tx-pool keeps the rejected transactions with unknown inputs as orphans, but the double
+    // spends and the transactions which spend dead cells are rejected at once.
+    pub(crate) fn is_orphan(&self, reject: &Reject) -> bool {
+        matches!(reject, Reject::Resolve(..))
+            && self.rival().is_none()
+            && !self.fail_reasons().contains(&FailReason::DeadCell)
+    }
+
+    pub(crate) fn package(&self) -> Option<&Package> {
+        if let TxOverlayChanges::Pending {
+            package: Some(ref package),
+            ..
+        } = self.changes
+        {
+            Some(package)
+        } else {
+            None
+        }
+    }
+}
+
+impl FailReason {
+    // The names of the variants of the rejects which are expected for the reason.
+    pub(crate) fn expected_rejects(self) -> &'static [&'static str] {
+        match self {
+            Self::EmptyInputs
+            | Self::EmptyOutputs
+            | Self::InactiveHashType
+            | Self::WrongVersion => &["Verification", "Malformed"],
+            Self::CapacityOverflow
+            | Self::InsufficientCapacity
+            | Self::ImmatureCell
+            | Self::ImmatureSince
+            | Self::ScriptFailure => &["Verification"],
+            Self::DeadCell | Self::UnknownCell => &["Resolve"],
+            // The duplicate inputs could be found either when resolving or verifying.
+            Self::DuplicateInput => &["Resolve", "Verification"],
+            Self::TooManyAncestors => &["ExceededMaximumAncestorsCount"],
+            Self::TooLarge => &["ExceededTransactionSizeLimit"],
+        }
+    }
+
+    // Whether the actual reject is expected by any of the reasons.
+    pub(crate) fn is_expected(reasons: &[Self], reject: &str) -> bool {
+        reasons
+            .iter()
+            .any(|reason| reason.expected_rejects().contains(&reject))
+    }
+}
+
+impl fmt::Display for FailReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::EmptyInputs => write!(f, "empty inputs"),
+            Self::EmptyOutputs => write!(f, "empty outputs"),
+            Self::CapacityOverflow => write!(f, "capacity overflow"),
+            Self::InsufficientCapacity => write!(f, "insufficient capacity"),
+            Self::DeadCell => write!(f, "dead cell"),
+            Self::UnknownCell => write!(f, "unknown cell"),
+            Self::ImmatureCell => write!(f, "immature cell"),
+            Self::ImmatureSince => write!(f, "immature since"),
+            Self::DuplicateInput => write!(f, "duplicate input"),
+            Self::ScriptFailure => write!(f, "script failure"),
+            Self::TooManyAncestors => write!(f, "too many ancestors"),
+            Self::InactiveHashType => write!(f, "inactive hash type"),
+            Self::WrongVersion => write!(f, "wrong version"),
+            Self::TooLarge => write!(f, "too large"),
+        }
+    }
 }
 
 impl TxOverlayChanges {
     fn is_failed(&self) -> bool {
-        matches!(self, Self::Failed { .. })
+        matches!(self, Self::Failed { .. } | Self::DoubleSpent { .. })
     }
 
     fn to_res(&self) -> StdResult<(TxStatus, TxUpdates), TxUpdates> {
@@ -67,12 +227,14 @@ impl TxOverlayChanges {
             Self::Pending {
                 ref new,
                 ref updates,
+                package: _,
             } => Ok((TxStatus::Pending(new.to_owned()), updates.to_owned())),
             Self::Committed {
                 ref new,
                 ref updates,
             } => Ok((TxStatus::Committed(new.to_owned()), updates.to_owned())),
-            Self::Failed { ref updates } => Err(updates.to_owned()),
+            Self::Failed { ref updates, .. } => Err(updates.to_owned()),
+            Self::DoubleSpent { rival: _ } => Err(TxUpdates::new()),
         }
     }
 
@@ -81,12 +243,13 @@ impl TxOverlayChanges {
             Self::Pending {
                 ref new,
                 updates: _,
+                package: _,
             } => TxStatus::Pending(new.to_owned()),
             Self::Committed {
                 ref new,
                 updates: _,
             } => TxStatus::Committed(new.to_owned()),
-            Self::Failed { updates: _ } => TxStatus::Failed,
+            Self::Failed { .. } | Self::DoubleSpent { rival: _ } => TxStatus::Failed,
         }
     }
 }
@@ -94,15 +257,46 @@ impl TxOverlayChanges {
 impl<'a> Overlay<'a> {
     pub(crate) fn new(storage: &'a Storage) -> Self {
         let txs = IndexMap::new();
-        Self { storage, txs }
+        let spent = IndexMap::new();
+        Self {
+            storage,
+            txs,
+            spent,
+        }
     }
 
     pub(crate) fn add_tx(&mut self, tx: TxOverlay) {
         let hash = tx.view.hash();
-        let result = self.txs.insert(hash, tx);
-        if result.is_some() {
+        if self.txs.contains_key(&hash) {
             panic!("Shouldn't insert same transaction into a overlay twice.");
         }
+        if !tx.is_failed() {
+            for out_point in tx.view.input_pts_iter() {
+                if let Some(spender) = self.spent.insert(out_point, hash.clone()) {
+                    panic!(
+                        "Shouldn't spend a cell twice in a overlay, unless it's a double spend, \
+                        but {:#x} spends the cell which is spent by {:#x}.",
+                        hash, spender
+                    );
+                }
+            }
+        }
+        self.txs.insert(hash, tx);
+    }
+
+    // Pick a cell which is spent by a transaction in the overlay, returns the cell and its
+    // spender.
+    pub(crate) fn random_spent_cell(
+        &self,
+        rg: &RandomGenerator,
+    ) -> Option<(packed::OutPoint, packed::Byte32)> {
+        if self.spent.is_empty() {
+            return None;
+        }
+        let index = rg.usize_less_than(self.spent.len());
+        self.spent
+            .get_index(index)
+            .map(|(out_point, spender)| (out_point.to_owned(), spender.to_owned()))
     }
 
     pub(crate) fn has_tx(&mut self, tx_hash: &packed::Byte32) -> bool {
@@ -128,6 +322,11 @@ impl<'a> Overlay<'a> {
         }
     }
 
+    // The committed cellbases whose origins are tracked, from the newest one.
+    pub(crate) fn recent_cellbases(&self, limit: usize) -> Vec<packed::Byte32> {
+        self.storage.recent_cellbases(limit)
+    }
+
     pub(crate) fn get_tx_status(&self, tx_hash: &packed::Byte32) -> Result<TxStatus> {
         for (new_tx_hash, tx_overlay) in self.txs.iter().rev() {
             if let Ok((_, updates)) = tx_overlay.changes() {
@@ -145,39 +344,122 @@ impl<'a> Overlay<'a> {
         })
     }
 
+    // Pick a transaction uniformly from all transactions in the storage and the overlay, except
+    // those which will be removed since they are spent by failed transactions in the overlay.
     pub(crate) fn random_tx(
         &self,
         rg: &RandomGenerator,
     ) -> Result<Option<(packed::Byte32, TxStatus)>> {
-        'found: for _ in 0..30 {
-            let tx_hash_start = rg.random_hash().pack();
-            let (mut tx_hash, mut tx_status) = self.storage.next_tx_status(&tx_hash_start)?;
-            let mut new_cell_since = None;
-            for (index, (new_tx_hash, tx_overlay)) in self.txs.iter().enumerate() {
-                if new_tx_hash < &tx_hash {
-                    new_cell_since = Some(index);
-                    tx_hash = new_tx_hash.to_owned();
-                    tx_status = tx_overlay.status();
+        let removed = self
+            .txs
+            .values()
+            .filter_map(|tx_overlay| tx_overlay.changes().err())
+            .flat_map(|updates| updates.into_keys())
+            .collect::<HashSet<_>>();
+        let mut removed_positions = removed
+            .iter()
+            .filter_map(|tx_hash| self.storage.tx_hash_position(tx_hash))
+            .collect::<Vec<_>>();
+        removed_positions.sort_unstable();
+        let overlay_candidates = self
+            .txs
+            .keys()
+            .enumerate()
+            .filter(|(_, tx_hash)| !removed.contains(tx_hash))
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        let stored_count = self.storage.tx_hashes_count() - removed_positions.len();
+        let total = stored_count + overlay_candidates.len();
+        if total == 0 {
+            return Ok(None);
+        }
+        let index = rg.usize_less_than(total);
+        let (tx_hash, mut tx_status, skipped) = if index < stored_count {
+            // Skip the removed transactions.
+            let mut position = index;
+            for removed_position in &removed_positions {
+                if *removed_position <= position {
+                    position += 1;
+                } else {
+                    break;
                 }
             }
-            let skipped = new_cell_since.map(|index| index + 1).unwrap_or(0);
-            for (_, tx_overlay) in self.txs.iter().skip(skipped).rev() {
-                if let Err(updates) = tx_overlay.changes() {
-                    if updates.get(&tx_hash).is_some() {
-                        continue 'found;
-                    }
+            let tx_hash = self.storage.tx_hash_at(position).ok_or_else(|| {
+                let errmsg = format!("failed to find tx hash at {}", position);
+                Error::runtime(errmsg)
+            })?;
+            let tx_status = self.storage.get_tx_status(&tx_hash)?.ok_or_else(|| {
+                let errmsg = format!("failed to find tx status for {:#x}", tx_hash);
+                Error::runtime(errmsg)
+            })?;
+            (tx_hash, tx_status, 0)
+        } else {
+            let position = overlay_candidates[index - stored_count];
+            let (tx_hash, tx_overlay) = self.txs.get_index(position).ok_or_else(|| {
+                let errmsg = format!("failed to find overlay tx at {}", position);
+                Error::runtime(errmsg)
+            })?;
+            (tx_hash.to_owned(), tx_overlay.status(), position + 1)
+        };
+        // Apply the latest updates.
+        for (_, tx_overlay) in self.txs.iter().skip(skipped).rev() {
+            if let Ok((_, updates)) = tx_overlay.changes() {
+                if let Some(new_tx_status) = updates.get(&tx_hash) {
+                    tx_status = new_tx_status.clone();
+                    break;
                 }
             }
-            for (_, tx_overlay) in self.txs.iter().skip(skipped).rev() {
-                if let Ok((_, updates)) = tx_overlay.changes() {
-                    if let Some(new_tx_status) = updates.get(&tx_hash) {
-                        tx_status = new_tx_status.clone();
-                        break;
-                    }
-                }
+        }
+        Ok(Some((tx_hash, tx_status)))
+    }
+
+    // Pick a live cell uniformly from the index of the storage and the outputs of the
+    // transactions in the overlay, except those which are spent in the overlay or will be
+    // removed.
+    pub(crate) fn random_live_cell(
+        &self,
+        rg: &RandomGenerator,
+    ) -> Option<(packed::OutPoint, CellStage)> {
+        let removed = self
+            .txs
+            .values()
+            .filter_map(|tx_overlay| tx_overlay.changes().err())
+            .flat_map(|updates| updates.into_keys())
+            .collect::<HashSet<_>>();
+        let overlay_cells = self
+            .txs
+            .iter()
+            .filter(|(_, tx_overlay)| !tx_overlay.is_failed())
+            .flat_map(|(tx_hash, tx_overlay)| {
+                let stages = tx_overlay.status().live_cell_stages();
+                stages
+                    .into_iter()
+                    .enumerate()
+                    .filter_map(move |(index, stage_opt)| {
+                        stage_opt.map(|stage| {
+                            let out_point = packed::OutPoint::new(tx_hash.clone(), index as u32);
+                            (out_point, stage)
+                        })
+                    })
+            })
+            .collect::<Vec<_>>();
+        let stored_count = self.storage.live_cells_index_count();
+        let total = stored_count + overlay_cells.len();
+        if total == 0 {
+            return None;
+        }
+        for _ in 0..LIVE_CELL_RETRIES {
+            let index = rg.usize_less_than(total);
+            let (out_point, stage) = if index < stored_count {
+                self.storage.live_cell_at(index)?
+            } else {
+                overlay_cells[index - stored_count].clone()
+            };
+            if self.spent.contains_key(&out_point) || removed.contains(&out_point.tx_hash()) {
+                continue;
             }
-            return Ok(Some((tx_hash, tx_status)));
+            return Some((out_point, stage));
         }
-        Ok(None)
+        None
     }
 }
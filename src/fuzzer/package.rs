@@ -0,0 +1,84 @@
+use std::collections::{HashMap, HashSet};
+
+use ckb_types::{
+    core::{tx_pool::TxPoolEntryInfo, BlockView, FeeRate},
+    packed,
+};
+
+use super::Package;
+use crate::types::BlockDelta;
+
+// The CPFP packages which are accepted by the tx-pool but not committed yet, indexed by the
+// children.
+#[derive(Default)]
+pub(crate) struct Packages {
+    inner: HashMap<packed::Byte32, Package>,
+}
+
+impl Packages {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    pub(crate) fn add(&mut self, child: packed::Byte32, package: Package) {
+        self.inner.insert(child, package);
+    }
+
+    // Check the block template against the proposed transactions before it, returns the reasons
+    // of the mismatches.
+    //
+    // A ready package should be committed before any proposed transaction which has a lower
+    // fee rate than the package.
+    pub(crate) fn check_template(
+        &self,
+        proposed_before: &TxPoolEntryInfo,
+        block: &BlockView,
+        delta: &BlockDelta,
+    ) -> Vec<String> {
+        let committed = block
+            .tx_hashes()
+            .iter()
+            .skip(1)
+            .chain(delta.dropped_txs.iter())
+            .filter(|tx_hash| !delta.added_txs.contains(*tx_hash))
+            .cloned()
+            .collect::<HashSet<_>>();
+        let lowest_committed = committed
+            .iter()
+            .filter_map(|tx_hash| {
+                proposed_before
+                    .proposed
+                    .get(tx_hash)
+                    .map(|info| (tx_hash, FeeRate::calculate(info.fee, info.size as usize)))
+            })
+            .min_by_key(|(_, fee_rate)| *fee_rate);
+        let (lowest_tx_hash, lowest_fee_rate) = if let Some(lowest) = lowest_committed {
+            lowest
+        } else {
+            return Vec::new();
+        };
+        self.inner
+            .iter()
+            .filter(|(child, package)| {
+                proposed_before.proposed.contains_key(*child)
+                    && proposed_before.proposed.contains_key(&package.parent)
+                    && !committed.contains(*child)
+                    && package.fee_rate > lowest_fee_rate
+            })
+            .map(|(child, package)| {
+                format!(
+                    "package {:#x} -> {:#x} (fee rate: {}) is skipped, \
+                    but tx {:#x} (fee rate: {}) is committed",
+                    package.parent, child, package.fee_rate, lowest_tx_hash, lowest_fee_rate
+                )
+            })
+            .collect()
+    }
+
+    // Forget the packages which are committed or not in the tx-pool any more.
+    pub(crate) fn retain_in_pool(&mut self, pool: &TxPoolEntryInfo) {
+        self.inner.retain(|child, _| {
+            pool.pending.contains_key(child) || pool.proposed.contains_key(child)
+        });
+    }
+}
@@ -0,0 +1,135 @@
+use std::{path::PathBuf, str::FromStr as _, thread};
+
+use super::{Fuzzer, Storage, REPORT_FILE};
+use crate::{
+    config::{ParallelConfig, RunConfig},
+    error::{Error, Result},
+    types::{ParallelReport, RunEnv, RunReport, Script},
+    utils::{self, signals::Signals},
+};
+
+// The aggregated report in the data directory, the reports of the instances are in their own
+// data directories.
+pub(crate) const PARALLEL_REPORT_FILE: &str = "parallel-report.yaml";
+
+// Run the instances concurrently, each in its own thread, with its own mocked chain and storage.
+//
+// The metrics and the signals are shared by all instances. A mismatch only stops its instance,
+// the others run to their ends, then the failures are recorded with the aggregated report and a
+// mismatch error is returned.
+//
+// The fake time is shared too, the tx-pools see the fastest clock of the instances instead of
+// the clocks of their chains, so the time lock strategy and the timestamp anomaly producer are
+// refused by the config, and the orphans are never expired in the storages.
+pub(crate) fn run_parallel(cfg: ParallelConfig) -> Result<()> {
+    let ParallelConfig {
+        data_dir,
+        run_env,
+        script,
+        metrics_listen,
+        resume,
+        instance_dirs,
+        lock: _lock,
+    } = cfg;
+    // Enable the shared states before any instance starts.
+    utils::faketime::enable()?;
+    if let Some(listen) = metrics_listen {
+        utils::metrics::serve(listen)?;
+    }
+    let signals = Signals::capture()?;
+    log::info!("[Parallel] run {} instances", instance_dirs.len());
+    let handles = instance_dirs
+        .iter()
+        .enumerate()
+        .map(|(index, instance_dir)| {
+            let instance_dir = instance_dir.clone();
+            let mut run_env = run_env.clone();
+            // Each instance derives its seed from the configured one, or chooses a random one.
+            run_env.seed = run_env.seed.map(|seed| seed.wrapping_add(index as u64));
+            let script = script.clone();
+            let signals = signals.clone();
+            thread::Builder::new()
+                .name(format!("instance-{}", index))
+                .spawn(move || run_instance(instance_dir, run_env, script, resume, signals))
+                .map_err(|err| {
+                    let errmsg = format!("failed to spawn instance {} since {}", index, err);
+                    Error::runtime(errmsg)
+                })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut failures = Vec::new();
+    let mut mismatched = false;
+    for (index, handle) in handles.into_iter().enumerate() {
+        let failure = match handle.join() {
+            Ok(Ok(())) => continue,
+            Ok(Err(err)) => {
+                mismatched |= matches!(err, Error::Mismatch(_));
+                err.to_string()
+            }
+            Err(_) => "panicked".to_owned(),
+        };
+        log::error!("[Parallel] instance {} failed: {}", index, failure);
+        failures.push((index as u32, failure));
+    }
+
+    let mut report = ParallelReport::default();
+    for (index, instance_dir) in instance_dirs.iter().enumerate() {
+        let report_file = instance_dir.join(REPORT_FILE);
+        if let Some(data) = utils::fs::read_file_opt(&report_file)? {
+            let instance_report = RunReport::from_str(&data).map_err(|err| {
+                let errmsg = format!("failed to parse {} since {}", report_file.display(), err);
+                Error::runtime(errmsg)
+            })?;
+            report.add(index as u32, instance_report);
+        }
+    }
+    report.failures.extend(failures.iter().cloned());
+    log::info!(
+        "[Parallel] {} blocks, {} accepted and {} rejected transactions, {} mismatches",
+        report.blocks_mined,
+        report.txs.accepted,
+        report.txs.rejected,
+        report.mismatches.total()
+    );
+    let report_file = data_dir.join(PARALLEL_REPORT_FILE);
+    log::info!("Save the aggregated report into {}", report_file.display());
+    utils::fs::write_file(&report_file, report.to_string())?;
+
+    if failures.is_empty() {
+        return Ok(());
+    }
+    let errmsg = failures
+        .iter()
+        .map(|(index, failure)| format!("instance {} failed since {}", index, failure))
+        .collect::<Vec<_>>()
+        .join(", ");
+    if mismatched {
+        Err(Error::mismatch(errmsg))
+    } else {
+        Err(Error::runtime(errmsg))
+    }
+}
+
+// The storage is loaded in the thread of the instance, so the instances load concurrently.
+fn run_instance(
+    data_dir: PathBuf,
+    run_env: RunEnv,
+    script: Option<Script>,
+    resume: bool,
+    signals: Signals,
+) -> Result<()> {
+    let mut storage = Storage::load(data_dir.join("storage"))?;
+    storage.disable_orphan_expiry();
+    let cfg = RunConfig {
+        data_dir,
+        storage,
+        run_env,
+        script,
+        metrics_listen: None,
+        resume,
+        baselines: None,
+        lock: None,
+        ephemeral: None,
+    };
+    Fuzzer::load(cfg)?.run_with(None, signals)
+}
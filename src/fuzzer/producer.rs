@@ -0,0 +1,378 @@
+use std::collections::{HashMap, HashSet};
+
+use ckb_dao_utils::{extract_dao_data, pack_dao_data};
+use ckb_store::ChainStore as _;
+use ckb_types::{
+    core::{BlockView, Capacity},
+    packed,
+    prelude::*,
+};
+
+use super::{ledger, MockedChain};
+use crate::{
+    error::{Error, Result},
+    types::{RandomGenerator, RunEnv, WeightedStrategy},
+};
+
+// A full node accepts the blocks which are at most 15 seconds in the future.
+const ALLOWED_FUTURE_BLOCKTIME_MILLIS: u64 = 15 * 1000;
+// The max distance of the timestamps which are far in the future.
+const FAR_FUTURE_MILLIS: u64 = 60 * 60 * 1000;
+
+// A policy to produce the next block from the block template.
+pub(crate) trait BlockProducer {
+    // Returns the block to commit, the dependencies of the committed transactions should be
+    // satisfied in it.
+    fn produce(
+        &self,
+        rg: &RandomGenerator,
+        chain: &MockedChain,
+        template: &BlockView,
+    ) -> Result<BlockView>;
+}
+
+// Commit the block template as it is.
+struct TemplateProducer;
+
+// Drop a random subset of the committed transactions.
+struct PartialCommitProducer;
+
+// Drop a random subset of the proposals, so the transactions are proposed later.
+struct DropProposalsProducer;
+
+// Never commit the withheld transactions, but still propose them.
+struct WithholdProducer;
+
+// Stamp the block with an anomalous timestamp, to disturb the median time of the next blocks.
+struct TimestampAnomalyProducer;
+
+// All available producers, new producers should be registered here.
+const REGISTRY: &[(&str, &dyn BlockProducer)] = &[
+    ("template", &TemplateProducer),
+    ("partial_commit", &PartialCommitProducer),
+    ("drop_proposals", &DropProposalsProducer),
+    ("withhold", &WithholdProducer),
+    ("timestamp_anomaly", &TimestampAnomalyProducer),
+];
+
+// The producers which are used in a run, one of them is chosen for each block.
+pub struct BlockProducers {
+    producers: Vec<(&'static str, &'static dyn BlockProducer)>,
+    weights: Vec<u32>,
+}
+
+impl Default for BlockProducers {
+    fn default() -> Self {
+        Self {
+            producers: vec![REGISTRY[0]],
+            weights: vec![1],
+        }
+    }
+}
+
+impl BlockProducers {
+    pub(crate) fn from_config(config: &[WeightedStrategy]) -> Result<Self> {
+        let mut producers = Vec::new();
+        let mut weights = Vec::new();
+        for WeightedStrategy { name, weight } in config {
+            producers.push(find_producer(name)?);
+            weights.push(*weight);
+        }
+        if weights.iter().all(|weight| *weight == 0) {
+            return Err(Error::config(
+                "at least one block producer should have a positive weight",
+            ));
+        }
+        Ok(Self { producers, weights })
+    }
+
+    // The producers from the config, the section "partial_commit" is still respected when no
+    // producers are set.
+    pub(crate) fn from_run_env(run_env: &RunEnv) -> Result<Self> {
+        if let Some(ref config) = run_env.block_producers {
+            Self::from_config(config)
+        } else if let Some(ref partial_commit) = run_env.partial_commit {
+            let config = vec![
+                WeightedStrategy {
+                    name: "template".to_owned(),
+                    weight: 100u32.saturating_sub(partial_commit.block_chance),
+                },
+                WeightedStrategy {
+                    name: "partial_commit".to_owned(),
+                    weight: partial_commit.block_chance,
+                },
+            ];
+            Self::from_config(&config)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    pub(crate) fn choose(
+        &self,
+        rg: &RandomGenerator,
+    ) -> (&'static str, &'static dyn BlockProducer) {
+        self.producers[rg.weighted_index(&self.weights)]
+    }
+}
+
+// The names of all available producers.
+pub(crate) fn producer_names() -> Vec<&'static str> {
+    REGISTRY.iter().map(|(name, _)| *name).collect()
+}
+
+fn find_producer(name: &str) -> Result<(&'static str, &'static dyn BlockProducer)> {
+    REGISTRY
+        .iter()
+        .find(|(registered, _)| *registered == name)
+        .copied()
+        .ok_or_else(|| {
+            let available = producer_names().join(", ");
+            let errmsg = format!(
+                "block producer {} is unknown (available: {})",
+                name, available
+            );
+            Error::config(errmsg)
+        })
+}
+
+impl BlockProducer for TemplateProducer {
+    fn produce(
+        &self,
+        _rg: &RandomGenerator,
+        _chain: &MockedChain,
+        template: &BlockView,
+    ) -> Result<BlockView> {
+        Ok(template.clone())
+    }
+}
+
+// A transaction is dropped if any of its inputs comes from a dropped transaction, so the
+// dependencies in the block are still satisfied.
+// The dropped transactions are still proposed in the tx-pool, they are pending in the storage.
+impl BlockProducer for PartialCommitProducer {
+    fn produce(
+        &self,
+        rg: &RandomGenerator,
+        chain: &MockedChain,
+        template: &BlockView,
+    ) -> Result<BlockView> {
+        let mut dropped = HashSet::new();
+        let mut txs = Vec::new();
+        for (index, tx) in template.transactions().into_iter().enumerate() {
+            if index == 0 {
+                // Always keep the cellbase.
+                txs.push(tx);
+                continue;
+            }
+            let tx_hash = tx.hash();
+            let depends_on_dropped = tx
+                .input_pts_iter()
+                .any(|out_point| dropped.contains(&out_point.tx_hash()));
+            if depends_on_dropped || !rg.keep_committed_tx() {
+                log::trace!("[BuildBlock] >>> drop {:#x}", tx_hash);
+                dropped.insert(tx_hash);
+            } else {
+                txs.push(tx);
+            }
+        }
+        log::trace!(
+            "[BuildBlock] commit partially: keep {} and drop {} transactions",
+            txs.len() - 1,
+            dropped.len()
+        );
+        let dao = dao_without_dropped(chain, template, &dropped)?;
+        let block = template
+            .as_advanced_builder()
+            .set_transactions(txs)
+            .dao(dao)
+            .build();
+        Ok(block)
+    }
+}
+
+// The dropped proposals are still pending in the tx-pool, they should be proposed again.
+impl BlockProducer for DropProposalsProducer {
+    fn produce(
+        &self,
+        rg: &RandomGenerator,
+        _chain: &MockedChain,
+        template: &BlockView,
+    ) -> Result<BlockView> {
+        let total = template.data().proposals().len();
+        let proposals = template
+            .data()
+            .proposals()
+            .into_iter()
+            .filter(|_| rg.keep_proposal())
+            .collect::<Vec<_>>();
+        log::trace!(
+            "[BuildBlock] drop proposals: keep {} of {} proposals",
+            proposals.len(),
+            total
+        );
+        let block = template
+            .as_advanced_builder()
+            .set_proposals(proposals)
+            .build();
+        Ok(block)
+    }
+}
+
+// The withheld transactions stay proposed in the tx-pool until the proposals are expired, then
+// they should be proposed again, so they are only committed by the other producers.
+// A transaction is dropped if any of its inputs comes from a dropped transaction, the same as
+// committing partially.
+impl BlockProducer for WithholdProducer {
+    fn produce(
+        &self,
+        rg: &RandomGenerator,
+        chain: &MockedChain,
+        template: &BlockView,
+    ) -> Result<BlockView> {
+        let mut withheld = HashSet::new();
+        let mut txs = Vec::new();
+        for (index, tx) in template.transactions().into_iter().enumerate() {
+            if index == 0 {
+                // Always keep the cellbase.
+                txs.push(tx);
+                continue;
+            }
+            let tx_hash = tx.hash();
+            let depends_on_withheld = tx
+                .input_pts_iter()
+                .any(|out_point| withheld.contains(&out_point.tx_hash()));
+            if depends_on_withheld || rg.is_withheld(&tx_hash) {
+                log::trace!("[BuildBlock] >>> withhold {:#x}", tx_hash);
+                withheld.insert(tx_hash);
+            } else {
+                txs.push(tx);
+            }
+        }
+        log::trace!(
+            "[BuildBlock] withhold: commit {} and withhold {} transactions",
+            txs.len() - 1,
+            withheld.len()
+        );
+        let dao = dao_without_dropped(chain, template, &withheld)?;
+        let block = template
+            .as_advanced_builder()
+            .set_transactions(txs)
+            .dao(dao)
+            .build();
+        Ok(block)
+    }
+}
+
+// The timestamp is the same as the parent, a little older than the parent, or in the future
+// within or beyond the window which a full node allows.
+// It's always newer than the median time of the parent, as the consensus requires. The clock of
+// the chain is not changed, the blocks after a future one are just newer than their parents.
+impl BlockProducer for TimestampAnomalyProducer {
+    fn produce(
+        &self,
+        rg: &RandomGenerator,
+        chain: &MockedChain,
+        template: &BlockView,
+    ) -> Result<BlockView> {
+        let parent_timestamp = chain.chain_tip_header().timestamp();
+        let earliest = chain.tip_median_time() + 1;
+        let (anomaly, timestamp) = match rg.usize_less_than(4) {
+            0 => ("same as the parent", parent_timestamp.max(earliest)),
+            1 => {
+                let timestamp = if parent_timestamp > earliest {
+                    rg.u64_between(earliest, parent_timestamp)
+                } else {
+                    earliest
+                };
+                ("older than the parent", timestamp)
+            }
+            2 => {
+                let offset = rg.u64_between(1, ALLOWED_FUTURE_BLOCKTIME_MILLIS + 1);
+                ("in the near future", template.timestamp() + offset)
+            }
+            _ => {
+                let offset = rg.u64_between(ALLOWED_FUTURE_BLOCKTIME_MILLIS + 1, FAR_FUTURE_MILLIS);
+                ("in the far future", template.timestamp() + offset)
+            }
+        };
+        log::trace!(
+            "[BuildBlock] stamp the block {} instead of {}: {}",
+            timestamp,
+            template.timestamp(),
+            anomaly
+        );
+        let block = template
+            .as_advanced_builder()
+            .timestamp(timestamp.pack())
+            .build();
+        Ok(block)
+    }
+}
+
+// The DAO field of the template counts all its transactions, so the occupied capacities and the
+// interests of the dropped transactions are reverted from it.
+// The accumulated rate and the total issuance don't depend on the transactions, and the cellbase
+// rewards an earlier block, so they are kept.
+fn dao_without_dropped(
+    chain: &MockedChain,
+    template: &BlockView,
+    dropped: &HashSet<packed::Byte32>,
+) -> Result<packed::Byte32> {
+    let dao = template.header().dao();
+    if dropped.is_empty() {
+        return Ok(dao);
+    }
+    let store = chain.store();
+    let in_block = template
+        .transactions()
+        .into_iter()
+        .map(|tx| (tx.hash(), tx))
+        .collect::<HashMap<_, _>>();
+    let capacities = ledger::committed_capacities(chain, template)?;
+    let (ar, total, mut secondary, mut occupied) = extract_dao_data(dao).map_err(Error::runtime)?;
+    for (tx, capacity) in template.transactions().into_iter().skip(1).zip(capacities) {
+        if !dropped.contains(&tx.hash()) {
+            continue;
+        }
+        for (output, data) in tx.outputs_with_data_iter() {
+            let added = occupied_capacity(&output, &data)?;
+            occupied = occupied.safe_sub(added).map_err(Error::runtime)?;
+        }
+        for out_point in tx.input_pts_iter() {
+            let tx_hash = out_point.tx_hash();
+            let index: u32 = out_point.index().unpack();
+            let (output, data) = in_block
+                .get(&tx_hash)
+                .cloned()
+                .or_else(|| {
+                    store
+                        .get_transaction(&tx_hash)
+                        .map(|(input_tx, _)| input_tx)
+                })
+                .and_then(|input_tx| input_tx.output_with_data(index as usize))
+                .ok_or_else(|| {
+                    let errmsg = format!(
+                        "failed to find the input {:#x} of dropped tx {:#x}",
+                        tx_hash,
+                        tx.hash()
+                    );
+                    Error::runtime(errmsg)
+                })?;
+            let freed = occupied_capacity(&output, &data)?;
+            occupied = occupied.safe_add(freed).map_err(Error::runtime)?;
+        }
+        // The interests are taken from the secondary issuance when they are withdrawn.
+        secondary = secondary
+            .safe_add(Capacity::shannons(capacity.interest))
+            .map_err(Error::runtime)?;
+    }
+    Ok(pack_dao_data(ar, total, secondary, occupied))
+}
+
+fn occupied_capacity(output: &packed::CellOutput, data: &[u8]) -> Result<Capacity> {
+    let data_capacity = Capacity::bytes(data.len()).map_err(Error::runtime)?;
+    output
+        .occupied_capacity(data_capacity)
+        .map_err(Error::runtime)
+}
@@ -0,0 +1,175 @@
+use std::{collections::HashSet, path::Path};
+
+use ckb_store::{ChainDB, ChainStore as _};
+use ckb_types::{
+    core::{BlockNumber, TransactionView},
+    packed,
+    prelude::*,
+};
+use serde_json::{json, Value};
+
+use super::{block::display_out_point, verify::Divergence, Storage};
+use crate::{
+    error::{Error, Result},
+    types::TxEventKind,
+    utils,
+};
+
+// The file in the data directory to export the provenances of the divergences.
+pub(crate) const PROVENANCE_FILE: &str = "provenance.json";
+
+// Stop at the ancestors which are too far, the chains of transactions could be very long.
+const MAX_DEPTH: usize = 32;
+
+// Export the provenances of the cells which are involved in the divergences into a JSON file.
+pub(crate) fn export(
+    path: &Path,
+    store: &ChainDB,
+    storage: &Storage,
+    divergences: &[Divergence],
+) -> Result<()> {
+    let mut items = Vec::with_capacity(divergences.len());
+    for divergence in divergences {
+        let mut visited = HashSet::new();
+        let mut cells = Vec::new();
+        for out_point in involved_cells(store, storage, divergence)? {
+            cells.push(cell_provenance(
+                store,
+                storage,
+                &out_point,
+                0,
+                &mut visited,
+            )?);
+        }
+        items.push(json!({
+            "divergence": divergence.to_string(),
+            "cells": cells,
+        }));
+    }
+    let content = serde_json::to_string_pretty(&items).map_err(|err| {
+        let errmsg = format!("failed to serialize the provenances since {}", err);
+        Error::runtime(errmsg)
+    })?;
+    log::info!(
+        "[Verify] export the provenances of {} divergences into {}",
+        divergences.len(),
+        path.display()
+    );
+    utils::fs::write_file(path, content)
+}
+
+// The cells of a divergence: the cell itself, or the inputs of the transaction, since the
+// transitions of the transaction are recorded in the histories of its inputs too.
+fn involved_cells(
+    store: &ChainDB,
+    storage: &Storage,
+    divergence: &Divergence,
+) -> Result<Vec<packed::OutPoint>> {
+    let tx_hash = match divergence {
+        Divergence::LiveCellNotInChain(out_point) => return Ok(vec![out_point.clone()]),
+        Divergence::CommittedNotInChain(tx_hash)
+        | Divergence::PendingInChain(tx_hash)
+        | Divergence::PendingNotInPool(tx_hash) => tx_hash,
+        Divergence::PoolTipNotChainTip(..) => return Ok(Vec::new()),
+    };
+    let cells = find_transaction(store, storage, tx_hash)?
+        .map(|tx| tx.input_pts_iter().collect())
+        .unwrap_or_default();
+    Ok(cells)
+}
+
+// The provenance of a cell: the transaction which creates it, all transitions of it, and the
+// provenances of the inputs of the transaction, recursively.
+fn cell_provenance(
+    store: &ChainDB,
+    storage: &Storage,
+    out_point: &packed::OutPoint,
+    depth: usize,
+    visited: &mut HashSet<packed::OutPoint>,
+) -> Result<Value> {
+    let tx_hash = out_point.tx_hash();
+    let index: u32 = out_point.index().unpack();
+    let mut node = json!({ "out_point": display_out_point(out_point) });
+    if !visited.insert(out_point.clone()) {
+        // The same cell is already in the tree.
+        node["repeated"] = json!(true);
+        return Ok(node);
+    }
+    let tx_status = storage.get_tx_status(&tx_hash)?;
+    node["status"] = match tx_status {
+        Some(ref tx_status) => match tx_status.outputs() {
+            Some(inner) if (index as usize) < inner.count() => {
+                json!(inner.status(index as usize).to_string())
+            }
+            Some(_) => json!("untracked"),
+            None => json!(tx_status.to_string()),
+        },
+        None => json!("untracked"),
+    };
+    let history = storage.get_tx_history(&tx_hash)?.unwrap_or_default();
+    let committed_in = history
+        .events
+        .iter()
+        .rev()
+        .find(|event| matches!(event.kind, TxEventKind::Committed))
+        .map(|event| event.number)
+        .or_else(|| committed_number(store, &tx_hash));
+    let mut created_by = json!({
+        "tx_hash": format!("{:#x}", tx_hash),
+        "status": tx_status.map(|status| status.to_string()),
+        "committed_in": committed_in,
+    });
+    if let Some(seq_id) = storage.get_tx_seq_id(&tx_hash)? {
+        created_by["sequence_id"] = json!(seq_id.to_string());
+    }
+    if let Some(proposals) = storage.get_proposals(&tx_hash)? {
+        created_by["proposed_in"] = json!(proposals.to_string());
+    }
+    if let Some(reason) = storage.get_reject(&tx_hash)? {
+        created_by["rejected_by"] = json!(reason);
+    }
+    node["created_by"] = created_by;
+    node["transitions"] = history
+        .events
+        .iter()
+        .filter(|event| event.concerns(index))
+        .map(|event| json!({ "number": event.number, "event": event.kind.to_string() }))
+        .collect();
+    node["spent_by"] = json!(history
+        .spender(index)
+        .map(|spender| format!("{:#x}", spender)));
+    if depth >= MAX_DEPTH {
+        node["truncated"] = json!(true);
+        return Ok(node);
+    }
+    if let Some(tx) = find_transaction(store, storage, &tx_hash)? {
+        let mut inputs = Vec::new();
+        // The cellbase has no real inputs.
+        if !tx.is_cellbase() {
+            for input in tx.input_pts_iter() {
+                inputs.push(cell_provenance(store, storage, &input, depth + 1, visited)?);
+            }
+        }
+        node["inputs"] = Value::Array(inputs);
+    }
+    Ok(node)
+}
+
+// Uncommitted transactions are in the storage, committed transactions are in the chain.
+fn find_transaction(
+    store: &ChainDB,
+    storage: &Storage,
+    tx_hash: &packed::Byte32,
+) -> Result<Option<TransactionView>> {
+    if let Some(tx) = storage.get_transaction(tx_hash)? {
+        return Ok(Some(tx));
+    }
+    Ok(store.get_transaction(tx_hash).map(|(tx, _)| tx))
+}
+
+// The transactions which are committed before the histories are recorded.
+fn committed_number(store: &ChainDB, tx_hash: &packed::Byte32) -> Option<BlockNumber> {
+    store
+        .get_transaction(tx_hash)
+        .and_then(|(_, block_hash)| store.get_block_number(&block_hash))
+}
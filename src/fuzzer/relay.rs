@@ -0,0 +1,142 @@
+use std::collections::{HashMap, HashSet};
+
+use ckb_network::PeerIndex;
+use ckb_tx_pool::service::TxVerificationResult;
+use ckb_types::{core::BlockNumber, packed};
+
+use super::MockedChain;
+
+// Whether the peer of a transaction should be penalized.
+enum Penalty {
+    // Local transactions have no peers to penalize.
+    Forbidden,
+    // Invalid remote transactions, the peers could be penalized or not.
+    Allowed,
+    // Declared wrong cycles.
+    Required(PeerIndex),
+}
+
+// The broadcast decision of a submitted transaction.
+struct ExpectedRelay {
+    // Only the accepted transactions are relayed, as they are from the original peers.
+    relayed: Option<Option<PeerIndex>>,
+    penalty: Penalty,
+}
+
+// Drain the results which are sent to the relayer, and check them against the results of the
+// submissions: exactly the accepted transactions are relayed, and the peers are only penalized
+// for the invalid transactions.
+#[derive(Default)]
+pub(crate) struct RelayOracle {
+    expected: HashMap<packed::Byte32, ExpectedRelay>,
+}
+
+impl RelayOracle {
+    // The last submission of a transaction decides whether it's relayed.
+    pub(crate) fn submitted(
+        &mut self,
+        tx_hash: packed::Byte32,
+        peer: Option<PeerIndex>,
+        accepted: bool,
+    ) {
+        let relayed = if accepted { Some(peer) } else { None };
+        self.expected
+            .entry(tx_hash)
+            .and_modify(|expected| expected.relayed = relayed)
+            .or_insert(ExpectedRelay {
+                relayed,
+                penalty: Penalty::Forbidden,
+            });
+    }
+
+    pub(crate) fn may_penalize(&mut self, tx_hash: packed::Byte32) {
+        if let Some(expected) = self.expected.get_mut(&tx_hash) {
+            if matches!(expected.penalty, Penalty::Forbidden) {
+                expected.penalty = Penalty::Allowed;
+            }
+        }
+    }
+
+    pub(crate) fn must_penalize(&mut self, tx_hash: packed::Byte32, peer: PeerIndex) {
+        if let Some(expected) = self.expected.get_mut(&tx_hash) {
+            expected.penalty = Penalty::Required(peer);
+        }
+    }
+
+    // Reconcile the results since the last block, all results are taken even if nothing is
+    // expected, since they are never consumed by others.
+    //
+    // The results of the transactions which are not submitted in this run, for example,
+    // resubmitted when resuming, are ignored.
+    pub(crate) fn check(&mut self, chain: &MockedChain, number: BlockNumber) -> Vec<String> {
+        let mut relayed = HashMap::new();
+        let mut penalized = HashSet::new();
+        let mut duplicated = Vec::new();
+        for result in chain.txpool_take_relay_results() {
+            match result {
+                TxVerificationResult::Ok {
+                    original_peer,
+                    tx_hash,
+                    ..
+                } => {
+                    if relayed.insert(tx_hash.clone(), original_peer).is_some() {
+                        duplicated.push(tx_hash);
+                    }
+                }
+                TxVerificationResult::Reject { tx_hash } => {
+                    penalized.insert(tx_hash);
+                }
+                _ => {}
+            }
+        }
+        let mut mismatches = Vec::new();
+        for tx_hash in duplicated {
+            if self.expected.contains_key(&tx_hash) {
+                mismatches.push(format!("{:#x} is relayed more than once", tx_hash));
+            }
+        }
+        let accepted = self
+            .expected
+            .values()
+            .filter(|expected| expected.relayed.is_some())
+            .count();
+        let mut matched = 0;
+        for (tx_hash, expected) in self.expected.drain() {
+            match (expected.relayed, relayed.get(&tx_hash)) {
+                (Some(peer), Some(original_peer)) if peer == *original_peer => matched += 1,
+                (Some(peer), Some(original_peer)) => mismatches.push(format!(
+                    "{:#x} from {:?} is relayed as it's from {:?}",
+                    tx_hash, peer, original_peer
+                )),
+                (Some(peer), None) => mismatches.push(format!(
+                    "{:#x} from {:?} is accepted but not relayed",
+                    tx_hash, peer
+                )),
+                (None, Some(original_peer)) => mismatches.push(format!(
+                    "{:#x} is rejected but relayed as it's from {:?}",
+                    tx_hash, original_peer
+                )),
+                (None, None) => {}
+            }
+            match (expected.penalty, penalized.contains(&tx_hash)) {
+                (Penalty::Required(peer), false) => mismatches.push(format!(
+                    "{:#x} from {:?} declared wrong cycles, but the peer is not penalized",
+                    tx_hash, peer
+                )),
+                (Penalty::Forbidden, true) => mismatches.push(format!(
+                    "{:#x} is submitted locally, but it's rejected to the relayer",
+                    tx_hash
+                )),
+                _ => {}
+            }
+        }
+        log::trace!(
+            "[Relay] block {}: {} of {} accepted transactions are relayed, {} results in total",
+            number,
+            matched,
+            accepted,
+            relayed.len() + penalized.len()
+        );
+        mismatches
+    }
+}
@@ -0,0 +1,95 @@
+use std::{collections::HashSet, thread, time};
+
+use ckb_types::{core::tx_pool::TxPoolEntryInfo, packed};
+
+use super::{MockedChain, Storage};
+use crate::error::Result;
+
+// The persisted transactions are loaded after the new tx-pool starts, wait for them for a while.
+const LOAD_TIMEOUT: time::Duration = time::Duration::from_secs(10);
+const LOAD_POLL_INTERVAL: time::Duration = time::Duration::from_millis(100);
+
+// Save the tx-pool and start a new one from the persisted data, then the pending and proposed
+// transactions should be exactly the uncommitted transactions in the storage, and none of them
+// should be moved between the pending pool and the proposed pool, since the chain is the same.
+//
+// Returns the mismatches.
+pub(crate) fn reload_pool(chain: &mut MockedChain, storage: &Storage) -> Result<Vec<String>> {
+    let before = chain.txpool_entries()?;
+    let saved_count = before.pending.len() + before.proposed.len();
+    chain.txpool_reload()?;
+    let after = wait_for_loaded(chain, saved_count)?;
+    // The loaded transactions are submitted again, they are not from the fuzzer.
+    let relayed = chain.txpool_take_relay_results().len();
+    let rejected = chain.txpool_take_recent_rejects().len();
+    log::debug!(
+        "[Reload] save {} transactions, load {} transactions, {} relayed and {} rejected",
+        saved_count,
+        after.pending.len() + after.proposed.len(),
+        relayed,
+        rejected
+    );
+
+    let mut mismatches = Vec::new();
+    let mut expected = HashSet::new();
+    for (tx_hash, tx_status) in storage.tx_statuses()? {
+        if !tx_status.is_uncommitted() {
+            continue;
+        }
+        if !after.pending.contains_key(&tx_hash) && !after.proposed.contains_key(&tx_hash) {
+            mismatches.push(format!(
+                "{:#x} is {} but it's lost after the tx-pool is reloaded",
+                tx_hash,
+                tx_status.name()
+            ));
+        }
+        expected.insert(tx_hash);
+    }
+    for tx_hash in after.pending.keys().chain(after.proposed.keys()) {
+        if !expected.contains(tx_hash) {
+            mismatches.push(format!(
+                "{:#x} is not expected but it's loaded into the tx-pool",
+                tx_hash
+            ));
+        }
+    }
+    for (tx_hash, from, to) in moved_txs(&before, &after) {
+        mismatches.push(format!(
+            "{:#x} is moved from {} to {} after the tx-pool is reloaded",
+            tx_hash, from, to
+        ));
+    }
+    Ok(mismatches)
+}
+
+// Poll the entries until all saved transactions are loaded, or the timeout, the lost ones are
+// reported by the caller.
+fn wait_for_loaded(chain: &MockedChain, saved_count: usize) -> Result<TxPoolEntryInfo> {
+    let started_at = time::Instant::now();
+    loop {
+        let entries = chain.txpool_entries()?;
+        if entries.pending.len() + entries.proposed.len() >= saved_count
+            || started_at.elapsed() >= LOAD_TIMEOUT
+        {
+            return Ok(entries);
+        }
+        thread::sleep(LOAD_POLL_INTERVAL);
+    }
+}
+
+fn moved_txs<'a>(
+    before: &'a TxPoolEntryInfo,
+    after: &'a TxPoolEntryInfo,
+) -> Vec<(&'a packed::Byte32, &'static str, &'static str)> {
+    let pending_to_proposed = before
+        .pending
+        .keys()
+        .filter(|tx_hash| after.proposed.contains_key(tx_hash))
+        .map(|tx_hash| (tx_hash, "pending", "proposed"));
+    let proposed_to_pending = before
+        .proposed
+        .keys()
+        .filter(|tx_hash| after.pending.contains_key(tx_hash))
+        .map(|tx_hash| (tx_hash, "proposed", "pending"));
+    pending_to_proposed.chain(proposed_to_pending).collect()
+}
@@ -0,0 +1,318 @@
+use std::{collections::HashMap, result::Result as StdResult, thread, time};
+
+use ckb_network::PeerIndex;
+use ckb_store::ChainStore as _;
+use ckb_types::{
+    core::{tx_pool::Reject, BlockNumber, Cycle, ScriptHashType, TransactionView},
+    packed,
+    prelude::*,
+};
+
+use super::{MockedChain, RelayOracle, Storage};
+use crate::{
+    error::Result,
+    types::{RandomGenerator, RunEnv},
+};
+
+// How long to wait for the chunked verification of a suspended transaction.
+const SUSPENDED_TIMEOUT: time::Duration = time::Duration::from_secs(30);
+const SUSPENDED_POLL_INTERVAL: time::Duration = time::Duration::from_millis(10);
+
+// Submit transactions as they are relayed from peers, and check how the tx-pool treats the
+// declared cycles.
+//
+// The transactions with more cycles than the chunk limit are always submitted remotely with the
+// correct declared cycles, since only the remote transactions are verified in chunks.
+pub(crate) struct RemoteSubmitter {
+    // Each transaction comes from a new peer, so the penalties are not mixed.
+    next_peer: usize,
+    chunk_limit: Option<Cycle>,
+    is_remote_enabled: bool,
+    // The transactions which were verified in chunks, and the tips when they are submitted.
+    chunked: HashMap<packed::Byte32, BlockNumber>,
+    mismatches: Vec<String>,
+}
+
+impl RemoteSubmitter {
+    pub(crate) fn new(run_env: &RunEnv) -> Self {
+        Self {
+            next_peer: 0,
+            chunk_limit: run_env
+                .large_cycles
+                .as_ref()
+                .map(|large_cycles| large_cycles.chunk_limit),
+            is_remote_enabled: run_env.remote_submit.is_some(),
+            chunked: HashMap::new(),
+            mismatches: Vec::new(),
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.is_remote_enabled || self.chunk_limit.is_some()
+    }
+
+    // Returns the result as the transaction is submitted locally, since the transactions which
+    // are rejected for the wrong declared cycles are submitted again as they come from an honest
+    // peer, so the statuses in the storage are still the same as the local submissions.
+    pub(crate) fn submit(
+        &mut self,
+        rg: &RandomGenerator,
+        chain: &MockedChain,
+        storage: &Storage,
+        relays: &mut RelayOracle,
+        tx: &TransactionView,
+    ) -> Result<StdResult<(), Reject>> {
+        let tx_hash = tx.hash();
+        let is_remote = rg.is_remote_submit();
+        if !is_remote && self.chunk_limit.is_none() {
+            return submit_local_tx(chain, relays, tx);
+        }
+        let cycles = if let Some(cycles) = expected_cycles(chain, storage, tx)? {
+            cycles
+        } else {
+            log::trace!(
+                "[SendTxs] cycles of {:#x} are unknown, submit it locally",
+                tx_hash
+            );
+            return submit_local_tx(chain, relays, tx);
+        };
+        if let Some(limit) = self.chunk_limit {
+            if cycles > limit {
+                return self.submit_large(chain, relays, tx, cycles);
+            }
+        }
+        if !is_remote {
+            return submit_local_tx(chain, relays, tx);
+        }
+        let is_wrong = rg.declare_wrong_cycles();
+        let declared = if is_wrong {
+            rg.wrong_cycles(cycles)
+        } else {
+            cycles
+        };
+        let peer = PeerIndex::new(self.next_peer);
+        self.next_peer += 1;
+        log::trace!(
+            "[SendTxs] submit {:#x} from {:?} with declared cycles {} (expected: {})",
+            tx_hash,
+            peer,
+            declared,
+            cycles
+        );
+        let result = chain.txpool_submit_remote_tx(tx, declared, peer)?;
+        relays.submitted(tx_hash.clone(), Some(peer), result.is_ok());
+        match result {
+            Ok(()) => {
+                if is_wrong {
+                    self.mismatches.push(format!(
+                        "{:#x} is accepted with the wrong declared cycles {} (expected: {})",
+                        tx_hash, declared, cycles
+                    ));
+                }
+                Ok(Ok(()))
+            }
+            Err(Reject::DeclaredWrongCycles(_, actual)) if !is_wrong => {
+                self.mismatches.push(format!(
+                    "{:#x} is rejected for the declared cycles {}, but the actual cycles are \
+                    expected to be {} (reported: {})",
+                    tx_hash, declared, cycles, actual
+                ));
+                relays.may_penalize(tx_hash);
+                submit_local_tx(chain, relays, tx)
+            }
+            // Less declared cycles could also be rejected as the cycles are exceeded, only the
+            // mismatches of the declared cycles have to be penalized.
+            Err(reject) if is_wrong => {
+                log::trace!(
+                    "[SendTxs] >>> remote {:#x} from {:?} failed ({}), submit it locally",
+                    tx_hash,
+                    peer,
+                    reject
+                );
+                if matches!(reject, Reject::DeclaredWrongCycles(..)) {
+                    relays.must_penalize(tx_hash, peer);
+                } else {
+                    relays.may_penalize(tx_hash);
+                }
+                submit_local_tx(chain, relays, tx)
+            }
+            // The invalid transactions are rejected before the cycles are compared, the peers
+            // could be penalized for them.
+            Err(reject) => {
+                relays.may_penalize(tx_hash);
+                Ok(Err(reject))
+            }
+        }
+    }
+
+    // The transaction is suspended after the submission, wait until it's pending after the
+    // chunked verification, so the following transactions could spend its outputs.
+    fn submit_large(
+        &mut self,
+        chain: &MockedChain,
+        relays: &mut RelayOracle,
+        tx: &TransactionView,
+        cycles: Cycle,
+    ) -> Result<StdResult<(), Reject>> {
+        let tx_hash = tx.hash();
+        let peer = PeerIndex::new(self.next_peer);
+        self.next_peer += 1;
+        log::trace!(
+            "[SendTxs] submit {:#x} from {:?} with large cycles {}",
+            tx_hash,
+            peer,
+            cycles
+        );
+        let result = chain.txpool_submit_remote_tx(tx, cycles, peer)?;
+        relays.submitted(tx_hash.clone(), Some(peer), result.is_ok());
+        if result.is_err() {
+            relays.may_penalize(tx_hash);
+            return Ok(result);
+        }
+        let started_at = time::Instant::now();
+        loop {
+            let pool = chain.txpool_entries()?;
+            if pool.pending.contains_key(&tx_hash) || pool.proposed.contains_key(&tx_hash) {
+                log::trace!(
+                    "[SendTxs] >>> {:#x} is verified in chunks after {:?}",
+                    tx_hash,
+                    started_at.elapsed()
+                );
+                break;
+            }
+            if started_at.elapsed() > SUSPENDED_TIMEOUT {
+                self.mismatches.push(format!(
+                    "{:#x} with cycles {} is still not pending after {:?}",
+                    tx_hash, cycles, SUSPENDED_TIMEOUT
+                ));
+                break;
+            }
+            thread::sleep(SUSPENDED_POLL_INTERVAL);
+        }
+        let tip_number = chain.chain_tip_header().number();
+        self.chunked.insert(tx_hash, tip_number);
+        Ok(result)
+    }
+
+    // The transactions which were verified in chunks should be committed as the others, they
+    // are not tracked anymore after they are invalidated in the storage.
+    pub(crate) fn check_chunked(&mut self, chain: &MockedChain, storage: &Storage) -> Result<()> {
+        if self.chunked.is_empty() {
+            return Ok(());
+        }
+        let pool = chain.txpool_entries()?;
+        let mut finished = Vec::new();
+        for (tx_hash, submitted_at) in &self.chunked {
+            if chain.store().get_transaction(tx_hash).is_some() {
+                log::trace!(
+                    "[SendTxs] >>> {:#x} which was verified in chunks is committed",
+                    tx_hash
+                );
+                finished.push(tx_hash.clone());
+            } else if !storage
+                .get_tx_status(tx_hash)?
+                .map(|tx_status| tx_status.is_uncommitted())
+                .unwrap_or(false)
+            {
+                finished.push(tx_hash.clone());
+            } else if !pool.pending.contains_key(tx_hash) && !pool.proposed.contains_key(tx_hash) {
+                self.mismatches.push(format!(
+                    "{:#x} which was verified in chunks at block {} left the tx-pool without \
+                    being committed",
+                    tx_hash, submitted_at
+                ));
+                finished.push(tx_hash.clone());
+            }
+        }
+        for tx_hash in finished {
+            self.chunked.remove(&tx_hash);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn take_mismatches(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.mismatches)
+    }
+}
+
+fn submit_local_tx(
+    chain: &MockedChain,
+    relays: &mut RelayOracle,
+    tx: &TransactionView,
+) -> Result<StdResult<(), Reject>> {
+    let result = chain.txpool_submit_local_tx(tx)?;
+    relays.submitted(tx.hash(), None, result.is_ok());
+    Ok(result)
+}
+
+// The cycles of the mocked scripts are in their arguments: the first half of the arguments is
+// for the lock groups and the second half is for the type groups, each half is the result and
+// the cycles of the script group.
+//
+// Returns none if the cycles are unknown, for example, the inputs are not found, or the
+// arguments of a mocked script are not in the format.
+fn expected_cycles(
+    chain: &MockedChain,
+    storage: &Storage,
+    tx: &TransactionView,
+) -> Result<Option<Cycle>> {
+    let mocked_script = chain.mocked_script();
+    let mut lock_groups = HashMap::new();
+    let mut type_groups = HashMap::new();
+    for out_point in tx.input_pts_iter() {
+        let tx_hash = out_point.tx_hash();
+        let index: u32 = out_point.index().unpack();
+        let input_tx = if let Some(input_tx) = storage.get_transaction(&tx_hash)? {
+            input_tx
+        } else if let Some((input_tx, _)) = chain.store().get_transaction(&tx_hash) {
+            input_tx
+        } else {
+            return Ok(None);
+        };
+        let output = if let Some(output) = input_tx.output(index as usize) {
+            output
+        } else {
+            return Ok(None);
+        };
+        let lock = output.lock();
+        lock_groups.insert(lock.calc_script_hash(), lock);
+        if let Some(type_) = output.type_().to_opt() {
+            type_groups.insert(type_.calc_script_hash(), type_);
+        }
+    }
+    for output in tx.outputs().into_iter() {
+        if let Some(type_) = output.type_().to_opt() {
+            type_groups.insert(type_.calc_script_hash(), type_);
+        }
+    }
+    let is_mocked = |script: &packed::Script| {
+        let hash_type: ScriptHashType = if let Ok(hash_type) = script.hash_type().try_into() {
+            hash_type
+        } else {
+            return false;
+        };
+        match hash_type {
+            ScriptHashType::Data => script.code_hash() == mocked_script.data_hash(),
+            ScriptHashType::Type => script.code_hash() == mocked_script.type_hash(),
+            _ => false,
+        }
+    };
+    let mut total: Cycle = 0;
+    let groups = lock_groups
+        .values()
+        .map(|script| (script, 8))
+        .chain(type_groups.values().map(|script| (script, 24)));
+    for (script, offset) in groups {
+        if !is_mocked(script) {
+            return Ok(None);
+        }
+        let args = script.args().raw_data();
+        if args.len() != 32 {
+            return Ok(None);
+        }
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&args[offset..offset + 8]);
+        total = total.saturating_add(u64::from_le_bytes(bytes));
+    }
+    Ok(Some(total))
+}
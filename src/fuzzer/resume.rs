@@ -0,0 +1,108 @@
+use std::collections::HashSet;
+
+use ckb_store::ChainStore as _;
+
+use super::{ledger, MockedChain, Storage};
+use crate::{
+    error::{Error, Result},
+    types::Checkpoint,
+};
+
+// Validate the checkpoint against the chain and resync the tx-pool before resuming.
+pub(crate) fn resume(chain: &MockedChain, storage: &Storage) -> Result<Checkpoint> {
+    let checkpoint = storage
+        .get_checkpoint()?
+        .ok_or_else(|| Error::storage("can not found the checkpoint to resume"))?;
+    log::info!(
+        "[Resume] checkpoint: block {} ({:#x})",
+        checkpoint.block_number,
+        checkpoint.block_hash
+    );
+    let tip_header = chain.chain_tip_header();
+    if tip_header.hash() != checkpoint.block_hash {
+        if tip_header.parent_hash() != checkpoint.block_hash {
+            let errmsg = format!(
+                "the chain tip {} ({:#x}) doesn't match the checkpoint {} ({:#x})",
+                tip_header.number(),
+                tip_header.hash(),
+                checkpoint.block_number,
+                checkpoint.block_hash
+            );
+            return Err(Error::storage(errmsg));
+        }
+        // The run was interrupted after the block was submitted to the chain but before it was
+        // confirmed in the storage.
+        let block = chain.store().get_block(&tip_header.hash()).ok_or_else(|| {
+            let errmsg = format!("the chain tip {:#x} is not stored", tip_header.hash());
+            Error::storage(errmsg)
+        })?;
+        log::warn!(
+            "[Resume] confirm the interrupted block {} ({:#x})",
+            block.number(),
+            block.hash()
+        );
+        let recovered = Checkpoint {
+            block_number: block.number(),
+            block_hash: block.hash(),
+            rng_seed: checkpoint.rng_seed,
+        };
+        let ledger = if let Some(mut ledger) = storage.get_ledger()? {
+            ledger::record_block(&mut ledger, chain, storage, &block)?;
+            ledger
+        } else {
+            // A new ledger is started from the chain, which already contains the block.
+            ledger::load_or_start(chain, storage)?
+        };
+        storage.confirm_block(&block, &recovered, &ledger, &chain.proposal_window())?;
+        resubmit_pending_txs(chain, storage)?;
+        return Ok(recovered);
+    }
+    resubmit_pending_txs(chain, storage)?;
+    Ok(checkpoint)
+}
+
+// The tx-pool is only persisted when a run finishes normally, so the pending transactions in
+// the storage could be lost from it.
+fn resubmit_pending_txs(chain: &MockedChain, storage: &Storage) -> Result<()> {
+    let entries = chain.txpool_entries()?;
+    let mut missing = storage
+        .pending_transactions()?
+        .into_iter()
+        .filter(|tx| {
+            let tx_hash = tx.hash();
+            !entries.pending.contains_key(&tx_hash) && !entries.proposed.contains_key(&tx_hash)
+        })
+        .collect::<Vec<_>>();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    log::info!(
+        "[Resume] resubmit {} pending transactions into the tx-pool",
+        missing.len()
+    );
+    // Submit the ancestors before their descendants.
+    while !missing.is_empty() {
+        let missing_hashes = missing.iter().map(|tx| tx.hash()).collect::<HashSet<_>>();
+        let (roots, rest): (Vec<_>, Vec<_>) = missing.into_iter().partition(|tx| {
+            !tx.input_pts_iter()
+                .any(|out_point| missing_hashes.contains(&out_point.tx_hash()))
+        });
+        if roots.is_empty() {
+            return Err(Error::runtime(
+                "pending transactions have circular dependencies",
+            ));
+        }
+        for tx in &roots {
+            if let Err(reject) = chain.txpool_submit_local_tx(tx)? {
+                let errmsg = format!(
+                    "the checkpoint is broken since pending tx {:#x} is rejected ({})",
+                    tx.hash(),
+                    reject
+                );
+                return Err(Error::storage(errmsg));
+            }
+        }
+        missing = rest;
+    }
+    Ok(())
+}
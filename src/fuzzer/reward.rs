@@ -0,0 +1,187 @@
+use std::{collections::HashSet, fmt, result::Result as StdResult};
+
+use ckb_dao_utils::extract_dao_data;
+use ckb_store::{ChainDB, ChainStore as _};
+use ckb_types::{
+    core::{BlockNumber, BlockView, HeaderView},
+    packed,
+};
+
+use super::MockedChain;
+use crate::error::{Error, Result};
+
+// The reward of a block, in shannons.
+#[derive(Debug, Default)]
+struct BlockReward {
+    primary: u64,
+    secondary: u64,
+    // The committer's share of the fees of the transactions which are committed in the block.
+    committed: u64,
+    // The proposer's share of the fees of the transactions which are proposed by the block.
+    proposed: u64,
+}
+
+impl BlockReward {
+    fn total(&self) -> u64 {
+        self.primary + self.secondary + self.committed + self.proposed
+    }
+}
+
+// Check the capacity of the cellbase in the block against the expected block reward.
+pub(crate) fn check_cellbase(
+    chain: &MockedChain,
+    block: &BlockView,
+) -> Result<StdResult<(), String>> {
+    let actual = block
+        .transaction(0)
+        .map(|cellbase| cellbase.outputs_capacity())
+        .transpose()
+        .map_err(Error::runtime)?
+        .map(|capacity| capacity.as_u64())
+        .unwrap_or(0);
+    let expected = expected_reward(chain, block.number())?;
+    let result = if actual == expected.total() {
+        Ok(())
+    } else {
+        Err(format!(
+            "the cellbase of block {} has {} shannons but {} is expected ({})",
+            block.number(),
+            actual,
+            expected.total(),
+            expected
+        ))
+    };
+    Ok(result)
+}
+
+// The cellbase of a block rewards the block which is `finalization_delay_length` blocks before
+// it, the target, so the reward is final when the proposal window of the target is passed.
+fn expected_reward(chain: &MockedChain, number: BlockNumber) -> Result<BlockReward> {
+    let consensus = chain.consensus();
+    let delay = consensus.finalization_delay_length();
+    if number <= delay {
+        return Ok(BlockReward::default());
+    }
+    let store = chain.store();
+    let target = header_by_number(store, number - delay)?;
+    let target_epoch = store.get_block_epoch(&target.hash()).ok_or_else(|| {
+        let errmsg = format!("the epoch of block {} is not found", target.number());
+        Error::runtime(errmsg)
+    })?;
+    let primary = target_epoch
+        .block_reward(target.number())
+        .map_err(Error::runtime)?
+        .as_u64();
+    // The secondary issuance is shared by the occupied capacity, the rest goes to the treasury.
+    let secondary = if target.number() == 0 {
+        0
+    } else {
+        let parent = header_by_number(store, target.number() - 1)?;
+        let issuance = target_epoch
+            .secondary_block_issuance(target.number(), consensus.secondary_epoch_reward())
+            .map_err(Error::runtime)?
+            .as_u64();
+        let (_, total, _, occupied) = extract_dao_data(parent.dao()).map_err(Error::runtime)?;
+        let reward =
+            u128::from(issuance) * u128::from(occupied.as_u64()) / u128::from(total.as_u64());
+        reward as u64
+    };
+    let committed = block_fees(store, &target)?
+        .into_iter()
+        .map(|fee| fee - proposer_share(chain, fee))
+        .sum();
+    let proposed = proposal_reward(chain, &target, number - 1)?;
+    Ok(BlockReward {
+        primary,
+        secondary,
+        committed,
+        proposed,
+    })
+}
+
+// The transactions which are proposed by the target, and committed in the blocks until the
+// parent, which are in the proposal window of the target.
+// The target is rewarded only if it's the earliest block which proposes the transaction, among
+// the blocks in the proposal window of the block which commits the transaction.
+fn proposal_reward(
+    chain: &MockedChain,
+    target: &HeaderView,
+    parent_number: BlockNumber,
+) -> Result<u64> {
+    let store = chain.store();
+    let window = chain.proposal_window();
+    let mut proposals = proposal_ids(store, target.number())?;
+    let start = (parent_number + 1)
+        .saturating_sub(window.length())
+        .max(window.closest() + 1);
+    // The transactions which are proposed before the target, in the windows of the visited
+    // blocks, the commits of them don't reward the target.
+    let mut proposed_before = HashSet::new();
+    let mut reward = 0;
+    let mut number = parent_number;
+    loop {
+        let header = header_by_number(store, number)?;
+        let tx_hashes = store.get_block_txs_hashes(&header.hash());
+        let fees = block_fees(store, &header)?;
+        for (tx_hash, fee) in tx_hashes.iter().skip(1).zip(fees) {
+            let id = packed::ProposalShortId::from_tx_hash(tx_hash);
+            if proposals.remove(&id) && !proposed_before.contains(&id) {
+                reward += proposer_share(chain, fee);
+            }
+        }
+        if number <= start || proposals.is_empty() {
+            break;
+        }
+        number -= 1;
+        let earliest = number.saturating_sub(window.farthest()).max(1);
+        proposed_before.extend(proposal_ids(store, earliest)?);
+    }
+    Ok(reward)
+}
+
+fn proposer_share(chain: &MockedChain, fee: u64) -> u64 {
+    let ratio = chain.consensus().proposer_reward_ratio();
+    (u128::from(fee) * u128::from(ratio.numer()) / u128::from(ratio.denom())) as u64
+}
+
+// The fees of the committed transactions in the block, except the cellbase.
+fn block_fees(store: &ChainDB, header: &HeaderView) -> Result<Vec<u64>> {
+    store
+        .get_block_ext(&header.hash())
+        .map(|ext| ext.txs_fees.into_iter().map(|fee| fee.as_u64()).collect())
+        .ok_or_else(|| {
+            let errmsg = format!("the ext of block {} is not found", header.number());
+            Error::runtime(errmsg)
+        })
+}
+
+fn proposal_ids(store: &ChainDB, number: BlockNumber) -> Result<HashSet<packed::ProposalShortId>> {
+    store
+        .get_block_hash(number)
+        .and_then(|block_hash| store.get_block(&block_hash))
+        .map(|block| block.union_proposal_ids())
+        .ok_or_else(|| {
+            let errmsg = format!("block {} is not found", number);
+            Error::runtime(errmsg)
+        })
+}
+
+fn header_by_number(store: &ChainDB, number: BlockNumber) -> Result<HeaderView> {
+    store
+        .get_block_hash(number)
+        .and_then(|block_hash| store.get_block_header(&block_hash))
+        .ok_or_else(|| {
+            let errmsg = format!("the header of block {} is not found", number);
+            Error::runtime(errmsg)
+        })
+}
+
+impl fmt::Display for BlockReward {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "primary: {}, secondary: {}, committed: {}, proposed: {}",
+            self.primary, self.secondary, self.committed, self.proposed
+        )
+    }
+}
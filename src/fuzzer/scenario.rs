@@ -0,0 +1,207 @@
+use std::{collections::HashMap, result::Result as StdResult};
+
+use ckb_store::ChainStore as _;
+use ckb_types::{core::BlockNumber, packed};
+
+use super::{MockedChain, Storage};
+use crate::{
+    error::Result,
+    types::{Expect, ExpectedStatus, Submit, TxStage, TxStatus},
+};
+
+// The transactions which are submitted by the named submit actions, so the expectations in the
+// script could refer them.
+#[derive(Default)]
+pub(crate) struct Scenario {
+    submitted: HashMap<String, Vec<packed::Byte32>>,
+}
+
+impl Scenario {
+    pub(crate) fn add_submitted(
+        &mut self,
+        submits: &[Submit],
+        submitted: Vec<Vec<packed::Byte32>>,
+    ) {
+        for (submit, tx_hashes) in submits.iter().zip(submitted) {
+            if let Some(ref name) = submit.name {
+                self.submitted.insert(name.to_owned(), tx_hashes);
+            }
+        }
+    }
+
+    // Check an expectation against both the storage and the tx-pool, returns the reason if it's
+    // not satisfied by any of them.
+    pub(crate) fn check(
+        &self,
+        expect: &Expect,
+        chain: &MockedChain,
+        storage: &Storage,
+    ) -> Result<StdResult<(), String>> {
+        let pool = chain.txpool_entries()?;
+        let next_number = chain.chain_tip_header().number() + 1;
+        match expect {
+            Expect::PoolPending(count) => {
+                let model = self.count_pending(chain, storage, next_number, false)?;
+                let live = pool.pending.len();
+                Ok(check_count(*count, model, live))
+            }
+            Expect::PoolProposed(count) => {
+                let model = self.count_pending(chain, storage, next_number, true)?;
+                let live = pool.proposed.len();
+                Ok(check_count(*count, model, live))
+            }
+            Expect::TxStatus(inner) => {
+                let tx_hashes = match self.refer(&inner.submit, inner.index) {
+                    Ok(tx_hashes) => tx_hashes,
+                    Err(reason) => return Ok(Err(reason)),
+                };
+                for tx_hash in tx_hashes {
+                    let model = self.model_status(chain, storage, next_number, tx_hash)?;
+                    let live = if pool.proposed.contains_key(tx_hash) {
+                        Some(ExpectedStatus::Proposed)
+                    } else if pool.pending.contains_key(tx_hash) {
+                        // The tx-pool doesn't tell the transactions in the gap.
+                        if inner.status == ExpectedStatus::Gap {
+                            Some(ExpectedStatus::Gap)
+                        } else {
+                            Some(ExpectedStatus::Pending)
+                        }
+                    } else if chain.store().get_transaction(tx_hash).is_some() {
+                        Some(ExpectedStatus::Committed)
+                    } else if storage.get_reject(tx_hash)?.is_some() {
+                        Some(ExpectedStatus::Rejected)
+                    } else {
+                        None
+                    };
+                    if model != Some(inner.status) || live != Some(inner.status) {
+                        return Ok(Err(format!(
+                            "tx {:#x} is {} in the model and {} in the tx-pool",
+                            tx_hash,
+                            display_status(model),
+                            display_status(live)
+                        )));
+                    }
+                }
+                Ok(Ok(()))
+            }
+            Expect::RejectReason(inner) => {
+                let tx_hashes = match self.refer(&inner.submit, inner.index) {
+                    Ok(tx_hashes) => tx_hashes,
+                    Err(reason) => return Ok(Err(reason)),
+                };
+                for tx_hash in tx_hashes {
+                    let is_failed = matches!(
+                        storage.get_tx_status(tx_hash)?,
+                        Some(TxStatus::Orphan(_))
+                            | Some(TxStatus::Expired)
+                            | Some(TxStatus::Failed)
+                    );
+                    let live = storage.get_reject(tx_hash)?;
+                    if !is_failed || live.as_ref() != Some(&inner.reason) {
+                        let model = if is_failed {
+                            "rejected"
+                        } else {
+                            "not rejected"
+                        };
+                        let live = live
+                            .map(|reason| format!("rejected by {}", reason))
+                            .unwrap_or_else(|| "not rejected".to_owned());
+                        return Ok(Err(format!(
+                            "tx {:#x} is {} in the model and {} by the tx-pool",
+                            tx_hash, model, live
+                        )));
+                    }
+                }
+                Ok(Ok(()))
+            }
+        }
+    }
+
+    fn refer(&self, submit: &str, index: Option<usize>) -> StdResult<&[packed::Byte32], String> {
+        let tx_hashes = self
+            .submitted
+            .get(submit)
+            .ok_or_else(|| format!("no transactions are submitted by {}", submit))?;
+        if let Some(index) = index {
+            tx_hashes.get(index..=index).ok_or_else(|| {
+                format!(
+                    "only {} transactions are submitted by {}",
+                    tx_hashes.len(),
+                    submit
+                )
+            })
+        } else {
+            Ok(tx_hashes)
+        }
+    }
+
+    // Count the pending transactions in the storage by the proposals of them.
+    fn count_pending(
+        &self,
+        chain: &MockedChain,
+        storage: &Storage,
+        next_number: BlockNumber,
+        is_proposed: bool,
+    ) -> Result<usize> {
+        let window = chain.proposal_window();
+        let mut count = 0;
+        for tx in storage.pending_transactions()? {
+            let stage = storage
+                .get_proposals(&tx.hash())?
+                .unwrap_or_default()
+                .stage(next_number, &window);
+            if (stage == TxStage::Proposed) == is_proposed {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    fn model_status(
+        &self,
+        chain: &MockedChain,
+        storage: &Storage,
+        next_number: BlockNumber,
+        tx_hash: &packed::Byte32,
+    ) -> Result<Option<ExpectedStatus>> {
+        let status = match storage.get_tx_status(tx_hash)? {
+            // The proposed ones could be in the gap, so the stage is still by the proposals.
+            Some(TxStatus::Pending(_)) | Some(TxStatus::Proposed(_)) => {
+                let window = chain.proposal_window();
+                let stage = storage
+                    .get_proposals(tx_hash)?
+                    .unwrap_or_default()
+                    .stage(next_number, &window);
+                match stage {
+                    TxStage::Pending => Some(ExpectedStatus::Pending),
+                    TxStage::Gap => Some(ExpectedStatus::Gap),
+                    TxStage::Proposed => Some(ExpectedStatus::Proposed),
+                }
+            }
+            Some(TxStatus::Committed(_)) => Some(ExpectedStatus::Committed),
+            // The orphans are rejected when they are submitted.
+            Some(TxStatus::Orphan(_)) | Some(TxStatus::Expired) | Some(TxStatus::Failed) => {
+                Some(ExpectedStatus::Rejected)
+            }
+            Some(TxStatus::Evicted) | None => None,
+        };
+        Ok(status)
+    }
+}
+
+fn check_count(expected: usize, model: usize, live: usize) -> StdResult<(), String> {
+    if model == expected && live == expected {
+        Ok(())
+    } else {
+        Err(format!(
+            "the model has {} and the tx-pool has {}",
+            model, live
+        ))
+    }
+}
+
+fn display_status(status: Option<ExpectedStatus>) -> String {
+    status
+        .map(|status| status.to_string())
+        .unwrap_or_else(|| "unknown".to_owned())
+}
@@ -0,0 +1,179 @@
+#[cfg(feature = "async-api")]
+use std::sync::{Arc, Mutex};
+use std::{result::Result as StdResult, time};
+
+use ckb_types::core::{tx_pool::Reject, BlockView};
+
+use super::{
+    build_block, build_transactions, ledger, BlockProducers, Fuzzer, TxOverlay, TxStrategies,
+};
+#[cfg(feature = "async-api")]
+use crate::error::Error;
+use crate::{
+    error::Result,
+    types::{Checkpoint, Ledger, RandomGenerator},
+};
+
+// Drive the core loop of the fuzzer step by step, for the harnesses which embed the fuzzer.
+//
+// A step is: generate a batch, submit the batch, then advance a block.
+// The statuses in the storage are updated as the `run` subcommand does, but the oracles are left
+// to the harness.
+pub struct Stepper {
+    fuzzer: Fuzzer,
+    rg: RandomGenerator,
+    ledger: Ledger,
+}
+
+impl Stepper {
+    pub fn new(fuzzer: Fuzzer) -> Result<Self> {
+        let rg = RandomGenerator::new(fuzzer.run_env(), None)?;
+        let ledger = ledger::load_or_start(fuzzer.chain(), fuzzer.storage())?;
+        Ok(Self { fuzzer, rg, ledger })
+    }
+
+    pub fn fuzzer(&self) -> &Fuzzer {
+        &self.fuzzer
+    }
+
+    pub fn random_generator(&self) -> &RandomGenerator {
+        &self.rg
+    }
+
+    // Generate a batch of transactions by the strategies in the run environment.
+    pub fn generate_batch(&self) -> Result<Vec<TxOverlay>> {
+        let run_env = self.fuzzer.run_env();
+        let strategies = TxStrategies::from_run_env(run_env)?;
+        let max_generation = run_env
+            .max_generation_millis
+            .map(time::Duration::from_millis);
+        build_transactions(
+            &self.rg,
+            self.fuzzer.chain(),
+            self.fuzzer.storage(),
+            &strategies,
+            max_generation,
+        )
+    }
+
+    // Submit the transactions locally, returns the results in the same order.
+    //
+    // The storage is only updated for the expected results, so the harness should check the
+    // results against `TxOverlay::is_failed` before the next batch.
+    pub fn submit_batch(&self, txs: &[TxOverlay]) -> Result<Vec<StdResult<(), Reject>>> {
+        let chain = self.fuzzer.chain();
+        let storage = self.fuzzer.storage();
+        let mut results = Vec::with_capacity(txs.len());
+        for tx in txs {
+            let tx_view = tx.view();
+            let result = chain.txpool_submit_local_tx(tx_view)?;
+            match (tx.changes(), &result) {
+                (Ok((tx_status, updates)), Ok(())) => {
+                    storage.submit_tx(tx_view, tx_status, updates)?;
+                }
+                (Err(updates), Err(reject)) => {
+                    storage.put_reject(&tx_view.hash(), reject)?;
+                    if tx.is_orphan(reject) {
+                        storage.submit_orphan_tx(tx_view, chain.clock().now())?;
+                    } else {
+                        storage.submit_invalid_tx(tx_view)?;
+                    }
+                    for (tx_hash, tx_status) in updates {
+                        storage.remove_invalid_tx(&tx_hash, &tx_status)?;
+                    }
+                }
+                _ => {
+                    log::warn!(
+                        "[SendTxs] >>> send {:#x} got an unexpected result, \
+                        the storage is not updated",
+                        tx_view.hash()
+                    );
+                }
+            }
+            results.push(result);
+        }
+        Ok(results)
+    }
+
+    // Move the time forward, build the next block by the producers in the run environment, and
+    // submit it into both the chain and the tx-pool, then confirm it in the storage.
+    //
+    // A mismatch error is returned if the block is broken by the assembler or the producer, the
+    // harness decides whether to stop.
+    pub fn advance_block(&mut self) -> Result<BlockView> {
+        self.fuzzer
+            .chain()
+            .clock()
+            .increase(self.rg.block_interval())?;
+        let producers = BlockProducers::from_run_env(self.fuzzer.run_env())?;
+        let (block, delta) = build_block(&self.rg, self.fuzzer.chain(), &producers)?;
+        self.fuzzer.chain_mut().chain_submit_block(&block);
+        let chain = self.fuzzer.chain();
+        let storage = self.fuzzer.storage();
+        chain.txpool_submit_block(&block)?;
+        // The broadcast decisions are not checked by the stepper, drop them to not pile up.
+        chain.txpool_take_relay_results();
+        let checkpoint = Checkpoint {
+            block_number: block.number(),
+            block_hash: block.hash(),
+            rng_seed: self.rg.checkpoint_seed(),
+        };
+        ledger::record_block(&mut self.ledger, chain, storage, &block)?;
+        storage.confirm_block(&block, &checkpoint, &self.ledger, &chain.proposal_window())?;
+        if !delta.is_empty() {
+            storage.put_block_delta(&block, &delta)?;
+        }
+        Ok(block)
+    }
+}
+
+// The async variant of the stepper, for the harnesses which already run in tokio runtimes.
+//
+// The tx-pool controller blocks on the responses, so each step runs in a blocking thread of the
+// current runtime, instead of blocking the async tasks.
+#[cfg(feature = "async-api")]
+#[derive(Clone)]
+pub struct AsyncStepper {
+    inner: Arc<Mutex<Stepper>>,
+}
+
+#[cfg(feature = "async-api")]
+impl AsyncStepper {
+    pub fn new(stepper: Stepper) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(stepper)),
+        }
+    }
+
+    pub async fn generate_batch(&self) -> Result<Vec<TxOverlay>> {
+        self.with(|stepper| stepper.generate_batch()).await
+    }
+
+    pub async fn submit_batch(&self, txs: Vec<TxOverlay>) -> Result<Vec<StdResult<(), Reject>>> {
+        self.with(move |stepper| stepper.submit_batch(&txs)).await
+    }
+
+    pub async fn advance_block(&self) -> Result<BlockView> {
+        self.with(|stepper| stepper.advance_block()).await
+    }
+
+    // Run a custom step with the stepper in a blocking thread.
+    pub async fn with<F, T>(&self, func: F) -> Result<T>
+    where
+        F: FnOnce(&mut Stepper) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let inner = Arc::clone(&self.inner);
+        tokio::task::spawn_blocking(move || {
+            let mut stepper = inner
+                .lock()
+                .map_err(|_| Error::runtime("the lock of the stepper is poisoned"))?;
+            func(&mut stepper)
+        })
+        .await
+        .map_err(|err| {
+            let errmsg = format!("the blocking step is failed since {}", err);
+            Error::runtime(errmsg)
+        })?
+    }
+}
@@ -1,26 +1,170 @@
-use std::{cell::RefCell, collections::HashMap, path::Path, str::FromStr};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt,
+    path::Path,
+    str::FromStr,
+    time,
+};
 
+use ckb_chain_spec::consensus::ProposalWindow;
 use ckb_types::{
-    core::{BlockView, TransactionView},
+    core::{tx_pool::Reject, BlockNumber, BlockView, TransactionView},
     packed,
     prelude::*,
 };
-use rocksdb::ops::{
-    DeleteCF as _, Get as _, GetCF as _, GetColumnFamilys as _, IterateCF as _, OpenCF as _,
-    Put as _, PutCF as _,
-};
+use indexmap::{IndexMap, IndexSet};
+use lru::LruCache;
 
+use super::{
+    backend::{
+        Backend, MemoryBackend, OpenMode, ReadOnlyRocksDbBackend, RocksDbBackend, WriteBatch,
+        CF_DEFAULT,
+    },
+    block::display_out_point,
+    faults::FaultInjector,
+};
 use crate::{
     error::{Error, Result},
-    types::{CacheStats, MetaData, TxStatus},
-    utils,
+    types::{
+        reject_reason, BlockDelta, CacheStats, CellStage, CellStatus, CellbaseOrigin, Checkpoint,
+        Ledger, MetaData, Proposals, RunRecord, RunReport, StorageFaults, TxEdge, TxEvent,
+        TxEventKind, TxHistory, TxSeqId, TxStage, TxStatus,
+    },
+    utils::{self, metrics::Operation},
 };
 
 const KEY_METADATA: &[u8] = b"meta_data";
+const KEY_REPORT: &[u8] = b"report";
+const KEY_SCHEMA_VERSION: &[u8] = b"schema_version";
+const KEY_CHECKPOINT: &[u8] = b"checkpoint";
+const KEY_RUNS_COUNT: &[u8] = b"runs_count";
+const KEY_LEDGER: &[u8] = b"ledger";
+const KEY_PROPOSALS_SINCE: &[u8] = b"proposals_since";
+
+// The version of the storage format, it's the count of the migrations, so add a migration when
+// the format is changed.
+const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+// The orphans are expired after that long, the same as the orphan pool of the tx-pool.
+const ORPHAN_EXPIRY_MILLIS: u64 = 2 * 48 * 1000;
+// The default capacity of the caches of the hot transactions and their statuses.
+const DEFAULT_CACHE_CAPACITY: usize = 10_000;
 
-pub(crate) struct Storage {
-    db: rocksdb::DB,
+// The oldest version which could be upgraded to the current version.
+// The storages which are created before the versioning are version 0.
+const MIN_UPGRADABLE_SCHEMA_VERSION: u32 = 0;
+
+// Rewrite the data of a column family in the new format.
+type MigrationHook = fn(&Storage) -> Result<()>;
+
+// A migration of the storage format, the index plus one is the version which it upgrades to.
+//
+// Each hook rewrites a column family whose format is changed, so the old bytes are never read in
+// the new format. The column families which are only added are created when opening, and the
+// data which are added are unknown for the old records, so most migrations have no hooks.
+struct Migration {
+    note: &'static str,
+    hooks: &'static [(&'static str, MigrationHook)],
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        note: "add the schema version",
+        hooks: &[],
+    },
+    Migration {
+        note: "add the sequence IDs of transactions",
+        hooks: &[],
+    },
+    // The proposal window is only checked after it's fully tracked.
+    Migration {
+        note: "add the proposals of transactions",
+        hooks: &[],
+    },
+    Migration {
+        note: "add the reject reasons of transactions",
+        hooks: &[],
+    },
+    Migration {
+        note: "add the histories of transactions",
+        hooks: &[],
+    },
+    Migration {
+        note: "add the records of runs",
+        hooks: &[],
+    },
+    Migration {
+        note: "add the index of live cells",
+        hooks: &[(Storage::CF_LIVE_CELLS, Storage::build_live_cells)],
+    },
+    Migration {
+        note: "add the pruned event into the histories of transactions",
+        hooks: &[],
+    },
+    // The meta data are migrated when parsing.
+    Migration {
+        note: "add the version of the meta data",
+        hooks: &[(CF_DEFAULT, Storage::rewrite_meta_data)],
+    },
+    // The old statuses are still valid, the proposed transactions are marked when the next
+    // block is confirmed, and the evicted transactions before were removed.
+    Migration {
+        note: "add the proposed, orphan, evicted and expired statuses of transactions",
+        hooks: &[],
+    },
+    // The spends of the pruned transactions are only known from their histories.
+    Migration {
+        note: "add the spend edges between transactions",
+        hooks: &[(Storage::CF_TX_EDGES, Storage::build_tx_edges)],
+    },
+    // The blocks are not in the storage, the immature cellbases are tracked again when the chain
+    // is loaded, and the older ones are mature already.
+    Migration {
+        note: "add the origins of the committed cellbases",
+        hooks: &[],
+    },
+    // The spenders are only known from the histories of the spent transactions.
+    Migration {
+        note: "add the spenders of the dead cells",
+        hooks: &[(Storage::CF_SPENDERS, Storage::build_spenders)],
+    },
+];
+
+pub struct Storage {
+    db: Box<dyn Backend>,
     stats: RefCell<CacheStats>,
+    // The hashes of all transactions which have statuses, for sampling.
+    tx_hashes: RefCell<IndexSet<packed::Byte32>>,
+    // All live cells and the stages of their transactions, for sampling.
+    live_cells: RefCell<IndexMap<packed::OutPoint, CellStage>>,
+    // The committed cellbases whose origins are tracked, by the numbers of their blocks.
+    cellbases: RefCell<BTreeMap<BlockNumber, packed::Byte32>>,
+    // The recently read transactions and statuses, none if the caches are disabled.
+    hot: RefCell<Option<HotCache>>,
+    // The number of the last confirmed block, the transitions are recorded at it.
+    tip_number: Cell<BlockNumber>,
+    // Only for testing the fuzzer itself.
+    faults: Option<FaultInjector>,
+    // Whether the orphans are expired by the timestamps of the blocks.
+    orphan_expiry: bool,
+}
+
+// The least recently used transactions and statuses, they are invalidated after they are changed.
+struct HotCache {
+    txs: LruCache<packed::Byte32, TransactionView>,
+    tx_statuses: LruCache<packed::Byte32, TxStatus>,
+    hits: u64,
+    misses: u64,
+}
+
+// The changes of the live cells in a batch, none means the cell is not live anymore.
+type LiveCellChanges = Vec<(packed::OutPoint, Option<CellStage>)>;
+
+// Measure the latency of a write operation when it's dropped, and warn about it if it's slow.
+struct WriteTimer<'a> {
+    operation: &'static str,
+    hash: &'a packed::Byte32,
+    started_at: time::Instant,
 }
 
 // Construction
@@ -35,223 +179,620 @@ impl Storage {
     // Store all transactions which are invalid but haven't been committed.
     const CF_PENDING_TXS: &'static str = "pending_txs";
 
+    // Store the differences between block templates and committed blocks.
+    const CF_BLOCK_DELTAS: &'static str = "block_deltas";
+
+    // Store the sequence IDs of all generated transactions.
+    const CF_TX_SEQ_IDS: &'static str = "tx_seq_ids";
+
+    // Store the numbers of the blocks which propose the uncommitted transactions.
+    const CF_PROPOSALS: &'static str = "proposals";
+
+    // Store the reasons why the transactions are rejected by the tx-pool.
+    const CF_REJECTS: &'static str = "rejects";
+
+    // Store the transitions of all transactions, including the removed ones.
+    const CF_TX_HISTORY: &'static str = "tx_history";
+
+    // Store the records of all runs, by the run IDs.
+    const CF_RUNS: &'static str = "runs";
+
+    // Store the index of all live cells, by the out points.
+    const CF_LIVE_CELLS: &'static str = "live_cells";
+
+    // Store the output indexes which a transaction spends from another, by the hash of the
+    // parent then the hash of the child.
+    const CF_TX_EDGES: &'static str = "tx_edges";
+
+    // Store the transactions which spend the dead cells, by the out points.
+    const CF_SPENDERS: &'static str = "spenders";
+
     const CF_NAMES: &'static [&'static str] = &[
         Self::CF_BLOCKS,
         Self::CF_TXS,
         Self::CF_TX_STATUSES,
         Self::CF_PENDING_TXS,
+        Self::CF_BLOCK_DELTAS,
+        Self::CF_TX_SEQ_IDS,
+        Self::CF_PROPOSALS,
+        Self::CF_REJECTS,
+        Self::CF_TX_HISTORY,
+        Self::CF_RUNS,
+        Self::CF_LIVE_CELLS,
+        Self::CF_TX_EDGES,
+        Self::CF_SPENDERS,
     ];
 
-    pub(crate) fn init<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let db = Self::open(path, true)?;
-        let stats = RefCell::new(CacheStats::default());
-        let ret = Self { db, stats };
+    pub fn init<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let db = RocksDbBackend::open(path, OpenMode::Create, Self::CF_NAMES)?;
+        let ret = Self::new(Box::new(db));
+        ret.put_schema_version(SCHEMA_VERSION)?;
         Ok(ret)
     }
 
-    pub(crate) fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let db = Self::open(path, false)?;
-        let stats = RefCell::new(CacheStats::default());
-        let ret = Self { db, stats };
-        ret.load_tx_statuses()?;
+    // Load the storage and upgrade it to the current schema version, so the data directory
+    // should be locked before loading.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let db = RocksDbBackend::open(path, OpenMode::Upgrade, Self::CF_NAMES)?;
+        let ret = Self::new(Box::new(db));
+        ret.check_schema_version(true)?;
+        // Upgrade before anything is read, so all paths read the data in the current format.
+        ret.upgrade()?;
+        ret.load_caches()?;
         Ok(ret)
     }
 
-    fn open<P: AsRef<Path>>(path: P, create: bool) -> Result<rocksdb::DB> {
-        utils::fs::check_directory(&path, !create)?;
-        let opts = Self::default_dboptions(create);
-        let cfs = Self::default_column_family_descriptors();
-        let db = rocksdb::DB::open_cf_descriptors(&opts, &path, cfs)?;
-        Ok(db)
+    // Load the storage without changing its schema, for the subcommands which don't lock the
+    // data directory, a storage in an outdated schema is refused.
+    pub fn load_current<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let db = RocksDbBackend::open(path, OpenMode::Existing, Self::CF_NAMES)?;
+        let ret = Self::new(Box::new(db));
+        ret.check_schema_version(false)?;
+        ret.load_caches()?;
+        Ok(ret)
     }
 
-    fn default_dboptions(create: bool) -> rocksdb::Options {
-        let mut opts = rocksdb::Options::default();
-        if create {
-            opts.create_if_missing(true);
-            opts.create_missing_column_families(true);
-        } else {
-            opts.create_if_missing(false);
-            opts.create_missing_column_families(false);
-        }
-        // DBOptions
-        opts.set_bytes_per_sync(1 << 20);
-        // TODO RocksDB API
-        opts.set_max_background_compactions(2);
-        opts.set_max_background_flushes(2);
-        // opts.set_max_background_jobs(4);
-        opts.set_max_total_wal_size((1 << 20) * 64);
-        opts.set_keep_log_file_num(64);
-        opts.set_max_open_files(64);
-        // CFOptions "default"
-        opts.set_level_compaction_dynamic_level_bytes(true);
-        opts.set_write_buffer_size((1 << 20) * 8);
-        opts.set_min_write_buffer_number_to_merge(1);
-        opts.set_max_write_buffer_number(2);
-        // TODO RocksDB API
-        // opts.set_max_write_buffer_size_to_maintain(-1);
-        // [TableOptions/BlockBasedTable "default"]
-        let block_opts = {
-            let mut block_opts = rocksdb::BlockBasedOptions::default();
-            block_opts.set_cache_index_and_filter_blocks(true);
-            block_opts.set_pin_l0_filter_and_index_blocks_in_cache(true);
-            block_opts
-        };
+    // Load the storage in the read-only mode of RocksDB, so the data directory is never changed,
+    // a storage in an outdated schema is refused, and all writes fail.
+    pub fn load_read_only<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let db = ReadOnlyRocksDbBackend::open(path, Self::CF_NAMES)?;
+        let ret = Self::new(Box::new(db));
+        ret.check_schema_version(false)?;
+        ret.load_caches()?;
+        Ok(ret)
+    }
 
-        opts.set_block_based_table_factory(&block_opts);
+    // Only read the meta data, the schema is neither checked nor changed, for the checks before
+    // a data directory is removed.
+    pub(crate) fn read_meta_data<P: AsRef<Path>>(path: P) -> Result<MetaData> {
+        let db = ReadOnlyRocksDbBackend::open(path, Self::CF_NAMES)?;
+        Self::new(Box::new(db)).get_meta_data()
+    }
 
-        opts
+    // The caches are always re-derived from the persisted data.
+    fn load_caches(&self) -> Result<()> {
+        self.load_tx_statuses()?;
+        self.load_rejects()?;
+        self.load_live_cells()?;
+        if let Some(checkpoint) = self.get_checkpoint()? {
+            self.tip_number.set(checkpoint.block_number);
+        }
+        Ok(())
     }
 
-    fn default_cfoptions() -> rocksdb::Options {
-        let mut opts = rocksdb::Options::default();
-        opts.set_level_compaction_dynamic_level_bytes(true);
-        opts.set_write_buffer_size((1 << 20) * 8);
-        opts.set_min_write_buffer_number_to_merge(1);
-        opts.set_max_write_buffer_number(2);
-        // TODO RocksDB API
-        // opts.set_max_write_buffer_size_to_maintain(-1);
-        opts
+    // Initialize a storage which only keeps data in memory, so the logic above the storage could
+    // be tested or benchmarked without RocksDB.
+    //
+    // All data are dropped with it, and it could not be checkpointed.
+    pub fn in_memory() -> Result<Self> {
+        let db = MemoryBackend::new(Self::CF_NAMES);
+        let ret = Self::new(Box::new(db));
+        ret.put_schema_version(SCHEMA_VERSION)?;
+        Ok(ret)
     }
 
-    fn default_column_family_descriptors() -> Vec<rocksdb::ColumnFamilyDescriptor> {
-        let cfopts = Self::default_cfoptions();
-        Self::CF_NAMES
-            .iter()
-            .map(|name| rocksdb::ColumnFamilyDescriptor::new(name.to_owned(), cfopts.clone()))
-            .collect()
+    fn new(db: Box<dyn Backend>) -> Self {
+        log::debug!("[Storage] open the {} backend", db.name());
+        Self {
+            db,
+            stats: RefCell::new(CacheStats::default()),
+            tx_hashes: RefCell::new(IndexSet::new()),
+            live_cells: RefCell::new(IndexMap::new()),
+            cellbases: RefCell::new(BTreeMap::new()),
+            hot: RefCell::new(Some(HotCache::new(DEFAULT_CACHE_CAPACITY))),
+            tip_number: Cell::new(0),
+            faults: None,
+            orphan_expiry: true,
+        }
     }
 }
 
 // Common
 impl Storage {
-    fn cf_handle(&self, cf_name: &str) -> Result<&rocksdb::ColumnFamily> {
-        self.db.cf_handle(cf_name).ok_or_else(|| {
-            let errmsg = format!("column family {} should exists", cf_name);
-            Error::storage(errmsg)
-        })
+    pub(crate) fn inject_faults(&mut self, config: &StorageFaults, seed: u64) {
+        self.faults = Some(FaultInjector::new(config, seed));
+    }
+
+    // The orphan pool of the tx-pool expires the orphans by the fake time, which doesn't follow
+    // the clock of the chain when it's shared by the parallel instances, so the orphans are
+    // never expired in the storage then.
+    pub(crate) fn disable_orphan_expiry(&mut self) {
+        self.orphan_expiry = false;
+    }
+
+    fn inject_fault(&self, operation: &str) -> Result<()> {
+        if let Some(ref faults) = self.faults {
+            faults.before(operation)
+        } else {
+            Ok(())
+        }
     }
 
-    pub(crate) fn trace(&self) {
-        log::trace!("[Storage] stats: {}", self.stats.borrow());
+    pub(crate) fn stats(&self) -> CacheStats {
+        self.stats.borrow().clone()
+    }
+
+    // Resize the caches of the hot transactions and their statuses, zero to disable them.
+    pub(crate) fn set_cache_capacity(&self, capacity: usize) {
+        let hot = if capacity == 0 {
+            None
+        } else {
+            Some(HotCache::new(capacity))
+        };
+        *self.hot.borrow_mut() = hot;
+    }
+
+    // Drop the cached transactions and statuses after they are changed.
+    fn invalidate<'a, I>(&self, tx_hashes: I)
+    where
+        I: IntoIterator<Item = &'a packed::Byte32>,
+    {
+        if let Some(ref mut hot) = *self.hot.borrow_mut() {
+            for tx_hash in tx_hashes {
+                hot.txs.pop(tx_hash);
+                hot.tx_statuses.pop(tx_hash);
+            }
+        }
+    }
+
+    // Create a consistent copy of the storage, by a RocksDB checkpoint.
+    pub(crate) fn create_checkpoint<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.db.create_checkpoint(path.as_ref())
+    }
+
+    pub fn trace(&self) {
+        let stats = self.stats.borrow();
+        log::trace!("[Storage] stats: {}", stats);
+        if let Some(ref hot) = *self.hot.borrow() {
+            log::trace!("[Storage] cache: {}", hot);
+        }
+        utils::metrics::global().set_live_cells(stats.cell_live_cnt());
+    }
+}
+
+impl HotCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            txs: LruCache::new(capacity),
+            tx_statuses: LruCache::new(capacity),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    fn hit(&mut self, is_hit: bool) {
+        if is_hit {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+    }
+}
+
+impl fmt::Display for HotCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let total = self.hits + self.misses;
+        let hit_rate = if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 * 100.0 / total as f64
+        };
+        write!(
+            f,
+            "txs: {}, tx_statuses: {}, hits: {}, misses: {}, hit_rate: {:.2}%",
+            self.txs.len(),
+            self.tx_statuses.len(),
+            self.hits,
+            self.misses,
+            hit_rate
+        )
+    }
+}
+
+impl<'a> WriteTimer<'a> {
+    fn start(operation: &'static str, hash: &'a packed::Byte32) -> Self {
+        Self {
+            operation,
+            hash,
+            started_at: time::Instant::now(),
+        }
+    }
+}
+
+impl<'a> Drop for WriteTimer<'a> {
+    fn drop(&mut self) {
+        let elapsed = self.started_at.elapsed();
+        if utils::metrics::global().observe_latency(Operation::StorageWrite, elapsed) {
+            log::warn!(
+                "[Latency] >>> {} {:#x} took {:?}",
+                self.operation,
+                self.hash,
+                elapsed
+            );
+        }
     }
 }
 
 // CF: Default
 impl Storage {
-    pub(crate) fn put_meta_data(&self, meta_data: &MetaData) -> Result<()> {
+    pub fn put_meta_data(&self, meta_data: &MetaData) -> Result<()> {
         self.db
-            .put(KEY_METADATA, meta_data.to_string().as_bytes())
-            .map_err(Into::into)
+            .put(CF_DEFAULT, KEY_METADATA, meta_data.to_string().as_bytes())
     }
 
-    pub(crate) fn get_meta_data(&self) -> Result<MetaData> {
+    pub fn get_meta_data(&self) -> Result<MetaData> {
         self.db
-            .get(KEY_METADATA)
-            .map_err::<Error, _>(Into::into)?
+            .get(CF_DEFAULT, KEY_METADATA)?
             .map(|slice| String::from_utf8(slice.to_vec()).map_err(Error::storage))
             .transpose()?
             .map(|s| FromStr::from_str(&s).map_err(Error::storage))
             .transpose()?
             .ok_or_else(|| Error::storage("can not found the meta_data"))
     }
+
+    fn put_schema_version(&self, version: u32) -> Result<()> {
+        self.db
+            .put(CF_DEFAULT, KEY_SCHEMA_VERSION, &version.to_le_bytes())
+    }
+
+    fn get_schema_version(&self) -> Result<u32> {
+        self.db
+            .get(CF_DEFAULT, KEY_SCHEMA_VERSION)?
+            .map(|slice| {
+                if slice.len() != 4 {
+                    let errmsg = format!("schema version should be 4 bytes but {}", slice.len());
+                    return Err(Error::storage(errmsg));
+                }
+                let mut b = [0u8; 4];
+                b.copy_from_slice(&slice);
+                Ok(u32::from_le_bytes(b))
+            })
+            .transpose()
+            .map(|version_opt| version_opt.unwrap_or(0))
+    }
+
+    // Only an upgradable storage could be in an older schema version.
+    fn check_schema_version(&self, upgradable: bool) -> Result<()> {
+        let version = self.get_schema_version()?;
+        if version > SCHEMA_VERSION {
+            let errmsg = format!(
+                "storage schema version {} is newer than the supported version {}, \
+                please upgrade the fuzzer",
+                version, SCHEMA_VERSION
+            );
+            return Err(Error::storage(errmsg));
+        }
+        if version < SCHEMA_VERSION {
+            if !upgradable {
+                let errmsg = format!(
+                    "storage schema version {} is older than the current version {}, \
+                    please upgrade it by the subcommand run or prune first",
+                    version, SCHEMA_VERSION
+                );
+                return Err(Error::storage(errmsg));
+            }
+            if !(MIN_UPGRADABLE_SCHEMA_VERSION..SCHEMA_VERSION).contains(&version) {
+                let errmsg = format!(
+                    "storage schema version {} is too old to upgrade to version {}, \
+                    please initialize a new data directory",
+                    version, SCHEMA_VERSION
+                );
+                return Err(Error::storage(errmsg));
+            }
+            log::info!(
+                "[Storage] schema version {} is older than {}, upgrade it",
+                version,
+                SCHEMA_VERSION
+            );
+        }
+        Ok(())
+    }
+
+    // Upgrade the storage to the current schema version, one version after another, the
+    // version is stored after each migration, so an interrupted upgrade is continued.
+    fn upgrade(&self) -> Result<()> {
+        let version = self.get_schema_version()?;
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+            let version = index as u32 + 1;
+            log::info!(
+                "[Storage] upgrade schema to version {}: {}",
+                version,
+                migration.note
+            );
+            for (cf_name, hook) in migration.hooks {
+                log::info!("[Storage] migrate column family {}", cf_name);
+                hook(self).map_err(|err| {
+                    let errmsg = format!(
+                        "failed to migrate column family {} to schema version {} since {}",
+                        cf_name, version, err
+                    );
+                    Error::storage(errmsg)
+                })?;
+            }
+            self.put_schema_version(version)?;
+        }
+        Ok(())
+    }
+
+    // Store the meta data again in the current format.
+    fn rewrite_meta_data(&self) -> Result<()> {
+        let meta_data = self.get_meta_data()?;
+        self.put_meta_data(&meta_data)
+    }
+
+    // Increase the count of runs and return it as the ID of the current run.
+    pub(crate) fn next_run_id(&self) -> Result<u32> {
+        let count = self
+            .db
+            .get(CF_DEFAULT, KEY_RUNS_COUNT)?
+            .map(|slice| {
+                if slice.len() != 4 {
+                    let errmsg = format!("runs count should be 4 bytes but {}", slice.len());
+                    return Err(Error::storage(errmsg));
+                }
+                let mut b = [0u8; 4];
+                b.copy_from_slice(&slice);
+                Ok(u32::from_le_bytes(b))
+            })
+            .transpose()?
+            .unwrap_or(0);
+        let run_id = count + 1;
+        self.db
+            .put(CF_DEFAULT, KEY_RUNS_COUNT, &run_id.to_le_bytes())?;
+        Ok(run_id)
+    }
+
+    pub(crate) fn get_checkpoint(&self) -> Result<Option<Checkpoint>> {
+        self.db
+            .get(CF_DEFAULT, KEY_CHECKPOINT)?
+            .map(|slice| Checkpoint::from_slice(&slice))
+            .transpose()
+    }
+
+    // The ledger is persisted with each confirmed block.
+    pub(crate) fn get_ledger(&self) -> Result<Option<Ledger>> {
+        self.db
+            .get(CF_DEFAULT, KEY_LEDGER)?
+            .map(|slice| Ledger::from_slice(&slice))
+            .transpose()
+    }
+
+    // The number of the first block whose proposals are tracked.
+    fn get_proposals_since(&self) -> Result<Option<BlockNumber>> {
+        self.db
+            .get(CF_DEFAULT, KEY_PROPOSALS_SINCE)?
+            .map(|slice| {
+                if slice.len() != 8 {
+                    let errmsg = format!("proposals since should be 8 bytes but {}", slice.len());
+                    return Err(Error::storage(errmsg));
+                }
+                let mut b = [0u8; 8];
+                b.copy_from_slice(&slice);
+                Ok(u64::from_le_bytes(b))
+            })
+            .transpose()
+    }
+
+    pub(crate) fn put_report(&self, report: &RunReport) -> Result<()> {
+        self.db
+            .put(CF_DEFAULT, KEY_REPORT, report.to_string().as_bytes())
+    }
+
+    pub(crate) fn get_report(&self) -> Result<Option<RunReport>> {
+        self.db
+            .get(CF_DEFAULT, KEY_REPORT)?
+            .map(|slice| String::from_utf8(slice.to_vec()).map_err(Error::storage))
+            .transpose()?
+            .map(|s| FromStr::from_str(&s).map_err(Error::storage))
+            .transpose()
+    }
 }
 
 // CF: Transactions
 impl Storage {
-    fn put_transaction(&self, tx: &TransactionView) -> Result<()> {
-        let cf = self.cf_handle(Self::CF_TXS)?;
+    fn put_transaction(batch: &mut WriteBatch, tx: &TransactionView) {
         let hash = tx.hash();
-        self.db
-            .put_cf(cf, hash.as_slice(), tx.data().as_slice())
-            .map_err(Into::into)
+        batch.put(Self::CF_TXS, hash.as_slice(), tx.data().as_slice());
     }
 
-    pub(crate) fn get_transaction(
-        &self,
-        tx_hash: &packed::Byte32,
-    ) -> Result<Option<TransactionView>> {
-        let cf = self.cf_handle(Self::CF_TXS)?;
-        self.db
-            .get_cf(cf, tx_hash.as_slice())?
+    pub fn get_transaction(&self, tx_hash: &packed::Byte32) -> Result<Option<TransactionView>> {
+        if let Some(ref mut hot) = *self.hot.borrow_mut() {
+            let cached = hot.txs.get(tx_hash).cloned();
+            hot.hit(cached.is_some());
+            if cached.is_some() {
+                return Ok(cached);
+            }
+        }
+        let tx_opt = self
+            .db
+            .get(Self::CF_TXS, tx_hash.as_slice())?
             .map(|tx| {
                 packed::Transaction::from_slice(&tx)
                     .map(packed::Transaction::into_view)
                     .map_err(Error::storage)
             })
-            .transpose()
+            .transpose()?;
+        if let Some(ref tx) = tx_opt {
+            if let Some(ref mut hot) = *self.hot.borrow_mut() {
+                hot.txs.put(tx_hash.to_owned(), tx.to_owned());
+            }
+        }
+        Ok(tx_opt)
     }
 
-    fn delete_transaction(&self, tx_hash: &packed::Byte32) -> Result<()> {
-        let cf = self.cf_handle(Self::CF_TXS)?;
+    // All stored transactions, in the order of the hashes.
+    pub(crate) fn transactions(&self) -> Result<Vec<TransactionView>> {
         self.db
-            .delete_cf(cf, tx_hash.as_slice())
-            .map_err(Into::into)
+            .pairs(Self::CF_TXS)?
+            .into_iter()
+            .map(|(_, value)| {
+                packed::Transaction::from_slice(&value)
+                    .map(packed::Transaction::into_view)
+                    .map_err(Error::storage)
+            })
+            .collect()
+    }
+
+    // All transactions which are in the tx-pool, pending or proposed.
+    pub fn pending_transactions(&self) -> Result<Vec<TransactionView>> {
+        let mut txs = Vec::new();
+        for (key, value) in self.db.pairs(Self::CF_TXS)? {
+            let tx_hash = packed::Byte32::from_slice(&key).map_err(Error::storage)?;
+            let tx_status = self.get_tx_status(&tx_hash)?;
+            if tx_status
+                .map(|tx_status| tx_status.is_uncommitted())
+                .unwrap_or(false)
+            {
+                let tx = packed::Transaction::from_slice(&value)
+                    .map(packed::Transaction::into_view)
+                    .map_err(Error::storage)?;
+                txs.push(tx);
+            }
+        }
+        Ok(txs)
+    }
+
+    fn delete_transaction(batch: &mut WriteBatch, tx_hash: &packed::Byte32) {
+        batch.delete(Self::CF_TXS, tx_hash.as_slice());
     }
 }
 
 // CF: TXs' statuses
 impl Storage {
-    fn put_tx_status(&self, tx_hash: packed::Byte32, tx_status: TxStatus) -> Result<()> {
-        let cf = self.cf_handle(Self::CF_TX_STATUSES)?;
-        self.db
-            .put_cf(cf, tx_hash.as_slice(), tx_status.to_vec()?)?;
+    // The hash should be inserted into the cache after the batch is written.
+    fn put_tx_status(
+        batch: &mut WriteBatch,
+        tx_hash: &packed::Byte32,
+        tx_status: &TxStatus,
+    ) -> Result<()> {
+        batch.put(
+            Self::CF_TX_STATUSES,
+            tx_hash.as_slice(),
+            tx_status.to_vec()?,
+        );
         Ok(())
     }
 
     pub(crate) fn get_tx_status(&self, tx_hash: &packed::Byte32) -> Result<Option<TxStatus>> {
-        let cf = self.cf_handle(Self::CF_TX_STATUSES)?;
-        self.db
-            .get_cf(cf, tx_hash.as_slice())?
+        if let Some(ref mut hot) = *self.hot.borrow_mut() {
+            let cached = hot.tx_statuses.get(tx_hash).cloned();
+            hot.hit(cached.is_some());
+            if cached.is_some() {
+                return Ok(cached);
+            }
+        }
+        let tx_status_opt = self
+            .db
+            .get(Self::CF_TX_STATUSES, tx_hash.as_slice())?
             .map(|tx| TxStatus::from_slice(&tx).map_err(Error::storage))
-            .transpose()
+            .transpose()?;
+        if let Some(ref tx_status) = tx_status_opt {
+            if let Some(ref mut hot) = *self.hot.borrow_mut() {
+                hot.tx_statuses
+                    .put(tx_hash.to_owned(), tx_status.to_owned());
+            }
+        }
+        Ok(tx_status_opt)
     }
 
-    fn delete_tx_status(&self, tx_hash: &packed::Byte32) -> Result<()> {
-        let cf = self.cf_handle(Self::CF_TX_STATUSES)?;
-        self.db
-            .delete_cf(cf, tx_hash.as_slice())
-            .map_err(Into::into)
+    // The hash should be removed from the cache after the batch is written.
+    fn delete_tx_status(batch: &mut WriteBatch, tx_hash: &packed::Byte32) {
+        batch.delete(Self::CF_TX_STATUSES, tx_hash.as_slice());
     }
 
-    pub(crate) fn next_tx_status(
-        &self,
-        tx_hash: &packed::Byte32,
-    ) -> Result<(packed::Byte32, TxStatus)> {
-        let cf = self.cf_handle(Self::CF_TX_STATUSES)?;
-        let mode = rocksdb::IteratorMode::From(tx_hash.as_slice(), rocksdb::Direction::Forward);
-        let next = self
-            .db
-            .full_iterator_cf(cf, mode)?
-            .next()
-            .ok_or_else(|| {
-                let errmsg = format!("no available cells from {:#x}", tx_hash);
-                Error::storage(errmsg)
-            })
-            .and_then(|(key, value)| {
-                let tx_hash = packed::Byte32::from_slice(&key).map_err(Error::storage)?;
-                let tx_status = TxStatus::from_slice(&value).map_err(Error::storage)?;
-                Ok((tx_hash, tx_status))
-            });
-        if next.is_ok() {
-            return next;
+    // All transactions' statuses, in the order of the hashes.
+    pub(crate) fn tx_statuses(&self) -> Result<Vec<(packed::Byte32, TxStatus)>> {
+        let mut statuses = Vec::new();
+        for (key, value) in self.db.pairs(Self::CF_TX_STATUSES)? {
+            let tx_hash = packed::Byte32::from_slice(&key).map_err(Error::storage)?;
+            let tx_status = TxStatus::from_slice(&value).map_err(Error::storage)?;
+            statuses.push((tx_hash, tx_status));
         }
-        self.db
-            .full_iterator_cf(cf, rocksdb::IteratorMode::Start)?
-            .next()
-            .ok_or_else(|| Error::storage("no available cells from start"))
-            .and_then(|(key, value)| {
-                let tx_hash = packed::Byte32::from_slice(&key).map_err(Error::storage)?;
-                let tx_status = TxStatus::from_slice(&value).map_err(Error::storage)?;
-                Ok((tx_hash, tx_status))
-            })
+        Ok(statuses)
+    }
+
+    pub(crate) fn tx_hashes_count(&self) -> usize {
+        self.tx_hashes.borrow().len()
+    }
+
+    pub(crate) fn tx_hash_at(&self, index: usize) -> Option<packed::Byte32> {
+        self.tx_hashes.borrow().get_index(index).cloned()
+    }
+
+    pub(crate) fn tx_hash_position(&self, tx_hash: &packed::Byte32) -> Option<usize> {
+        self.tx_hashes.borrow().get_index_of(tx_hash)
     }
 
     fn load_tx_statuses(&self) -> Result<()> {
-        let cf = self.cf_handle(Self::CF_TX_STATUSES)?;
-        for (_, value) in self.db.full_iterator_cf(cf, rocksdb::IteratorMode::Start)? {
+        for (key, value) in self.db.pairs(Self::CF_TX_STATUSES)? {
+            let tx_hash = packed::Byte32::from_slice(&key).map_err(Error::storage)?;
             let tx_status = TxStatus::from_slice(&value).map_err(Error::storage)?;
             self.stats.borrow_mut().load_tx(&tx_status);
+            if let Some(origin) = tx_status.cellbase_origin() {
+                self.cellbases
+                    .borrow_mut()
+                    .insert(origin.block_number, tx_hash.clone());
+            }
+            self.tx_hashes.borrow_mut().insert(tx_hash);
+        }
+        Ok(())
+    }
+
+    // The committed cellbases whose origins are tracked, from the newest one.
+    pub(crate) fn recent_cellbases(&self, limit: usize) -> Vec<packed::Byte32> {
+        self.cellbases
+            .borrow()
+            .values()
+            .rev()
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    // Track the origins of the committed cellbases which were stored without them.
+    pub(crate) fn put_cellbase_origins(
+        &self,
+        origins: Vec<(packed::Byte32, CellbaseOrigin)>,
+    ) -> Result<()> {
+        let mut batch = WriteBatch::default();
+        let mut changed = Vec::new();
+        for (tx_hash, origin) in origins {
+            if let Some(mut tx_status) = self.get_tx_status(&tx_hash)? {
+                if tx_status.set_cellbase_origin(origin) {
+                    Self::put_tx_status(&mut batch, &tx_hash, &tx_status)?;
+                    changed.push((tx_hash, origin));
+                }
+            }
+        }
+        if changed.is_empty() {
+            return Ok(());
+        }
+        self.db.write(batch)?;
+        log::info!("[Storage] track the origins of {} cellbases", changed.len());
+        self.invalidate(changed.iter().map(|(tx_hash, _)| tx_hash));
+        let mut cellbases = self.cellbases.borrow_mut();
+        for (tx_hash, origin) in changed {
+            cellbases.insert(origin.block_number, tx_hash);
         }
         Ok(())
     }
@@ -261,53 +802,539 @@ impl Storage {
     }
 }
 
-// CF: Pending transactions not in TXs' statuses
+// CF: Live cells
 impl Storage {
-    fn put_pending_tx(&self, tx_hash: packed::Byte32) -> Result<()> {
-        let cf = self.cf_handle(Self::CF_PENDING_TXS)?;
-        self.db.put_cf(cf, tx_hash.as_slice(), &[])?;
+    // Put the changes of the live cells into the batch when the status of a transaction is
+    // changed, and collect them to update the cache after the batch is written.
+    fn index_live_cells(
+        batch: &mut WriteBatch,
+        changes: &mut LiveCellChanges,
+        tx_hash: &packed::Byte32,
+        old: Option<&TxStatus>,
+        new: Option<&TxStatus>,
+    ) {
+        let old_stages = old.map(TxStatus::live_cell_stages).unwrap_or_default();
+        let new_stages = new.map(TxStatus::live_cell_stages).unwrap_or_default();
+        for index in 0..old_stages.len().max(new_stages.len()) {
+            let old_stage = old_stages.get(index).copied().flatten();
+            let new_stage = new_stages.get(index).copied().flatten();
+            if old_stage == new_stage {
+                continue;
+            }
+            let out_point = packed::OutPoint::new(tx_hash.clone(), index as u32);
+            if let Some(stage) = new_stage {
+                let value: u8 = stage.into();
+                batch.put(Self::CF_LIVE_CELLS, out_point.as_slice(), [value]);
+            } else {
+                batch.delete(Self::CF_LIVE_CELLS, out_point.as_slice());
+            }
+            changes.push((out_point, new_stage));
+        }
+    }
+
+    fn apply_live_cells(&self, changes: LiveCellChanges) {
+        let mut live_cells = self.live_cells.borrow_mut();
+        for (out_point, stage_opt) in changes {
+            if let Some(stage) = stage_opt {
+                live_cells.insert(out_point, stage);
+            } else {
+                live_cells.swap_remove(&out_point);
+            }
+        }
+    }
+
+    pub(crate) fn live_cells_index_count(&self) -> usize {
+        self.live_cells.borrow().len()
+    }
+
+    pub(crate) fn live_cell_at(&self, index: usize) -> Option<(packed::OutPoint, CellStage)> {
+        self.live_cells
+            .borrow()
+            .get_index(index)
+            .map(|(out_point, stage)| (out_point.to_owned(), *stage))
+    }
+
+    fn load_live_cells(&self) -> Result<()> {
+        let mut live_cells = self.live_cells.borrow_mut();
+        for (key, value) in self.db.pairs(Self::CF_LIVE_CELLS)? {
+            let out_point = packed::OutPoint::from_slice(&key).map_err(Error::storage)?;
+            let stage = value
+                .first()
+                .copied()
+                .ok_or_else(|| Error::broken_since("CellStage", "no enough data"))
+                .and_then(CellStage::try_from)?;
+            live_cells.insert(out_point, stage);
+        }
         Ok(())
     }
 
+    // Build the index from the statuses of all transactions, for the storages which are created
+    // before the index.
+    fn build_live_cells(&self) -> Result<()> {
+        let mut batch = WriteBatch::default();
+        let mut changes = LiveCellChanges::new();
+        for (tx_hash, tx_status) in self.tx_statuses()? {
+            Self::index_live_cells(&mut batch, &mut changes, &tx_hash, None, Some(&tx_status));
+        }
+        self.db.write(batch)?;
+        log::info!("[Storage] index {} live cells", changes.len());
+        self.apply_live_cells(changes);
+        Ok(())
+    }
+}
+
+// CF: Pending transactions not in TXs' statuses
+impl Storage {
+    fn put_pending_tx(batch: &mut WriteBatch, tx_hash: &packed::Byte32) {
+        batch.put(Self::CF_PENDING_TXS, tx_hash.as_slice(), &[]);
+    }
+
     fn has_pending_tx(&self, tx_hash: &packed::Byte32) -> Result<bool> {
-        let cf = self.cf_handle(Self::CF_PENDING_TXS)?;
-        let had = self.db.get_cf(cf, tx_hash.as_slice())?.is_some();
+        let had = self
+            .db
+            .get(Self::CF_PENDING_TXS, tx_hash.as_slice())?
+            .is_some();
         Ok(had)
     }
+}
+
+// CF: TXs' sequence IDs
+impl Storage {
+    pub(crate) fn put_tx_seq_id(&self, tx_hash: &packed::Byte32, seq_id: &TxSeqId) -> Result<()> {
+        self.inject_fault("put_tx_seq_id")?;
+        self.db
+            .put(Self::CF_TX_SEQ_IDS, tx_hash.as_slice(), &seq_id.to_vec())
+    }
+
+    pub(crate) fn get_tx_seq_id(&self, tx_hash: &packed::Byte32) -> Result<Option<TxSeqId>> {
+        self.db
+            .get(Self::CF_TX_SEQ_IDS, tx_hash.as_slice())?
+            .map(|slice| TxSeqId::from_slice(&slice))
+            .transpose()
+    }
+}
+
+// CF: Proposals
+impl Storage {
+    pub(crate) fn get_proposals(&self, tx_hash: &packed::Byte32) -> Result<Option<Proposals>> {
+        let id = packed::ProposalShortId::from_tx_hash(tx_hash);
+        self.db
+            .get(Self::CF_PROPOSALS, id.as_slice())?
+            .map(|slice| Proposals::from_slice(&slice))
+            .transpose()
+    }
+
+    // The stage of a transaction when the block of the number is built, returns nothing if
+    // the proposals in the window are not fully tracked.
+    pub(crate) fn tx_stage(
+        &self,
+        tx_hash: &packed::Byte32,
+        number: BlockNumber,
+        window: &ProposalWindow,
+    ) -> Result<Option<TxStage>> {
+        let stage = self
+            .get_proposals(tx_hash)?
+            .unwrap_or_default()
+            .stage(number, window);
+        if stage == TxStage::Proposed {
+            return Ok(Some(stage));
+        }
+        let is_tracked = self
+            .get_proposals_since()?
+            .map(|since| since + window.farthest() <= number)
+            .unwrap_or(false);
+        Ok(if is_tracked { Some(stage) } else { None })
+    }
+}
 
-    fn delete_pending_tx(&self, tx_hash: &packed::Byte32) -> Result<()> {
-        let cf = self.cf_handle(Self::CF_PENDING_TXS)?;
+// CF: Rejects
+impl Storage {
+    pub(crate) fn put_reject(&self, tx_hash: &packed::Byte32, reject: &Reject) -> Result<()> {
+        self.inject_fault("put_reject")?;
+        let reason = reject_reason(reject);
+        self.db
+            .put(Self::CF_REJECTS, tx_hash.as_slice(), reason.as_bytes())?;
+        self.stats.borrow_mut().reject(&reason);
+        Ok(())
+    }
+
+    // The name of the variant of the reject, for example, "Resolve".
+    pub(crate) fn get_reject(&self, tx_hash: &packed::Byte32) -> Result<Option<String>> {
         self.db
-            .delete_cf(cf, tx_hash.as_slice())
-            .map_err(Into::into)
+            .get(Self::CF_REJECTS, tx_hash.as_slice())?
+            .map(|slice| String::from_utf8(slice.to_vec()).map_err(Error::storage))
+            .transpose()
+    }
+
+    // All reject reasons, in the order of the hashes.
+    pub(crate) fn rejects(&self) -> Result<Vec<(packed::Byte32, String)>> {
+        let mut rejects = Vec::new();
+        for (key, value) in self.db.pairs(Self::CF_REJECTS)? {
+            let tx_hash = packed::Byte32::from_slice(&key).map_err(Error::storage)?;
+            let reason = String::from_utf8(value.to_vec()).map_err(Error::storage)?;
+            rejects.push((tx_hash, reason));
+        }
+        Ok(rejects)
+    }
+
+    fn load_rejects(&self) -> Result<()> {
+        for (_, value) in self.db.pairs(Self::CF_REJECTS)? {
+            let reason = String::from_utf8(value.to_vec()).map_err(Error::storage)?;
+            self.stats.borrow_mut().reject(&reason);
+        }
+        Ok(())
+    }
+}
+
+// CF: TXs' histories
+impl Storage {
+    pub(crate) fn get_tx_history(&self, tx_hash: &packed::Byte32) -> Result<Option<TxHistory>> {
+        self.db
+            .get(Self::CF_TX_HISTORY, tx_hash.as_slice())?
+            .map(|slice| TxHistory::from_slice(&slice))
+            .transpose()
+    }
+
+    // Append the transitions into the histories of transactions, at the current tip.
+    //
+    // A transaction could have several transitions in one batch, so the histories are merged
+    // before they are put into the batch.
+    fn record_events(
+        &self,
+        batch: &mut WriteBatch,
+        events: Vec<(packed::Byte32, TxEventKind)>,
+    ) -> Result<()> {
+        let mut histories = HashMap::new();
+        for (tx_hash, kind) in events {
+            let mut history = match histories.remove(&tx_hash) {
+                Some(history) => history,
+                None => self.get_tx_history(&tx_hash)?.unwrap_or_default(),
+            };
+            history.push(TxEvent::new(self.tip_number.get(), kind));
+            histories.insert(tx_hash, history);
+        }
+        for (tx_hash, history) in histories {
+            batch.put(Self::CF_TX_HISTORY, tx_hash.as_slice(), history.to_vec());
+        }
+        Ok(())
+    }
+}
+
+// CF: TXs' edges
+impl Storage {
+    // The edges are never removed, so the graph still explains the removed transactions.
+    fn put_tx_edges(batch: &mut WriteBatch, tx: &TransactionView) {
+        let tx_hash = tx.hash();
+        let mut edges: HashMap<packed::Byte32, Vec<u32>> = HashMap::new();
+        for out_point in tx.input_pts_iter() {
+            edges
+                .entry(out_point.tx_hash())
+                .or_default()
+                .push(out_point.index().unpack());
+        }
+        for (parent, indexes) in edges {
+            Self::put_tx_edge(batch, &parent, &tx_hash, &indexes);
+        }
+    }
+
+    fn put_tx_edge(
+        batch: &mut WriteBatch,
+        parent: &packed::Byte32,
+        child: &packed::Byte32,
+        indexes: &[u32],
+    ) {
+        let mut key = Vec::with_capacity(64);
+        key.extend_from_slice(parent.as_slice());
+        key.extend_from_slice(child.as_slice());
+        let value = indexes
+            .iter()
+            .flat_map(|index| index.to_le_bytes())
+            .collect::<Vec<_>>();
+        batch.put(Self::CF_TX_EDGES, key, value);
+    }
+
+    // All edges as the parent, the child and the spent output indexes, in the order of the
+    // parents.
+    pub(crate) fn tx_edges(&self) -> Result<Vec<TxEdge>> {
+        let mut edges = Vec::new();
+        for (key, value) in self.db.pairs(Self::CF_TX_EDGES)? {
+            if key.len() != 64 || value.len() % 4 != 0 {
+                return Err(Error::broken_since("TxEdge", "the size is wrong"));
+            }
+            let parent = packed::Byte32::from_slice(&key[..32]).map_err(Error::storage)?;
+            let child = packed::Byte32::from_slice(&key[32..]).map_err(Error::storage)?;
+            let outputs = value
+                .chunks(4)
+                .map(|chunk| {
+                    let mut bytes = [0u8; 4];
+                    bytes.copy_from_slice(chunk);
+                    u32::from_le_bytes(bytes)
+                })
+                .collect();
+            edges.push(TxEdge {
+                parent,
+                child,
+                outputs,
+            });
+        }
+        Ok(edges)
+    }
+
+    // Build the edges from the inputs of the stored transactions and the spends in the
+    // histories, for the storages which are created before the edges.
+    fn build_tx_edges(&self) -> Result<()> {
+        let mut edges: HashMap<(packed::Byte32, packed::Byte32), BTreeSet<u32>> = HashMap::new();
+        for (key, value) in self.db.pairs(Self::CF_TXS)? {
+            let tx_hash = packed::Byte32::from_slice(&key).map_err(Error::storage)?;
+            let tx = packed::Transaction::from_slice(&value).map_err(Error::storage)?;
+            for input in tx.raw().inputs().into_iter() {
+                let out_point = input.previous_output();
+                edges
+                    .entry((out_point.tx_hash(), tx_hash.clone()))
+                    .or_default()
+                    .insert(out_point.index().unpack());
+            }
+        }
+        for (key, value) in self.db.pairs(Self::CF_TX_HISTORY)? {
+            let tx_hash = packed::Byte32::from_slice(&key).map_err(Error::storage)?;
+            for event in TxHistory::from_slice(&value)?.events {
+                if let TxEventKind::Spent(index, spender) = event.kind {
+                    edges
+                        .entry((tx_hash.clone(), spender))
+                        .or_default()
+                        .insert(index);
+                }
+            }
+        }
+        let mut batch = WriteBatch::default();
+        for ((parent, child), indexes) in &edges {
+            let indexes = indexes.iter().copied().collect::<Vec<_>>();
+            Self::put_tx_edge(&mut batch, parent, child, &indexes);
+        }
+        self.db.write(batch)?;
+        log::info!("[Storage] build {} edges between transactions", edges.len());
+        Ok(())
+    }
+}
+
+// CF: Spenders of dead cells
+impl Storage {
+    // Record the spender when a cell is marked as dead, a cell is spent by only one transaction
+    // until it's revived.
+    fn put_spender(
+        &self,
+        batch: &mut WriteBatch,
+        out_point: &packed::OutPoint,
+        spender: &packed::Byte32,
+    ) -> Result<()> {
+        if let Some(recorded) = self.get_spender(out_point)? {
+            if &recorded != spender {
+                let errmsg = format!(
+                    "{} is spent by tx {:#x}, but tx {:#x} spends it again",
+                    display_out_point(out_point),
+                    recorded,
+                    spender
+                );
+                return Err(Error::runtime(errmsg));
+            }
+        }
+        batch.put(Self::CF_SPENDERS, out_point.as_slice(), spender.as_slice());
+        Ok(())
+    }
+
+    fn delete_spender(batch: &mut WriteBatch, out_point: &packed::OutPoint) {
+        batch.delete(Self::CF_SPENDERS, out_point.as_slice());
+    }
+
+    pub(crate) fn get_spender(
+        &self,
+        out_point: &packed::OutPoint,
+    ) -> Result<Option<packed::Byte32>> {
+        self.db
+            .get(Self::CF_SPENDERS, out_point.as_slice())?
+            .map(|slice| packed::Byte32::from_slice(&slice).map_err(Error::storage))
+            .transpose()
+    }
+
+    // Replay the spent and revived events of all transactions.
+    fn build_spenders(&self) -> Result<()> {
+        let mut spenders = HashMap::new();
+        for (key, value) in self.db.pairs(Self::CF_TX_HISTORY)? {
+            let tx_hash = packed::Byte32::from_slice(&key).map_err(Error::storage)?;
+            for event in TxHistory::from_slice(&value)?.events {
+                match event.kind {
+                    TxEventKind::Spent(index, spender) => {
+                        let out_point = packed::OutPoint::new(tx_hash.clone(), index);
+                        spenders.insert(out_point, spender);
+                    }
+                    TxEventKind::Revived(index) => {
+                        let out_point = packed::OutPoint::new(tx_hash.clone(), index);
+                        spenders.remove(&out_point);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let mut batch = WriteBatch::default();
+        for (out_point, spender) in &spenders {
+            batch.put(Self::CF_SPENDERS, out_point.as_slice(), spender.as_slice());
+        }
+        self.db.write(batch)?;
+        log::info!(
+            "[Storage] record the spenders of {} dead cells",
+            spenders.len()
+        );
+        Ok(())
+    }
+}
+
+// CF: Block deltas
+impl Storage {
+    pub(crate) fn put_block_delta(&self, block: &BlockView, delta: &BlockDelta) -> Result<()> {
+        self.inject_fault("put_block_delta")?;
+        self.db.put(
+            Self::CF_BLOCK_DELTAS,
+            &block.number().to_be_bytes(),
+            &delta.to_vec()?,
+        )
+    }
+
+    pub(crate) fn get_block_delta(&self, number: BlockNumber) -> Result<Option<BlockDelta>> {
+        self.db
+            .get(Self::CF_BLOCK_DELTAS, &number.to_be_bytes())?
+            .map(|slice| BlockDelta::from_slice(&slice))
+            .transpose()
+    }
+}
+
+// CF: Runs
+impl Storage {
+    pub(crate) fn put_run_record(&self, record: &RunRecord) -> Result<()> {
+        self.db.put(
+            Self::CF_RUNS,
+            &record.run_id.to_be_bytes(),
+            record.to_string().as_bytes(),
+        )
+    }
+
+    // All records in the order of the run IDs.
+    pub(crate) fn run_records(&self) -> Result<Vec<RunRecord>> {
+        let mut records = Vec::new();
+        for (_, value) in self.db.pairs(Self::CF_RUNS)? {
+            let s = String::from_utf8(value.to_vec()).map_err(Error::storage)?;
+            records.push(FromStr::from_str(&s).map_err(Error::storage)?);
+        }
+        Ok(records)
     }
 }
 
 // Hybrid
 impl Storage {
+    // Track the committed transactions when initializing, such as the genesis cellbase whose
+    // issued cells are live since the beginning, or the transactions of an imported chain.
+    pub(crate) fn put_initial_txs(&self, txs: Vec<(packed::Byte32, TxStatus)>) -> Result<()> {
+        let mut batch = WriteBatch::default();
+        let mut live_cells = LiveCellChanges::new();
+        for (tx_hash, tx_status) in &txs {
+            Self::put_tx_status(&mut batch, tx_hash, tx_status)?;
+            Self::index_live_cells(&mut batch, &mut live_cells, tx_hash, None, Some(tx_status));
+        }
+        self.db.write(batch)?;
+        self.apply_live_cells(live_cells);
+        let mut stats = self.stats.borrow_mut();
+        let mut tx_hashes = self.tx_hashes.borrow_mut();
+        for (tx_hash, tx_status) in txs {
+            stats.load_tx(&tx_status);
+            tx_hashes.insert(tx_hash);
+        }
+        Ok(())
+    }
+
+    // All changes of a transaction are written in one batch, so an interrupted submission never
+    // leaves a transaction without its status or its inputs not spent.
     pub(crate) fn submit_tx(
         &self,
         tx: &TransactionView,
         tx_status: TxStatus,
         changes: HashMap<packed::Byte32, TxStatus>,
     ) -> Result<()> {
+        let tx_hash = tx.hash();
+        let _timer = WriteTimer::start("submit_tx", &tx_hash);
         let inputs_count = tx.inputs().len();
         self.stats
             .borrow_mut()
             .submit_tx(inputs_count, &tx_status)?;
-        self.put_transaction(tx)?;
-        self.put_tx_status(tx.hash(), tx_status)?;
-        for (hash, status) in changes {
-            self.put_tx_status(hash, status)?;
+        let mut batch = WriteBatch::default();
+        let mut live_cells = LiveCellChanges::new();
+        Self::put_transaction(&mut batch, tx);
+        Self::put_tx_status(&mut batch, &tx_hash, &tx_status)?;
+        Self::put_tx_edges(&mut batch, tx);
+        Self::index_live_cells(
+            &mut batch,
+            &mut live_cells,
+            &tx_hash,
+            None,
+            Some(&tx_status),
+        );
+        let mut events = vec![(tx_hash.clone(), TxEventKind::Submitted)];
+        for out_point in tx.input_pts_iter() {
+            let input_tx_hash = out_point.tx_hash();
+            if changes.contains_key(&input_tx_hash) {
+                self.put_spender(&mut batch, &out_point, &tx_hash)?;
+                let spent = TxEventKind::Spent(out_point.index().unpack(), tx_hash.clone());
+                events.push((input_tx_hash, spent));
+            }
         }
+        self.record_events(&mut batch, events)?;
+        for (hash, status) in &changes {
+            let old_status = self.get_tx_status(hash)?;
+            Self::put_tx_status(&mut batch, hash, status)?;
+            Self::index_live_cells(
+                &mut batch,
+                &mut live_cells,
+                hash,
+                old_status.as_ref(),
+                Some(status),
+            );
+        }
+        self.inject_fault("submit_tx")?;
+        self.db.write(batch)?;
+        self.invalidate(changes.keys().chain(Some(&tx_hash)));
+        self.apply_live_cells(live_cells);
+        let mut tx_hashes = self.tx_hashes.borrow_mut();
+        tx_hashes.insert(tx_hash);
+        tx_hashes.extend(changes.into_iter().map(|(hash, _)| hash));
         Ok(())
     }
 
     pub(crate) fn submit_invalid_tx(&self, tx: &TransactionView) -> Result<()> {
-        let tx_status = TxStatus::Failed;
+        self.submit_rejected_tx(tx, TxStatus::Failed, TxEventKind::Failed)
+    }
+
+    // The transaction is rejected since its inputs are unknown, but the tx-pool keeps it in the
+    // orphan pool until it's expired.
+    pub(crate) fn submit_orphan_tx(&self, tx: &TransactionView, since: u64) -> Result<()> {
+        self.submit_rejected_tx(tx, TxStatus::Orphan(since), TxEventKind::Orphaned)
+    }
+
+    fn submit_rejected_tx(
+        &self,
+        tx: &TransactionView,
+        tx_status: TxStatus,
+        event: TxEventKind,
+    ) -> Result<()> {
+        let tx_hash = tx.hash();
+        let _timer = WriteTimer::start("submit_invalid_tx", &tx_hash);
         self.stats.borrow_mut().submit_tx(0, &tx_status)?;
-        self.put_transaction(tx)?;
-        self.put_tx_status(tx.hash(), tx_status)?;
+        let mut batch = WriteBatch::default();
+        Self::put_transaction(&mut batch, tx);
+        Self::put_tx_status(&mut batch, &tx_hash, &tx_status)?;
+        Self::put_tx_edges(&mut batch, tx);
+        self.record_events(&mut batch, vec![(tx_hash.clone(), event)])?;
+        self.inject_fault("submit_invalid_tx")?;
+        self.db.write(batch)?;
+        self.invalidate(Some(&tx_hash));
+        self.tx_hashes.borrow_mut().insert(tx_hash);
         Ok(())
     }
 
@@ -316,18 +1343,133 @@ impl Storage {
         tx_hash: &packed::Byte32,
         tx_status: &TxStatus,
     ) -> Result<()> {
-        if matches!(tx_status, TxStatus::Pending(_)) {
-            self.put_pending_tx(tx_hash.to_owned())?;
+        let _timer = WriteTimer::start("remove_invalid_tx", tx_hash);
+        let mut batch = WriteBatch::default();
+        let mut live_cells = LiveCellChanges::new();
+        if tx_status.is_uncommitted() {
+            Self::put_pending_tx(&mut batch, tx_hash);
         }
-        self.delete_transaction(tx_hash)?;
-        self.delete_tx_status(tx_hash)?;
+        Self::delete_transaction(&mut batch, tx_hash);
+        Self::delete_tx_status(&mut batch, tx_hash);
+        Self::index_live_cells(&mut batch, &mut live_cells, tx_hash, Some(tx_status), None);
+        self.record_events(&mut batch, vec![(tx_hash.clone(), TxEventKind::Removed)])?;
+        self.inject_fault("remove_invalid_tx")?;
+        self.db.write(batch)?;
+        self.invalidate(Some(tx_hash));
+        self.apply_live_cells(live_cells);
+        self.tx_hashes.borrow_mut().swap_remove(tx_hash);
         self.stats.borrow_mut().remove_tx(tx_status);
         Ok(())
     }
 
-    pub(crate) fn confirm_block(&self, block: &BlockView) -> Result<()> {
-        let cf_blocks = self.cf_handle(Self::CF_BLOCKS)?;
-        self.db.delete_cf(cf_blocks, block.hash().as_slice())?;
+    // Mark a pending transaction as evicted from the tx-pool, and revive its inputs.
+    pub(crate) fn evict_tx(&self, tx: &TransactionView) -> Result<()> {
+        let tx_hash = tx.hash();
+        let _timer = WriteTimer::start("evict_tx", &tx_hash);
+        let tx_status = self.get_tx_status(&tx_hash)?.ok_or_else(|| {
+            let errmsg = format!("tx {:#x} is evicted but it's unknown", tx_hash);
+            Error::runtime(errmsg)
+        })?;
+        if !tx_status.is_uncommitted() {
+            let errmsg = format!("tx {:#x} is evicted but it's not pending", tx_hash);
+            return Err(Error::runtime(errmsg));
+        }
+        // Several inputs could be the outputs of the same transaction.
+        let mut revived = HashMap::new();
+        let mut revived_cells = Vec::new();
+        let mut events = Vec::new();
+        for out_point in tx.input_pts_iter() {
+            let input_tx_hash = out_point.tx_hash();
+            if !revived.contains_key(&input_tx_hash) {
+                if let Some(input_tx_status) = self.get_tx_status(&input_tx_hash)? {
+                    let old_status = input_tx_status.clone();
+                    revived.insert(input_tx_hash.clone(), (old_status, input_tx_status));
+                }
+            }
+            if let Some((_, input_tx_status)) = revived.get_mut(&input_tx_hash) {
+                let index: u32 = out_point.index().unpack();
+                input_tx_status.revive(index as usize);
+                events.push((input_tx_hash, TxEventKind::Revived(index)));
+                revived_cells.push(out_point);
+            }
+        }
+        let mut batch = WriteBatch::default();
+        for out_point in &revived_cells {
+            Self::delete_spender(&mut batch, out_point);
+        }
+        let mut live_cells = LiveCellChanges::new();
+        for (input_tx_hash, (old_status, input_tx_status)) in &revived {
+            Self::put_tx_status(&mut batch, input_tx_hash, input_tx_status)?;
+            Self::index_live_cells(
+                &mut batch,
+                &mut live_cells,
+                input_tx_hash,
+                Some(old_status),
+                Some(input_tx_status),
+            );
+        }
+        Self::put_tx_status(&mut batch, &tx_hash, &TxStatus::Evicted)?;
+        Self::index_live_cells(
+            &mut batch,
+            &mut live_cells,
+            &tx_hash,
+            Some(&tx_status),
+            Some(&TxStatus::Evicted),
+        );
+        events.push((tx_hash.clone(), TxEventKind::Evicted));
+        self.record_events(&mut batch, events)?;
+        self.inject_fault("evict_tx")?;
+        self.db.write(batch)?;
+        self.invalidate(revived.keys().chain(Some(&tx_hash)));
+        self.apply_live_cells(live_cells);
+        self.stats
+            .borrow_mut()
+            .evict_tx(&tx_status, tx.inputs().len());
+        Ok(())
+    }
+
+    // All changes of a block, the checkpoint and the ledger are written in one batch, so an
+    // interrupted run never leaves a partially confirmed block.
+    pub(crate) fn confirm_block(
+        &self,
+        block: &BlockView,
+        checkpoint: &Checkpoint,
+        ledger: &Ledger,
+        window: &ProposalWindow,
+    ) -> Result<()> {
+        let block_hash = block.hash();
+        let _timer = WriteTimer::start("confirm_block", &block_hash);
+        self.check_no_double_spend(block)?;
+        self.check_proposal_window(block, window)?;
+        let mut batch = WriteBatch::default();
+        let mut live_cells = LiveCellChanges::new();
+        batch.delete(Self::CF_BLOCKS, block.hash().as_slice());
+        // The proposals which are changed by the block, they are not written yet.
+        let mut updated_proposals = HashMap::new();
+        for id in block.union_proposal_ids() {
+            let mut proposals = self
+                .db
+                .get(Self::CF_PROPOSALS, id.as_slice())?
+                .map(|slice| Proposals::from_slice(&slice))
+                .transpose()?
+                .unwrap_or_default();
+            proposals.add(block.number(), window);
+            batch.put(Self::CF_PROPOSALS, id.as_slice(), proposals.to_vec());
+            updated_proposals.insert(id, proposals);
+        }
+        if self.get_proposals_since()?.is_none() {
+            batch.put(
+                CF_DEFAULT,
+                KEY_PROPOSALS_SINCE,
+                block.number().to_le_bytes(),
+            );
+        }
+        let mut committed = Vec::new();
+        let mut committed_removed = Vec::new();
+        // The old and the new statuses of the tracked transactions.
+        let mut changed = Vec::new();
+        let mut cellbase_outputs_count = None;
+        let mut committed_cellbase = None;
         let mut is_cellbase = true;
         for tx in block.transactions() {
             let tx_hash = tx.hash();
@@ -335,18 +1477,39 @@ impl Storage {
                 if !tx.outputs().is_empty() {
                     log::trace!("[Storage] commit cellbase {:#x}", tx_hash);
                     let outputs_count = tx.outputs().len();
-                    let tx_status = TxStatus::new_committed(outputs_count);
-                    self.put_tx_status(tx_hash, tx_status)?;
-                    self.stats.borrow_mut().commit_cellbase(outputs_count);
+                    let origin = CellbaseOrigin {
+                        block_number: block.number(),
+                        epoch: block.epoch(),
+                    };
+                    let tx_status = TxStatus::new_committed_cellbase(outputs_count, origin);
+                    batch.put(
+                        Self::CF_TX_STATUSES,
+                        tx_hash.as_slice(),
+                        tx_status.to_vec()?,
+                    );
+                    Self::index_live_cells(
+                        &mut batch,
+                        &mut live_cells,
+                        &tx_hash,
+                        None,
+                        Some(&tx_status),
+                    );
+                    committed_cellbase = Some(tx_hash.clone());
+                    committed.push(tx_hash);
+                    cellbase_outputs_count = Some(outputs_count);
                 }
                 is_cellbase = false;
             } else {
-                self.delete_transaction(&tx_hash)?;
+                batch.delete(Self::CF_TXS, tx_hash.as_slice());
+                batch.delete(Self::CF_PROPOSALS, tx.proposal_short_id().as_slice());
                 if let Some(tx_status) = self.get_tx_status(&tx_hash)? {
                     match tx_status {
-                        TxStatus::Failed => {
+                        TxStatus::Orphan(_)
+                        | TxStatus::Evicted
+                        | TxStatus::Expired
+                        | TxStatus::Failed => {
                             let errmsg =
-                                format!("tx {:#x} is committed but it should be failed", tx_hash);
+                                format!("tx {:#x} is committed but it's {}", tx_hash, tx_status);
                             return Err(Error::runtime(errmsg));
                         }
                         TxStatus::Committed(..) => {
@@ -354,21 +1517,277 @@ impl Storage {
                                 format!("tx {:#x} is committed but it already committed", tx_hash);
                             return Err(Error::runtime(errmsg));
                         }
-                        TxStatus::Pending(inner) => {
-                            log::trace!("[Storage] commit pending {:#x}", tx_hash);
-                            let new_tx_status = TxStatus::Committed(inner);
-                            self.put_tx_status(tx_hash, new_tx_status)?;
-                            self.stats.borrow_mut().commit_pending();
+                        TxStatus::Pending(ref inner) | TxStatus::Proposed(ref inner) => {
+                            log::trace!("[Storage] commit {} {:#x}", tx_status, tx_hash);
+                            let new_tx_status = TxStatus::Committed(inner.to_owned());
+                            batch.put(
+                                Self::CF_TX_STATUSES,
+                                tx_hash.as_slice(),
+                                new_tx_status.to_vec()?,
+                            );
+                            Self::index_live_cells(
+                                &mut batch,
+                                &mut live_cells,
+                                &tx_hash,
+                                Some(&tx_status),
+                                Some(&new_tx_status),
+                            );
+                            committed.push(tx_hash);
+                            changed.push((tx_status.clone(), new_tx_status));
                         }
                     }
                 } else if self.has_pending_tx(&tx_hash)? {
-                    self.delete_pending_tx(&tx_hash)?;
+                    batch.delete(Self::CF_PENDING_TXS, tx_hash.as_slice());
+                    // It's removed from the storage, but its history is still recorded.
+                    committed_removed.push(tx_hash);
                 } else {
                     let errmsg = format!("tx {:#x} is committed but it's unknown", tx_hash);
                     return Err(Error::runtime(errmsg));
                 }
             }
         }
+        for tx_hash in committed.iter().chain(&committed_removed) {
+            let mut history = self.get_tx_history(tx_hash)?.unwrap_or_default();
+            history.push(TxEvent::new(block.number(), TxEventKind::Committed));
+            batch.put(Self::CF_TX_HISTORY, tx_hash.as_slice(), history.to_vec());
+        }
+        let transited = self.transit_txs(&mut batch, block, window, &updated_proposals)?;
+        batch.put(CF_DEFAULT, KEY_CHECKPOINT, checkpoint.to_vec());
+        batch.put(CF_DEFAULT, KEY_LEDGER, ledger.to_vec());
+        self.inject_fault("confirm_block")?;
+        self.db.write(batch)?;
+        self.invalidate(block.tx_hashes());
+        self.invalidate(transited.iter().map(|(tx_hash, _, _)| tx_hash));
+        self.tip_number.set(block.number());
+        // Update the caches only after the data are persisted.
+        self.apply_live_cells(live_cells);
+        let mut stats = self.stats.borrow_mut();
+        if let Some(outputs_count) = cellbase_outputs_count {
+            stats.commit_cellbase(outputs_count);
+        }
+        if let Some(tx_hash) = committed_cellbase {
+            self.cellbases.borrow_mut().insert(block.number(), tx_hash);
+        }
+        self.tx_hashes.borrow_mut().extend(committed);
+        for (old, new) in &changed {
+            stats.change_tx(old, new);
+        }
+        for (_, old, new) in &transited {
+            stats.change_tx(old, new);
+        }
+        Ok(())
+    }
+
+    // Follow the transitions of the transactions which are not in the block: the pending ones
+    // are proposed, the proposed ones are pending again if the proposals are expired, and the
+    // orphans are expired, by the timestamp of the block.
+    //
+    // Returns the transactions whose statuses are changed, with the old and the new statuses.
+    fn transit_txs(
+        &self,
+        batch: &mut WriteBatch,
+        block: &BlockView,
+        window: &ProposalWindow,
+        updated_proposals: &HashMap<packed::ProposalShortId, Proposals>,
+    ) -> Result<Vec<(packed::Byte32, TxStatus, TxStatus)>> {
+        let in_block = block.tx_hashes().iter().cloned().collect::<HashSet<_>>();
+        let next_number = block.number() + 1;
+        let mut transited = Vec::new();
+        for (key, _) in self.db.pairs(Self::CF_TXS)? {
+            let tx_hash = packed::Byte32::from_slice(&key).map_err(Error::storage)?;
+            if in_block.contains(&tx_hash) {
+                continue;
+            }
+            let old = if let Some(tx_status) = self.get_tx_status(&tx_hash)? {
+                tx_status
+            } else {
+                continue;
+            };
+            let mut new = old.clone();
+            let event = match old {
+                TxStatus::Pending(_) | TxStatus::Proposed(_) => {
+                    let id = packed::ProposalShortId::from_tx_hash(&tx_hash);
+                    let proposals = match updated_proposals.get(&id) {
+                        Some(proposals) => proposals.to_owned(),
+                        None => self.get_proposals(&tx_hash)?.unwrap_or_default(),
+                    };
+                    let proposed = proposals.stage(next_number, window) != TxStage::Pending;
+                    if !new.set_proposed(proposed) {
+                        continue;
+                    }
+                    if proposed {
+                        TxEventKind::Proposed
+                    } else {
+                        TxEventKind::Unproposed
+                    }
+                }
+                TxStatus::Orphan(since)
+                    if self.orphan_expiry && since + ORPHAN_EXPIRY_MILLIS <= block.timestamp() =>
+                {
+                    new = TxStatus::Expired;
+                    TxEventKind::Expired
+                }
+                _ => continue,
+            };
+            log::trace!("[Storage] tx {:#x} is {}", tx_hash, event);
+            Self::put_tx_status(batch, &tx_hash, &new)?;
+            let mut history = self.get_tx_history(&tx_hash)?.unwrap_or_default();
+            history.push(TxEvent::new(block.number(), event));
+            batch.put(Self::CF_TX_HISTORY, tx_hash.as_slice(), history.to_vec());
+            transited.push((tx_hash, old, new));
+        }
+        Ok(transited)
+    }
+
+    // Remove the committed transactions whose outputs are all spent by committed transactions
+    // or burned, their statuses could never be changed again, so only their histories are kept.
+    //
+    // Returns how many transactions are pruned.
+    pub(crate) fn prune(&self) -> Result<usize> {
+        let mut batch = WriteBatch::default();
+        let mut pruned = Vec::new();
+        for (tx_hash, tx_status) in self.tx_statuses()? {
+            if self.is_prunable(&tx_hash, &tx_status)? {
+                Self::delete_tx_status(&mut batch, &tx_hash);
+                pruned.push((tx_hash, tx_status));
+            }
+        }
+        if pruned.is_empty() {
+            return Ok(0);
+        }
+        let events = pruned
+            .iter()
+            .map(|(tx_hash, _)| (tx_hash.clone(), TxEventKind::Pruned))
+            .collect();
+        self.record_events(&mut batch, events)?;
+        self.inject_fault("prune")?;
+        self.db.write(batch)?;
+        self.invalidate(pruned.iter().map(|(tx_hash, _)| tx_hash));
+        let mut stats = self.stats.borrow_mut();
+        let mut tx_hashes = self.tx_hashes.borrow_mut();
+        for (tx_hash, tx_status) in &pruned {
+            tx_hashes.swap_remove(tx_hash);
+            stats.remove_tx(tx_status);
+        }
+        Ok(pruned.len())
+    }
+
+    // A dead cell could be revived only if its spender is pending, so the spenders of all dead
+    // cells should be known, and none of them is pending.
+    fn is_prunable(&self, tx_hash: &packed::Byte32, tx_status: &TxStatus) -> Result<bool> {
+        let cells = if let TxStatus::Committed(ref cells) = tx_status {
+            cells
+        } else {
+            return Ok(false);
+        };
+        if tx_status.live_cells_count() > 0 {
+            return Ok(false);
+        }
+        let history = if let Some(history) = self.get_tx_history(tx_hash)? {
+            history
+        } else {
+            return Ok(false);
+        };
+        for (index, status) in cells.statuses.iter().enumerate() {
+            if *status != CellStatus::Dead {
+                continue;
+            }
+            let spender = if let Some(spender) = history.spender(index as u32) {
+                spender
+            } else {
+                return Ok(false);
+            };
+            let spender_status = self.get_tx_status(&spender)?;
+            if spender_status
+                .map(|st| st.is_uncommitted())
+                .unwrap_or(false)
+            {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    // Invariant: all committed transactions are proposed in the proposal window, it's only
+    // checked for the transactions whose proposals are fully tracked.
+    fn check_proposal_window(&self, block: &BlockView, window: &ProposalWindow) -> Result<()> {
+        for tx_hash in block.tx_hashes().iter().skip(1) {
+            let stage = self.tx_stage(tx_hash, block.number(), window)?;
+            if let Some(stage) = stage.filter(|stage| *stage != TxStage::Proposed) {
+                let proposals = self.get_proposals(tx_hash)?.unwrap_or_default();
+                let errmsg = format!(
+                    "block {} ({:#x}) commits tx {:#x} which is {} (proposed in: {}), \
+                    but the proposal window is [{}, {}]",
+                    block.number(),
+                    block.hash(),
+                    tx_hash,
+                    stage,
+                    proposals,
+                    window.closest(),
+                    window.farthest()
+                );
+                return Err(Error::runtime(errmsg));
+            }
+        }
+        Ok(())
+    }
+
+    // Invariant: no out-point is spent twice in a block, and all cells spent by committed
+    // transactions are dead in the storage.
+    fn check_no_double_spend(&self, block: &BlockView) -> Result<()> {
+        let mut spent_by = HashMap::new();
+        for tx in block.transactions().into_iter().skip(1) {
+            let tx_hash = tx.hash();
+            for out_point in tx.input_pts_iter() {
+                if let Some(spender) = spent_by.insert(out_point.clone(), tx_hash.clone()) {
+                    let errmsg = format!(
+                        "block {} ({:#x}) double spends {} in tx {:#x} and tx {:#x}",
+                        block.number(),
+                        block.hash(),
+                        display_out_point(&out_point),
+                        spender,
+                        tx_hash
+                    );
+                    return Err(Error::runtime(errmsg));
+                }
+                let index: u32 = out_point.index().unpack();
+                let tx_status = self.get_tx_status(&out_point.tx_hash())?;
+                let cell_status = match tx_status.as_ref().and_then(TxStatus::outputs) {
+                    Some(inner) if (index as usize) < inner.count() => {
+                        *inner.status(index as usize)
+                    }
+                    // The input is not tracked by the storage.
+                    _ => continue,
+                };
+                if cell_status != CellStatus::Dead {
+                    let errmsg = format!(
+                        "block {} ({:#x}) commits tx {:#x} which spends {}, \
+                        but the cell is {} in the storage",
+                        block.number(),
+                        block.hash(),
+                        tx_hash,
+                        display_out_point(&out_point),
+                        cell_status
+                    );
+                    return Err(Error::runtime(errmsg));
+                }
+                // The cell is spent in another block, or by a rival in the tx-pool.
+                if let Some(spender) = self.get_spender(&out_point)? {
+                    if spender != tx_hash {
+                        let errmsg = format!(
+                            "block {} ({:#x}) commits tx {:#x} which spends {}, \
+                            but the cell is spent by tx {:#x} in the storage",
+                            block.number(),
+                            block.hash(),
+                            tx_hash,
+                            display_out_point(&out_point),
+                            spender
+                        );
+                        return Err(Error::runtime(errmsg));
+                    }
+                }
+            }
+        }
         Ok(())
     }
 }
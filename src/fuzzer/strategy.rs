@@ -1,16 +1,118 @@
-use std::{collections::HashMap, fmt};
+use std::{collections::HashMap, fmt, time};
 
+use ckb_chain_spec::build_type_id_script;
 use ckb_store::ChainStore as _;
-use ckb_types::{core, packed, prelude::*};
+use ckb_types::{bytes::Bytes, core, packed, prelude::*};
 
-use super::{MockedChain, Overlay, Storage, TxOverlay, TxOverlayChanges};
+use super::{FailReason, MockedChain, Overlay, Package, Storage, TxOverlay, TxOverlayChanges};
 use crate::{
-    error::Result,
-    types::{CellStatus, RandomGenerator, ScriptAnchor, TxOutputsStatus, TxStatus},
+    error::{Error, Result},
+    types::{
+        ArgsShape, Branch, CellStage, CellStatus, InputSelection, Keyring, RandomGenerator, RunEnv,
+        ScriptAnchor, Signer, Submit, TxOutputsStatus, TypeIdFault, WeightedStrategy,
+    },
+    utils,
 };
 
 const BYTE_SHANNONS: u64 = 100_000_000;
-const SMALLEST_SHANNONS: u64 = 138 * BYTE_SHANNONS;
+// The capacity of a cell which doesn't exist, it's only used to generate the outputs of the
+// transactions which fail anyway.
+const UNKNOWN_CELL_SHANNONS: u64 = 138 * BYTE_SHANNONS;
+// The constant fee when no fee rate distribution is configured.
+const FEE_SHANNONS: u64 = 10_000_000;
+// How many live cells are sampled uniformly, before one of them is picked by the weights.
+const WEIGHTED_CANDIDATES: usize = 8;
+// The max count of the inputs of a near-limit transaction.
+const NEAR_LIMIT_MAX_INPUTS: usize = 100;
+// The max distance between the size of a near-limit transaction and the size limit.
+const NEAR_LIMIT_MAX_OFFSET: u64 = 2;
+// How many live cells are sampled to find a cell of a special type script, such as a DAO cell.
+const TYPED_CELL_RETRIES: usize = 100;
+// The data of a deposited DAO cell is 8 zeros, and the data of a withdrawing DAO cell is the
+// number of the block which commits the deposit.
+pub(super) const DAO_DATA_SIZE: usize = 8;
+// The deposit is locked for whole lock periods, each lock period is 180 epochs.
+const DAO_LOCK_PERIOD_EPOCHS: u64 = 180;
+// The flags of a since which is an absolute epoch number with fraction.
+const SINCE_ABSOLUTE_EPOCH_FLAGS: u64 = 0x2000_0000_0000_0000;
+// The flags of a since which is a timestamp in seconds, absolute or relative.
+const SINCE_ABSOLUTE_TIMESTAMP_FLAGS: u64 = 0x4000_0000_0000_0000;
+const SINCE_RELATIVE_TIMESTAMP_FLAGS: u64 = 0xc000_0000_0000_0000;
+// The max distance between a timestamp-based since and the median time, in seconds.
+const TIME_LOCK_MAX_OFFSET_SECS: u64 = 120;
+// The max count of the outputs of a split transaction, or the inputs of a consolidation
+// transaction.
+const MAINTENANCE_MAX_CELLS: usize = 100;
+// How many committed live cells are sampled, the richest one of them is split.
+const SPLIT_CANDIDATES: usize = 8;
+
+// A generator of transactions, the generated transactions are added into the overlay in order.
+pub(crate) trait TxStrategy {
+    // Returns nothing if no transactions could be generated for now.
+    fn generate(
+        &self,
+        rg: &RandomGenerator,
+        chain: &MockedChain,
+        overlay: &Overlay,
+        deadline: &Deadline,
+    ) -> Result<Vec<TxOverlay>>;
+}
+
+// A random transaction from the live cells, the dead cells and the failed transactions.
+struct RandomTxStrategy;
+
+// A chain of unconfirmed transactions which is deeper than the max ancestors count.
+struct LongChainStrategy;
+
+// A parent which pays the min fee, and a child which pays a high fee for both of them.
+struct CpfpStrategy;
+
+// A transaction which spends the same cell as another transaction in the same batch.
+struct DoubleSpendStrategy;
+
+// A flood of tiny transactions which spend the outputs of a fan-out transaction.
+struct BurstStrategy;
+
+// A transaction whose size is around the size limit of the tx-pool.
+struct NearLimitStrategy;
+
+// A deposit, a prepare or a withdraw of the NervosDAO.
+struct DaoStrategy;
+
+// A creation, an update or a destruction of a cell which is guarded by the type ID.
+struct TypeIdStrategy;
+
+// A transaction whose input is locked by a timestamp-based since.
+struct TimeLockStrategy;
+
+// A transaction which splits a cell or consolidates the cells, when the count of the live cells
+// is out of the range.
+//
+// It's not registered, since it runs at the start of each batch when the range is configured.
+struct MaintenanceStrategy {
+    floor: usize,
+    ceiling: usize,
+}
+
+// All available strategies, new strategies should be registered here.
+const REGISTRY: &[(&str, &dyn TxStrategy)] = &[
+    ("random", &RandomTxStrategy),
+    ("long_chain", &LongChainStrategy),
+    ("cpfp", &CpfpStrategy),
+    ("double_spend", &DoubleSpendStrategy),
+    ("burst", &BurstStrategy),
+    ("near_limit", &NearLimitStrategy),
+    ("dao", &DaoStrategy),
+    ("type_id", &TypeIdStrategy),
+    ("time_lock", &TimeLockStrategy),
+];
+
+// The strategies which are used in a run.
+pub struct TxStrategies {
+    strategies: Vec<(&'static str, &'static dyn TxStrategy)>,
+    weights: Vec<u32>,
+    maintenance: Option<MaintenanceStrategy>,
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Status {
@@ -32,6 +134,16 @@ struct InputCell {
     capacity: core::Capacity,
 }
 
+// A committed live cell whose type script is the DAO script.
+struct DaoCell {
+    out_point: packed::OutPoint,
+    output: packed::CellOutput,
+    data: Bytes,
+    // The number and the hash of the block which commits the cell.
+    block_number: core::BlockNumber,
+    block_hash: packed::Byte32,
+}
+
 struct RawOutputCell {
     output: packed::CellOutput,
     data_size: usize,
@@ -60,6 +172,24 @@ impl Status {
     }
 }
 
+// The time limit of generating transactions for one step.
+pub(crate) struct Deadline {
+    inner: Option<time::Instant>,
+}
+
+impl Deadline {
+    fn new(max_duration: Option<time::Duration>) -> Self {
+        let inner = max_duration.map(|duration| time::Instant::now() + duration);
+        Self { inner }
+    }
+
+    fn is_expired(&self) -> bool {
+        self.inner
+            .map(|instant| time::Instant::now() >= instant)
+            .unwrap_or(false)
+    }
+}
+
 impl RawInputCell {
     fn new(tx_hash: packed::Byte32, index: usize, status: Status) -> Self {
         Self {
@@ -68,6 +198,15 @@ impl RawInputCell {
             status,
         }
     }
+
+    fn live(out_point: &packed::OutPoint, stage: CellStage) -> Self {
+        let index: u32 = out_point.index().unpack();
+        let status = match stage {
+            CellStage::Pending => Status::Pending,
+            CellStage::Committed => Status::Committed,
+        };
+        Self::new(out_point.tx_hash(), index as usize, status)
+    }
 }
 
 impl RawOutputCell {
@@ -80,15 +219,229 @@ impl RawOutputCell {
     }
 }
 
-pub(crate) fn build_transactions(
+impl Default for TxStrategies {
+    fn default() -> Self {
+        Self {
+            strategies: vec![REGISTRY[0]],
+            weights: vec![1],
+            maintenance: None,
+        }
+    }
+}
+
+impl TxStrategies {
+    pub(crate) fn from_config(config: &[WeightedStrategy]) -> Result<Self> {
+        let mut strategies = Vec::new();
+        let mut weights = Vec::new();
+        for WeightedStrategy { name, weight } in config {
+            strategies.push(find_strategy(name)?);
+            weights.push(*weight);
+        }
+        if weights.iter().all(|weight| *weight == 0) {
+            return Err(Error::config(
+                "at least one strategy should have a positive weight",
+            ));
+        }
+        Ok(Self {
+            strategies,
+            weights,
+            maintenance: None,
+        })
+    }
+
+    // The strategies from the config, or from the workload profile when no strategies are set.
+    // The maintenance is enabled by the range of the live cells.
+    pub(crate) fn from_run_env(run_env: &RunEnv) -> Result<Self> {
+        let mut strategies = if let Some(ref config) = run_env.strategies {
+            Self::from_config(config)?
+        } else if let Some(profile) = run_env.profile {
+            Self::from_config(&profile.strategies())?
+        } else {
+            Self::default()
+        };
+        strategies.maintenance =
+            run_env
+                .live_cells
+                .as_ref()
+                .map(|live_cells| MaintenanceStrategy {
+                    floor: live_cells.floor,
+                    ceiling: live_cells.ceiling,
+                });
+        Ok(strategies)
+    }
+
+    fn choose(&self, rg: &RandomGenerator) -> (&'static str, &'static dyn TxStrategy) {
+        self.strategies[rg.weighted_index(&self.weights)]
+    }
+}
+
+// The names of all available strategies.
+pub(crate) fn strategy_names() -> Vec<&'static str> {
+    REGISTRY.iter().map(|(name, _)| *name).collect()
+}
+
+pub(crate) fn find_strategy(name: &str) -> Result<(&'static str, &'static dyn TxStrategy)> {
+    REGISTRY
+        .iter()
+        .find(|(registered, _)| *registered == name)
+        .copied()
+        .ok_or_else(|| {
+            let available = strategy_names().join(", ");
+            let errmsg = format!("strategy {} is unknown (available: {})", name, available);
+            Error::config(errmsg)
+        })
+}
+
+impl TxStrategy for RandomTxStrategy {
+    fn generate(
+        &self,
+        rg: &RandomGenerator,
+        chain: &MockedChain,
+        overlay: &Overlay,
+        deadline: &Deadline,
+    ) -> Result<Vec<TxOverlay>> {
+        generate_transaction(rg, chain, overlay, deadline).map(|tx| tx.into_iter().collect())
+    }
+}
+
+impl TxStrategy for LongChainStrategy {
+    fn generate(
+        &self,
+        rg: &RandomGenerator,
+        chain: &MockedChain,
+        overlay: &Overlay,
+        _deadline: &Deadline,
+    ) -> Result<Vec<TxOverlay>> {
+        build_long_chain(rg, chain, overlay)
+    }
+}
+
+impl TxStrategy for CpfpStrategy {
+    fn generate(
+        &self,
+        rg: &RandomGenerator,
+        chain: &MockedChain,
+        overlay: &Overlay,
+        _deadline: &Deadline,
+    ) -> Result<Vec<TxOverlay>> {
+        build_cpfp_package(rg, chain, overlay)
+    }
+}
+
+impl TxStrategy for DoubleSpendStrategy {
+    fn generate(
+        &self,
+        rg: &RandomGenerator,
+        chain: &MockedChain,
+        overlay: &Overlay,
+        _deadline: &Deadline,
+    ) -> Result<Vec<TxOverlay>> {
+        build_double_spend(rg, chain, overlay)
+    }
+}
+
+impl TxStrategy for BurstStrategy {
+    fn generate(
+        &self,
+        rg: &RandomGenerator,
+        chain: &MockedChain,
+        overlay: &Overlay,
+        _deadline: &Deadline,
+    ) -> Result<Vec<TxOverlay>> {
+        build_burst(rg, chain, overlay)
+    }
+}
+
+impl TxStrategy for NearLimitStrategy {
+    fn generate(
+        &self,
+        rg: &RandomGenerator,
+        chain: &MockedChain,
+        overlay: &Overlay,
+        _deadline: &Deadline,
+    ) -> Result<Vec<TxOverlay>> {
+        build_near_limit(rg, chain, overlay)
+    }
+}
+
+impl TxStrategy for DaoStrategy {
+    fn generate(
+        &self,
+        rg: &RandomGenerator,
+        chain: &MockedChain,
+        overlay: &Overlay,
+        _deadline: &Deadline,
+    ) -> Result<Vec<TxOverlay>> {
+        build_dao(rg, chain, overlay)
+    }
+}
+
+impl TxStrategy for TypeIdStrategy {
+    fn generate(
+        &self,
+        rg: &RandomGenerator,
+        chain: &MockedChain,
+        overlay: &Overlay,
+        _deadline: &Deadline,
+    ) -> Result<Vec<TxOverlay>> {
+        build_type_id(rg, chain, overlay)
+    }
+}
+
+impl TxStrategy for TimeLockStrategy {
+    fn generate(
+        &self,
+        rg: &RandomGenerator,
+        chain: &MockedChain,
+        overlay: &Overlay,
+        _deadline: &Deadline,
+    ) -> Result<Vec<TxOverlay>> {
+        build_time_lock(rg, chain, overlay)
+    }
+}
+
+impl TxStrategy for MaintenanceStrategy {
+    fn generate(
+        &self,
+        rg: &RandomGenerator,
+        chain: &MockedChain,
+        overlay: &Overlay,
+        _deadline: &Deadline,
+    ) -> Result<Vec<TxOverlay>> {
+        build_maintenance(rg, chain, overlay, self.floor, self.ceiling)
+    }
+}
+
+pub fn build_transactions(
     rg: &RandomGenerator,
     chain: &MockedChain,
     storage: &Storage,
+    strategies: &TxStrategies,
+    max_generation: Option<time::Duration>,
 ) -> Result<Vec<TxOverlay>> {
+    let deadline = Deadline::new(max_generation);
     let mut overlay = Overlay::new(storage);
-    while rg.has_next_transaction() {
-        log::trace!("[BuildTx] try to generate one more transaction");
-        if let Some(tx) = generate_transaction(rg, chain, &overlay)? {
+    if rg.build_long_chain() {
+        for tx in LongChainStrategy.generate(rg, chain, &overlay, &deadline)? {
+            overlay.add_tx(tx);
+        }
+    }
+    if let Some(ref maintenance) = strategies.maintenance {
+        for tx in maintenance.generate(rg, chain, &overlay, &deadline)? {
+            overlay.add_tx(tx);
+        }
+    }
+    'generate: while overlay.txs.len() < rg.min_batch_size() || rg.has_next_transaction() {
+        if deadline.is_expired() {
+            break;
+        }
+        let (name, strategy) = strategies.choose(rg);
+        log::trace!("[BuildTx] try to generate more transactions by {}", name);
+        let txs = strategy.generate(rg, chain, &overlay, &deadline)?;
+        if txs.is_empty() {
+            break;
+        }
+        for tx in txs {
             let tx_view = tx.view();
             log::trace!(
                 "[BuildTx] the new transaction is {:#x} ({} -> {}, {:?})",
@@ -98,32 +451,90 @@ pub(crate) fn build_transactions(
                 tx.status(),
             );
             if overlay.has_tx(&tx_view.hash()) {
-                break;
+                break 'generate;
             }
             overlay.add_tx(tx);
-        } else {
-            break;
         }
     }
+    if deadline.is_expired() {
+        log::warn!(
+            "[BuildTx] reach the time limit of generation, only {} transactions are generated",
+            overlay.txs.len()
+        );
+        utils::metrics::global().inc_generation_timeouts();
+    }
     Ok(overlay.txs.into_values().collect())
 }
 
-pub(crate) fn generate_transaction(
+// Generate the transactions by the strategies in order, until the counts are reached or no more
+// transactions could be generated.
+// Also returns the hashes of the transactions which are generated by each submit action.
+pub(crate) fn build_scripted_transactions(
+    rg: &RandomGenerator,
+    chain: &MockedChain,
+    storage: &Storage,
+    submits: &[Submit],
+    max_generation: Option<time::Duration>,
+) -> Result<(Vec<TxOverlay>, Vec<Vec<packed::Byte32>>)> {
+    let deadline = Deadline::new(max_generation);
+    let mut overlay = Overlay::new(storage);
+    let mut submitted = Vec::with_capacity(submits.len());
+    for submit in submits {
+        let (name, strategy) = find_strategy(&submit.strategy)?;
+        let mut tx_hashes = Vec::new();
+        let mut count = 0;
+        'generate: while count < submit.count {
+            if deadline.is_expired() {
+                break;
+            }
+            let txs = strategy.generate(rg, chain, &overlay, &deadline)?;
+            if txs.is_empty() {
+                break;
+            }
+            for tx in txs {
+                let tx_hash = tx.view().hash();
+                if overlay.has_tx(&tx_hash) {
+                    break 'generate;
+                }
+                overlay.add_tx(tx);
+                tx_hashes.push(tx_hash);
+                count += 1;
+            }
+        }
+        if count < submit.count {
+            log::warn!(
+                "[Script] only {} of {} transactions are generated by {}",
+                count,
+                submit.count,
+                name
+            );
+        }
+        submitted.push(tx_hashes);
+    }
+    Ok((overlay.txs.into_values().collect(), submitted))
+}
+
+fn generate_transaction(
     rg: &RandomGenerator,
     chain: &MockedChain,
     overlay: &Overlay,
+    deadline: &Deadline,
 ) -> Result<Option<TxOverlay>> {
+    // Drop the branches which were exercised but didn't lead to a transaction.
+    let _ = rg.take_branches();
     // Waiting for enough cells.
-    let live_cells_count = overlay.live_cells_count();
-    if live_cells_count < 1_000 {
-        log::trace!(
-            "[BuildTx] >>> live cells (size: {}) is not enough",
-            live_cells_count
-        );
+    if !has_enough_live_cells(rg, overlay) {
+        return Ok(None);
+    }
+    // The reasons why the transaction should be rejected.
+    let mut reasons = Vec::new();
+    let inputs = generate_inputs(rg, chain, overlay, deadline, &mut reasons);
+    if deadline.is_expired() {
+        log::trace!("[BuildTx] >>> reach the time limit when generating inputs");
         return Ok(None);
     }
-    let inputs = generate_inputs(rg, overlay);
     let inputs_status = if inputs.is_empty() {
+        reasons.push(FailReason::EmptyInputs);
         Status::Failed
     } else {
         inputs
@@ -150,13 +561,52 @@ pub(crate) fn generate_transaction(
         }
     }
     let mocked_script = chain.mocked_script();
-    let (outputs, outputs_status) = generate_outputs(rg, &inputs, &mocked_script);
+    let keyring = chain.keyring();
+    let signers = keyring
+        .map(|keyring| group_signed_inputs(chain, overlay, keyring, &inputs))
+        .unwrap_or_default();
+    let fee_rate = rg.fee_rate();
+    // The fee by the fee rate is paid after the size is known.
+    let reserved_fee = if fee_rate.is_some() { 0 } else { FEE_SHANNONS };
+    let data1_enabled = chain.is_data1_enabled();
+    let (mut outputs, outputs_status) = generate_outputs(
+        rg,
+        &inputs,
+        &mocked_script,
+        chain.custom_scripts(),
+        keyring,
+        data1_enabled,
+        reserved_fee,
+        &mut reasons,
+    );
     log::trace!(
         "[BuildTx] >>> generate {} output cells (expected: {})",
         outputs.len(),
         outputs_status
     );
-    let tx_view = {
+    let version = if let Some(version) = rg.wrong_version() {
+        log::trace!("[BuildTx] >>> failed since: wrong version {}", version);
+        reasons.push(FailReason::WrongVersion);
+        version
+    } else {
+        core::TX_VERSION
+    };
+    let mut witnesses = generate_witnesses(rg, inputs.len());
+    // Reserve the space of the signatures, so the size doesn't change after signing.
+    if let Some(keyring) = keyring {
+        for (signer, group) in &signers {
+            if witnesses.len() <= group[0] {
+                witnesses.resize(group[0] + 1, packed::Bytes::default());
+            }
+            witnesses[group[0]] = keyring.placeholder(*signer);
+        }
+    }
+    let mut cell_deps = match keyring {
+        Some(keyring) if !signers.is_empty() => keyring.cell_deps(),
+        _ => Vec::new(),
+    };
+    cell_deps.extend(custom_cell_deps(chain, overlay, &inputs, &outputs));
+    let build_tx_view = |outputs: &[RawOutputCell]| {
         let inputs = inputs.iter().map(|item| {
             let op = packed::OutPoint::new(item.tx_hash.to_owned(), item.index);
             packed::CellInput::new(op, 0)
@@ -170,20 +620,99 @@ pub(crate) fn generate_transaction(
             },
         );
         core::TransactionView::new_advanced_builder()
+            .version(version.pack())
             .cell_dep(mocked_script.cell_dep())
+            .cell_deps(cell_deps.clone())
             .inputs(inputs)
             .outputs(outputs)
             .outputs_data(outputs_data)
+            .witnesses(witnesses.clone())
             .build()
     };
+    let mut tx_view = build_tx_view(&outputs);
+    // The overflowed outputs should not be fixed by the fee.
+    if let Some(fee_rate) = fee_rate {
+        if !outputs.is_empty() && !reasons.contains(&FailReason::CapacityOverflow) {
+            // The capacities don't change the size.
+            let size = tx_view.data().serialized_size_in_block();
+            let fee = fee_rate.fee(size).as_u64();
+            let paid = pay_fee(&mut outputs, fee);
+            log::trace!(
+                "[BuildTx] >>> pay {} shannons for {} bytes (fee rate: {}, expected fee: {})",
+                paid,
+                size,
+                fee_rate,
+                fee
+            );
+            tx_view = build_tx_view(&outputs);
+        }
+    }
+    let signature_status = match keyring {
+        Some(keyring) if !signers.is_empty() => {
+            let has_multisig = signers
+                .iter()
+                .any(|(signer, _)| matches!(signer, Signer::Multisig(_)));
+            let fault = rg.signature_fault(has_multisig);
+            let mut witnesses = tx_view.witnesses().into_iter().collect::<Vec<_>>();
+            for (signer, group) in &signers {
+                keyring.sign(
+                    *signer,
+                    &tx_view.hash(),
+                    &mut witnesses,
+                    group,
+                    inputs.len(),
+                    fault,
+                )?;
+            }
+            tx_view = tx_view
+                .as_advanced_builder()
+                .set_witnesses(witnesses)
+                .build();
+            if let Some(fault) = fault {
+                log::trace!("[BuildTx] >>> failed since: signatures are {:?}", fault);
+                reasons.push(FailReason::ScriptFailure);
+                Status::Failed
+            } else {
+                Status::Pending
+            }
+        }
+        _ => Status::Pending,
+    };
+    let size_status = {
+        let size = tx_view.data().serialized_size_in_block() as u64;
+        let limit = chain.max_tx_size();
+        if size > limit {
+            log::trace!(
+                "[BuildTx] >>> failed since: size {} exceeds the limit {}",
+                size,
+                limit
+            );
+            reasons.push(FailReason::TooLarge);
+            Status::Failed
+        } else {
+            Status::Pending
+        }
+    };
     let changes = {
-        let final_status = inputs_status.merge(outputs_status);
+        let version_status = if version == core::TX_VERSION {
+            Status::Pending
+        } else {
+            Status::Failed
+        };
+        let final_status = inputs_status
+            .merge(outputs_status)
+            .merge(version_status)
+            .merge(signature_status)
+            .merge(size_status);
         let new = {
             let statuses = outputs
                 .iter()
                 .map(|raw| raw.cell_status)
                 .collect::<Vec<_>>();
-            TxOutputsStatus { statuses }
+            TxOutputsStatus {
+                statuses,
+                cellbase: None,
+            }
         };
         match final_status {
             Status::Pending => {
@@ -198,7 +727,11 @@ pub(crate) fn generate_transaction(
                         .or_insert(tx_status)
                         .spent(input.index as usize);
                 }
-                TxOverlayChanges::Pending { new, updates }
+                TxOverlayChanges::Pending {
+                    new,
+                    updates,
+                    package: None,
+                }
             }
             Status::Committed => {
                 let mut updates = HashMap::new();
@@ -224,158 +757,1887 @@ pub(crate) fn generate_transaction(
                         }
                     }
                 }
-                TxOverlayChanges::Failed { updates }
+                TxOverlayChanges::Failed { updates, reasons }
             }
         }
     };
-    Ok(Some(TxOverlay::new(tx_view, changes)))
+    let tx = TxOverlay::new(tx_view, changes).with_branches(rg.take_branches());
+    Ok(Some(tx))
 }
 
-fn generate_inputs(rg: &RandomGenerator, overlay: &Overlay) -> Vec<RawInputCell> {
-    let mut inputs = Vec::new();
-    if rg.no_inputs() {
-        return inputs;
+// Build a chain of unconfirmed transactions, each transaction spends the only output of the
+// previous one.
+//
+// The tx-pool counts the transaction itself as one of its ancestors, so the first transaction
+// which is deeper than the max ancestors count should be rejected, so do its descendants.
+fn build_long_chain(
+    rg: &RandomGenerator,
+    chain: &MockedChain,
+    overlay: &Overlay,
+) -> Result<Vec<TxOverlay>> {
+    let mut txs: Vec<TxOverlay> = Vec::new();
+    if !has_enough_live_cells(rg, overlay) {
+        return Ok(txs);
     }
-    'found_inputs: loop {
-        if !inputs.is_empty() && !rg.has_next_input() {
+    let root = if let Some(root) = random_committed_live_cell(rg, chain, overlay)? {
+        root
+    } else {
+        log::trace!("[BuildTx] >>> no committed live cells for a long chain");
+        return Ok(txs);
+    };
+    let root = complete_inputs(chain, overlay, vec![root]).remove(0);
+    let max_ancestors_count = chain.tx_pool_config().max_ancestors_count;
+    let depth = max_ancestors_count + rg.long_chain_extra_depth();
+    log::trace!(
+        "[BuildTx] try to build a long chain (depth: {}, max ancestors: {}) from {:#x},{}",
+        depth,
+        max_ancestors_count,
+        root.tx_hash,
+        root.index
+    );
+    let mocked_script = chain.mocked_script();
+    let _ = rg.take_branches();
+    let mut capacity = root.capacity.as_u64();
+    let mut parent = (root.tx_hash, root.index);
+    for link in 1..=depth {
+        let (parent_tx_hash, parent_index) = parent;
+        let lock = generate_script(rg, &mocked_script, true);
+        let build_tx = |capacity: u64| {
+            let op = packed::OutPoint::new(parent_tx_hash.clone(), parent_index);
+            let output = packed::CellOutput::new_builder()
+                .lock(lock.clone())
+                .capacity(core::Capacity::shannons(capacity).pack())
+                .build();
+            core::TransactionView::new_advanced_builder()
+                .cell_dep(mocked_script.cell_dep())
+                .input(packed::CellInput::new(op, 0))
+                .output(output)
+                .output_data(Default::default())
+                .build()
+        };
+        // The size doesn't depend on the capacity.
+        let fee = random_fee(rg, build_tx(capacity).data().serialized_size_in_block());
+        if capacity < rg.smallest_shannons() + fee {
+            log::trace!("[BuildTx] >>> >>> stop the long chain since: no enough capacity");
             break;
         }
-        let cell_opt;
-        'loop_cells: loop {
-            let random_tx = overlay.random_tx(rg).unwrap();
-            if random_tx.is_none() {
-                break 'found_inputs;
+        capacity -= fee;
+        let tx_view = build_tx(capacity);
+        // The parent is the previous link, or the root for the first link.
+        let parent_tx_status = if let Some(parent_tx) = txs.last() {
+            parent_tx.status()
+        } else {
+            overlay.get_tx_status(&parent_tx_hash)?
+        };
+        let mut updates = HashMap::new();
+        let changes = if link <= max_ancestors_count {
+            let new = TxOutputsStatus {
+                statuses: vec![CellStatus::Live],
+                cellbase: None,
+            };
+            updates
+                .entry(parent_tx_hash)
+                .or_insert(parent_tx_status)
+                .spent(parent_index as usize);
+            TxOverlayChanges::Pending {
+                new,
+                updates,
+                package: None,
             }
-            let (tx_hash, tx_status) = random_tx.unwrap();
-            match tx_status {
-                TxStatus::Pending(ref cells) | TxStatus::Committed(ref cells) => {
-                    let status = match tx_status {
-                        TxStatus::Pending(_) => Status::Pending,
-                        TxStatus::Committed(_) => Status::Committed,
-                        _ => unreachable!(),
-                    };
-                    let cells_count = cells.count();
-                    let cell_index_start = rg.usize_less_than(cells_count);
-                    for cell_index in (cell_index_start..cells_count)
-                        .into_iter()
-                        .chain((0..cell_index_start).into_iter())
-                    {
-                        match cells.status(cell_index) {
-                            CellStatus::Live => {
-                                cell_opt =
-                                    Some(RawInputCell::new(tx_hash.to_owned(), cell_index, status));
-                                break 'loop_cells;
-                            }
-                            CellStatus::Burn => {
-                                if rg.could_has_burned_input() {
-                                    cell_opt = Some(RawInputCell::new(
-                                        tx_hash.to_owned(),
-                                        cell_index,
-                                        Status::Failed,
-                                    ));
-                                    break 'loop_cells;
-                                }
-                            }
-                            CellStatus::Dead => {
-                                if rg.could_has_dead_input() {
-                                    cell_opt = Some(RawInputCell::new(
-                                        tx_hash.to_owned(),
-                                        cell_index,
-                                        Status::Failed,
-                                    ));
-                                    break 'loop_cells;
-                                }
-                            }
-                        }
-                    }
-                }
-                TxStatus::Failed => {
-                    if rg.could_be_from_failed_tx() {
-                        cell_opt = Some(RawInputCell::new(tx_hash.to_owned(), 0, Status::Failed));
-                        break 'loop_cells;
-                    }
-                }
+        } else {
+            log::trace!(
+                "[BuildTx] >>> >>> link {} ({:#x}) exceeds the max ancestors count",
+                link,
+                tx_view.hash()
+            );
+            // Only the first link which is too deep is rejected by the ancestors count, its
+            // descendants spend the cells which are unknown.
+            let reason = if link == max_ancestors_count + 1 {
+                FailReason::TooManyAncestors
+            } else {
+                FailReason::UnknownCell
+            };
+            if parent_tx_status.is_invalid() {
+                updates.insert(parent_tx_hash, parent_tx_status);
             }
-        }
-        if let Some(mut cell) = cell_opt {
-            if !inputs
-                .iter()
-                .any(|item| item.tx_hash == cell.tx_hash && item.index == cell.index)
-            {
-                inputs.push(cell);
-            } else if rg.allow_duplicated() {
-                cell.status = Status::Failed;
-                inputs.push(cell);
+            TxOverlayChanges::Failed {
+                updates,
+                reasons: vec![reason],
             }
-        }
+        };
+        parent = (tx_view.hash(), 0);
+        let mut branches = rg.take_branches();
+        branches.push(Branch::LongChain);
+        txs.push(TxOverlay::new(tx_view, changes).with_branches(branches));
     }
-    inputs
+    Ok(txs)
 }
 
-fn complete_inputs(
+// Build a parent which pays the min fee rate, and a child which spends the only output of the
+// parent and pays a high fee, so the parent should be packaged with the child.
+fn build_cpfp_package(
+    rg: &RandomGenerator,
     chain: &MockedChain,
     overlay: &Overlay,
-    raw_cells: Vec<RawInputCell>,
-) -> Vec<InputCell> {
-    raw_cells
-        .into_iter()
-        .map(|raw| {
-            let index = raw.index as u32;
-            let outputs = if let Some(tx_view) = overlay.get_tx(&raw.tx_hash) {
-                tx_view
-            } else {
-                chain
-                    .store()
-                    .get_transaction(&raw.tx_hash)
-                    .map(|(tx, _)| tx)
-                    .unwrap()
-            }
-            .outputs();
-            let capacity = if let Some(output) = outputs.get(raw.index) {
-                output.capacity().unpack()
-            } else {
-                core::Capacity::shannons(SMALLEST_SHANNONS)
-            };
-            InputCell {
-                tx_hash: raw.tx_hash,
-                index,
-                status: raw.status,
-                capacity,
-            }
-        })
-        .collect()
+) -> Result<Vec<TxOverlay>> {
+    if !has_enough_live_cells(rg, overlay) {
+        return Ok(Vec::new());
+    }
+    let root = if let Some(root) = random_committed_live_cell(rg, chain, overlay)? {
+        root
+    } else {
+        log::trace!("[BuildTx] >>> no committed live cells for a cpfp package");
+        return Ok(Vec::new());
+    };
+    let root = complete_inputs(chain, overlay, vec![root]).remove(0);
+    let capacity = root.capacity.as_u64();
+    if capacity < rg.smallest_shannons() + FEE_SHANNONS * 2 {
+        log::trace!("[BuildTx] >>> no enough capacity for a cpfp package");
+        return Ok(Vec::new());
+    }
+    let mocked_script = chain.mocked_script();
+    let _ = rg.take_branches();
+    let build_tx = |op: packed::OutPoint, lock: packed::Script, capacity: u64| {
+        let output = packed::CellOutput::new_builder()
+            .lock(lock)
+            .capacity(core::Capacity::shannons(capacity).pack())
+            .build();
+        core::TransactionView::new_advanced_builder()
+            .cell_dep(mocked_script.cell_dep())
+            .input(packed::CellInput::new(op, 0))
+            .output(output)
+            .output_data(Default::default())
+            .build()
+    };
+    let min_fee_rate = chain.tx_pool_config().min_fee_rate;
+    let parent_view = {
+        let op = packed::OutPoint::new(root.tx_hash.clone(), root.index);
+        let lock = generate_script(rg, &mocked_script, true);
+        // The size doesn't depend on the capacity.
+        let size = build_tx(op.clone(), lock.clone(), capacity)
+            .data()
+            .serialized_size_in_block();
+        let fee = min_fee_rate.fee(size).as_u64();
+        build_tx(op, lock, capacity - fee)
+    };
+    let parent_capacity: core::Capacity = parent_view
+        .output(0)
+        .expect("the parent has one output")
+        .capacity()
+        .unpack();
+    let child_view = {
+        let op = packed::OutPoint::new(parent_view.hash(), 0);
+        let lock = generate_script(rg, &mocked_script, true);
+        build_tx(op, lock, parent_capacity.as_u64() - FEE_SHANNONS)
+    };
+    let fee_rate = {
+        let fee = capacity
+            - child_view
+                .outputs_capacity()
+                .map_err(Error::runtime)?
+                .as_u64();
+        let size = parent_view.data().serialized_size_in_block()
+            + child_view.data().serialized_size_in_block();
+        core::FeeRate::calculate(core::Capacity::shannons(fee), size)
+    };
+    log::trace!(
+        "[BuildTx] build a cpfp package: parent {:#x}, child {:#x}, fee rate: {}",
+        parent_view.hash(),
+        child_view.hash(),
+        fee_rate
+    );
+    let mut branches = rg.take_branches();
+    branches.push(Branch::CpfpPackage);
+    let parent = {
+        let mut updates = HashMap::new();
+        updates
+            .entry(root.tx_hash.clone())
+            .or_insert(overlay.get_tx_status(&root.tx_hash)?)
+            .spent(root.index as usize);
+        let changes = TxOverlayChanges::Pending {
+            new: TxOutputsStatus {
+                statuses: vec![CellStatus::Live],
+                cellbase: None,
+            },
+            updates,
+            package: None,
+        };
+        TxOverlay::new(parent_view.clone(), changes).with_branches(branches.clone())
+    };
+    let child = {
+        let mut parent_status = parent.status();
+        parent_status.spent(0);
+        let mut updates = HashMap::new();
+        updates.insert(parent_view.hash(), parent_status);
+        let package = Package {
+            parent: parent_view.hash(),
+            fee_rate,
+        };
+        let changes = TxOverlayChanges::Pending {
+            new: TxOutputsStatus {
+                statuses: vec![CellStatus::Live],
+                cellbase: None,
+            },
+            updates,
+            package: Some(package),
+        };
+        TxOverlay::new(child_view, changes).with_branches(branches)
+    };
+    Ok(vec![parent, child])
 }
 
-fn generate_outputs(
+// Build a transaction which spends the same cell as its rival, the rival is a transaction in
+// the overlay, or a new transaction which is built together.
+//
+// The rival is submitted first, so it should be accepted and the double spend should be
+// rejected as a conflict.
+fn build_double_spend(
     rg: &RandomGenerator,
-    inputs: &[InputCell],
-    mocked_script: &ScriptAnchor,
-) -> (Vec<RawOutputCell>, Status) {
-    let mut expected_status = Status::Failed;
-    let mut outputs = Vec::new();
-    if inputs.is_empty() || rg.no_outputs() {
-        log::trace!("[BuildTx] >>> >>> failed since: inputs or outputs is empty");
-        return (outputs, expected_status);
-    }
-    // TODO Random fee base on the fee rate.
-    let fee = core::Capacity::shannons(10_000_000);
-    let total_capacity = inputs
-        .iter()
-        .map(|item| item.capacity)
-        .try_fold(core::Capacity::zero(), core::Capacity::safe_add)
-        .unwrap();
-    if total_capacity < fee {
-        log::trace!("[BuildTx] >>> >>> failed since: no enough fee");
-        return (outputs, expected_status);
+    chain: &MockedChain,
+    overlay: &Overlay,
+) -> Result<Vec<TxOverlay>> {
+    if !has_enough_live_cells(rg, overlay) {
+        return Ok(Vec::new());
     }
-    let remain_capacity = total_capacity.safe_sub(fee).unwrap();
-    if remain_capacity.as_u64() < SMALLEST_SHANNONS {
-        log::trace!("[BuildTx] >>> >>> failed since: no enough capacity");
-        return (outputs, expected_status);
+    let raced = if rg.race_in_batch() {
+        overlay.random_spent_cell(rg)
+    } else {
+        None
+    };
+    let (input, rival) = if let Some((out_point, spender)) = raced {
+        let index: u32 = out_point.index().unpack();
+        let input = RawInputCell::new(out_point.tx_hash(), index as usize, Status::Pending);
+        (input, Some(spender))
+    } else if let Some(root) = random_committed_live_cell(rg, chain, overlay)? {
+        (root, None)
+    } else {
+        log::trace!("[BuildTx] >>> no cells for a double spend");
+        return Ok(Vec::new());
+    };
+    let input = complete_inputs(chain, overlay, vec![input]).remove(0);
+    let capacity = input.capacity.as_u64();
+    if capacity < rg.smallest_shannons() + FEE_SHANNONS {
+        log::trace!("[BuildTx] >>> no enough capacity for a double spend");
+        return Ok(Vec::new());
     }
-    let mut remain_shannons = {
-        if rg.allow_capacity_overflow() {
-            log::trace!("[BuildTx] >>> >>> failed since: capacity overflow");
+    let mocked_script = chain.mocked_script();
+    let _ = rg.take_branches();
+    let op = packed::OutPoint::new(input.tx_hash.clone(), input.index);
+    let build_tx = |capacity: u64| {
+        let output = packed::CellOutput::new_builder()
+            .lock(generate_script(rg, &mocked_script, true))
+            .capacity(core::Capacity::shannons(capacity).pack())
+            .build();
+        core::TransactionView::new_advanced_builder()
+            .cell_dep(mocked_script.cell_dep())
+            .input(packed::CellInput::new(op.clone(), 0))
+            .output(output)
+            .output_data(Default::default())
+            .build()
+    };
+    let mut txs = Vec::new();
+    let rival = if let Some(rival) = rival {
+        rival
+    } else {
+        let fee = random_fee(rg, build_tx(capacity).data().serialized_size_in_block());
+        let rival_view = build_tx(capacity.saturating_sub(fee).max(rg.smallest_shannons()));
+        let mut updates = HashMap::new();
+        updates
+            .entry(input.tx_hash.clone())
+            .or_insert(overlay.get_tx_status(&input.tx_hash)?)
+            .spent(input.index as usize);
+        let changes = TxOverlayChanges::Pending {
+            new: TxOutputsStatus {
+                statuses: vec![CellStatus::Live],
+                cellbase: None,
+            },
+            updates,
+            package: None,
+        };
+        let rival = rival_view.hash();
+        txs.push(TxOverlay::new(rival_view, changes).with_branches(vec![Branch::DoubleSpend]));
+        rival
+    };
+    // Pay the min fee, so it couldn't replace the rival by a higher fee.
+    let tx_view = {
+        let size = build_tx(capacity).data().serialized_size_in_block();
+        let fee = chain.tx_pool_config().min_fee_rate.fee(size).as_u64();
+        build_tx(capacity - fee)
+    };
+    log::trace!(
+        "[BuildTx] build a double spend {:#x} of {:#x},{} against {:#x}",
+        tx_view.hash(),
+        input.tx_hash,
+        input.index,
+        rival
+    );
+    let changes = TxOverlayChanges::DoubleSpent { rival };
+    txs.push(TxOverlay::new(tx_view, changes).with_branches(vec![Branch::DoubleSpend]));
+    Ok(txs)
+}
+
+// Split a committed live cell into many cells by a fan-out transaction, then spend each of them
+// by a tiny transaction which has only one output with the smallest capacity.
+//
+// The count of the tiny transactions is limited by the capacity of the root cell.
+fn build_burst(
+    rg: &RandomGenerator,
+    chain: &MockedChain,
+    overlay: &Overlay,
+) -> Result<Vec<TxOverlay>> {
+    if !has_enough_live_cells(rg, overlay) {
+        return Ok(Vec::new());
+    }
+    let root = if let Some(root) = random_committed_live_cell(rg, chain, overlay)? {
+        root
+    } else {
+        log::trace!("[BuildTx] >>> no committed live cells for a burst");
+        return Ok(Vec::new());
+    };
+    let root = complete_inputs(chain, overlay, vec![root]).remove(0);
+    let identical_fee_rates = rg.burst_identical_fee_rates();
+    let fees = (0..rg.burst_size())
+        .map(|_| {
+            if identical_fee_rates {
+                FEE_SHANNONS
+            } else {
+                rg.u64_between(FEE_SHANNONS, FEE_SHANNONS * 10)
+            }
+        })
+        .collect::<Vec<_>>();
+    // Keep the fees which the capacity of the root cell could afford.
+    let mut total = FEE_SHANNONS;
+    let count = fees
+        .iter()
+        .take_while(|fee| {
+            total += rg.smallest_shannons() + **fee;
+            total <= root.capacity.as_u64()
+        })
+        .count();
+    if count < 2 {
+        log::trace!("[BuildTx] >>> no enough capacity for a burst");
+        return Ok(Vec::new());
+    }
+    let mocked_script = chain.mocked_script();
+    let _ = rg.take_branches();
+    let fan_out_view = {
+        let mut builder = core::TransactionView::new_advanced_builder()
+            .cell_dep(mocked_script.cell_dep())
+            .input(packed::CellInput::new(
+                packed::OutPoint::new(root.tx_hash.clone(), root.index),
+                0,
+            ));
+        let mut rest = root.capacity.as_u64() - FEE_SHANNONS;
+        for (index, fee) in fees[..count].iter().enumerate() {
+            // The change is in the last output.
+            let capacity = if index + 1 == count {
+                rest
+            } else {
+                rg.smallest_shannons() + fee
+            };
+            rest -= capacity;
+            let output = packed::CellOutput::new_builder()
+                .lock(generate_script(rg, &mocked_script, true))
+                .capacity(core::Capacity::shannons(capacity).pack())
+                .build();
+            builder = builder.output(output).output_data(Default::default());
+        }
+        builder.build()
+    };
+    log::trace!(
+        "[BuildTx] build a burst of {} transactions from {:#x} (identical fee rates: {})",
+        count,
+        fan_out_view.hash(),
+        identical_fee_rates
+    );
+    let mut branches = rg.take_branches();
+    branches.push(Branch::Burst);
+    let fan_out = {
+        let mut updates = HashMap::new();
+        updates
+            .entry(root.tx_hash.clone())
+            .or_insert(overlay.get_tx_status(&root.tx_hash)?)
+            .spent(root.index as usize);
+        let changes = TxOverlayChanges::Pending {
+            new: TxOutputsStatus {
+                statuses: vec![CellStatus::Live; count],
+                cellbase: None,
+            },
+            updates,
+            package: None,
+        };
+        TxOverlay::new(fan_out_view.clone(), changes).with_branches(branches)
+    };
+    let mut fan_out_status = fan_out.status();
+    let mut txs = Vec::with_capacity(count + 1);
+    txs.push(fan_out);
+    for (index, output) in fan_out_view.outputs().into_iter().enumerate() {
+        let capacity: core::Capacity = output.capacity().unpack();
+        let fee = if index + 1 == count {
+            fees[index]
+        } else {
+            capacity.as_u64() - rg.smallest_shannons()
+        };
+        let tx_view = {
+            let op = packed::OutPoint::new(fan_out_view.hash(), index as u32);
+            let output = packed::CellOutput::new_builder()
+                .lock(generate_script(rg, &mocked_script, true))
+                .capacity(core::Capacity::shannons(capacity.as_u64() - fee).pack())
+                .build();
+            core::TransactionView::new_advanced_builder()
+                .cell_dep(mocked_script.cell_dep())
+                .input(packed::CellInput::new(op, 0))
+                .output(output)
+                .output_data(Default::default())
+                .build()
+        };
+        // Each tiny transaction spends one more output of the fan-out transaction.
+        fan_out_status.spent(index);
+        let mut updates = HashMap::new();
+        updates.insert(fan_out_view.hash(), fan_out_status.clone());
+        let changes = TxOverlayChanges::Pending {
+            new: TxOutputsStatus {
+                statuses: vec![CellStatus::Live],
+                cellbase: None,
+            },
+            updates,
+            package: None,
+        };
+        let mut branches = rg.take_branches();
+        branches.push(Branch::Burst);
+        txs.push(TxOverlay::new(tx_view, changes).with_branches(branches));
+    }
+    Ok(txs)
+}
+
+// Build a transaction whose size is just below, at or just above the size limit of the tx-pool,
+// it should be rejected only if its size is greater than the limit.
+//
+// The transaction spends many committed live cells, its only output takes a data as large as
+// the capacity could afford, and a witness pads the rest bytes.
+fn build_near_limit(
+    rg: &RandomGenerator,
+    chain: &MockedChain,
+    overlay: &Overlay,
+) -> Result<Vec<TxOverlay>> {
+    if !has_enough_live_cells(rg, overlay) {
+        return Ok(Vec::new());
+    }
+    let mut raw_inputs: Vec<RawInputCell> = Vec::new();
+    for _ in 0..=rg.usize_less_than(NEAR_LIMIT_MAX_INPUTS) {
+        if let Some(cell) = random_committed_live_cell(rg, chain, overlay)? {
+            let duplicated = raw_inputs
+                .iter()
+                .any(|raw| raw.tx_hash == cell.tx_hash && raw.index == cell.index);
+            if !duplicated {
+                raw_inputs.push(cell);
+            }
+        }
+    }
+    if raw_inputs.is_empty() {
+        log::trace!("[BuildTx] >>> no committed live cells for a near-limit transaction");
+        return Ok(Vec::new());
+    }
+    let inputs = complete_inputs(chain, overlay, raw_inputs);
+    let total = inputs.iter().fold(0u64, |total, item| {
+        total.saturating_add(item.capacity.as_u64())
+    });
+    let limit = chain.max_tx_size();
+    // From the limit minus the max offset, to the limit plus the max offset.
+    let target = (limit + rg.u64_between(0, NEAR_LIMIT_MAX_OFFSET * 2 + 1))
+        .saturating_sub(NEAR_LIMIT_MAX_OFFSET);
+    let mocked_script = chain.mocked_script();
+    let _ = rg.take_branches();
+    let lock = generate_script(rg, &mocked_script, true);
+    let build_tx = |capacity: u64, data_size: u64, witness_size: u64| {
+        let inputs = inputs.iter().map(|item| {
+            let op = packed::OutPoint::new(item.tx_hash.to_owned(), item.index);
+            packed::CellInput::new(op, 0)
+        });
+        let output = packed::CellOutput::new_builder()
+            .lock(lock.clone())
+            .capacity(core::Capacity::shannons(capacity).pack())
+            .build();
+        core::TransactionView::new_advanced_builder()
+            .cell_dep(mocked_script.cell_dep())
+            .inputs(inputs)
+            .output(output)
+            .output_data(vec![0u8; data_size as usize].pack())
+            .witness(vec![0u8; witness_size as usize].pack())
+            .build()
+    };
+    // Each byte of the data or the witness takes exactly one byte in the transaction.
+    let base_view = build_tx(total, 0, 0);
+    let base_size = base_view.data().serialized_size_in_block() as u64;
+    if base_size > target {
+        log::trace!("[BuildTx] >>> too many inputs for a near-limit transaction");
+        return Ok(Vec::new());
+    }
+    let padding = target - base_size;
+    let fee = random_fee(rg, target as usize);
+    let occupied = base_view
+        .output(0)
+        .expect("the transaction has one output")
+        .occupied_capacity(core::Capacity::zero())
+        .map_err(Error::runtime)?
+        .as_u64();
+    if total < occupied + fee {
+        log::trace!("[BuildTx] >>> no enough capacity for a near-limit transaction");
+        return Ok(Vec::new());
+    }
+    let data_size = ((total - occupied - fee) / BYTE_SHANNONS).min(padding);
+    let tx_view = build_tx(total - fee, data_size, padding - data_size);
+    let size = tx_view.data().serialized_size_in_block() as u64;
+    log::trace!(
+        "[BuildTx] build a near-limit transaction {:#x}: inputs: {}, data: {}, witness: {}, \
+        size: {} (limit: {})",
+        tx_view.hash(),
+        inputs.len(),
+        data_size,
+        padding - data_size,
+        size,
+        limit
+    );
+    let mut updates = HashMap::new();
+    let changes = if size <= limit {
+        for input in &inputs {
+            let tx_status = overlay.get_tx_status(&input.tx_hash)?;
+            updates
+                .entry(input.tx_hash.to_owned())
+                .or_insert(tx_status)
+                .spent(input.index as usize);
+        }
+        TxOverlayChanges::Pending {
+            new: TxOutputsStatus {
+                statuses: vec![CellStatus::Live],
+                cellbase: None,
+            },
+            updates,
+            package: None,
+        }
+    } else {
+        TxOverlayChanges::Failed {
+            updates,
+            reasons: vec![FailReason::TooLarge],
+        }
+    };
+    let mut branches = rg.take_branches();
+    branches.push(Branch::NearLimit);
+    Ok(vec![
+        TxOverlay::new(tx_view, changes).with_branches(branches)
+    ])
+}
+
+// Build a transaction of the NervosDAO, it's a deposit, a prepare (the phase-1 withdraw) or a
+// withdraw (the phase-2 withdraw), and it should be accepted.
+//
+// The phase is chosen randomly, and a deposit is built instead if no DAO cells could be spent
+// by the chosen phase.
+fn build_dao(
+    rg: &RandomGenerator,
+    chain: &MockedChain,
+    overlay: &Overlay,
+) -> Result<Vec<TxOverlay>> {
+    if !has_enough_live_cells(rg, overlay) {
+        return Ok(Vec::new());
+    }
+    let _ = rg.take_branches();
+    let tx_opt = match rg.usize_less_than(3) {
+        0 => build_dao_prepare(rg, chain, overlay)?,
+        1 => build_dao_withdraw(rg, chain, overlay)?,
+        _ => None,
+    };
+    let (tx_view, branch) = if let Some(built) = tx_opt {
+        built
+    } else if let Some(built) = build_dao_deposit(rg, chain, overlay)? {
+        built
+    } else {
+        return Ok(Vec::new());
+    };
+    log::trace!(
+        "[BuildTx] build a DAO transaction {:#x} ({})",
+        tx_view.hash(),
+        branch.name()
+    );
+    let changes = accepted_changes(overlay, &tx_view)?;
+    let mut branches = rg.take_branches();
+    branches.push(branch);
+    Ok(vec![
+        TxOverlay::new(tx_view, changes).with_branches(branches)
+    ])
+}
+
+// Deposit a part of a committed live cell, the rest is the change.
+fn build_dao_deposit(
+    rg: &RandomGenerator,
+    chain: &MockedChain,
+    overlay: &Overlay,
+) -> Result<Option<(core::TransactionView, Branch)>> {
+    let root = if let Some(root) = random_committed_live_cell(rg, chain, overlay)? {
+        root
+    } else {
+        log::trace!("[BuildTx] >>> no committed live cells for a DAO deposit");
+        return Ok(None);
+    };
+    let root = complete_inputs(chain, overlay, vec![root]).remove(0);
+    let capacity = root.capacity.as_u64();
+    let mocked_script = chain.mocked_script();
+    let dao_script = chain.dao_script();
+    let deposit = packed::CellOutput::new_builder()
+        .lock(generate_script(rg, &mocked_script, true))
+        .type_(Some(dao_type_script(dao_script)).pack())
+        .build();
+    let occupied = deposit
+        .occupied_capacity(core::Capacity::shannons(
+            DAO_DATA_SIZE as u64 * BYTE_SHANNONS,
+        ))
+        .map_err(Error::runtime)?
+        .as_u64();
+    // The deposit should be withdrawn as a cell which is not smaller than the smallest cell.
+    let smallest = occupied.max(rg.smallest_shannons()) + FEE_SHANNONS;
+    if capacity < smallest + rg.smallest_shannons() + FEE_SHANNONS {
+        log::trace!("[BuildTx] >>> no enough capacity for a DAO deposit");
+        return Ok(None);
+    }
+    let deposited = rg.u64_between(
+        smallest,
+        capacity - rg.smallest_shannons() - FEE_SHANNONS + 1,
+    );
+    let deposit = deposit
+        .as_builder()
+        .capacity(core::Capacity::shannons(deposited).pack())
+        .build();
+    let lock = generate_script(rg, &mocked_script, true);
+    let build_tx = |change: u64| {
+        let output = packed::CellOutput::new_builder()
+            .lock(lock.clone())
+            .capacity(core::Capacity::shannons(change).pack())
+            .build();
+        let op = packed::OutPoint::new(root.tx_hash.clone(), root.index);
+        core::TransactionView::new_advanced_builder()
+            .cell_dep(mocked_script.cell_dep())
+            .cell_dep(dao_script.cell_dep())
+            .input(packed::CellInput::new(op, 0))
+            .output(deposit.clone())
+            .output_data(vec![0u8; DAO_DATA_SIZE].pack())
+            .output(output)
+            .output_data(Default::default())
+            .build()
+    };
+    let change = capacity - deposited;
+    let size = build_tx(change).data().serialized_size_in_block();
+    let fee = random_fee(rg, size);
+    if change < rg.smallest_shannons() + fee {
+        log::trace!("[BuildTx] >>> no enough capacity for the fee of a DAO deposit");
+        return Ok(None);
+    }
+    Ok(Some((build_tx(change - fee), Branch::DaoDeposit)))
+}
+
+// Prepare to withdraw a deposited DAO cell, the withdrawing cell keeps the capacity and the
+// lock of the deposited cell, so the fee is paid by another input.
+fn build_dao_prepare(
+    rg: &RandomGenerator,
+    chain: &MockedChain,
+    overlay: &Overlay,
+) -> Result<Option<(core::TransactionView, Branch)>> {
+    let deposit = if let Some(cell) = random_dao_cell(rg, chain, overlay, true) {
+        cell
+    } else {
+        log::trace!("[BuildTx] >>> no deposited DAO cells to prepare");
+        return Ok(None);
+    };
+    let payer = if let Some(payer) = random_committed_live_cell(rg, chain, overlay)? {
+        payer
+    } else {
+        log::trace!("[BuildTx] >>> no committed live cells to pay for a DAO prepare");
+        return Ok(None);
+    };
+    let payer = complete_inputs(chain, overlay, vec![payer]).remove(0);
+    let capacity = payer.capacity.as_u64();
+    let mocked_script = chain.mocked_script();
+    let dao_script = chain.dao_script();
+    let withdrawing_data = deposit.block_number.to_le_bytes().to_vec().pack();
+    let lock = generate_script(rg, &mocked_script, true);
+    let build_tx = |change: u64| {
+        let output = packed::CellOutput::new_builder()
+            .lock(lock.clone())
+            .capacity(core::Capacity::shannons(change).pack())
+            .build();
+        let op = packed::OutPoint::new(payer.tx_hash.clone(), payer.index);
+        // The withdrawing cell should have the same index as the deposited cell.
+        core::TransactionView::new_advanced_builder()
+            .cell_dep(mocked_script.cell_dep())
+            .cell_dep(dao_script.cell_dep())
+            .header_dep(deposit.block_hash.clone())
+            .input(packed::CellInput::new(deposit.out_point.clone(), 0))
+            .input(packed::CellInput::new(op, 0))
+            .output(deposit.output.clone())
+            .output_data(withdrawing_data.clone())
+            .output(output)
+            .output_data(Default::default())
+            .build()
+    };
+    let size = build_tx(capacity).data().serialized_size_in_block();
+    let fee = random_fee(rg, size);
+    if capacity < rg.smallest_shannons() + fee {
+        log::trace!("[BuildTx] >>> no enough capacity for the fee of a DAO prepare");
+        return Ok(None);
+    }
+    Ok(Some((build_tx(capacity - fee), Branch::DaoPrepare)))
+}
+
+// Withdraw a withdrawing DAO cell with the interest, after the lock periods are passed.
+fn build_dao_withdraw(
+    rg: &RandomGenerator,
+    chain: &MockedChain,
+    overlay: &Overlay,
+) -> Result<Option<(core::TransactionView, Branch)>> {
+    let withdrawing = if let Some(cell) = random_dao_cell(rg, chain, overlay, false) {
+        cell
+    } else {
+        log::trace!("[BuildTx] >>> no withdrawing DAO cells to withdraw");
+        return Ok(None);
+    };
+    let deposit_number = {
+        let mut bytes = [0u8; DAO_DATA_SIZE];
+        bytes.copy_from_slice(&withdrawing.data);
+        u64::from_le_bytes(bytes)
+    };
+    let store = chain.store();
+    let deposit_header = store
+        .get_block_hash(deposit_number)
+        .and_then(|block_hash| store.get_block_header(&block_hash))
+        .ok_or_else(|| {
+            let errmsg = format!(
+                "the block {} of the DAO deposit is not found",
+                deposit_number
+            );
+            Error::runtime(errmsg)
+        })?;
+    let withdraw_header = store
+        .get_block_header(&withdrawing.block_hash)
+        .ok_or_else(|| {
+            let errmsg = format!(
+                "the block {:#x} of the DAO prepare is not found",
+                withdrawing.block_hash
+            );
+            Error::runtime(errmsg)
+        })?;
+    let since_epoch = dao_minimal_since(&deposit_header.epoch(), &withdraw_header.epoch());
+    if chain.chain_tip_header().epoch().to_rational() < since_epoch.to_rational() {
+        log::trace!(
+            "[BuildTx] >>> the withdrawing DAO cell is locked until epoch {}",
+            since_epoch
+        );
+        return Ok(None);
+    }
+    let since = SINCE_ABSOLUTE_EPOCH_FLAGS | since_epoch.full_value();
+    let max_capacity =
+        dao_maximum_withdraw(&withdrawing.output, &deposit_header, &withdraw_header)?;
+    let mocked_script = chain.mocked_script();
+    let dao_script = chain.dao_script();
+    // The index of the header of the deposit block in the header deps.
+    let witness = packed::WitnessArgs::new_builder()
+        .input_type(Some(Bytes::from(0u64.to_le_bytes().to_vec())).pack())
+        .build();
+    let lock = generate_script(rg, &mocked_script, true);
+    let build_tx = |capacity: u64| {
+        let output = packed::CellOutput::new_builder()
+            .lock(lock.clone())
+            .capacity(core::Capacity::shannons(capacity).pack())
+            .build();
+        core::TransactionView::new_advanced_builder()
+            .cell_dep(mocked_script.cell_dep())
+            .cell_dep(dao_script.cell_dep())
+            .header_dep(deposit_header.hash())
+            .header_dep(withdraw_header.hash())
+            .input(packed::CellInput::new(withdrawing.out_point.clone(), since))
+            .output(output)
+            .output_data(Default::default())
+            .witness(witness.as_bytes().pack())
+            .build()
+    };
+    let base_view = build_tx(max_capacity);
+    let size = base_view.data().serialized_size_in_block();
+    let fee = random_fee(rg, size);
+    let occupied = base_view
+        .output(0)
+        .expect("the transaction has one output")
+        .occupied_capacity(core::Capacity::zero())
+        .map_err(Error::runtime)?
+        .as_u64();
+    if max_capacity < occupied + fee {
+        log::trace!("[BuildTx] >>> no enough capacity for the fee of a DAO withdraw");
+        return Ok(None);
+    }
+    Ok(Some((build_tx(max_capacity - fee), Branch::DaoWithdraw)))
+}
+
+// Find a committed live DAO cell, which is deposited, or is withdrawing.
+fn random_dao_cell(
+    rg: &RandomGenerator,
+    chain: &MockedChain,
+    overlay: &Overlay,
+    deposited: bool,
+) -> Option<DaoCell> {
+    let dao_type_hash = chain.dao_script().type_hash();
+    for _ in 0..TYPED_CELL_RETRIES {
+        let (out_point, stage) = overlay.random_live_cell(rg)?;
+        if stage != CellStage::Committed {
+            continue;
+        }
+        let (output, data) = if let Some(cell) = cell_with_data(chain, overlay, &out_point) {
+            cell
+        } else {
+            continue;
+        };
+        if !has_type_script(&output, &dao_type_hash) || data.len() != DAO_DATA_SIZE {
+            continue;
+        }
+        if data.iter().all(|byte| *byte == 0) != deposited {
+            continue;
+        }
+        if let Some(info) = chain.store().get_transaction_info(&out_point.tx_hash()) {
+            return Some(DaoCell {
+                out_point,
+                output,
+                data,
+                block_number: info.block_number,
+                block_hash: info.block_hash,
+            });
+        }
+    }
+    None
+}
+
+// The deposit is locked until the end of the lock periods which cover the epochs from the deposit
+// to the prepare.
+fn dao_minimal_since(
+    deposit: &core::EpochNumberWithFraction,
+    prepare: &core::EpochNumberWithFraction,
+) -> core::EpochNumberWithFraction {
+    let mut deposited_epochs = prepare.number().saturating_sub(deposit.number());
+    if prepare.index() * deposit.length() > deposit.index() * prepare.length() {
+        deposited_epochs += 1;
+    }
+    let periods = ((deposited_epochs + DAO_LOCK_PERIOD_EPOCHS - 1) / DAO_LOCK_PERIOD_EPOCHS).max(1);
+    core::EpochNumberWithFraction::new(
+        deposit.number() + periods * DAO_LOCK_PERIOD_EPOCHS,
+        deposit.index(),
+        deposit.length(),
+    )
+}
+
+// Only the capacity which is not occupied earns the interest, by the accumulated rates from the
+// deposit block to the prepare block.
+pub(super) fn dao_maximum_withdraw(
+    output: &packed::CellOutput,
+    deposit_header: &core::HeaderView,
+    prepare_header: &core::HeaderView,
+) -> Result<u64> {
+    let occupied = output
+        .occupied_capacity(core::Capacity::shannons(
+            DAO_DATA_SIZE as u64 * BYTE_SHANNONS,
+        ))
+        .map_err(Error::runtime)?
+        .as_u64();
+    let capacity: core::Capacity = output.capacity().unpack();
+    let counted = capacity.as_u64().saturating_sub(occupied);
+    let deposit_ar = dao_accumulated_rate(deposit_header);
+    let prepare_ar = dao_accumulated_rate(prepare_header);
+    let withdraw_counted = u128::from(counted) * u128::from(prepare_ar) / u128::from(deposit_ar);
+    Ok(withdraw_counted as u64 + occupied)
+}
+
+// The DAO field of a header is the total issuance, the accumulated rate, the secondary issuance
+// and the occupied capacity, each of them is an u64 in little endian.
+fn dao_accumulated_rate(header: &core::HeaderView) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&header.dao().as_slice()[8..16]);
+    u64::from_le_bytes(bytes)
+}
+
+fn dao_type_script(dao_script: &ScriptAnchor) -> packed::Script {
+    packed::Script::new_builder()
+        .code_hash(dao_script.type_hash())
+        .hash_type(core::ScriptHashType::Type.into())
+        .build()
+}
+
+// Whether the type script of the cell refers the code by the type hash type.
+pub(super) fn has_type_script(output: &packed::CellOutput, code_hash: &packed::Byte32) -> bool {
+    output
+        .type_()
+        .to_opt()
+        .map(|script| {
+            let hash_type: u8 = script.hash_type().into();
+            hash_type == core::ScriptHashType::Type as u8 && &script.code_hash() == code_hash
+        })
+        .unwrap_or(false)
+}
+
+// Build a transaction of a cell which is guarded by the type ID, it creates, updates or destroys
+// the cell.
+//
+// The creations and the updates break the type IDs sometimes, then they should be rejected by
+// the type ID script.
+fn build_type_id(
+    rg: &RandomGenerator,
+    chain: &MockedChain,
+    overlay: &Overlay,
+) -> Result<Vec<TxOverlay>> {
+    if !has_enough_live_cells(rg, overlay) {
+        return Ok(Vec::new());
+    }
+    let _ = rg.take_branches();
+    let tx_opt = match rg.usize_less_than(3) {
+        0 => build_type_id_update(rg, chain, overlay)?,
+        1 => build_type_id_destroy(rg, chain, overlay)?,
+        _ => None,
+    };
+    let (tx_view, branch, fault) = if let Some(built) = tx_opt {
+        built
+    } else if let Some(built) = build_type_id_create(rg, chain, overlay)? {
+        built
+    } else {
+        return Ok(Vec::new());
+    };
+    log::trace!(
+        "[BuildTx] build a type ID transaction {:#x} ({}, fault: {:?})",
+        tx_view.hash(),
+        branch.name(),
+        fault
+    );
+    let changes = if fault.is_some() {
+        TxOverlayChanges::Failed {
+            updates: HashMap::new(),
+            reasons: vec![FailReason::ScriptFailure],
+        }
+    } else {
+        accepted_changes(overlay, &tx_view)?
+    };
+    let mut branches = rg.take_branches();
+    branches.push(branch);
+    Ok(vec![
+        TxOverlay::new(tx_view, changes).with_branches(branches)
+    ])
+}
+
+// Create a cell from a committed live cell, the type ID is calculated from the only input.
+fn build_type_id_create(
+    rg: &RandomGenerator,
+    chain: &MockedChain,
+    overlay: &Overlay,
+) -> Result<Option<(core::TransactionView, Branch, Option<TypeIdFault>)>> {
+    let root = if let Some(root) = random_committed_live_cell(rg, chain, overlay)? {
+        root
+    } else {
+        log::trace!("[BuildTx] >>> no committed live cells for a type ID creation");
+        return Ok(None);
+    };
+    let root = complete_inputs(chain, overlay, vec![root]).remove(0);
+    let capacity = root.capacity.as_u64();
+    // Large enough to be split into two cells, for the duplicate updates.
+    let cell_capacity = rg.u64_between(rg.smallest_shannons() * 3, rg.smallest_shannons() * 10);
+    if capacity < cell_capacity * 2 + rg.smallest_shannons() + FEE_SHANNONS {
+        log::trace!("[BuildTx] >>> no enough capacity for a type ID creation");
+        return Ok(None);
+    }
+    let input = {
+        let op = packed::OutPoint::new(root.tx_hash.clone(), root.index);
+        packed::CellInput::new(op, 0)
+    };
+    let fault = rg.type_id_fault();
+    let type_script = build_type_id_script(&input, 0);
+    let type_script = if fault == Some(TypeIdFault::WrongId) {
+        with_random_type_id(rg, type_script)
+    } else {
+        type_script
+    };
+    let copies = if fault == Some(TypeIdFault::Duplicate) {
+        2
+    } else {
+        1
+    };
+    let mocked_script = chain.mocked_script();
+    let cell = packed::CellOutput::new_builder()
+        .lock(generate_script(rg, &mocked_script, true))
+        .type_(Some(type_script).pack())
+        .capacity(core::Capacity::shannons(cell_capacity).pack())
+        .build();
+    let lock = generate_script(rg, &mocked_script, true);
+    let build_tx = |change: u64| {
+        let output = packed::CellOutput::new_builder()
+            .lock(lock.clone())
+            .capacity(core::Capacity::shannons(change).pack())
+            .build();
+        // The created cells are the first outputs, the type ID is calculated by the index 0.
+        let mut builder = core::TransactionView::new_advanced_builder()
+            .cell_dep(mocked_script.cell_dep())
+            .input(input.clone());
+        for _ in 0..copies {
+            builder = builder.output(cell.clone()).output_data(Default::default());
+        }
+        builder
+            .output(output)
+            .output_data(Default::default())
+            .build()
+    };
+    let change = capacity - cell_capacity * copies;
+    let size = build_tx(change).data().serialized_size_in_block();
+    let fee = random_fee(rg, size);
+    if change < rg.smallest_shannons() + fee {
+        log::trace!("[BuildTx] >>> no enough capacity for the fee of a type ID creation");
+        return Ok(None);
+    }
+    Ok(Some((build_tx(change - fee), Branch::TypeIdCreate, fault)))
+}
+
+// Update a cell which is guarded by the type ID, the new cell takes the same type ID, and the fee
+// is paid by the cell itself.
+fn build_type_id_update(
+    rg: &RandomGenerator,
+    chain: &MockedChain,
+    overlay: &Overlay,
+) -> Result<Option<(core::TransactionView, Branch, Option<TypeIdFault>)>> {
+    let (out_point, output) = if let Some(cell) = random_type_id_cell(rg, chain, overlay) {
+        cell
+    } else {
+        log::trace!("[BuildTx] >>> no type ID cells to update");
+        return Ok(None);
+    };
+    let capacity: core::Capacity = output.capacity().unpack();
+    let fault = rg.type_id_fault();
+    let type_script = output
+        .type_()
+        .to_opt()
+        .expect("the type ID cell has a type script");
+    let type_script = if fault == Some(TypeIdFault::WrongId) {
+        with_random_type_id(rg, type_script)
+    } else {
+        type_script
+    };
+    let copies = if fault == Some(TypeIdFault::Duplicate) {
+        2
+    } else {
+        1
+    };
+    let lock = generate_script(rg, &chain.mocked_script(), true);
+    let build_tx = |fee: u64| {
+        let cell = output
+            .clone()
+            .as_builder()
+            .lock(lock.clone())
+            .type_(Some(type_script.clone()).pack())
+            .capacity(core::Capacity::shannons((capacity.as_u64() - fee) / copies).pack())
+            .build();
+        let mut builder = core::TransactionView::new_advanced_builder()
+            .cell_dep(chain.mocked_script().cell_dep())
+            .input(packed::CellInput::new(out_point.clone(), 0));
+        for _ in 0..copies {
+            builder = builder.output(cell.clone()).output_data(Default::default());
+        }
+        builder.build()
+    };
+    let size = build_tx(0).data().serialized_size_in_block();
+    let fee = random_fee(rg, size);
+    if capacity.as_u64() < rg.smallest_shannons() * copies + fee {
+        log::trace!("[BuildTx] >>> no enough capacity for the fee of a type ID update");
+        return Ok(None);
+    }
+    Ok(Some((build_tx(fee), Branch::TypeIdUpdate, fault)))
+}
+
+// Destroy a cell which is guarded by the type ID, the capacity goes to a cell without type script.
+fn build_type_id_destroy(
+    rg: &RandomGenerator,
+    chain: &MockedChain,
+    overlay: &Overlay,
+) -> Result<Option<(core::TransactionView, Branch, Option<TypeIdFault>)>> {
+    let (out_point, output) = if let Some(cell) = random_type_id_cell(rg, chain, overlay) {
+        cell
+    } else {
+        log::trace!("[BuildTx] >>> no type ID cells to destroy");
+        return Ok(None);
+    };
+    let capacity: core::Capacity = output.capacity().unpack();
+    let mocked_script = chain.mocked_script();
+    let lock = generate_script(rg, &mocked_script, true);
+    let build_tx = |capacity: u64| {
+        let output = packed::CellOutput::new_builder()
+            .lock(lock.clone())
+            .capacity(core::Capacity::shannons(capacity).pack())
+            .build();
+        core::TransactionView::new_advanced_builder()
+            .cell_dep(mocked_script.cell_dep())
+            .input(packed::CellInput::new(out_point.clone(), 0))
+            .output(output)
+            .output_data(Default::default())
+            .build()
+    };
+    let size = build_tx(capacity.as_u64())
+        .data()
+        .serialized_size_in_block();
+    let fee = random_fee(rg, size);
+    if capacity.as_u64() < rg.smallest_shannons() + fee {
+        log::trace!("[BuildTx] >>> no enough capacity for the fee of a type ID destruction");
+        return Ok(None);
+    }
+    Ok(Some((
+        build_tx(capacity.as_u64() - fee),
+        Branch::TypeIdDestroy,
+        None,
+    )))
+}
+
+// Find a live cell which is guarded by the type ID.
+fn random_type_id_cell(
+    rg: &RandomGenerator,
+    chain: &MockedChain,
+    overlay: &Overlay,
+) -> Option<(packed::OutPoint, packed::CellOutput)> {
+    let code_hash = type_id_code_hash();
+    for _ in 0..TYPED_CELL_RETRIES {
+        let (out_point, _) = overlay.random_live_cell(rg)?;
+        if let Some((output, _)) = cell_with_data(chain, overlay, &out_point) {
+            if has_type_script(&output, &code_hash) {
+                return Some((out_point, output));
+            }
+        }
+    }
+    None
+}
+
+// The type ID script is a builtin script, which is referred by a special code hash.
+fn type_id_code_hash() -> packed::Byte32 {
+    build_type_id_script(&packed::CellInput::default(), 0).code_hash()
+}
+
+fn with_random_type_id(rg: &RandomGenerator, script: packed::Script) -> packed::Script {
+    let mut type_id = [0u8; 32];
+    rg.fill_bytes(&mut type_id);
+    script.as_builder().args(type_id.to_vec().pack()).build()
+}
+
+// Spend a committed live cell by a timestamp-based since, which is absolute, or relative to the
+// median time when the cell is committed.
+//
+// The since is beyond the median time of the tip sometimes, then the transaction should be
+// rejected as immature. The accepted ones are still checked when they are committed, since the
+// median time could go backwards after the blocks with anomalous timestamps.
+fn build_time_lock(
+    rg: &RandomGenerator,
+    chain: &MockedChain,
+    overlay: &Overlay,
+) -> Result<Vec<TxOverlay>> {
+    if !has_enough_live_cells(rg, overlay) {
+        return Ok(Vec::new());
+    }
+    let _ = rg.take_branches();
+    let root = if let Some(root) = random_committed_live_cell(rg, chain, overlay)? {
+        root
+    } else {
+        log::trace!("[BuildTx] >>> no committed live cells for a time lock");
+        return Ok(Vec::new());
+    };
+    let root = complete_inputs(chain, overlay, vec![root]).remove(0);
+    let capacity = root.capacity.as_u64();
+    let out_point = packed::OutPoint::new(root.tx_hash.clone(), root.index);
+    let tip_median_time = chain.tip_median_time();
+    let (flags, base, branch) = if rg.usize_less_than(2) == 0 {
+        (SINCE_ABSOLUTE_TIMESTAMP_FLAGS, 0, Branch::AbsoluteTimeLock)
+    } else if let Some(base) = chain.committed_median_time(&root.tx_hash) {
+        (
+            SINCE_RELATIVE_TIMESTAMP_FLAGS,
+            base,
+            Branch::RelativeTimeLock,
+        )
+    } else {
+        log::trace!("[BuildTx] >>> the cell for a relative time lock is not committed");
+        return Ok(Vec::new());
+    };
+    // The since is satisfied if it's not greater than the passed seconds.
+    let passed_secs = tip_median_time.saturating_sub(base) / 1000;
+    let immature = rg.usize_less_than(4) == 0;
+    let offset = rg.u64_between(0, TIME_LOCK_MAX_OFFSET_SECS);
+    let value = if immature {
+        passed_secs + 1 + offset
+    } else {
+        passed_secs.saturating_sub(offset)
+    };
+    let since = flags | value;
+    let mocked_script = chain.mocked_script();
+    let lock = generate_script(rg, &mocked_script, true);
+    let build_tx = |capacity: u64| {
+        let output = packed::CellOutput::new_builder()
+            .lock(lock.clone())
+            .capacity(core::Capacity::shannons(capacity).pack())
+            .build();
+        core::TransactionView::new_advanced_builder()
+            .cell_dep(mocked_script.cell_dep())
+            .input(packed::CellInput::new(out_point.clone(), since))
+            .output(output)
+            .output_data(Default::default())
+            .build()
+    };
+    let size = build_tx(capacity).data().serialized_size_in_block();
+    let fee = random_fee(rg, size);
+    if capacity < rg.smallest_shannons() + fee {
+        log::trace!("[BuildTx] >>> no enough capacity for the fee of a time lock");
+        return Ok(Vec::new());
+    }
+    let tx_view = build_tx(capacity - fee);
+    log::trace!(
+        "[BuildTx] build a time lock transaction {:#x} ({}, since: {:#x}, immature: {})",
+        tx_view.hash(),
+        branch.name(),
+        since,
+        immature
+    );
+    let changes = if immature {
+        TxOverlayChanges::Failed {
+            updates: HashMap::new(),
+            reasons: vec![FailReason::ImmatureSince],
+        }
+    } else {
+        accepted_changes(overlay, &tx_view)?
+    };
+    let mut branches = rg.take_branches();
+    branches.push(branch);
+    Ok(vec![
+        TxOverlay::new(tx_view, changes).with_branches(branches)
+    ])
+}
+
+// The changes of a transaction which should be accepted, all its inputs are spent and all its
+// outputs are live.
+// Split a cell when there are fewer live cells than the floor, or consolidate the cells when
+// there are more than the ceiling.
+//
+// It doesn't wait for enough live cells, since it's how the live cells are refilled.
+fn build_maintenance(
+    rg: &RandomGenerator,
+    chain: &MockedChain,
+    overlay: &Overlay,
+    floor: usize,
+    ceiling: usize,
+) -> Result<Vec<TxOverlay>> {
+    let live_cells_count = overlay.live_cells_count();
+    let tx_opt = if live_cells_count < floor {
+        build_split(rg, chain, overlay, floor - live_cells_count)?
+    } else if live_cells_count > ceiling {
+        build_consolidation(rg, chain, overlay, live_cells_count - ceiling)?
+    } else {
+        None
+    };
+    Ok(tx_opt.into_iter().collect())
+}
+
+// Split the richest one of a few committed live cells into the cells of the smallest capacity,
+// the change is in the last output.
+fn build_split(
+    rg: &RandomGenerator,
+    chain: &MockedChain,
+    overlay: &Overlay,
+    deficit: usize,
+) -> Result<Option<TxOverlay>> {
+    let mut candidates = Vec::with_capacity(SPLIT_CANDIDATES);
+    for _ in 0..SPLIT_CANDIDATES {
+        if let Some(cell) = random_committed_live_cell(rg, chain, overlay)? {
+            candidates.push(cell);
+        }
+    }
+    let root = if let Some(root) = complete_inputs(chain, overlay, candidates)
+        .into_iter()
+        .max_by_key(|item| item.capacity.as_u64())
+    {
+        root
+    } else {
+        log::trace!("[BuildTx] >>> no committed live cells to split");
+        return Ok(None);
+    };
+    let total = root.capacity.as_u64();
+    // One more output, since the split cell is spent.
+    let affordable = (total.saturating_sub(FEE_SHANNONS) / rg.smallest_shannons()) as usize;
+    let count = (deficit + 1).min(MAINTENANCE_MAX_CELLS).min(affordable);
+    if count < 2 {
+        log::trace!("[BuildTx] >>> no enough capacity to split");
+        return Ok(None);
+    }
+    let mocked_script = chain.mocked_script();
+    let _ = rg.take_branches();
+    let lock = generate_script(rg, &mocked_script, true);
+    let build_tx = |fee: u64| {
+        let mut builder = core::TransactionView::new_advanced_builder()
+            .cell_dep(mocked_script.cell_dep())
+            .input(packed::CellInput::new(
+                packed::OutPoint::new(root.tx_hash.clone(), root.index),
+                0,
+            ));
+        let mut rest = total - fee;
+        for index in 0..count {
+            let capacity = if index + 1 == count {
+                rest
+            } else {
+                rg.smallest_shannons()
+            };
+            rest -= capacity;
+            let output = packed::CellOutput::new_builder()
+                .lock(lock.clone())
+                .capacity(core::Capacity::shannons(capacity).pack())
+                .build();
+            builder = builder.output(output).output_data(Default::default());
+        }
+        builder.build()
+    };
+    // The size doesn't depend on the capacities.
+    let size = build_tx(FEE_SHANNONS).data().serialized_size_in_block();
+    let fee = random_fee(rg, size);
+    if total < rg.smallest_shannons() * count as u64 + fee || size as u64 > chain.max_tx_size() {
+        log::trace!("[BuildTx] >>> no enough capacity or too large to split");
+        return Ok(None);
+    }
+    let tx_view = build_tx(fee);
+    log::trace!(
+        "[BuildTx] build a split transaction {:#x} of {} outputs (deficit: {})",
+        tx_view.hash(),
+        count,
+        deficit
+    );
+    let changes = accepted_changes(overlay, &tx_view)?;
+    let mut branches = rg.take_branches();
+    branches.push(Branch::Split);
+    Ok(Some(
+        TxOverlay::new(tx_view, changes).with_branches(branches),
+    ))
+}
+
+// Consolidate the committed live cells into one cell.
+fn build_consolidation(
+    rg: &RandomGenerator,
+    chain: &MockedChain,
+    overlay: &Overlay,
+    excess: usize,
+) -> Result<Option<TxOverlay>> {
+    // One more input, since a new cell is created.
+    let target = (excess + 1).min(MAINTENANCE_MAX_CELLS);
+    let mut raw_inputs: Vec<RawInputCell> = Vec::with_capacity(target);
+    for _ in 0..target * 2 {
+        if raw_inputs.len() >= target {
+            break;
+        }
+        if let Some(cell) = random_committed_live_cell(rg, chain, overlay)? {
+            let duplicated = raw_inputs
+                .iter()
+                .any(|raw| raw.tx_hash == cell.tx_hash && raw.index == cell.index);
+            if !duplicated {
+                raw_inputs.push(cell);
+            }
+        }
+    }
+    if raw_inputs.len() < 2 {
+        log::trace!("[BuildTx] >>> no enough committed live cells to consolidate");
+        return Ok(None);
+    }
+    let inputs = complete_inputs(chain, overlay, raw_inputs);
+    let total = inputs.iter().fold(0u64, |total, item| {
+        total.saturating_add(item.capacity.as_u64())
+    });
+    let mocked_script = chain.mocked_script();
+    let _ = rg.take_branches();
+    let lock = generate_script(rg, &mocked_script, true);
+    let build_tx = |capacity: u64| {
+        let inputs = inputs.iter().map(|item| {
+            let op = packed::OutPoint::new(item.tx_hash.to_owned(), item.index);
+            packed::CellInput::new(op, 0)
+        });
+        let output = packed::CellOutput::new_builder()
+            .lock(lock.clone())
+            .capacity(core::Capacity::shannons(capacity).pack())
+            .build();
+        core::TransactionView::new_advanced_builder()
+            .cell_dep(mocked_script.cell_dep())
+            .inputs(inputs)
+            .output(output)
+            .output_data(Default::default())
+            .build()
+    };
+    // The size doesn't depend on the capacity.
+    let size = build_tx(total).data().serialized_size_in_block();
+    let fee = random_fee(rg, size);
+    if total < rg.smallest_shannons() + fee || size as u64 > chain.max_tx_size() {
+        log::trace!("[BuildTx] >>> no enough capacity or too large to consolidate");
+        return Ok(None);
+    }
+    let tx_view = build_tx(total - fee);
+    log::trace!(
+        "[BuildTx] build a consolidation transaction {:#x} of {} inputs (excess: {})",
+        tx_view.hash(),
+        inputs.len(),
+        excess
+    );
+    let changes = accepted_changes(overlay, &tx_view)?;
+    let mut branches = rg.take_branches();
+    branches.push(Branch::Consolidate);
+    Ok(Some(
+        TxOverlay::new(tx_view, changes).with_branches(branches),
+    ))
+}
+
+fn accepted_changes(
+    overlay: &Overlay,
+    tx_view: &core::TransactionView,
+) -> Result<TxOverlayChanges> {
+    let mut updates = HashMap::new();
+    for out_point in tx_view.input_pts_iter() {
+        let tx_hash = out_point.tx_hash();
+        let index: u32 = out_point.index().unpack();
+        let tx_status = overlay.get_tx_status(&tx_hash)?;
+        updates
+            .entry(tx_hash)
+            .or_insert(tx_status)
+            .spent(index as usize);
+    }
+    Ok(TxOverlayChanges::Pending {
+        new: TxOutputsStatus {
+            statuses: vec![CellStatus::Live; tx_view.outputs().len()],
+            cellbase: None,
+        },
+        updates,
+        package: None,
+    })
+}
+
+fn has_enough_live_cells(rg: &RandomGenerator, overlay: &Overlay) -> bool {
+    let live_cells_count = overlay.live_cells_count();
+    if live_cells_count < rg.min_live_cells() {
+        log::trace!(
+            "[BuildTx] >>> live cells (size: {}) is not enough",
+            live_cells_count
+        );
+        false
+    } else {
+        true
+    }
+}
+
+fn random_committed_live_cell(
+    rg: &RandomGenerator,
+    chain: &MockedChain,
+    overlay: &Overlay,
+) -> Result<Option<RawInputCell>> {
+    for _ in 0..rg.retries() {
+        if let Some((out_point, CellStage::Committed)) =
+            random_spendable_live_cell(rg, chain, overlay, false)
+        {
+            return Ok(Some(RawInputCell::live(&out_point, CellStage::Committed)));
+        }
+    }
+    Ok(None)
+}
+
+// A random live cell, except the outputs of the immature cellbases, which are spent only by
+// the transactions which are expected to be rejected, and the DAO cells, which are spent only by
+// the DAO transactions.
+// The cells which are locked by the keyring, or whose type scripts are the script binaries of the
+// users, are skipped too, unless the transaction is built by `generate_transaction`, which signs
+// the inputs and adds the cell deps of the script binaries.
+fn random_spendable_live_cell(
+    rg: &RandomGenerator,
+    chain: &MockedChain,
+    overlay: &Overlay,
+    generic: bool,
+) -> Option<(packed::OutPoint, CellStage)> {
+    let dao_type_hash = chain.dao_script().type_hash();
+    let keyring = chain.keyring().filter(|_| !generic);
+    let custom_scripts: &[(ScriptAnchor, bool)] =
+        if generic { &[] } else { chain.custom_scripts() };
+    for _ in 0..rg.retries() {
+        let (out_point, stage) = overlay.random_live_cell(rg)?;
+        if stage == CellStage::Committed && is_immature_cellbase(chain, overlay, &out_point) {
+            continue;
+        }
+        let skipped = cell_with_data(chain, overlay, &out_point)
+            .map(|(output, _)| {
+                has_type_script(&output, &dao_type_hash)
+                    || keyring
+                        .and_then(|keyring| keyring.signer(&output.lock()))
+                        .is_some()
+                    || output
+                        .type_()
+                        .to_opt()
+                        .map(|script| {
+                            custom_scripts
+                                .iter()
+                                .any(|(anchor, _)| refers_anchor(&script, anchor))
+                        })
+                        .unwrap_or(false)
+            })
+            .unwrap_or(false);
+        if !skipped {
+            return Some((out_point, stage));
+        }
+    }
+    None
+}
+
+fn generate_inputs(
+    rg: &RandomGenerator,
+    chain: &MockedChain,
+    overlay: &Overlay,
+    deadline: &Deadline,
+    reasons: &mut Vec<FailReason>,
+) -> Vec<RawInputCell> {
+    let mut inputs = Vec::new();
+    if rg.no_inputs() {
+        return inputs;
+    }
+    loop {
+        if !inputs.is_empty() && !rg.has_next_input() {
+            break;
+        }
+        if deadline.is_expired() {
+            break;
+        }
+        let mut cell = if let Some(cell) = random_unspendable_input(rg, chain, overlay, reasons) {
+            cell
+        } else if let Some((out_point, stage)) = pick_live_cell(rg, chain, overlay) {
+            RawInputCell::live(&out_point, stage)
+        } else {
+            break;
+        };
+        if !inputs
+            .iter()
+            .any(|item| item.tx_hash == cell.tx_hash && item.index == cell.index)
+        {
+            inputs.push(cell);
+        } else if rg.allow_duplicated() {
+            reasons.push(FailReason::DuplicateInput);
+            cell.status = Status::Failed;
+            inputs.push(cell);
+        }
+    }
+    inputs
+}
+
+// Pick a live cell by the input selection of the run.
+//
+// The weighted selections sample a few live cells uniformly and pick one of them by the weights,
+// so the picking is still in constant time.
+fn pick_live_cell(
+    rg: &RandomGenerator,
+    chain: &MockedChain,
+    overlay: &Overlay,
+) -> Option<(packed::OutPoint, CellStage)> {
+    let selection = rg.input_selection();
+    if selection == InputSelection::Uniform {
+        return random_spendable_live_cell(rg, chain, overlay, true);
+    }
+    let mut candidates = (0..WEIGHTED_CANDIDATES)
+        .filter_map(|_| random_spendable_live_cell(rg, chain, overlay, true))
+        .collect::<Vec<_>>();
+    if candidates.is_empty() {
+        return None;
+    }
+    let weights = candidates
+        .iter()
+        .map(|(out_point, stage)| match selection {
+            InputSelection::Uniform => 1,
+            InputSelection::Capacity => {
+                let index: u32 = out_point.index().unpack();
+                let capacity = cell_capacity(chain, overlay, &out_point.tx_hash(), index as usize);
+                // In CKBytes, so the weights fit in u32.
+                (capacity.as_u64() / BYTE_SHANNONS).clamp(1, u64::from(u32::MAX)) as u32
+            }
+            InputSelection::Age => {
+                let committed_at = match stage {
+                    CellStage::Pending => None,
+                    CellStage::Committed => chain
+                        .store()
+                        .get_transaction_info(&out_point.tx_hash())
+                        .map(|info| info.block_number),
+                };
+                let tip_number = chain.chain_tip_header().number();
+                committed_at
+                    .map(|number| tip_number.saturating_sub(number) + 1)
+                    .unwrap_or(1)
+                    .min(u64::from(u32::MAX)) as u32
+            }
+        })
+        .collect::<Vec<_>>();
+    let index = rg.weighted_index(&weights);
+    Some(candidates.swap_remove(index))
+}
+
+// Pick an input which could not be spent, if the generator decides to.
+//
+// The index only contains the live cells, so the burned cells, the dead cells and the cells of
+// the failed transactions are found by scanning random transactions.
+fn random_unspendable_input(
+    rg: &RandomGenerator,
+    chain: &MockedChain,
+    overlay: &Overlay,
+    reasons: &mut Vec<FailReason>,
+) -> Option<RawInputCell> {
+    let (wanted, reason) = if rg.could_has_burned_input() {
+        (Some(CellStatus::Burn), FailReason::ScriptFailure)
+    } else if rg.could_has_dead_input() {
+        (Some(CellStatus::Dead), FailReason::DeadCell)
+    } else if rg.could_be_from_failed_tx() {
+        (None, FailReason::UnknownCell)
+    } else if rg.could_has_out_of_range_input() {
+        return random_out_of_range_input(rg, overlay, reasons);
+    } else if rg.could_has_immature_input() {
+        return random_immature_input(rg, chain, overlay, reasons);
+    } else {
+        return None;
+    };
+    for _ in 0..rg.retries() {
+        let (tx_hash, tx_status) = overlay.random_tx(rg).unwrap()?;
+        let index_opt = match (wanted, tx_status.outputs()) {
+            // The outputs of the transactions which are not in the tx-pool or in chain.
+            (None, None) => Some(0),
+            (Some(wanted), Some(cells)) => {
+                let cells_count = cells.count();
+                let cell_index_start = rg.usize_less_than(cells_count);
+                (cell_index_start..cells_count)
+                    .chain(0..cell_index_start)
+                    .find(|index| *cells.status(*index) == wanted)
+            }
+            _ => None,
+        };
+        if let Some(index) = index_opt {
+            reasons.push(reason);
+            return Some(RawInputCell::new(tx_hash, index, Status::Failed));
+        }
+    }
+    None
+}
+
+// Pick an input whose index is out of the range of the outputs of a known transaction, so the
+// input could not be resolved.
+fn random_out_of_range_input(
+    rg: &RandomGenerator,
+    overlay: &Overlay,
+    reasons: &mut Vec<FailReason>,
+) -> Option<RawInputCell> {
+    for _ in 0..rg.retries() {
+        let (tx_hash, tx_status) = overlay.random_tx(rg).unwrap()?;
+        if let Some(cells) = tx_status.outputs() {
+            let index = cells.count() + rg.usize_less_than(3);
+            reasons.push(FailReason::UnknownCell);
+            return Some(RawInputCell::new(tx_hash, index, Status::Failed));
+        }
+    }
+    None
+}
+
+// Whether the cell is an output of a cellbase which couldn't be spent in the next block, by the
+// origin which is tracked with the status of the cellbase.
+fn is_immature_cellbase(
+    chain: &MockedChain,
+    overlay: &Overlay,
+    out_point: &packed::OutPoint,
+) -> bool {
+    overlay
+        .get_tx_status(&out_point.tx_hash())
+        .ok()
+        .and_then(|tx_status| tx_status.cellbase_origin())
+        .map(|origin| chain.is_immature_cellbase(&origin))
+        .unwrap_or(false)
+}
+
+// Pick a live cell of an immature cellbase, from the recent blocks.
+fn random_immature_input(
+    rg: &RandomGenerator,
+    chain: &MockedChain,
+    overlay: &Overlay,
+    reasons: &mut Vec<FailReason>,
+) -> Option<RawInputCell> {
+    for cellbase_hash in overlay.recent_cellbases(rg.retries()) {
+        let tx_status = if let Ok(tx_status) = overlay.get_tx_status(&cellbase_hash) {
+            tx_status
+        } else {
+            continue;
+        };
+        let is_immature = tx_status
+            .cellbase_origin()
+            .map(|origin| chain.is_immature_cellbase(&origin))
+            .unwrap_or(false);
+        if !is_immature {
+            // The older cellbases are mature too.
+            break;
+        }
+        let stages = tx_status.live_cell_stages();
+        let live_indexes = stages
+            .iter()
+            .enumerate()
+            .filter_map(|(index, stage)| stage.map(|_| index))
+            .collect::<Vec<_>>();
+        if !live_indexes.is_empty() {
+            let index = live_indexes[rg.usize_less_than(live_indexes.len())];
+            reasons.push(FailReason::ImmatureCell);
+            return Some(RawInputCell::new(cellbase_hash, index, Status::Failed));
+        }
+    }
+    None
+}
+
+fn complete_inputs(
+    chain: &MockedChain,
+    overlay: &Overlay,
+    raw_cells: Vec<RawInputCell>,
+) -> Vec<InputCell> {
+    raw_cells
+        .into_iter()
+        .map(|raw| {
+            let index = raw.index as u32;
+            let capacity = cell_capacity(chain, overlay, &raw.tx_hash, raw.index);
+            InputCell {
+                tx_hash: raw.tx_hash,
+                index,
+                status: raw.status,
+                capacity,
+            }
+        })
+        .collect()
+}
+
+fn cell_capacity(
+    chain: &MockedChain,
+    overlay: &Overlay,
+    tx_hash: &packed::Byte32,
+    index: usize,
+) -> core::Capacity {
+    let outputs = if let Some(tx_view) = overlay.get_tx(tx_hash) {
+        tx_view
+    } else {
+        chain
+            .store()
+            .get_transaction(tx_hash)
+            .map(|(tx, _)| tx)
+            .unwrap()
+    }
+    .outputs();
+    if let Some(output) = outputs.get(index) {
+        output.capacity().unpack()
+    } else {
+        core::Capacity::shannons(UNKNOWN_CELL_SHANNONS)
+    }
+}
+
+// Group the inputs which are locked by the keyring by their signers, each group is signed once, by
+// the first witness of the group.
+fn group_signed_inputs(
+    chain: &MockedChain,
+    overlay: &Overlay,
+    keyring: &Keyring,
+    inputs: &[InputCell],
+) -> Vec<(Signer, Vec<usize>)> {
+    let mut groups: Vec<(Signer, Vec<usize>)> = Vec::new();
+    for (index, input) in inputs.iter().enumerate() {
+        let out_point = packed::OutPoint::new(input.tx_hash.clone(), input.index);
+        let signer_opt = cell_with_data(chain, overlay, &out_point)
+            .and_then(|(output, _)| keyring.signer(&output.lock()));
+        if let Some(signer) = signer_opt {
+            if let Some((_, group)) = groups.iter_mut().find(|(s, _)| *s == signer) {
+                group.push(index);
+            } else {
+                groups.push((signer, vec![index]));
+            }
+        }
+    }
+    groups
+}
+
+// The cell deps of the script binaries of the users, which are the type scripts of the inputs or
+// the outputs.
+fn custom_cell_deps(
+    chain: &MockedChain,
+    overlay: &Overlay,
+    inputs: &[InputCell],
+    outputs: &[RawOutputCell],
+) -> Vec<packed::CellDep> {
+    let custom_scripts = chain.custom_scripts();
+    if custom_scripts.is_empty() {
+        return Vec::new();
+    }
+    let type_scripts = inputs
+        .iter()
+        .filter_map(|input| {
+            let out_point = packed::OutPoint::new(input.tx_hash.clone(), input.index);
+            cell_with_data(chain, overlay, &out_point).map(|(output, _)| output)
+        })
+        .chain(outputs.iter().map(|item| item.output.clone()))
+        .filter_map(|output| output.type_().to_opt())
+        .collect::<Vec<_>>();
+    custom_scripts
+        .iter()
+        .filter(|(anchor, _)| {
+            type_scripts
+                .iter()
+                .any(|script| refers_anchor(script, anchor))
+        })
+        .map(|(anchor, _)| anchor.cell_dep())
+        .collect()
+}
+
+fn cell_with_data(
+    chain: &MockedChain,
+    overlay: &Overlay,
+    out_point: &packed::OutPoint,
+) -> Option<(packed::CellOutput, Bytes)> {
+    let tx_hash = out_point.tx_hash();
+    let index: u32 = out_point.index().unpack();
+    overlay
+        .get_tx(&tx_hash)
+        .or_else(|| chain.store().get_transaction(&tx_hash).map(|(tx, _)| tx))
+        .and_then(|tx_view| tx_view.output_with_data(index as usize))
+}
+
+fn generate_outputs(
+    rg: &RandomGenerator,
+    inputs: &[InputCell],
+    mocked_script: &ScriptAnchor,
+    custom_scripts: &[(ScriptAnchor, bool)],
+    keyring: Option<&Keyring>,
+    data1_enabled: Option<bool>,
+    reserved_fee: u64,
+    reasons: &mut Vec<FailReason>,
+) -> (Vec<RawOutputCell>, Status) {
+    let mut expected_status = Status::Failed;
+    let mut outputs = Vec::new();
+    if inputs.is_empty() {
+        log::trace!("[BuildTx] >>> >>> failed since: inputs is empty");
+        return (outputs, expected_status);
+    }
+    if rg.no_outputs() {
+        log::trace!("[BuildTx] >>> >>> failed since: outputs is empty");
+        reasons.push(FailReason::EmptyOutputs);
+        return (outputs, expected_status);
+    }
+    let fee = core::Capacity::shannons(reserved_fee);
+    let total_capacity = inputs
+        .iter()
+        .map(|item| item.capacity)
+        .try_fold(core::Capacity::zero(), core::Capacity::safe_add)
+        .unwrap();
+    if total_capacity < fee {
+        log::trace!("[BuildTx] >>> >>> failed since: no enough fee");
+        reasons.push(FailReason::EmptyOutputs);
+        return (outputs, expected_status);
+    }
+    let remain_capacity = total_capacity.safe_sub(fee).unwrap();
+    if remain_capacity.as_u64() < rg.smallest_shannons() {
+        log::trace!("[BuildTx] >>> >>> failed since: no enough capacity");
+        reasons.push(FailReason::EmptyOutputs);
+        return (outputs, expected_status);
+    }
+    let mut remain_shannons = {
+        if rg.allow_capacity_overflow() {
+            log::trace!("[BuildTx] >>> >>> failed since: capacity overflow");
+            reasons.push(FailReason::CapacityOverflow);
             expected_status = Status::Failed;
             let one_shannon = core::Capacity::shannons(1);
             total_capacity.safe_add(one_shannon).unwrap()
@@ -385,42 +2647,103 @@ fn generate_outputs(
         }
     }
     .as_u64();
+    // Each output should have the smallest capacity at least.
+    let target_count = rg.outputs_count().map(|count| {
+        let affordable = (remain_shannons / rg.smallest_shannons()) as usize;
+        count.min(affordable).max(1)
+    });
     loop {
         if remain_shannons == 0 {
             break;
         }
         let output_shannons = {
-            let mut shannons = if remain_shannons == SMALLEST_SHANNONS {
-                remain_shannons
-            } else {
-                rg.u64_between(SMALLEST_SHANNONS, remain_shannons)
-            };
+            let mut shannons = rg.output_shannons(
+                rg.smallest_shannons(),
+                remain_shannons,
+                outputs.len(),
+                target_count,
+            );
             remain_shannons -= shannons;
-            if remain_shannons < SMALLEST_SHANNONS {
+            if remain_shannons < rg.smallest_shannons() {
                 shannons += remain_shannons;
                 remain_shannons = 0;
             }
             shannons
         };
-        let lock_status = rg.lock_status();
+        let (lock_script, lock_status) = match (rg.lock_status(), keyring) {
+            (None, _) => (packed::Script::default(), None),
+            (Some(true), Some(keyring)) if rg.is_secp256k1_lock() => {
+                let index = rg.usize_less_than(keyring.keys_count());
+                let signer = if rg.is_multisig_lock() {
+                    Signer::Multisig(index)
+                } else {
+                    Signer::Single(index)
+                };
+                (keyring.lock_script(signer), Some(true))
+            }
+            (Some(inner), _) => {
+                let script = generate_script(rg, mocked_script, inner);
+                // The lock scripts are not run until they are spent, so only the enabled hash
+                // types are used, otherwise the cells could be spent after the hardfork.
+                let script = if data1_enabled == Some(true)
+                    && is_data_hash_type(&script)
+                    && rg.is_data1_hash_type()
+                {
+                    with_data1_hash_type(script)
+                } else {
+                    script
+                };
+                let (script, inner) = fuzz_script_args(rg, script, inner);
+                (script, Some(inner))
+            }
+        };
         let cell_status = if lock_status.unwrap_or(false) {
             CellStatus::Live
         } else {
             CellStatus::Burn
         };
-        let lock_script = match lock_status {
-            None => packed::Script::default(),
-            Some(inner) => generate_script(rg, mocked_script, inner),
+        let (type_script_opt, type_status) = match rg.type_status() {
+            None => (None, None),
+            Some(inner) => {
+                let custom = if !custom_scripts.is_empty() && rg.is_custom_type_script() {
+                    custom_scripts.get(rg.usize_less_than(custom_scripts.len()))
+                } else {
+                    None
+                };
+                let (script, inner) = if let Some((anchor, passes)) = custom {
+                    (generate_custom_script(rg, anchor), *passes)
+                } else {
+                    (generate_script(rg, mocked_script, inner), inner)
+                };
+                let (script, inner) = match data1_enabled {
+                    Some(enabled) if is_data_hash_type(&script) && rg.is_data1_hash_type() => {
+                        let script = with_data1_hash_type(script);
+                        if !enabled {
+                            log::trace!("[BuildTx] >>> >>> failed since: data1 is not enabled");
+                            reasons.push(FailReason::InactiveHashType);
+                            expected_status = Status::Failed;
+                        }
+                        (script, inner)
+                    }
+                    _ => (script, inner),
+                };
+                // The results of the script binaries of the users are unknown for other args.
+                let (script, inner) = if custom.is_some() {
+                    (script, inner)
+                } else {
+                    fuzz_script_args(rg, script, inner)
+                };
+                (Some(script), Some(inner))
+            }
         };
-        let type_status = rg.type_status();
         let status = if matches!(type_status, Some(false)) {
             log::trace!("[BuildTx] >>> >>> failed since: type script");
+            reasons.push(FailReason::ScriptFailure);
             Status::Failed
         } else {
             Status::Pending
         };
         expected_status = expected_status.merge(status);
-        let type_script_opt = type_status.map(|inner| generate_script(rg, mocked_script, inner));
         let output = {
             let tmp_output = packed::CellOutput::new_builder()
                 .lock(lock_script)
@@ -428,15 +2751,28 @@ fn generate_outputs(
                 .build_exact_capacity(core::Capacity::zero())
                 .unwrap();
             let tmp_shannons: u64 = tmp_output.capacity().unpack();
-            let free_bytes = ((output_shannons - tmp_shannons) / BYTE_SHANNONS) as usize;
-            let data_size = if free_bytes > 0 {
-                rg.usize_less_than(free_bytes)
+            // The scripts could occupy more than the capacity, for example, the huge args.
+            if tmp_shannons > output_shannons {
+                log::trace!("[BuildTx] >>> >>> failed since: scripts occupy too much capacity");
+                reasons.push(FailReason::InsufficientCapacity);
+                expected_status = Status::Failed;
+            }
+            let free_bytes =
+                (output_shannons.saturating_sub(tmp_shannons) / BYTE_SHANNONS) as usize;
+            let data_size = rg.data_size(free_bytes);
+            // The rest capacity is paid as the fee, since the transaction fails anyway.
+            let declared_shannons = if rg.break_occupied_capacity() {
+                log::trace!("[BuildTx] >>> >>> failed since: insufficient cell capacity");
+                reasons.push(FailReason::InsufficientCapacity);
+                expected_status = Status::Failed;
+                let occupied_shannons = tmp_shannons + data_size as u64 * BYTE_SHANNONS;
+                occupied_shannons - rg.u64_between(1, BYTE_SHANNONS + 1)
             } else {
-                0
+                output_shannons
             };
             let output = tmp_output
                 .as_builder()
-                .capacity(core::Capacity::shannons(output_shannons).pack())
+                .capacity(core::Capacity::shannons(declared_shannons).pack())
                 .build();
             RawOutputCell::new(output, data_size as usize, cell_status)
         };
@@ -445,13 +2781,115 @@ fn generate_outputs(
     (outputs, expected_status)
 }
 
+// The witnesses are not checked by the mocked scripts, so they should not change the result,
+// except that they make the transaction larger.
+fn generate_witnesses(rg: &RandomGenerator, inputs_count: usize) -> Vec<packed::Bytes> {
+    let mut witnesses = Vec::new();
+    if let Some(sizes) = rg.witness_sizes(inputs_count) {
+        for size in sizes {
+            let mut witness = vec![0u8; size];
+            rg.fill_bytes(&mut witness);
+            witnesses.push(witness.pack());
+        }
+    } else if rg.has_empty_witnesses() {
+        witnesses.resize(inputs_count, packed::Bytes::default());
+    }
+    if rg.has_extra_witnesses() {
+        for _ in 0..=rg.usize_less_than(3) {
+            let size = rg.usize_less_than(100);
+            witnesses.push(vec![0u8; size].pack());
+        }
+    }
+    witnesses
+}
+
+// Take the fee from the outputs, from the last one, each output keeps its occupied capacity.
+// Returns the paid fee, which is less than the expected fee if the outputs couldn't afford it.
+fn pay_fee(outputs: &mut [RawOutputCell], fee: u64) -> u64 {
+    let mut unpaid = fee;
+    for item in outputs.iter_mut().rev() {
+        if unpaid == 0 {
+            break;
+        }
+        let capacity: u64 = item.output.capacity().unpack();
+        let occupied = core::Capacity::bytes(item.data_size)
+            .and_then(|data_capacity| item.output.occupied_capacity(data_capacity))
+            .map(|occupied| occupied.as_u64())
+            .unwrap_or(capacity);
+        let paid = capacity.saturating_sub(occupied).min(unpaid);
+        unpaid -= paid;
+        item.output = item
+            .output
+            .clone()
+            .as_builder()
+            .capacity(core::Capacity::shannons(capacity - paid).pack())
+            .build();
+    }
+    fee - unpaid
+}
+
+// The fee of a transaction by a random fee rate, or the constant fee.
+fn random_fee(rg: &RandomGenerator, size: usize) -> u64 {
+    rg.fee_rate()
+        .map(|fee_rate| fee_rate.fee(size).as_u64())
+        .unwrap_or(FEE_SHANNONS)
+}
+
+// Change the args of a mocked script randomly, returns the changed script and whether it should
+// pass.
+//
+// The mocked script reads the result and the cycles from the first 32 bytes of the args, and the
+// rest bytes are ignored, so it fails if the args are shorter or the result is not zero.
+fn fuzz_script_args(
+    rg: &RandomGenerator,
+    script: packed::Script,
+    result: bool,
+) -> (packed::Script, bool) {
+    let mut args = script.args().raw_data().to_vec();
+    let result = match rg.args_shape(args.len()) {
+        ArgsShape::Exact => return (script, result),
+        ArgsShape::Longer(extra) | ArgsShape::Huge(extra) => {
+            let start = args.len();
+            args.resize(start + extra, 0);
+            rg.fill_bytes(&mut args[start..]);
+            result
+        }
+        ArgsShape::Shorter(len) => {
+            args.truncate(len);
+            false
+        }
+        ArgsShape::Garbage => {
+            rg.fill_bytes(&mut args);
+            // Make sure the result is not zero.
+            args[0] |= 1;
+            false
+        }
+    };
+    let script = script.as_builder().args(args.pack()).build();
+    (script, result)
+}
+
+fn is_data_hash_type(script: &packed::Script) -> bool {
+    let hash_type: u8 = script.hash_type().into();
+    hash_type == core::ScriptHashType::Data as u8
+}
+
+// The data1 hash type refers the same code as the data hash type, but runs in the new VM.
+// TODO Add the data2 hash type after upgrading ckb, it doesn't exist in the current version.
+fn with_data1_hash_type(script: packed::Script) -> packed::Script {
+    script
+        .as_builder()
+        .hash_type(core::ScriptHashType::Data1.into())
+        .build()
+}
+
 fn generate_script(
     rg: &RandomGenerator,
     mocked_script: &ScriptAnchor,
     result: bool,
 ) -> packed::Script {
     let result: u64 = if result { 0 } else { 1 };
-    let cycles: u64 = rg.u64_between(500, 1_000_000);
+    let cycles = rg.script_cycles();
     let (hash_type, code_hash) = if rg.is_data_hash_type() {
         (core::ScriptHashType::Data, mocked_script.data_hash())
     } else {
@@ -473,3 +2911,26 @@ fn generate_script(
         .args(args.pack())
         .build()
 }
+
+// The script binaries of the users run with empty args.
+fn generate_custom_script(rg: &RandomGenerator, anchor: &ScriptAnchor) -> packed::Script {
+    let (hash_type, code_hash) = if rg.is_data_hash_type() {
+        (core::ScriptHashType::Data, anchor.data_hash())
+    } else {
+        (core::ScriptHashType::Type, anchor.type_hash())
+    };
+    packed::Script::new_builder()
+        .hash_type(hash_type.into())
+        .code_hash(code_hash)
+        .build()
+}
+
+// Whether the script refers the code of the anchor, in any hash type.
+fn refers_anchor(script: &packed::Script, anchor: &ScriptAnchor) -> bool {
+    let hash_type: u8 = script.hash_type().into();
+    if hash_type == core::ScriptHashType::Type as u8 {
+        script.code_hash() == anchor.type_hash()
+    } else {
+        script.code_hash() == anchor.data_hash()
+    }
+}
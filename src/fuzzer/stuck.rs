@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use ckb_types::{
+    core::{
+        tx_pool::{TxEntryInfo, TxPoolEntryInfo},
+        BlockNumber, FeeRate,
+    },
+    packed,
+};
+
+use super::{MockedChain, Storage};
+use crate::error::Result;
+
+// The accepted transactions which are not committed yet, and the blocks when they are
+// submitted, to find the transactions which are never committed.
+#[derive(Default)]
+pub(crate) struct StuckTxs {
+    inner: HashMap<packed::Byte32, BlockNumber>,
+}
+
+// A transaction which is not committed for too many blocks, and why it could be stuck.
+pub(crate) struct StuckTx {
+    pub(crate) tx_hash: packed::Byte32,
+    pub(crate) submitted_at: BlockNumber,
+    pub(crate) diagnostic: String,
+}
+
+impl StuckTxs {
+    // Track an accepted transaction, the number is the tip when it's submitted.
+    pub(crate) fn add(&mut self, tx_hash: packed::Byte32, number: BlockNumber) {
+        self.inner.insert(tx_hash, number);
+    }
+
+    // Track the pending transactions after the block, returns the transactions which just have
+    // been pending for the limit of blocks, so each stuck transaction is only reported once.
+    //
+    // The transactions which are left the tx-pool are not tracked anymore. The transactions
+    // which are in the tx-pool but not submitted in this run, for example, resubmitted when
+    // resuming, are tracked since the block.
+    //
+    // The commits could be delayed, for example, withheld by the producers, so the limit should
+    // be much longer than the proposal window.
+    pub(crate) fn check(
+        &mut self,
+        chain: &MockedChain,
+        storage: &Storage,
+        number: BlockNumber,
+        limit: BlockNumber,
+    ) -> Result<Vec<StuckTx>> {
+        let pool = chain.txpool_entries()?;
+        let pending = storage
+            .pending_transactions()?
+            .into_iter()
+            .map(|tx| tx.hash())
+            .filter(|tx_hash| {
+                pool.pending.contains_key(tx_hash) || pool.proposed.contains_key(tx_hash)
+            })
+            .collect::<Vec<_>>();
+        let mut tracked = HashMap::with_capacity(pending.len());
+        for tx_hash in pending {
+            let submitted_at = self.inner.get(&tx_hash).copied().unwrap_or(number);
+            tracked.insert(tx_hash, submitted_at);
+        }
+        self.inner = tracked;
+        let mut stuck = Vec::new();
+        for (tx_hash, submitted_at) in &self.inner {
+            if number - submitted_at != limit {
+                continue;
+            }
+            let diagnostic = diagnose(chain, storage, &pool, tx_hash, number)?;
+            stuck.push(StuckTx {
+                tx_hash: tx_hash.clone(),
+                submitted_at: *submitted_at,
+                diagnostic,
+            });
+        }
+        Ok(stuck)
+    }
+}
+
+// Explain why a transaction could be stuck, by its entry in the tx-pool and its proposals.
+fn diagnose(
+    chain: &MockedChain,
+    storage: &Storage,
+    pool: &TxPoolEntryInfo,
+    tx_hash: &packed::Byte32,
+    number: BlockNumber,
+) -> Result<String> {
+    let (status, info) = if let Some(info) = pool.proposed.get(tx_hash) {
+        ("proposed", info)
+    } else if let Some(info) = pool.pending.get(tx_hash) {
+        ("pending", info)
+    } else {
+        return Ok("it's not in the tx-pool".to_owned());
+    };
+    let proposals = storage.get_proposals(tx_hash)?.unwrap_or_default();
+    let stage = proposals.stage(number + 1, &chain.proposal_window());
+    let reason = if status == "proposed" {
+        "it's proposed but never committed, the blocks may be full or withheld"
+    } else if info.ancestors_count > 1 {
+        "it's pending and waiting for its ancestors"
+    } else {
+        "it's pending but never proposed or the proposals are expired"
+    };
+    Ok(format!(
+        "{} in the tx-pool and {} in the storage (proposed in: {}), {}, {}",
+        status,
+        stage,
+        proposals,
+        display_entry(info),
+        reason
+    ))
+}
+
+fn display_entry(info: &TxEntryInfo) -> String {
+    let fee_rate = FeeRate::calculate(info.fee, info.size as usize);
+    format!(
+        "fee rate: {}, size: {}, cycles: {}, ancestors: {} (size: {}, cycles: {})",
+        fee_rate,
+        info.size,
+        info.cycles,
+        info.ancestors_count,
+        info.ancestors_size,
+        info.ancestors_cycles
+    )
+}
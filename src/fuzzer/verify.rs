@@ -0,0 +1,100 @@
+use std::fmt;
+
+use ckb_store::{ChainDB, ChainStore as _};
+use ckb_types::packed;
+
+use super::{block::display_out_point, MockedChain, Storage};
+use crate::{
+    error::Result,
+    types::{CellStatus, TxStatus},
+};
+
+// A divergence between the storage of the fuzzer and the chain.
+pub(crate) enum Divergence {
+    // A committed transaction doesn't exist in the chain.
+    CommittedNotInChain(packed::Byte32),
+    // A pending transaction already exists in the chain.
+    PendingInChain(packed::Byte32),
+    // A live cell of a committed transaction is not live in the chain.
+    LiveCellNotInChain(packed::OutPoint),
+    // A pending transaction is not in the tx-pool.
+    PendingNotInPool(packed::Byte32),
+    // The tip of the tx-pool is not the tip of the chain.
+    PoolTipNotChainTip(packed::Byte32, packed::Byte32),
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::CommittedNotInChain(tx_hash) => {
+                write!(f, "committed tx {:#x} is not in the chain", tx_hash)
+            }
+            Self::PendingInChain(tx_hash) => {
+                write!(f, "pending tx {:#x} is already in the chain", tx_hash)
+            }
+            Self::LiveCellNotInChain(out_point) => write!(
+                f,
+                "live cell {} is not live in the chain",
+                display_out_point(out_point)
+            ),
+            Self::PendingNotInPool(tx_hash) => {
+                write!(f, "pending tx {:#x} is not in the tx-pool", tx_hash)
+            }
+            Self::PoolTipNotChainTip(pool_tip, chain_tip) => write!(
+                f,
+                "tip of the tx-pool {:#x} is not the tip of the chain {:#x}",
+                pool_tip, chain_tip
+            ),
+        }
+    }
+}
+
+// Cross-check all transactions' statuses in the storage against the chain.
+pub(crate) fn verify_storage(store: &ChainDB, storage: &Storage) -> Result<Vec<Divergence>> {
+    let mut divergences = Vec::new();
+    for (tx_hash, tx_status) in storage.tx_statuses()? {
+        match tx_status {
+            TxStatus::Committed(inner) => {
+                if store.get_transaction(&tx_hash).is_none() {
+                    divergences.push(Divergence::CommittedNotInChain(tx_hash));
+                    continue;
+                }
+                for (index, status) in inner.statuses.iter().enumerate() {
+                    if *status != CellStatus::Live {
+                        continue;
+                    }
+                    let out_point = packed::OutPoint::new(tx_hash.clone(), index as u32);
+                    if store.get_cell(&out_point).is_none() {
+                        divergences.push(Divergence::LiveCellNotInChain(out_point));
+                    }
+                }
+            }
+            TxStatus::Pending(_) | TxStatus::Proposed(_) => {
+                if store.get_transaction(&tx_hash).is_some() {
+                    divergences.push(Divergence::PendingInChain(tx_hash));
+                }
+            }
+            TxStatus::Orphan(_) | TxStatus::Evicted | TxStatus::Expired | TxStatus::Failed => {}
+        }
+    }
+    Ok(divergences)
+}
+
+// Cross-check the storage against both the chain and the tx-pool, during a run.
+pub(crate) fn verify_running(chain: &MockedChain, storage: &Storage) -> Result<Vec<Divergence>> {
+    let mut divergences = verify_storage(chain.store(), storage)?;
+    let pool_tip = chain.txpool_tip_hash()?;
+    let chain_tip = chain.chain_tip_header().hash();
+    if pool_tip != chain_tip {
+        divergences.push(Divergence::PoolTipNotChainTip(pool_tip, chain_tip));
+    }
+    let entries = chain.txpool_entries()?;
+    for (tx_hash, tx_status) in storage.tx_statuses()? {
+        if tx_status.is_uncommitted() {
+            if !entries.pending.contains_key(&tx_hash) && !entries.proposed.contains_key(&tx_hash) {
+                divergences.push(Divergence::PendingNotInPool(tx_hash));
+            }
+        }
+    }
+    Ok(divergences)
+}
@@ -0,0 +1,159 @@
+use std::{
+    process,
+    sync::{mpsc, Arc, Mutex, MutexGuard, Weak},
+    thread, time,
+};
+
+use ckb_tx_pool::TxPoolController;
+use ckb_types::packed;
+
+use crate::error::{Error, Result};
+
+// The exit code when a request to the tx-pool hangs, so the hangs could be distinguished from
+// the mismatches, which exit with 1.
+pub(crate) const HANG_EXIT_CODE: i32 = 3;
+
+// How long to wait for the tx-pool info when dumping, the tx-pool is probably deadlocked.
+const DUMP_TIMEOUT: time::Duration = time::Duration::from_secs(5);
+const MAX_POLL_INTERVAL: time::Duration = time::Duration::from_secs(1);
+
+// The request which is waiting for the response of the tx-pool.
+struct InFlight {
+    request: &'static str,
+    // The hash of the transaction or the block in the request.
+    hash: Option<packed::Byte32>,
+    started_at: time::Instant,
+}
+
+type Slot = Mutex<Option<InFlight>>;
+
+// Watch the requests to the tx-pool in a background thread, and exit the process if a request
+// is not responded in time, since the requests block forever if the tx-pool is deadlocked.
+//
+// The background thread stops after the watchdog is dropped.
+pub(crate) struct Watchdog {
+    in_flight: Arc<Slot>,
+}
+
+// Clear the in-flight request when the response is received.
+pub(crate) struct WatchGuard<'a> {
+    watchdog: &'a Watchdog,
+}
+
+impl Watchdog {
+    pub(crate) fn spawn(timeout: time::Duration, controller: TxPoolController) -> Result<Self> {
+        let in_flight = Arc::new(Slot::default());
+        let weak = Arc::downgrade(&in_flight);
+        let poll_interval = (timeout / 10).min(MAX_POLL_INTERVAL);
+        thread::Builder::new()
+            .name("watchdog".to_owned())
+            .spawn(move || watch(&weak, timeout, poll_interval, &controller))
+            .map_err(|err| {
+                let errmsg = format!("failed to spawn watchdog thread since {}", err);
+                Error::runtime(errmsg)
+            })?;
+        log::info!(
+            "[Watchdog] watch the requests to the tx-pool, timeout: {:?}",
+            timeout
+        );
+        Ok(Self { in_flight })
+    }
+
+    pub(crate) fn watch(
+        &self,
+        request: &'static str,
+        hash: Option<packed::Byte32>,
+    ) -> WatchGuard<'_> {
+        *self.lock() = Some(InFlight {
+            request,
+            hash,
+            started_at: time::Instant::now(),
+        });
+        WatchGuard { watchdog: self }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<InFlight>> {
+        self.in_flight
+            .lock()
+            .expect("the lock of the watchdog is poisoned")
+    }
+}
+
+impl<'a> Drop for WatchGuard<'a> {
+    fn drop(&mut self) {
+        *self.watchdog.lock() = None;
+    }
+}
+
+fn watch(
+    weak: &Weak<Slot>,
+    timeout: time::Duration,
+    poll_interval: time::Duration,
+    controller: &TxPoolController,
+) {
+    loop {
+        thread::sleep(poll_interval);
+        let in_flight = if let Some(in_flight) = weak.upgrade() {
+            in_flight
+        } else {
+            break;
+        };
+        let guard = in_flight
+            .lock()
+            .expect("the lock of the watchdog is poisoned");
+        if let Some(ref request) = *guard {
+            let elapsed = request.started_at.elapsed();
+            if elapsed > timeout {
+                dump(request, elapsed, controller);
+                process::exit(HANG_EXIT_CODE);
+            }
+        }
+    }
+}
+
+fn dump(request: &InFlight, elapsed: time::Duration, controller: &TxPoolController) {
+    if let Some(ref hash) = request.hash {
+        log::error!(
+            "[Watchdog] >>> request {} of {:#x} is not responded after {:?}",
+            request.request,
+            hash,
+            elapsed
+        );
+    } else {
+        log::error!(
+            "[Watchdog] >>> request {} is not responded after {:?}",
+            request.request,
+            elapsed
+        );
+    }
+    // The tx-pool info is requested in another thread, since it could hang too.
+    let (sender, receiver) = mpsc::channel();
+    let controller = controller.clone();
+    let _ = thread::Builder::new()
+        .name("watchdog-dump".to_owned())
+        .spawn(move || {
+            let _ = sender.send(controller.get_tx_pool_info());
+        });
+    match receiver.recv_timeout(DUMP_TIMEOUT) {
+        Ok(Ok(info)) => log::error!(
+            "[Watchdog] tx-pool: tip: {}, hash: {:#x}, \
+            pending: {}, proposed: {}, orphan: {}, \
+            total_size: {}, total_cycles: {}",
+            info.tip_number,
+            info.tip_hash,
+            info.pending_size,
+            info.proposed_size,
+            info.orphan_size,
+            info.total_tx_size,
+            info.total_tx_cycles,
+        ),
+        Ok(Err(err)) => log::error!("[Watchdog] failed to get the tx-pool info since {}", err),
+        Err(_) => log::error!(
+            "[Watchdog] the tx-pool info is not responded after {:?} either",
+            DUMP_TIMEOUT
+        ),
+    }
+    // The standard library could not capture the backtraces of other threads.
+    log::error!("[Watchdog] the backtraces of other threads are not available");
+    log::error!("[Watchdog] exit with code {}", HANG_EXIT_CODE);
+}
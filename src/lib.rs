@@ -0,0 +1,32 @@
+mod config;
+mod error;
+mod fuzzer;
+mod subcmds;
+mod types;
+mod utils;
+
+use config::AppConfig;
+
+pub use config::{ChainSource, InitConfig, RunConfig};
+pub use error::{Error, Result};
+#[cfg(feature = "async-api")]
+pub use fuzzer::AsyncStepper;
+pub use fuzzer::{
+    build_block, build_transactions, BlockProducers, Fuzzer, MockedChain, Stepper, Storage,
+    TxOverlay, TxStrategies,
+};
+pub use types::{BlockDelta, MetaData, Problems, RandomGenerator, RunEnv, Script};
+pub use utils::lock::DataDirLock;
+
+// Parse the command line arguments and execute the subcommand.
+pub fn execute_cli() -> Result<()> {
+    AppConfig::load()?.execute()
+}
+
+// Run the fuzzer in a temporary data directory, all random decisions are consumed from the
+// bytes, so it could be driven by coverage-guided fuzzers, such as cargo-fuzz or AFL.
+//
+// An `Error::Mismatch` is returned when an unexpected result is found.
+pub fn fuzz_one(data: &[u8]) -> Result<()> {
+    fuzzer::Fuzzer::fuzz_one(data)
+}
@@ -1,18 +1,22 @@
-mod config;
-mod error;
-mod fuzzer;
-mod subcmds;
-mod types;
-mod utils;
+use std::process;
 
-use config::AppConfig;
+use ckb_txpool_fuzzer::Error;
+
+// The exit code when an unexpected result is found.
+const MISMATCH_EXIT_CODE: i32 = 1;
 
 fn main() -> anyhow::Result<()> {
     env_logger::init();
 
     log::info!("Starting ...");
 
-    AppConfig::load()?.execute()?;
+    match ckb_txpool_fuzzer::execute_cli() {
+        Err(Error::Mismatch(reason)) => {
+            log::error!("Stopped since mismatched: {}", reason);
+            process::exit(MISMATCH_EXIT_CODE);
+        }
+        result => result?,
+    }
 
     log::info!("Done.");
 
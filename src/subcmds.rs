@@ -1,7 +1,17 @@
+use std::str::FromStr as _;
+
+use ckb_types::prelude::*;
+
 use crate::{
-    config::{InitConfig, RunConfig},
-    error::Result,
-    fuzzer::Fuzzer,
+    config::{
+        CleanConfig, DumpChainConfig, ExportConfig, GraphConfig, HistoryConfig, InitConfig,
+        InspectConfig, MatrixConfig, NewConfigConfig, ParallelConfig, PruneConfig, ReportConfig,
+        RestoreConfig, RunConfig, SnapshotConfig, VerifyConfig,
+    },
+    error::{Error, Result},
+    fuzzer::{self, Fuzzer},
+    types::{combination_summary, CellStatus, MatrixReport, MetaData, RunEnv, Verdict},
+    utils::{self, rpc::RpcClient},
 };
 
 impl InitConfig {
@@ -17,3 +27,384 @@ impl RunConfig {
         Fuzzer::load(self)?.run()
     }
 }
+
+impl ParallelConfig {
+    pub(crate) fn execute(self) -> Result<()> {
+        log::info!("Run in parallel ...");
+        fuzzer::run_parallel(self)
+    }
+}
+
+impl ReportConfig {
+    pub(crate) fn execute(self) -> Result<()> {
+        log::info!("Report ...");
+        let mut report = self
+            .storage
+            .get_report()?
+            .ok_or_else(|| Error::storage("can not found the report, please run first"))?;
+        // Regenerate the statistics from the current storage.
+        report.storage = (&self.storage.stats()).into();
+        if let Some(output) = self.output {
+            utils::fs::write_file(output, report.to_string())
+        } else {
+            println!("{}", report);
+            Ok(())
+        }
+    }
+}
+
+impl HistoryConfig {
+    pub(crate) fn execute(self) -> Result<()> {
+        log::info!("History ...");
+        let records = self.storage.run_records()?;
+        if let Some(run_id) = self.run_id {
+            let record = records
+                .into_iter()
+                .find(|record| record.run_id == run_id)
+                .ok_or_else(|| {
+                    let errmsg = format!("can not found the record of run {}", run_id);
+                    Error::storage(errmsg)
+                })?;
+            println!("{}", record);
+        } else if records.is_empty() {
+            println!("no records of runs");
+        } else {
+            for record in &records {
+                println!("{}", record.summary());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl InspectConfig {
+    pub(crate) fn execute(self) -> Result<()> {
+        log::info!("Inspect ...");
+        if self.stats {
+            println!("stats: {}", self.storage.stats());
+            if let Some(ledger) = self.storage.get_ledger()? {
+                println!("ledger: {}", ledger);
+            }
+        }
+        if self.list || self.cells {
+            let tx_statuses = self.storage.tx_statuses()?;
+            if self.list {
+                for (tx_hash, tx_status) in &tx_statuses {
+                    println!("{:#x}: {}", tx_hash, tx_status);
+                }
+            }
+            if self.cells {
+                let (mut live, mut dead, mut burn) = (0, 0, 0);
+                for (_, tx_status) in &tx_statuses {
+                    if let Some(inner) = tx_status.outputs() {
+                        for status in &inner.statuses {
+                            match status {
+                                CellStatus::Live => live += 1,
+                                CellStatus::Dead => dead += 1,
+                                CellStatus::Burn => burn += 1,
+                            }
+                        }
+                    }
+                }
+                println!("cells: live: {}, dead: {}, burn: {}", live, dead, burn);
+            }
+        }
+        if let Some(tx_hash) = self.tx_hash {
+            let tx_hash = tx_hash.pack();
+            let tx_status = self.storage.get_tx_status(&tx_hash)?;
+            let tx_opt = self.storage.get_transaction(&tx_hash)?;
+            // Only the histories of the pruned transactions are kept.
+            let history_opt = self.storage.get_tx_history(&tx_hash)?;
+            if tx_status.is_none() && tx_opt.is_none() && history_opt.is_none() {
+                let errmsg = format!("can not found the tx {:#x}", tx_hash);
+                return Err(Error::storage(errmsg));
+            }
+            if let Some(seq_id) = self.storage.get_tx_seq_id(&tx_hash)? {
+                println!("sequence id: {}", seq_id);
+            }
+            if let Some(tx_status) = tx_status {
+                println!("status: {}", tx_status);
+            }
+            if let Some(reason) = self.storage.get_reject(&tx_hash)? {
+                println!("rejected by: {}", reason);
+            }
+            if let Some(proposals) = self.storage.get_proposals(&tx_hash)? {
+                println!("proposed in: {}", proposals);
+            }
+            if let Some(history) = history_opt {
+                println!("history: {}", history);
+            }
+            if let Some(tx) = tx_opt {
+                println!("transaction: {}", tx.data());
+            } else {
+                // Committed transactions are only stored in the chain.
+                println!("transaction: not in the storage");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl VerifyConfig {
+    pub(crate) fn execute(self) -> Result<()> {
+        log::info!("Verify ...");
+        let divergences = fuzzer::verify_storage(self.store.store(), &self.storage)?;
+        if divergences.is_empty() {
+            println!("storage is consistent with the chain");
+            return Ok(());
+        }
+        for divergence in &divergences {
+            println!("{}", divergence);
+        }
+        let provenance_file = self.data_dir.join(fuzzer::PROVENANCE_FILE);
+        fuzzer::export_provenance(
+            &provenance_file,
+            self.store.store(),
+            &self.storage,
+            &divergences,
+        )?;
+        println!(
+            "provenances of the involved cells are exported into {}",
+            provenance_file.display()
+        );
+        let errmsg = format!("found {} divergences", divergences.len());
+        Err(Error::storage(errmsg))
+    }
+}
+
+// The directories in the data directory which are included in a snapshot.
+const SNAPSHOT_CHAIN_DIR: &str = "chain";
+const SNAPSHOT_STORAGE_DIR: &str = "storage";
+const SNAPSHOT_TX_POOL_DIR: &str = "tx_pool";
+
+impl SnapshotConfig {
+    pub(crate) fn execute(self) -> Result<()> {
+        log::info!("Snapshot ...");
+        utils::fs::create_directory(&self.snapshot_dir)?;
+        self.store
+            .create_checkpoint(self.snapshot_dir.join(SNAPSHOT_CHAIN_DIR))?;
+        self.storage
+            .create_checkpoint(self.snapshot_dir.join(SNAPSHOT_STORAGE_DIR))?;
+        // The persisted tx-pool is not a database, just copy it.
+        let tx_pool_dir = self.data_dir.join(SNAPSHOT_TX_POOL_DIR);
+        if tx_pool_dir.exists() {
+            utils::fs::copy_directory(&tx_pool_dir, self.snapshot_dir.join(SNAPSHOT_TX_POOL_DIR))?;
+        }
+        println!("snapshot is saved into {}", self.snapshot_dir.display());
+        Ok(())
+    }
+}
+
+impl RestoreConfig {
+    pub(crate) fn execute(self) -> Result<()> {
+        log::info!("Restore ...");
+        for name in &[SNAPSHOT_CHAIN_DIR, SNAPSHOT_STORAGE_DIR] {
+            utils::fs::check_directory(self.snapshot_dir.join(name), true)?;
+        }
+        for name in &[
+            SNAPSHOT_CHAIN_DIR,
+            SNAPSHOT_STORAGE_DIR,
+            SNAPSHOT_TX_POOL_DIR,
+        ] {
+            let target = self.data_dir.join(name);
+            if target.exists() {
+                utils::fs::remove_directory(&target)?;
+            }
+            let source = self.snapshot_dir.join(name);
+            // Copy the snapshot, so it could be restored repeatedly.
+            if source.exists() {
+                utils::fs::copy_directory(&source, &target)?;
+            }
+        }
+        println!(
+            "data directory is restored from {}",
+            self.snapshot_dir.display()
+        );
+        // Keep the data directory locked until all directories are restored.
+        drop(self.lock);
+        Ok(())
+    }
+}
+
+impl CleanConfig {
+    pub(crate) fn execute(self) -> Result<()> {
+        log::info!("Clean ...");
+        utils::fs::remove_directory(&self.data_dir)?;
+        // The lock file is removed with the directory.
+        drop(self.lock);
+        println!("data directory {} is removed", self.data_dir.display());
+        Ok(())
+    }
+}
+
+impl PruneConfig {
+    pub(crate) fn execute(self) -> Result<()> {
+        log::info!("Prune ...");
+        let pruned = self.storage.prune()?;
+        println!("{} transactions are pruned", pruned);
+        Ok(())
+    }
+}
+
+impl GraphConfig {
+    pub(crate) fn execute(self) -> Result<()> {
+        log::info!("Graph ...");
+        let tx_hash = self.tx_hash.map(|tx_hash| tx_hash.pack());
+        let graph =
+            fuzzer::build_graph(&self.storage, tx_hash.as_ref(), self.direction, self.depth)?;
+        let content = graph.render(self.format)?;
+        if let Some(output) = self.output {
+            log::info!("Save the graph into {}", output.display());
+            utils::fs::write_file(&output, content)?;
+        } else {
+            println!("{}", content);
+        }
+        Ok(())
+    }
+}
+
+impl ExportConfig {
+    pub(crate) fn execute(self) -> Result<()> {
+        log::info!("Export ...");
+        let tables = fuzzer::collect_export_tables(&self.storage, self.store.store())?;
+        fuzzer::write_export_tables(self.format, &self.output_dir, &tables)?;
+        for table in &tables {
+            println!("{}: {} rows", table.name, table.rows.len());
+        }
+        Ok(())
+    }
+}
+
+impl DumpChainConfig {
+    pub(crate) fn execute(self) -> Result<()> {
+        log::info!("DumpChain ...");
+        if let Some(ref output) = self.output {
+            fuzzer::dump_chain(self.store.store(), self.format, output)?;
+        }
+        if let Some(rpc) = self.rpc {
+            let mut client = RpcClient::new(rpc);
+            let submitted = fuzzer::submit_chain(self.store.store(), &mut client)?;
+            println!("{} blocks are submitted into the node {}", submitted, rpc);
+        }
+        Ok(())
+    }
+}
+
+// The files written by the subcommand "new-config".
+const NEW_INIT_CONFIG_FILE: &str = "init.yaml";
+const NEW_RUN_CONFIG_FILE: &str = "run.yaml";
+
+impl NewConfigConfig {
+    pub(crate) fn execute(self) -> Result<()> {
+        log::info!("NewConfig ...");
+        let init_file = self.output_dir.join(NEW_INIT_CONFIG_FILE);
+        let run_file = self.output_dir.join(NEW_RUN_CONFIG_FILE);
+        for file in &[&init_file, &run_file] {
+            if file.exists() {
+                let errmsg = format!("the path [{}] alreay exists", file.display());
+                return Err(Error::config(errmsg));
+            }
+        }
+        // The examples are embedded, check them in case they are out of date.
+        let init_config = self.profile.init_config();
+        let run_config = self.profile.run_config();
+        MetaData::from_str(init_config).map_err(|err| {
+            let errmsg = format!(
+                "the init config of profile {} is broken since {}",
+                self.profile, err
+            );
+            Error::runtime(errmsg)
+        })?;
+        RunEnv::from_str(run_config).map_err(|err| {
+            let errmsg = format!("the profile {} is broken since {}", self.profile, err);
+            Error::runtime(errmsg)
+        })?;
+        utils::fs::need_directory(&self.output_dir)?;
+        utils::fs::write_file(&init_file, init_config)?;
+        utils::fs::write_file(&run_file, run_config)?;
+        println!(
+            "config files of profile {} are written into {} and {}",
+            self.profile,
+            init_file.display(),
+            run_file.display()
+        );
+        Ok(())
+    }
+}
+
+// The report written by the subcommand "matrix".
+const MATRIX_REPORT_FILE: &str = "matrix.yaml";
+
+impl MatrixConfig {
+    pub(crate) fn execute(self) -> Result<()> {
+        log::info!("Matrix ...");
+        let (seed, combinations) = self.spec.combinations();
+        if let Some(seed) = seed {
+            log::info!(
+                "[Matrix] sample {} combinations, seed: {}",
+                combinations.len(),
+                seed
+            );
+        } else {
+            log::info!("[Matrix] run all {} combinations", combinations.len());
+        }
+        // Check all combinations first, so the matrix never stops halfway for a broken one.
+        let sessions = combinations
+            .into_iter()
+            .map(|combination| {
+                fuzzer::build_session_configs(&combination, &self.meta_data, &self.run_env)
+                    .map(|configs| (combination, configs))
+                    .map_err(|err| {
+                        let errmsg = format!(
+                            "the combination [{}] is invalid since {}",
+                            combination_summary(&combination),
+                            err
+                        );
+                        Error::config(errmsg)
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        utils::fs::need_directory(&self.output_dir)?;
+        let report_file = self.output_dir.join(MATRIX_REPORT_FILE);
+        let mut report = MatrixReport {
+            seed,
+            ..Default::default()
+        };
+        let total = sessions.len();
+        for (index, (combination, (meta_data, run_env))) in sessions.into_iter().enumerate() {
+            let summary = combination_summary(&combination);
+            log::info!("[Matrix] session {}/{}: {}", index + 1, total, summary);
+            let outcome =
+                fuzzer::run_session(index, combination, &meta_data, &run_env, &self.output_dir)?;
+            if outcome.verdict == Verdict::Passed {
+                log::info!("[Matrix] session {} is passed", index);
+            } else {
+                log::warn!(
+                    "[Matrix] >>> session {} is {} (mismatches: {}): {}",
+                    index,
+                    outcome.verdict,
+                    outcome.mismatches,
+                    summary
+                );
+            }
+            report.add(outcome);
+            // Save the report after each session, so the finished sessions are kept even if
+            // the matrix is interrupted.
+            utils::fs::write_file(&report_file, report.to_string())?;
+        }
+        for (value, stats) in &report.by_value {
+            println!(
+                "{}: sessions: {}, mismatched: {}, hung: {}, failed: {}",
+                value, stats.sessions, stats.mismatched, stats.hung, stats.failed
+            );
+        }
+        println!("report is saved into {}", report_file.display());
+        let unpassed = report.unpassed();
+        if unpassed > 0 {
+            let errmsg = format!("{} of {} sessions are not passed", unpassed, total);
+            Err(Error::runtime(errmsg))
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    result::Result as StdResult,
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{RunReport, WorkloadProfile};
+
+// The count of summaries to keep in the index, the oldest ones are dropped.
+const MAX_SUMMARIES: usize = 64;
+// The threshold of the z-score of a significant shift of a proportion, about 0.3% by chance.
+const Z_THRESHOLD: f64 = 3.0;
+// Too few samples are not enough to compare the proportions.
+const MIN_SAMPLES: u64 = 30;
+// The latency buckets are 2 to 2.5 times apart, so a shift across one bucket is just noise.
+const LATENCY_SHIFT_RATIO: f64 = 4.0;
+// The name of the profile when no workload profile is set.
+const DEFAULT_PROFILE: &str = "default";
+
+// The summary of a run, to be compared with the following runs of the same profile.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RunSummary {
+    pub(crate) profile: String,
+    pub(crate) seed: u64,
+    pub(crate) blocks_mined: u64,
+    pub(crate) txs_accepted: u64,
+    pub(crate) txs_rejected: u64,
+    // The upper bounds of the latency buckets of p50, p90 and p99, in microseconds.
+    pub(crate) latency_micros: Option<[u64; 3]>,
+    pub(crate) reject_reasons: BTreeMap<String, u64>,
+}
+
+// The index of the summaries of past runs, in the order of the runs.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Baselines {
+    summaries: Vec<RunSummary>,
+}
+
+impl RunSummary {
+    // The names and the quantiles of the latency percentiles.
+    pub(crate) const LATENCY_PERCENTILES: [(&'static str, f64); 3] =
+        [("p50", 0.5), ("p90", 0.9), ("p99", 0.99)];
+
+    pub(crate) fn new(
+        profile: Option<WorkloadProfile>,
+        report: &RunReport,
+        latency_micros: Option<[u64; 3]>,
+    ) -> Self {
+        let profile = profile
+            .map(|profile| profile.to_string())
+            .unwrap_or_else(|| DEFAULT_PROFILE.to_owned());
+        Self {
+            profile,
+            seed: report.seed,
+            blocks_mined: report.blocks_mined,
+            txs_accepted: report.txs.accepted,
+            txs_rejected: report.txs.rejected,
+            latency_micros,
+            reject_reasons: report.reject_reasons.clone(),
+        }
+    }
+
+    fn txs_submitted(&self) -> u64 {
+        self.txs_accepted + self.txs_rejected
+    }
+
+    // The significant shifts from the baseline, which are potential regressions of the tx-pool.
+    pub(crate) fn compare(&self, baseline: &Self) -> Vec<String> {
+        let mut shifts = Vec::new();
+        if let Some(z) = z_score(
+            self.txs_accepted,
+            self.txs_submitted(),
+            baseline.txs_accepted,
+            baseline.txs_submitted(),
+        ) {
+            if z.abs() > Z_THRESHOLD {
+                shifts.push(format!(
+                    "acceptance rate {} (baseline: {}, z: {:.2})",
+                    percent(self.txs_accepted, self.txs_submitted()),
+                    percent(baseline.txs_accepted, baseline.txs_submitted()),
+                    z
+                ));
+            }
+        }
+        let reasons = self
+            .reject_reasons
+            .keys()
+            .chain(baseline.reject_reasons.keys())
+            .collect::<BTreeSet<_>>();
+        for reason in reasons {
+            let count = self.reject_reasons.get(reason).copied().unwrap_or(0);
+            let baseline_count = baseline.reject_reasons.get(reason).copied().unwrap_or(0);
+            if let Some(z) = z_score(
+                count,
+                self.txs_rejected,
+                baseline_count,
+                baseline.txs_rejected,
+            ) {
+                if z.abs() > Z_THRESHOLD {
+                    shifts.push(format!(
+                        "share of {} in rejects {} (baseline: {}, z: {:.2})",
+                        reason,
+                        percent(count, self.txs_rejected),
+                        percent(baseline_count, baseline.txs_rejected),
+                        z
+                    ));
+                }
+            }
+        }
+        if let (Some(latency), Some(baseline_latency)) =
+            (self.latency_micros, baseline.latency_micros)
+        {
+            let percentiles = Self::LATENCY_PERCENTILES.iter();
+            for (((name, _), current), previous) in percentiles.zip(&latency).zip(&baseline_latency)
+            {
+                let ratio = *current as f64 / (*previous).max(1) as f64;
+                if ratio >= LATENCY_SHIFT_RATIO || ratio <= 1.0 / LATENCY_SHIFT_RATIO {
+                    shifts.push(format!(
+                        "{} submit latency <= {}us (baseline: <= {}us)",
+                        name, current, previous
+                    ));
+                }
+            }
+        }
+        shifts
+    }
+}
+
+impl Baselines {
+    // The most recent summary of the profile.
+    pub(crate) fn latest(&self, profile: &str) -> Option<&RunSummary> {
+        self.summaries
+            .iter()
+            .rev()
+            .find(|summary| summary.profile == profile)
+    }
+
+    pub(crate) fn add(&mut self, summary: RunSummary) {
+        self.summaries.push(summary);
+        if self.summaries.len() > MAX_SUMMARIES {
+            let overflow = self.summaries.len() - MAX_SUMMARIES;
+            self.summaries.drain(..overflow);
+        }
+    }
+}
+
+// The z-score of the two-proportion z-test, none if any side doesn't have enough samples.
+fn z_score(hits: u64, total: u64, baseline_hits: u64, baseline_total: u64) -> Option<f64> {
+    if total < MIN_SAMPLES || baseline_total < MIN_SAMPLES {
+        return None;
+    }
+    let p1 = hits as f64 / total as f64;
+    let p2 = baseline_hits as f64 / baseline_total as f64;
+    let pooled = (hits + baseline_hits) as f64 / (total + baseline_total) as f64;
+    let se = (pooled * (1.0 - pooled) * (1.0 / total as f64 + 1.0 / baseline_total as f64)).sqrt();
+    // All samples are the same on both sides if it's zero.
+    if se > 0.0 {
+        Some((p1 - p2) / se)
+    } else {
+        None
+    }
+}
+
+fn percent(hits: u64, total: u64) -> String {
+    if total == 0 {
+        "n/a".to_owned()
+    } else {
+        format!("{:.2}%", hits as f64 * 100.0 / total as f64)
+    }
+}
+
+impl FromStr for Baselines {
+    type Err = serde_yaml::Error;
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        serde_yaml::from_str(s)
+    }
+}
+
+impl fmt::Display for Baselines {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        serde_yaml::to_string(self)
+            .map_err(|_| fmt::Error)
+            .and_then(|s| write!(f, "{}", s))
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::BTreeMap;
+
+use ckb_types::core::BlockNumber;
+use serde::{Deserialize, Serialize};
+
+use super::{Adaptive, RandomGenerator};
+
+// The branches of the transaction generator which lead to special transactions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Branch {
+    NoInputs,
+    NoOutputs,
+    BurnedInput,
+    DeadInput,
+    FailedTxInput,
+    OutOfRangeInput,
+    ImmatureInput,
+    DuplicatedInput,
+    CapacityOverflow,
+    InsufficientCapacity,
+    NoLockScript,
+    FailedLockScript,
+    NoTypeScript,
+    FailedTypeScript,
+    DataHashType,
+    TypeHashType,
+    Data1HashType,
+    FuzzedArgs,
+    CustomTypeScript,
+    Secp256k1Lock,
+    MultisigLock,
+    WrongSignature,
+    ShortSignatures,
+    WrongVersion,
+    EmptyWitnesses,
+    ExtraWitnesses,
+    LongChain,
+    CpfpPackage,
+    DoubleSpend,
+    Burst,
+    NearLimit,
+    Split,
+    Consolidate,
+    DaoDeposit,
+    DaoPrepare,
+    DaoWithdraw,
+    TypeIdCreate,
+    TypeIdUpdate,
+    TypeIdDestroy,
+    WrongTypeId,
+    DuplicateTypeId,
+    AbsoluteTimeLock,
+    RelativeTimeLock,
+}
+
+// How many times a branch was exercised, by the results of the transactions.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct BranchCounts {
+    pub(crate) accepted: u64,
+    pub(crate) rejected: u64,
+}
+
+// The coverage of all branches of the transaction generator.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(transparent)]
+pub(crate) struct GenerationBias {
+    branches: BTreeMap<String, BranchCounts>,
+}
+
+// Boost the chances of the rare branches which were rarely exercised recently.
+pub(crate) struct BiasAdapter {
+    every_n_blocks: BlockNumber,
+    min_hits: u64,
+    max_boost: u32,
+    // The hits of the branches at the last adaption, indexed by the branches.
+    last_hits: Vec<u64>,
+}
+
+impl Branch {
+    pub(crate) const ALL: &'static [Self] = &[
+        Self::NoInputs,
+        Self::NoOutputs,
+        Self::BurnedInput,
+        Self::DeadInput,
+        Self::FailedTxInput,
+        Self::OutOfRangeInput,
+        Self::ImmatureInput,
+        Self::DuplicatedInput,
+        Self::CapacityOverflow,
+        Self::InsufficientCapacity,
+        Self::NoLockScript,
+        Self::FailedLockScript,
+        Self::NoTypeScript,
+        Self::FailedTypeScript,
+        Self::DataHashType,
+        Self::TypeHashType,
+        Self::Data1HashType,
+        Self::FuzzedArgs,
+        Self::CustomTypeScript,
+        Self::Secp256k1Lock,
+        Self::MultisigLock,
+        Self::WrongSignature,
+        Self::ShortSignatures,
+        Self::WrongVersion,
+        Self::EmptyWitnesses,
+        Self::ExtraWitnesses,
+        Self::LongChain,
+        Self::CpfpPackage,
+        Self::DoubleSpend,
+        Self::Burst,
+        Self::NearLimit,
+        Self::Split,
+        Self::Consolidate,
+        Self::DaoDeposit,
+        Self::DaoPrepare,
+        Self::DaoWithdraw,
+        Self::TypeIdCreate,
+        Self::TypeIdUpdate,
+        Self::TypeIdDestroy,
+        Self::WrongTypeId,
+        Self::DuplicateTypeId,
+        Self::AbsoluteTimeLock,
+        Self::RelativeTimeLock,
+    ];
+
+    // The branches which are rare, and their chances could be boosted.
+    pub(crate) const ADAPTABLE: &'static [Self] = &[
+        Self::NoInputs,
+        Self::NoOutputs,
+        Self::BurnedInput,
+        Self::DeadInput,
+        Self::FailedTxInput,
+        Self::OutOfRangeInput,
+        Self::ImmatureInput,
+        Self::DuplicatedInput,
+        Self::CapacityOverflow,
+        Self::InsufficientCapacity,
+        Self::NoLockScript,
+        Self::FailedLockScript,
+        Self::WrongSignature,
+        Self::ShortSignatures,
+        Self::WrongVersion,
+    ];
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::NoInputs => "no_inputs",
+            Self::NoOutputs => "no_outputs",
+            Self::BurnedInput => "burned_input",
+            Self::DeadInput => "dead_input",
+            Self::FailedTxInput => "failed_tx_input",
+            Self::OutOfRangeInput => "out_of_range_input",
+            Self::ImmatureInput => "immature_input",
+            Self::DuplicatedInput => "duplicated_input",
+            Self::CapacityOverflow => "capacity_overflow",
+            Self::InsufficientCapacity => "insufficient_capacity",
+            Self::NoLockScript => "no_lock_script",
+            Self::FailedLockScript => "failed_lock_script",
+            Self::NoTypeScript => "no_type_script",
+            Self::FailedTypeScript => "failed_type_script",
+            Self::DataHashType => "data_hash_type",
+            Self::TypeHashType => "type_hash_type",
+            Self::Data1HashType => "data1_hash_type",
+            Self::FuzzedArgs => "fuzzed_args",
+            Self::CustomTypeScript => "custom_type_script",
+            Self::Secp256k1Lock => "secp256k1_lock",
+            Self::MultisigLock => "multisig_lock",
+            Self::WrongSignature => "wrong_signature",
+            Self::ShortSignatures => "short_signatures",
+            Self::WrongVersion => "wrong_version",
+            Self::EmptyWitnesses => "empty_witnesses",
+            Self::ExtraWitnesses => "extra_witnesses",
+            Self::LongChain => "long_chain",
+            Self::CpfpPackage => "cpfp_package",
+            Self::DoubleSpend => "double_spend",
+            Self::Burst => "burst",
+            Self::NearLimit => "near_limit",
+            Self::Split => "split",
+            Self::Consolidate => "consolidate",
+            Self::DaoDeposit => "dao_deposit",
+            Self::DaoPrepare => "dao_prepare",
+            Self::DaoWithdraw => "dao_withdraw",
+            Self::TypeIdCreate => "type_id_create",
+            Self::TypeIdUpdate => "type_id_update",
+            Self::TypeIdDestroy => "type_id_destroy",
+            Self::WrongTypeId => "wrong_type_id",
+            Self::DuplicateTypeId => "duplicate_type_id",
+            Self::AbsoluteTimeLock => "absolute_time_lock",
+            Self::RelativeTimeLock => "relative_time_lock",
+        }
+    }
+}
+
+impl GenerationBias {
+    // A branch is counted once for each transaction, even it's exercised several times.
+    pub(crate) fn add_tx(&mut self, branches: &[Branch], accepted: bool) {
+        for branch in Branch::ALL {
+            if !branches.contains(branch) {
+                continue;
+            }
+            let counts = self.branches.entry(branch.name().to_owned()).or_default();
+            if accepted {
+                counts.accepted += 1;
+            } else {
+                counts.rejected += 1;
+            }
+        }
+    }
+
+    // How many transactions exercised the branch.
+    pub(crate) fn hits(&self, branch: Branch) -> u64 {
+        self.branches
+            .get(branch.name())
+            .map(|counts| counts.accepted + counts.rejected)
+            .unwrap_or(0)
+    }
+
+    // The branches which were never exercised.
+    pub(crate) fn uncovered(&self) -> Vec<&'static str> {
+        Branch::ALL
+            .iter()
+            .map(|branch| branch.name())
+            .filter(|name| !self.branches.contains_key(*name))
+            .collect()
+    }
+
+    pub(crate) fn trace(&self) {
+        for (name, counts) in &self.branches {
+            log::info!(
+                "[Bias] {}: accepted: {}, rejected: {}",
+                name,
+                counts.accepted,
+                counts.rejected
+            );
+        }
+        let uncovered = self.uncovered();
+        if !uncovered.is_empty() {
+            log::warn!(
+                "[Bias] branches are never exercised: {}",
+                uncovered.join(", ")
+            );
+        }
+    }
+}
+
+impl BiasAdapter {
+    pub(crate) fn new(config: &Adaptive) -> Self {
+        Self {
+            every_n_blocks: config.every_n_blocks,
+            min_hits: config.min_hits,
+            max_boost: config.max_boost.max(1),
+            last_hits: vec![0; Branch::ALL.len()],
+        }
+    }
+
+    pub(crate) fn is_due(&self, blocks: BlockNumber) -> bool {
+        self.every_n_blocks > 0 && blocks % self.every_n_blocks == 0
+    }
+
+    // Double the boost of a branch if it's under-exercised since the last adaption, halve it
+    // if it's exercised much more than enough.
+    pub(crate) fn adapt(&mut self, bias: &GenerationBias, rg: &RandomGenerator) {
+        for branch in Branch::ADAPTABLE {
+            let hits = bias.hits(*branch);
+            let recent = hits - self.last_hits[*branch as usize];
+            self.last_hits[*branch as usize] = hits;
+            let boost = rg.boost(*branch);
+            let new_boost = if recent < self.min_hits {
+                boost.saturating_mul(2).min(self.max_boost)
+            } else if recent > self.min_hits.saturating_mul(4) {
+                (boost / 2).max(1)
+            } else {
+                boost
+            };
+            if new_boost != boost {
+                log::info!(
+                    "[Bias] {} is exercised {} times recently, boost its chance from x{} to x{}",
+                    branch.name(),
+                    recent,
+                    boost,
+                    new_boost
+                );
+                rg.set_boost(*branch, new_boost);
+            }
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::{io, result::Result as StdResult};
+
+use ckb_types::{core::BlockView, packed, prelude::*};
+
+use super::cache::{read_u32, write_u32};
+use crate::error::{Error, Result};
+
+// The differences between the block template and the block which is actually committed.
+#[derive(Debug, Clone, Default)]
+pub struct BlockDelta {
+    // Transactions in the template but not in the block.
+    pub(crate) dropped_txs: Vec<packed::Byte32>,
+    // Transactions in the block but not in the template.
+    pub(crate) added_txs: Vec<packed::Byte32>,
+    // Proposals in the template but not in the block.
+    pub(crate) dropped_proposals: Vec<packed::ProposalShortId>,
+    // Proposals in the block but not in the template.
+    pub(crate) added_proposals: Vec<packed::ProposalShortId>,
+}
+
+impl BlockDelta {
+    pub(crate) fn new(template: &BlockView, block: &BlockView) -> Self {
+        let template_txs = template.tx_hashes();
+        let block_txs = block.tx_hashes();
+        let template_proposals = template.data().proposals().into_iter().collect::<Vec<_>>();
+        let block_proposals = block.data().proposals().into_iter().collect::<Vec<_>>();
+        Self {
+            dropped_txs: difference(template_txs, block_txs),
+            added_txs: difference(block_txs, template_txs),
+            dropped_proposals: difference(&template_proposals, &block_proposals),
+            added_proposals: difference(&block_proposals, &template_proposals),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dropped_txs.is_empty()
+            && self.added_txs.is_empty()
+            && self.dropped_proposals.is_empty()
+            && self.added_proposals.is_empty()
+    }
+
+    pub(crate) fn from_slice(mut slice: &[u8]) -> Result<Self> {
+        let dropped_txs = read_items(&mut slice, packed::Byte32::TOTAL_SIZE)?;
+        let added_txs = read_items(&mut slice, packed::Byte32::TOTAL_SIZE)?;
+        let dropped_proposals = read_items(&mut slice, packed::ProposalShortId::TOTAL_SIZE)?;
+        let added_proposals = read_items(&mut slice, packed::ProposalShortId::TOTAL_SIZE)?;
+        if !slice.is_empty() {
+            return Err(Error::broken_since("BlockDelta", "too much data"));
+        }
+        Ok(Self {
+            dropped_txs,
+            added_txs,
+            dropped_proposals,
+            added_proposals,
+        })
+    }
+
+    pub(crate) fn to_vec(&self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.write_into(&mut bytes)
+            .map(|_| bytes)
+            .map_err(Error::runtime)
+    }
+
+    fn write_into<W: io::Write>(&self, output: &mut W) -> StdResult<(), io::Error> {
+        write_items(output, &self.dropped_txs)?;
+        write_items(output, &self.added_txs)?;
+        write_items(output, &self.dropped_proposals)?;
+        write_items(output, &self.added_proposals)?;
+        Ok(())
+    }
+}
+
+fn difference<T: PartialEq + Clone>(lhs: &[T], rhs: &[T]) -> Vec<T> {
+    lhs.iter()
+        .filter(|item| !rhs.contains(item))
+        .cloned()
+        .collect()
+}
+
+fn read_items<T: Entity>(slice: &mut &[u8], item_size: usize) -> Result<Vec<T>> {
+    let count = read_u32(slice)? as usize;
+    let size = count * item_size;
+    if slice.len() < 4 + size {
+        return Err(Error::broken_since("BlockDelta", "no enough data"));
+    }
+    let items = slice[4..4 + size]
+        .chunks(item_size)
+        .map(|chunk| T::from_slice(chunk).map_err(Error::storage))
+        .collect::<Result<Vec<_>>>()?;
+    *slice = &slice[4 + size..];
+    Ok(items)
+}
+
+fn write_items<W: io::Write, T: Entity>(output: &mut W, items: &[T]) -> StdResult<(), io::Error> {
+    write_u32(output, items.len() as u32)?;
+    for item in items {
+        output.write_all(item.as_slice())?;
+    }
+    Ok(())
+}
@@ -1,13 +1,29 @@
-use std::{fmt, io, result::Result as StdResult};
+use std::{collections::BTreeMap, fmt, io, result::Result as StdResult};
+
+use ckb_types::core::{BlockNumber, EpochNumberWithFraction};
 
 use crate::error::{Error, Result};
 
+// The statuses follow the states of the tx-pool:
+//
+// - Pending -> Proposed -> Committed, and back to Pending if the proposals are expired.
+// - Pending or Proposed -> Evicted, when the tx-pool is full.
+// - Orphan -> Expired, when it's in the orphan pool for too long.
 #[derive(Debug, Clone)]
 pub(crate) enum TxStatus {
     // The transaction will be committed in chain but it doesn't now.
     Pending(TxOutputsStatus),
+    // The transaction is proposed in chain, and the proposals are not expired.
+    Proposed(TxOutputsStatus),
     // The transaction is committed in chain.
     Committed(TxOutputsStatus),
+    // The transaction spends the cells which are unknown to the tx-pool, so it's kept in the
+    // orphan pool since the timestamp (in milliseconds), but it couldn't be committed.
+    Orphan(u64),
+    // The transaction was evicted from the tx-pool since the tx-pool is full.
+    Evicted,
+    // The orphan transaction was expired from the orphan pool.
+    Expired,
     // The transaction couldn't be committed in chain.
     Failed,
 }
@@ -22,44 +38,161 @@ pub(crate) enum CellStatus {
     Dead,
 }
 
+// The stage of the transaction of a live cell, in the index of live cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CellStage {
+    Pending,
+    Committed,
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct TxOutputsStatus {
     // The statuses of output cells.
     // If A cell is spent, then its status is `false` (0), otherwise its status is `true` (1).
     pub(crate) statuses: Vec<CellStatus>,
+    // The block of a committed cellbase, its outputs couldn't be spent until they are mature.
+    // None for the other transactions, and the cellbases which are always mature.
+    pub(crate) cellbase: Option<CellbaseOrigin>,
+}
+
+// The block which commits a cellbase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct CellbaseOrigin {
+    pub(crate) block_number: BlockNumber,
+    pub(crate) epoch: EpochNumberWithFraction,
 }
 
 #[derive(Default, Debug, Clone)]
 pub(crate) struct CacheStats {
     tx_pending_cnt: usize,
+    tx_proposed_cnt: usize,
     tx_committed_cnt: usize,
+    tx_orphan_cnt: usize,
+    tx_evicted_cnt: usize,
+    tx_expired_cnt: usize,
     tx_failed_cnt: usize,
     cell_live_cnt: usize,
+    // The counts of the rejected transactions, by the reasons.
+    rejects: BTreeMap<String, u64>,
 }
 
 impl TxStatus {
-    pub(crate) fn new_committed(cells_count: usize) -> Self {
-        Self::Committed(TxOutputsStatus::new_all_live(cells_count))
+    pub(crate) fn new_committed_cellbase(cells_count: usize, origin: CellbaseOrigin) -> Self {
+        let mut inner = TxOutputsStatus::new_all_live(cells_count);
+        inner.cellbase = Some(origin);
+        Self::Committed(inner)
     }
 
-    pub(crate) fn is_invalid(&self) -> bool {
-        match self {
-            Self::Pending(ref inner) | Self::Committed(ref inner) => inner.is_invalid(),
-            Self::Failed => true,
+    // The origin of a committed cellbase.
+    pub(crate) fn cellbase_origin(&self) -> Option<CellbaseOrigin> {
+        if let Self::Committed(ref inner) = self {
+            inner.cellbase
+        } else {
+            None
         }
     }
 
-    pub(crate) fn spent(&mut self, cell_index: usize) {
+    // Track the origin of a committed cellbase, returns whether it's changed.
+    pub(crate) fn set_cellbase_origin(&mut self, origin: CellbaseOrigin) -> bool {
         match self {
-            Self::Pending(ref mut inner) | Self::Committed(ref mut inner) => {
-                inner.spent(cell_index);
+            Self::Committed(ref mut inner) if inner.cellbase.is_none() => {
+                inner.cellbase = Some(origin);
+                true
             }
-            Self::Failed => {
-                panic!("the cell should be in an existed transaction before spent");
+            _ => false,
+        }
+    }
+
+    // The statuses of the outputs, none if the transaction is not in the tx-pool or in chain.
+    pub(crate) fn outputs(&self) -> Option<&TxOutputsStatus> {
+        match self {
+            Self::Pending(ref inner) | Self::Proposed(ref inner) | Self::Committed(ref inner) => {
+                Some(inner)
             }
+            Self::Orphan(_) | Self::Evicted | Self::Expired | Self::Failed => None,
         }
     }
 
+    fn outputs_mut(&mut self) -> Option<&mut TxOutputsStatus> {
+        match self {
+            Self::Pending(ref mut inner)
+            | Self::Proposed(ref mut inner)
+            | Self::Committed(ref mut inner) => Some(inner),
+            Self::Orphan(_) | Self::Evicted | Self::Expired | Self::Failed => None,
+        }
+    }
+
+    // The transaction is in the tx-pool, and it could be committed.
+    pub(crate) fn is_uncommitted(&self) -> bool {
+        matches!(self, Self::Pending(_) | Self::Proposed(_))
+    }
+
+    // The name of the status, without the statuses of the outputs.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::Pending(_) => "pending",
+            Self::Proposed(_) => "proposed",
+            Self::Committed(_) => "committed",
+            Self::Orphan(_) => "orphan",
+            Self::Evicted => "evicted",
+            Self::Expired => "expired",
+            Self::Failed => "failed",
+        }
+    }
+
+    pub(crate) fn is_invalid(&self) -> bool {
+        self.outputs()
+            .map(TxOutputsStatus::is_invalid)
+            .unwrap_or(true)
+    }
+
+    pub(crate) fn spent(&mut self, cell_index: usize) {
+        self.outputs_mut()
+            .expect("the cell should be in an existed transaction before spent")
+            .spent(cell_index);
+    }
+
+    pub(crate) fn revive(&mut self, cell_index: usize) {
+        self.outputs_mut()
+            .expect("the cell should be in an existed transaction before revived")
+            .revive(cell_index);
+    }
+
+    pub(crate) fn live_cells_count(&self) -> usize {
+        self.outputs().map(TxOutputsStatus::live_count).unwrap_or(0)
+    }
+
+    // The stages of all output cells by the indexes, none if the cell is not live.
+    pub(crate) fn live_cell_stages(&self) -> Vec<Option<CellStage>> {
+        let (inner, stage) = match self {
+            Self::Pending(ref inner) | Self::Proposed(ref inner) => (inner, CellStage::Pending),
+            Self::Committed(ref inner) => (inner, CellStage::Committed),
+            Self::Orphan(_) | Self::Evicted | Self::Expired | Self::Failed => return Vec::new(),
+        };
+        inner
+            .statuses
+            .iter()
+            .map(|st| {
+                if *st == CellStatus::Live {
+                    Some(stage)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    // Propose or unpropose an uncommitted transaction, returns whether it's changed.
+    pub(crate) fn set_proposed(&mut self, proposed: bool) -> bool {
+        let changed = match self {
+            Self::Pending(ref inner) if proposed => Self::Proposed(inner.to_owned()),
+            Self::Proposed(ref inner) if !proposed => Self::Pending(inner.to_owned()),
+            _ => return false,
+        };
+        *self = changed;
+        true
+    }
+
     pub(crate) fn from_slice(slice: &[u8]) -> Result<Self> {
         if slice.is_empty() {
             return Err(Error::broken_since("TxStatus", "no enough data"));
@@ -67,6 +200,41 @@ impl TxStatus {
         let ret = match slice[0] {
             0x00 => Self::Pending(TxOutputsStatus::from_slice(&slice[1..])?),
             0x01 => Self::Committed(TxOutputsStatus::from_slice(&slice[1..])?),
+            0x02 => Self::Proposed(TxOutputsStatus::from_slice(&slice[1..])?),
+            0x03 => {
+                if slice.len() != 9 {
+                    let errmsg = format!(
+                        "incorrect data size of orphan (expect: 9, actual: {})",
+                        slice.len()
+                    );
+                    return Err(Error::broken_since("TxStatus", &errmsg));
+                }
+                let mut b = [0u8; 8];
+                b.copy_from_slice(&slice[1..]);
+                Self::Orphan(u64::from_le_bytes(b))
+            }
+            0x04 => {
+                if slice.len() < 17 {
+                    let errmsg = format!(
+                        "incorrect data size of cellbase (expect: >= 17, actual: {})",
+                        slice.len()
+                    );
+                    return Err(Error::broken_since("TxStatus", &errmsg));
+                }
+                let mut b = [0u8; 8];
+                b.copy_from_slice(&slice[1..9]);
+                let block_number = u64::from_le_bytes(b);
+                b.copy_from_slice(&slice[9..17]);
+                let epoch = EpochNumberWithFraction::from_full_value(u64::from_le_bytes(b));
+                let mut inner = TxOutputsStatus::from_slice(&slice[17..])?;
+                inner.cellbase = Some(CellbaseOrigin {
+                    block_number,
+                    epoch,
+                });
+                Self::Committed(inner)
+            }
+            0xfd => Self::Evicted,
+            0xfe => Self::Expired,
             0xff => Self::Failed,
             x => {
                 let errmsg = format!("transaction status type is unknown [{}]", x);
@@ -90,9 +258,29 @@ impl TxStatus {
                 inner.write_into(output)?;
             }
             Self::Committed(ref inner) => {
-                output.write_all(&[0x01])?;
+                if let Some(origin) = inner.cellbase {
+                    output.write_all(&[0x04])?;
+                    output.write_all(&origin.block_number.to_le_bytes())?;
+                    output.write_all(&origin.epoch.full_value().to_le_bytes())?;
+                } else {
+                    output.write_all(&[0x01])?;
+                }
+                inner.write_into(output)?;
+            }
+            Self::Proposed(ref inner) => {
+                output.write_all(&[0x02])?;
                 inner.write_into(output)?;
             }
+            Self::Orphan(since) => {
+                output.write_all(&[0x03])?;
+                output.write_all(&since.to_le_bytes())?;
+            }
+            Self::Evicted => {
+                output.write_all(&[0xfd])?;
+            }
+            Self::Expired => {
+                output.write_all(&[0xfe])?;
+            }
             Self::Failed => {
                 output.write_all(&[0xff])?;
             }
@@ -127,12 +315,39 @@ impl TryFrom<u8> for CellStatus {
     }
 }
 
+impl Into<u8> for CellStage {
+    fn into(self) -> u8 {
+        match self {
+            Self::Pending => 0x00,
+            Self::Committed => 0x01,
+        }
+    }
+}
+
+impl TryFrom<u8> for CellStage {
+    type Error = Error;
+    fn try_from(value: u8) -> Result<Self> {
+        let ret = match value {
+            0x00 => Self::Pending,
+            0x01 => Self::Committed,
+            x => {
+                let errmsg = format!("cell stage is unknown [{}]", x);
+                return Err(Error::broken_since("CellStage", &errmsg));
+            }
+        };
+        Ok(ret)
+    }
+}
+
 impl TxOutputsStatus {
     const NAME: &'static str = "TxOutputsStatus";
 
     fn new_all_live(count: usize) -> Self {
         let statuses = vec![CellStatus::Live; count];
-        Self { statuses }
+        Self {
+            statuses,
+            cellbase: None,
+        }
     }
 
     pub(crate) fn count(&self) -> usize {
@@ -154,6 +369,20 @@ impl TxOutputsStatus {
         self.statuses[index] = CellStatus::Dead;
     }
 
+    fn revive(&mut self, index: usize) {
+        if self.statuses[index] != CellStatus::Dead {
+            panic!("the cell should be dead before revived");
+        }
+        self.statuses[index] = CellStatus::Live;
+    }
+
+    fn live_count(&self) -> usize {
+        self.statuses
+            .iter()
+            .filter(|st| matches!(st, CellStatus::Live))
+            .count()
+    }
+
     fn from_slice(slice: &[u8]) -> Result<Self> {
         let count = read_u32(slice)? as usize;
         let expected = 4 + (count + 3) / 4;
@@ -185,7 +414,10 @@ impl TxOutputsStatus {
             ));
         }
         statuses.truncate(count);
-        Ok(Self { statuses })
+        Ok(Self {
+            statuses,
+            cellbase: None,
+        })
     }
 
     fn write_into<W: io::Write>(&self, output: &mut W) -> StdResult<(), io::Error> {
@@ -207,16 +439,72 @@ impl TxOutputsStatus {
     }
 }
 
+impl fmt::Display for TxStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Pending(ref inner) => write!(f, "pending {}", inner),
+            Self::Proposed(ref inner) => write!(f, "proposed {}", inner),
+            Self::Committed(ref inner) => {
+                if let Some(origin) = inner.cellbase {
+                    write!(f, "committed cellbase at {} {}", origin.block_number, inner)
+                } else {
+                    write!(f, "committed {}", inner)
+                }
+            }
+            Self::Orphan(since) => write!(f, "orphan since {}", since),
+            Self::Evicted => write!(f, "evicted"),
+            Self::Expired => write!(f, "expired"),
+            Self::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+impl fmt::Display for TxOutputsStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let statuses = self
+            .statuses
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        write!(f, "[{}]", statuses.join(", "))
+    }
+}
+
+impl fmt::Display for CellStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Live => write!(f, "live"),
+            Self::Burn => write!(f, "burn"),
+            Self::Dead => write!(f, "dead"),
+        }
+    }
+}
+
 impl fmt::Display for CacheStats {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "tx.pending: {}, tx.committed: {}, tx.failed: {}, cell.live: {}",
+            "tx.pending: {}, tx.proposed: {}, tx.committed: {}, tx.orphan: {}, tx.evicted: {}, \
+            tx.expired: {}, tx.failed: {}, cell.live: {}",
             self.tx_pending_cnt(),
+            self.tx_proposed_cnt(),
             self.tx_committed_cnt(),
+            self.tx_orphan_cnt(),
+            self.tx_evicted_cnt(),
+            self.tx_expired_cnt(),
             self.tx_failed_cnt(),
             self.cell_live_cnt()
-        )
+        )?;
+        if !self.rejects.is_empty() {
+            let rejects = self
+                .rejects
+                .iter()
+                .map(|(reason, count)| format!("{}: {}", reason, count))
+                .collect::<Vec<_>>()
+                .join(", ");
+            write!(f, ", rejects: {{{}}}", rejects)?;
+        }
+        Ok(())
     }
 }
 
@@ -225,10 +513,26 @@ impl CacheStats {
         self.tx_pending_cnt
     }
 
+    pub(crate) fn tx_proposed_cnt(&self) -> usize {
+        self.tx_proposed_cnt
+    }
+
     pub(crate) fn tx_committed_cnt(&self) -> usize {
         self.tx_committed_cnt
     }
 
+    pub(crate) fn tx_orphan_cnt(&self) -> usize {
+        self.tx_orphan_cnt
+    }
+
+    pub(crate) fn tx_evicted_cnt(&self) -> usize {
+        self.tx_evicted_cnt
+    }
+
+    pub(crate) fn tx_expired_cnt(&self) -> usize {
+        self.tx_expired_cnt
+    }
+
     pub(crate) fn tx_failed_cnt(&self) -> usize {
         self.tx_failed_cnt
     }
@@ -237,57 +541,58 @@ impl CacheStats {
         self.cell_live_cnt
     }
 
-    pub(crate) fn submit_tx(&mut self, inputs_count: usize, tx_status: &TxStatus) -> Result<()> {
-        self.tx_pending_cnt += 1;
-        self.cell_live_cnt -= inputs_count;
+    pub(crate) fn rejects(&self) -> &BTreeMap<String, u64> {
+        &self.rejects
+    }
+
+    pub(crate) fn reject(&mut self, reason: &str) {
+        *self.rejects.entry(reason.to_owned()).or_default() += 1;
+    }
+
+    // The counter of the transactions in the status.
+    fn tx_cnt_mut(&mut self, tx_status: &TxStatus) -> &mut usize {
         match tx_status {
-            TxStatus::Pending(ref inner) | TxStatus::Committed(ref inner) => {
-                self.load_cells(&inner.statuses);
-            }
-            TxStatus::Failed => {
-                self.tx_failed_cnt += 1;
-            }
+            TxStatus::Pending(_) => &mut self.tx_pending_cnt,
+            TxStatus::Proposed(_) => &mut self.tx_proposed_cnt,
+            TxStatus::Committed(_) => &mut self.tx_committed_cnt,
+            TxStatus::Orphan(_) => &mut self.tx_orphan_cnt,
+            TxStatus::Evicted => &mut self.tx_evicted_cnt,
+            TxStatus::Expired => &mut self.tx_expired_cnt,
+            TxStatus::Failed => &mut self.tx_failed_cnt,
         }
+    }
+
+    pub(crate) fn submit_tx(&mut self, inputs_count: usize, tx_status: &TxStatus) -> Result<()> {
+        self.cell_live_cnt -= inputs_count;
+        self.load_tx(tx_status);
         Ok(())
     }
 
     pub(crate) fn remove_tx(&mut self, tx_status: &TxStatus) {
-        match tx_status {
-            TxStatus::Pending(..) => {
-                self.tx_pending_cnt -= 1;
-            }
-            TxStatus::Committed(..) => {
-                self.tx_committed_cnt -= 1;
-            }
-            TxStatus::Failed => {
-                self.tx_failed_cnt -= 1;
-            }
-        }
+        *self.tx_cnt_mut(tx_status) -= 1;
     }
 
-    pub(crate) fn commit_cellbase(&mut self, outputs_count: usize) {
-        self.tx_committed_cnt += 1;
-        self.cell_live_cnt += outputs_count;
+    // Move a transaction from a status to another, the cells are not changed.
+    pub(crate) fn change_tx(&mut self, old: &TxStatus, new: &TxStatus) {
+        *self.tx_cnt_mut(old) -= 1;
+        *self.tx_cnt_mut(new) += 1;
     }
 
-    pub(crate) fn commit_pending(&mut self) {
-        self.tx_pending_cnt -= 1;
+    pub(crate) fn evict_tx(&mut self, tx_status: &TxStatus, inputs_count: usize) {
+        self.change_tx(tx_status, &TxStatus::Evicted);
+        self.cell_live_cnt += inputs_count;
+        self.cell_live_cnt -= tx_status.live_cells_count();
+    }
+
+    pub(crate) fn commit_cellbase(&mut self, outputs_count: usize) {
         self.tx_committed_cnt += 1;
+        self.cell_live_cnt += outputs_count;
     }
 
     pub(crate) fn load_tx(&mut self, tx_status: &TxStatus) {
-        match tx_status {
-            TxStatus::Pending(ref inner) => {
-                self.tx_pending_cnt += 1;
-                self.load_cells(&inner.statuses);
-            }
-            TxStatus::Committed(ref inner) => {
-                self.tx_committed_cnt += 1;
-                self.load_cells(&inner.statuses);
-            }
-            TxStatus::Failed => {
-                self.tx_failed_cnt += 1;
-            }
+        *self.tx_cnt_mut(tx_status) += 1;
+        if let Some(inner) = tx_status.outputs() {
+            self.load_cells(&inner.statuses);
         }
     }
 
@@ -300,13 +605,13 @@ impl CacheStats {
     }
 }
 
-fn write_u32<W: io::Write>(output: &mut W, num: u32) -> StdResult<(), io::Error> {
+pub(super) fn write_u32<W: io::Write>(output: &mut W, num: u32) -> StdResult<(), io::Error> {
     let num_bytes = num.to_le_bytes();
     output.write_all(&num_bytes)?;
     Ok(())
 }
 
-fn read_u32(slice: &[u8]) -> Result<u32> {
+pub(crate) fn read_u32(slice: &[u8]) -> Result<u32> {
     if slice.len() < 4 {
         return Err(Error::broken_since("u32", "no enough data"));
     }
@@ -0,0 +1,51 @@
+use ckb_types::{core::BlockNumber, packed, prelude::*};
+
+use crate::error::{Error, Result};
+
+// The state which is persisted with each confirmed block, to resume an interrupted run.
+//
+// The overlays only live within a step, so all pending transactions are in the storage when
+// a block is confirmed.
+#[derive(Debug, Clone)]
+pub(crate) struct Checkpoint {
+    // The last confirmed block.
+    pub(crate) block_number: BlockNumber,
+    pub(crate) block_hash: packed::Byte32,
+    // The random generator is reseeded with it when the checkpoint is created.
+    pub(crate) rng_seed: u64,
+}
+
+impl Checkpoint {
+    const NAME: &'static str = "Checkpoint";
+    const SIZE: usize = 8 + 32 + 8;
+
+    pub(crate) fn from_slice(slice: &[u8]) -> Result<Self> {
+        if slice.len() != Self::SIZE {
+            let reason = format!(
+                "incorrect data size (expect: {}, actual: {})",
+                Self::SIZE,
+                slice.len()
+            );
+            return Err(Error::broken_since(Self::NAME, &reason));
+        }
+        let mut number_bytes = [0u8; 8];
+        number_bytes.copy_from_slice(&slice[..8]);
+        let block_hash = packed::Byte32::from_slice(&slice[8..40])
+            .map_err(|err| Error::broken_since(Self::NAME, &err.to_string()))?;
+        let mut seed_bytes = [0u8; 8];
+        seed_bytes.copy_from_slice(&slice[40..]);
+        Ok(Self {
+            block_number: u64::from_le_bytes(number_bytes),
+            block_hash,
+            rng_seed: u64::from_le_bytes(seed_bytes),
+        })
+    }
+
+    pub(crate) fn to_vec(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::SIZE);
+        bytes.extend_from_slice(&self.block_number.to_le_bytes());
+        bytes.extend_from_slice(self.block_hash.as_slice());
+        bytes.extend_from_slice(&self.rng_seed.to_le_bytes());
+        bytes
+    }
+}
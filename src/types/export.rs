@@ -0,0 +1,126 @@
+use std::{result::Result as StdResult, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExportFormat {
+    // A CSV file for each table.
+    Csv,
+    // All tables in one SQLite database.
+    Sqlite,
+}
+
+// How the blocks are dumped, one block after another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ChainFormat {
+    // The molecule bytes, each block starts with its total size, so they are just concatenated.
+    Molecule,
+    // A JSON line for each block, the same as the exported files which `ckb import` reads.
+    Json,
+}
+
+// A cell of a table, the types are the storage classes of SQLite.
+#[derive(Debug, Clone)]
+pub(crate) enum ExportValue {
+    Null,
+    Integer(i64),
+    Text(String),
+}
+
+// The rows of a table, each row has a value for each column.
+#[derive(Debug, Clone)]
+pub(crate) struct ExportTable {
+    pub(crate) name: &'static str,
+    pub(crate) columns: &'static [&'static str],
+    pub(crate) rows: Vec<Vec<ExportValue>>,
+}
+
+impl ExportFormat {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Sqlite => "sqlite",
+        }
+    }
+}
+
+impl ExportValue {
+    // Empty for null, and quoted if it has any separators or quotes.
+    pub(crate) fn to_csv(&self) -> String {
+        match self {
+            Self::Null => String::new(),
+            Self::Integer(value) => value.to_string(),
+            Self::Text(value) => {
+                if value.contains(&[',', '"', '\n', '\r'][..]) {
+                    format!("\"{}\"", value.replace('"', "\"\""))
+                } else {
+                    value.to_owned()
+                }
+            }
+        }
+    }
+}
+
+impl ExportTable {
+    pub(crate) fn new(name: &'static str, columns: &'static [&'static str]) -> Self {
+        Self {
+            name,
+            columns,
+            rows: Vec::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, row: Vec<ExportValue>) {
+        debug_assert_eq!(row.len(), self.columns.len());
+        self.rows.push(row);
+    }
+
+    pub(crate) fn to_csv(&self) -> String {
+        let mut lines = Vec::with_capacity(self.rows.len() + 1);
+        lines.push(self.columns.join(","));
+        for row in &self.rows {
+            let values = row.iter().map(ExportValue::to_csv).collect::<Vec<_>>();
+            lines.push(values.join(","));
+        }
+        lines.push(String::new());
+        lines.join("\n")
+    }
+}
+
+impl From<u64> for ExportValue {
+    fn from(value: u64) -> Self {
+        Self::Integer(value as i64)
+    }
+}
+
+impl From<String> for ExportValue {
+    fn from(value: String) -> Self {
+        Self::Text(value)
+    }
+}
+
+impl<T: Into<ExportValue>> From<Option<T>> for ExportValue {
+    fn from(value: Option<T>) -> Self {
+        value.map(Into::into).unwrap_or(Self::Null)
+    }
+}
+
+impl FromStr for ChainFormat {
+    type Err = String;
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        match s {
+            "molecule" => Ok(Self::Molecule),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("chain format {} is unknown", s)),
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "sqlite" => Ok(Self::Sqlite),
+            _ => Err(format!("export format {} is unknown", s)),
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use std::{fmt, result::Result as StdResult, str::FromStr};
+
+use ckb_types::packed;
+use serde_json::json;
+
+use super::TxSeqId;
+use crate::error::{Error, Result};
+
+// The outputs of the parent which are spent by the child.
+#[derive(Debug, Clone)]
+pub(crate) struct TxEdge {
+    pub(crate) parent: packed::Byte32,
+    pub(crate) child: packed::Byte32,
+    pub(crate) outputs: Vec<u32>,
+}
+
+// A transaction in the dependency graph.
+#[derive(Debug, Clone)]
+pub(crate) struct TxNode {
+    pub(crate) tx_hash: packed::Byte32,
+    // The name of the status, or what happened at last if the status is removed, or
+    // "untracked" for the transactions which are not generated by the fuzzer, such as the
+    // genesis transactions.
+    pub(crate) status: String,
+    pub(crate) seq_id: Option<TxSeqId>,
+}
+
+// The transactions and the spends between them.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TxGraph {
+    pub(crate) nodes: Vec<TxNode>,
+    pub(crate) edges: Vec<TxEdge>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum GraphFormat {
+    // The Graphviz DOT language.
+    Dot,
+    Json,
+}
+
+// Which transactions are related to the chosen one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum GraphDirection {
+    // The transactions whose outputs it depends on.
+    Ancestors,
+    // The transactions which depend on its outputs.
+    Descendants,
+    Both,
+}
+
+impl TxGraph {
+    pub(crate) fn render(&self, format: GraphFormat) -> Result<String> {
+        match format {
+            GraphFormat::Dot => Ok(self.to_dot()),
+            GraphFormat::Json => self.to_json(),
+        }
+    }
+
+    fn to_dot(&self) -> String {
+        let mut lines = vec!["digraph txs {".to_owned(), "    rankdir=LR;".to_owned()];
+        lines.push("    node [shape=box, style=filled, fontname=monospace];".to_owned());
+        for node in &self.nodes {
+            let mut label = format!("{}\\n{}", short_hash(&node.tx_hash), node.status);
+            if let Some(ref seq_id) = node.seq_id {
+                label.push_str(&format!("\\n{}", seq_id));
+            }
+            lines.push(format!(
+                "    \"{:#x}\" [label=\"{}\", fillcolor={}];",
+                node.tx_hash,
+                label,
+                status_color(&node.status)
+            ));
+        }
+        for edge in &self.edges {
+            let outputs = edge
+                .outputs
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            lines.push(format!(
+                "    \"{:#x}\" -> \"{:#x}\" [label=\"{}\"];",
+                edge.parent,
+                edge.child,
+                outputs.join(",")
+            ));
+        }
+        lines.push("}".to_owned());
+        lines.join("\n")
+    }
+
+    fn to_json(&self) -> Result<String> {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| {
+                json!({
+                    "tx_hash": format!("{:#x}", node.tx_hash),
+                    "status": node.status,
+                    "seq_id": node.seq_id.as_ref().map(ToString::to_string),
+                })
+            })
+            .collect::<Vec<_>>();
+        let edges = self
+            .edges
+            .iter()
+            .map(|edge| {
+                json!({
+                    "parent": format!("{:#x}", edge.parent),
+                    "child": format!("{:#x}", edge.child),
+                    "outputs": edge.outputs,
+                })
+            })
+            .collect::<Vec<_>>();
+        let graph = json!({ "nodes": nodes, "edges": edges });
+        serde_json::to_string_pretty(&graph).map_err(|err| {
+            let errmsg = format!("failed to serialize the graph since {}", err);
+            Error::runtime(errmsg)
+        })
+    }
+}
+
+// The first bytes are enough to tell the transactions apart in a picture.
+fn short_hash(hash: &packed::Byte32) -> String {
+    let hex = format!("{:#x}", hash);
+    hex[..10].to_owned()
+}
+
+fn status_color(status: &str) -> &'static str {
+    match status {
+        "pending" => "lightyellow",
+        "proposed" => "gold",
+        "committed" | "pruned" => "palegreen",
+        "orphan" => "lightblue",
+        "evicted" | "expired" => "lightgray",
+        "failed" | "removed" => "salmon",
+        _ => "white",
+    }
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        match s {
+            "dot" => Ok(Self::Dot),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("graph format {} is unknown", s)),
+        }
+    }
+}
+
+impl FromStr for GraphDirection {
+    type Err = String;
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        match s {
+            "ancestors" => Ok(Self::Ancestors),
+            "descendants" => Ok(Self::Descendants),
+            "both" => Ok(Self::Both),
+            _ => Err(format!("graph direction {} is unknown", s)),
+        }
+    }
+}
+
+impl fmt::Display for GraphDirection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Ancestors => "ancestors",
+            Self::Descendants => "descendants",
+            Self::Both => "both",
+        };
+        write!(f, "{}", name)
+    }
+}
@@ -0,0 +1,215 @@
+use std::fmt;
+
+use ckb_types::{core::BlockNumber, packed, prelude::*};
+
+use crate::error::{Error, Result};
+
+// A transition of a transaction or of its output cells.
+#[derive(Debug, Clone)]
+pub(crate) struct TxEvent {
+    // The tip number when it happens, or the number of the block which commits it.
+    pub(crate) number: BlockNumber,
+    pub(crate) kind: TxEventKind,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum TxEventKind {
+    // Accepted by the tx-pool.
+    Submitted,
+    // Rejected by the tx-pool.
+    Failed,
+    Committed,
+    // Evicted from the tx-pool.
+    Evicted,
+    // Removed from the storage since it's invalidated by a failed transaction.
+    Removed,
+    // An output cell is spent by a transaction.
+    Spent(u32, packed::Byte32),
+    // An output cell is live again since its spender is evicted.
+    Revived(u32),
+    // Removed from the storage since all its outputs are spent by committed transactions or
+    // burned, so its status could never be changed again.
+    Pruned,
+    // The proposal is in chain.
+    Proposed,
+    // All proposals are expired, it's pending again.
+    Unproposed,
+    // Kept in the orphan pool since its inputs are unknown.
+    Orphaned,
+    // Expired from the orphan pool.
+    Expired,
+}
+
+// All transitions of a transaction, in the order they happen.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TxHistory {
+    pub(crate) events: Vec<TxEvent>,
+}
+
+impl TxEvent {
+    pub(crate) fn new(number: BlockNumber, kind: TxEventKind) -> Self {
+        Self { number, kind }
+    }
+
+    // Whether it's a transition of the output cell of the index.
+    pub(crate) fn concerns(&self, index: u32) -> bool {
+        match self.kind {
+            TxEventKind::Spent(cell_index, _) | TxEventKind::Revived(cell_index) => {
+                cell_index == index
+            }
+            // The transitions of the transaction are also the transitions of its cells.
+            _ => true,
+        }
+    }
+}
+
+impl TxHistory {
+    const NAME: &'static str = "TxHistory";
+
+    pub(crate) fn push(&mut self, event: TxEvent) {
+        self.events.push(event);
+    }
+
+    // The transaction which spends the cell of the index at last.
+    pub(crate) fn spender(&self, index: u32) -> Option<packed::Byte32> {
+        self.events
+            .iter()
+            .rev()
+            .find_map(|event| match event.kind {
+                TxEventKind::Spent(spent, ref spender) if spent == index => {
+                    Some(Some(spender.clone()))
+                }
+                TxEventKind::Revived(revived) if revived == index => Some(None),
+                _ => None,
+            })
+            .flatten()
+    }
+
+    pub(crate) fn from_slice(mut slice: &[u8]) -> Result<Self> {
+        let mut events = Vec::new();
+        while !slice.is_empty() {
+            if slice.len() < 9 {
+                return Err(Error::broken_since(Self::NAME, "no enough data"));
+            }
+            let number = read_u64(&slice[..8]);
+            let tag = slice[8];
+            slice = &slice[9..];
+            let kind = match tag {
+                0x00 => TxEventKind::Submitted,
+                0x01 => TxEventKind::Failed,
+                0x02 => TxEventKind::Committed,
+                0x03 => TxEventKind::Evicted,
+                0x04 => TxEventKind::Removed,
+                0x05 => {
+                    if slice.len() < 36 {
+                        return Err(Error::broken_since(Self::NAME, "no enough data"));
+                    }
+                    let index = read_u32(&slice[..4]);
+                    let spender = packed::Byte32::from_slice(&slice[4..36]).map_err(|err| {
+                        let errmsg = err.to_string();
+                        Error::broken_since(Self::NAME, &errmsg)
+                    })?;
+                    slice = &slice[36..];
+                    TxEventKind::Spent(index, spender)
+                }
+                0x06 => {
+                    if slice.len() < 4 {
+                        return Err(Error::broken_since(Self::NAME, "no enough data"));
+                    }
+                    let index = read_u32(&slice[..4]);
+                    slice = &slice[4..];
+                    TxEventKind::Revived(index)
+                }
+                0x07 => TxEventKind::Pruned,
+                0x08 => TxEventKind::Proposed,
+                0x09 => TxEventKind::Unproposed,
+                0x0a => TxEventKind::Orphaned,
+                0x0b => TxEventKind::Expired,
+                x => {
+                    let errmsg = format!("event type is unknown [{}]", x);
+                    return Err(Error::broken_since(Self::NAME, &errmsg));
+                }
+            };
+            events.push(TxEvent { number, kind });
+        }
+        Ok(Self { events })
+    }
+
+    pub(crate) fn to_vec(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for event in &self.events {
+            bytes.extend_from_slice(&event.number.to_le_bytes());
+            match event.kind {
+                TxEventKind::Submitted => bytes.push(0x00),
+                TxEventKind::Failed => bytes.push(0x01),
+                TxEventKind::Committed => bytes.push(0x02),
+                TxEventKind::Evicted => bytes.push(0x03),
+                TxEventKind::Removed => bytes.push(0x04),
+                TxEventKind::Spent(index, ref spender) => {
+                    bytes.push(0x05);
+                    bytes.extend_from_slice(&index.to_le_bytes());
+                    bytes.extend_from_slice(spender.as_slice());
+                }
+                TxEventKind::Revived(index) => {
+                    bytes.push(0x06);
+                    bytes.extend_from_slice(&index.to_le_bytes());
+                }
+                TxEventKind::Pruned => bytes.push(0x07),
+                TxEventKind::Proposed => bytes.push(0x08),
+                TxEventKind::Unproposed => bytes.push(0x09),
+                TxEventKind::Orphaned => bytes.push(0x0a),
+                TxEventKind::Expired => bytes.push(0x0b),
+            }
+        }
+        bytes
+    }
+}
+
+fn read_u64(slice: &[u8]) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(slice);
+    u64::from_le_bytes(bytes)
+}
+
+fn read_u32(slice: &[u8]) -> u32 {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(slice);
+    u32::from_le_bytes(bytes)
+}
+
+impl fmt::Display for TxEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Submitted => write!(f, "submitted"),
+            Self::Failed => write!(f, "failed"),
+            Self::Committed => write!(f, "committed"),
+            Self::Evicted => write!(f, "evicted"),
+            Self::Removed => write!(f, "removed"),
+            Self::Spent(index, spender) => write!(f, "output {} spent by {:#x}", index, spender),
+            Self::Revived(index) => write!(f, "output {} revived", index),
+            Self::Pruned => write!(f, "pruned"),
+            Self::Proposed => write!(f, "proposed"),
+            Self::Unproposed => write!(f, "unproposed"),
+            Self::Orphaned => write!(f, "orphaned"),
+            Self::Expired => write!(f, "expired"),
+        }
+    }
+}
+
+impl fmt::Display for TxEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}@{}", self.kind, self.number)
+    }
+}
+
+impl fmt::Display for TxHistory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let events = self
+            .events
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(f, "[{}]", events)
+    }
+}
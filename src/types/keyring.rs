@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+
+use ckb_crypto::secp::Privkey;
+use ckb_hash::{blake2b_256, new_blake2b};
+use ckb_types::{bytes::Bytes, core::ScriptHashType, packed, prelude::*, H256};
+
+use crate::error::{Error, Result};
+
+// The size of a recoverable secp256k1 signature.
+const SIGNATURE_SIZE: usize = 65;
+// The size of the args of the secp256k1 locks, it's the blake160 hash of a public key, or the
+// blake160 hash of a multisig script.
+const BLAKE160_SIZE: usize = 20;
+// The max count of the keys of a multisig lock.
+const MULTISIG_MAX_KEYS: usize = 4;
+
+// The keys which own the cells locked by the secp256k1 locks.
+//
+// The keys are derived from their indexes, so the same keyring is rebuilt when loading, and the
+// keys are never stored.
+pub(crate) struct Keyring {
+    // The secp256k1 lock code, the secp256k1 data which the locks load, and the multisig lock
+    // code.
+    cell_deps: Vec<packed::CellDep>,
+    code_hash: packed::Byte32,
+    multisig_code_hash: packed::Byte32,
+    // The keys and the blake160 hashes of their public keys.
+    keys: Vec<(Privkey, Bytes)>,
+    multisigs: Vec<Multisig>,
+    // The owners of the locks, by the args of the locks.
+    signers: HashMap<Bytes, Signer>,
+}
+
+// A m-of-n multisig of the keys.
+struct Multisig {
+    // The multisig script, which is the reserved byte, the count of the keys which are required
+    // to be the first ones, the threshold, the count of the keys, and the blake160 hashes of all
+    // public keys.
+    script: Bytes,
+    args: Bytes,
+    threshold: usize,
+    key_indexes: Vec<usize>,
+}
+
+// The owner of a lock of the keyring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Signer {
+    Single(usize),
+    Multisig(usize),
+}
+
+// How the signatures are broken, the transactions with broken signatures should be rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SignatureFault {
+    // Sign another message.
+    Wrong,
+    // Miss the last signature of a multisig lock.
+    Short,
+}
+
+impl Keyring {
+    pub(crate) fn new(
+        cell_deps: Vec<packed::CellDep>,
+        code_hash: packed::Byte32,
+        multisig_code_hash: packed::Byte32,
+        keys_count: u32,
+    ) -> Result<Self> {
+        let mut keys = Vec::with_capacity(keys_count as usize);
+        let mut signers = HashMap::with_capacity(keys_count as usize * 2);
+        for index in 0..keys_count as usize {
+            let seed = format!("ckb-txpool-fuzzer keyring {}", index);
+            let key = Privkey::from(H256::from(blake2b_256(seed.as_bytes())));
+            let pubkey = key.pubkey().map_err(|err| {
+                let errmsg = format!("failed to derive the key {} since {}", index, err);
+                Error::config(errmsg)
+            })?;
+            let hash = Bytes::from(blake2b_256(pubkey.serialize())[..BLAKE160_SIZE].to_vec());
+            signers.insert(hash.clone(), Signer::Single(index));
+            keys.push((key, hash));
+        }
+        // Each key starts a multisig of the following keys, the shapes are from 1-of-1 to
+        // 4-of-4, by the index.
+        let multisigs = (0..keys.len())
+            .map(|index| {
+                let keys_count = (index % MULTISIG_MAX_KEYS + 1).min(keys.len());
+                let threshold = index / MULTISIG_MAX_KEYS % keys_count + 1;
+                let key_indexes = (index..index + keys_count)
+                    .map(|key_index| key_index % keys.len())
+                    .collect::<Vec<_>>();
+                let mut script = vec![0, 0, threshold as u8, keys_count as u8];
+                for key_index in &key_indexes {
+                    script.extend_from_slice(&keys[*key_index].1);
+                }
+                let args = Bytes::from(blake2b_256(&script)[..BLAKE160_SIZE].to_vec());
+                signers.insert(args.clone(), Signer::Multisig(index));
+                Multisig {
+                    script: Bytes::from(script),
+                    args,
+                    threshold,
+                    key_indexes,
+                }
+            })
+            .collect();
+        Ok(Self {
+            cell_deps,
+            code_hash,
+            multisig_code_hash,
+            keys,
+            multisigs,
+            signers,
+        })
+    }
+
+    pub(crate) fn cell_deps(&self) -> Vec<packed::CellDep> {
+        self.cell_deps.clone()
+    }
+
+    pub(crate) fn keys_count(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub(crate) fn lock_script(&self, signer: Signer) -> packed::Script {
+        let (code_hash, args) = match signer {
+            Signer::Single(index) => (&self.code_hash, &self.keys[index].1),
+            Signer::Multisig(index) => (&self.multisig_code_hash, &self.multisigs[index].args),
+        };
+        packed::Script::new_builder()
+            .code_hash(code_hash.clone())
+            .hash_type(ScriptHashType::Data.into())
+            .args(args.pack())
+            .build()
+    }
+
+    // The owner of the lock, or nothing if the lock is not a secp256k1 lock of this keyring.
+    pub(crate) fn signer(&self, lock: &packed::Script) -> Option<Signer> {
+        let hash_type: u8 = lock.hash_type().into();
+        if hash_type != ScriptHashType::Data as u8 {
+            return None;
+        }
+        let code_hash = lock.code_hash();
+        let signer = self.signers.get(&lock.args().raw_data()).copied()?;
+        let matched = match signer {
+            Signer::Single(_) => code_hash == self.code_hash,
+            Signer::Multisig(_) => code_hash == self.multisig_code_hash,
+        };
+        if matched {
+            Some(signer)
+        } else {
+            None
+        }
+    }
+
+    // The witness which only reserves the space of the signatures.
+    pub(crate) fn placeholder(&self, signer: Signer) -> packed::Bytes {
+        Self::witness_with_lock(self.lock_with_signatures(signer, None))
+    }
+
+    // Sign a group of inputs by the sighash-all algorithm, the first witness of the group is
+    // replaced by the signed one.
+    //
+    // The message covers the transaction hash, the witnesses of the group and the witnesses
+    // which are not covered by any inputs, each witness is prefixed with its length in u64.
+    // A wrong signature signs another message, so it is still a valid signature.
+    pub(crate) fn sign(
+        &self,
+        signer: Signer,
+        tx_hash: &packed::Byte32,
+        witnesses: &mut [packed::Bytes],
+        group: &[usize],
+        inputs_count: usize,
+        fault: Option<SignatureFault>,
+    ) -> Result<()> {
+        let placeholder = self.placeholder(signer);
+        let mut hasher = new_blake2b();
+        hasher.update(tx_hash.as_slice());
+        // The lock script stops at the first missing witness of the group.
+        let group_witnesses = group
+            .iter()
+            .map(|index| witnesses.get(*index))
+            .take_while(Option::is_some)
+            .flatten()
+            .skip(1);
+        let rest_witnesses = witnesses.iter().skip(inputs_count);
+        for witness in Some(&placeholder)
+            .into_iter()
+            .chain(group_witnesses)
+            .chain(rest_witnesses)
+        {
+            let data = witness.raw_data();
+            hasher.update(&(data.len() as u64).to_le_bytes());
+            hasher.update(&data);
+        }
+        let mut message = [0u8; 32];
+        hasher.finalize(&mut message);
+        if fault == Some(SignatureFault::Wrong) {
+            message[0] ^= 1;
+        }
+        let mut key_indexes = match signer {
+            Signer::Single(index) => vec![index],
+            Signer::Multisig(index) => {
+                let multisig = &self.multisigs[index];
+                multisig.key_indexes[..multisig.threshold].to_vec()
+            }
+        };
+        if fault == Some(SignatureFault::Short) {
+            key_indexes.pop();
+        }
+        let mut signatures = Vec::with_capacity(key_indexes.len());
+        for key_index in key_indexes {
+            let signature = self.keys[key_index]
+                .0
+                .sign_recoverable(&H256::from(message))
+                .map_err(|err| {
+                    let errmsg = format!("failed to sign by the key {} since {}", key_index, err);
+                    Error::runtime(errmsg)
+                })?;
+            signatures.push(signature.serialize());
+        }
+        let lock = self.lock_with_signatures(signer, Some(signatures));
+        witnesses[group[0]] = Self::witness_with_lock(lock);
+        Ok(())
+    }
+
+    // The lock of the witness, the signatures are filled with zeros if they are not signed yet.
+    fn lock_with_signatures(&self, signer: Signer, signatures: Option<Vec<Vec<u8>>>) -> Vec<u8> {
+        let (mut lock, threshold) = match signer {
+            Signer::Single(_) => (Vec::new(), 1),
+            Signer::Multisig(index) => {
+                let multisig = &self.multisigs[index];
+                (multisig.script.to_vec(), multisig.threshold)
+            }
+        };
+        if let Some(signatures) = signatures {
+            for signature in signatures {
+                lock.extend_from_slice(&signature);
+            }
+        } else {
+            lock.resize(lock.len() + SIGNATURE_SIZE * threshold, 0);
+        }
+        lock
+    }
+
+    fn witness_with_lock(lock: Vec<u8>) -> packed::Bytes {
+        packed::WitnessArgs::new_builder()
+            .lock(Some(Bytes::from(lock)).pack())
+            .build()
+            .as_bytes()
+            .pack()
+    }
+}
@@ -0,0 +1,67 @@
+use std::fmt;
+
+use crate::error::{Error, Result};
+
+// The capacity ledger of the chain, in shannons.
+//
+// The ledger is started from the live cells in the chain, so the capacity conservation is:
+// the capacity of all live cells in the chain equals `issued - fees`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Ledger {
+    // The capacity of the live cells when the ledger was started, and the capacity issued by
+    // the cellbases and the DAO interest after that.
+    pub(crate) issued: u64,
+    // The capacity of the committed cells which could never be unlocked.
+    pub(crate) burned: u64,
+    // The fees collected from the committed transactions.
+    pub(crate) fees: u64,
+}
+
+impl Ledger {
+    const NAME: &'static str = "Ledger";
+    const SIZE: usize = 8 * 3;
+
+    // The capacity of all live cells in the chain.
+    pub(crate) fn expected_live_capacity(&self) -> Option<u64> {
+        self.issued.checked_sub(self.fees)
+    }
+
+    pub(crate) fn from_slice(slice: &[u8]) -> Result<Self> {
+        if slice.len() != Self::SIZE {
+            let reason = format!(
+                "incorrect data size (expect: {}, actual: {})",
+                Self::SIZE,
+                slice.len()
+            );
+            return Err(Error::broken_since(Self::NAME, &reason));
+        }
+        let read_u64 = |start: usize| {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&slice[start..start + 8]);
+            u64::from_le_bytes(bytes)
+        };
+        Ok(Self {
+            issued: read_u64(0),
+            burned: read_u64(8),
+            fees: read_u64(16),
+        })
+    }
+
+    pub(crate) fn to_vec(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::SIZE);
+        bytes.extend_from_slice(&self.issued.to_le_bytes());
+        bytes.extend_from_slice(&self.burned.to_le_bytes());
+        bytes.extend_from_slice(&self.fees.to_le_bytes());
+        bytes
+    }
+}
+
+impl fmt::Display for Ledger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "issued: {}, burned: {}, fees: {}",
+            self.issued, self.burned, self.fees
+        )
+    }
+}
@@ -0,0 +1,297 @@
+use std::{collections::BTreeMap, fmt, path::PathBuf, result::Result as StdResult, str::FromStr};
+
+use rand::{rngs::StdRng, thread_rng, Rng as _, SeedableRng as _};
+use serde::{Deserialize, Serialize};
+
+use super::{MetaData, Problems, RunEnv, TxPoolOptions};
+
+// Too many sessions in a grid are not "short" any more, sample them instead.
+const MAX_GRID_SESSIONS: u64 = 10_000;
+
+// The configurations to run the sessions with, each dimension lists its candidate values, and
+// the dimensions which are not listed keep the base configs.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct MatrixSpec {
+    // Sample that many combinations randomly, or run all combinations if it's not set.
+    pub(crate) sessions: Option<u32>,
+    pub(crate) seed: Option<u64>,
+    pub(crate) tx_pool: Option<TxPoolDimensions>,
+    pub(crate) params: Option<ParamsDimensions>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TxPoolDimensions {
+    pub(crate) max_mem_size: Option<Vec<u64>>,
+    pub(crate) max_cycles: Option<Vec<u64>>,
+    pub(crate) max_tx_verify_cycles: Option<Vec<u64>>,
+    pub(crate) max_ancestors_count: Option<Vec<u64>>,
+}
+
+// The consensus parameters of the chain spec.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ParamsDimensions {
+    pub(crate) max_block_cycles: Option<Vec<u64>>,
+    pub(crate) max_block_bytes: Option<Vec<u64>>,
+    pub(crate) max_block_proposals_limit: Option<Vec<u64>>,
+}
+
+// The values of a session, by the paths of the dimensions, such as "tx_pool.max_cycles".
+pub(crate) type Combination = BTreeMap<String, u64>;
+
+// How a session ends.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Verdict {
+    Passed,
+    // The oracle disagrees with the tx-pool.
+    Mismatched,
+    // A request to the tx-pool is not responded in time.
+    Hung,
+    // The session could not finish for other reasons, such as a broken config.
+    Failed,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct SessionOutcome {
+    pub(crate) index: usize,
+    pub(crate) combination: Combination,
+    pub(crate) verdict: Verdict,
+    pub(crate) exit_code: Option<i32>,
+    pub(crate) mismatches: u64,
+    pub(crate) divergence: Option<String>,
+    // The data directory is kept for the sessions which are not passed, to reproduce them.
+    pub(crate) data_dir: Option<PathBuf>,
+}
+
+// The sessions which produce mismatches, aggregated by each value of each dimension.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ValueStats {
+    pub(crate) sessions: u64,
+    pub(crate) mismatched: u64,
+    pub(crate) hung: u64,
+    pub(crate) failed: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct MatrixReport {
+    // None if all combinations are run.
+    pub(crate) seed: Option<u64>,
+    pub(crate) sessions: Vec<SessionOutcome>,
+    // By "path=value", such as "tx_pool.max_cycles=1000000".
+    pub(crate) by_value: BTreeMap<String, ValueStats>,
+}
+
+impl MatrixSpec {
+    pub(crate) fn validate(&self, problems: &mut Problems) {
+        let dimensions = self.dimensions();
+        problems.check(
+            !dimensions.is_empty(),
+            "tx_pool, params",
+            "at least one dimension is required",
+        );
+        for (path, values) in &dimensions {
+            problems.check(!values.is_empty(), path, "should not be empty");
+            problems.check(
+                values.iter().all(|value| *value > 0),
+                path,
+                "all values should be positive",
+            );
+        }
+        if let Some(sessions) = self.sessions {
+            problems.check(sessions > 0, "sessions", "should be positive");
+        } else {
+            let total = dimensions.iter().fold(1u64, |total, (_, values)| {
+                total.saturating_mul(values.len() as u64)
+            });
+            problems.check(
+                total <= MAX_GRID_SESSIONS,
+                "sessions",
+                format!(
+                    "the grid has {} combinations (max: {}), set it to sample them",
+                    total, MAX_GRID_SESSIONS
+                ),
+            );
+        }
+    }
+
+    // The listed dimensions, by their paths.
+    fn dimensions(&self) -> Vec<(&'static str, &[u64])> {
+        let tx_pool = self.tx_pool.as_ref();
+        let params = self.params.as_ref();
+        vec![
+            (
+                "tx_pool.max_mem_size",
+                tx_pool.and_then(|dims| dims.max_mem_size.as_ref()),
+            ),
+            (
+                "tx_pool.max_cycles",
+                tx_pool.and_then(|dims| dims.max_cycles.as_ref()),
+            ),
+            (
+                "tx_pool.max_tx_verify_cycles",
+                tx_pool.and_then(|dims| dims.max_tx_verify_cycles.as_ref()),
+            ),
+            (
+                "tx_pool.max_ancestors_count",
+                tx_pool.and_then(|dims| dims.max_ancestors_count.as_ref()),
+            ),
+            (
+                "params.max_block_cycles",
+                params.and_then(|dims| dims.max_block_cycles.as_ref()),
+            ),
+            (
+                "params.max_block_bytes",
+                params.and_then(|dims| dims.max_block_bytes.as_ref()),
+            ),
+            (
+                "params.max_block_proposals_limit",
+                params.and_then(|dims| dims.max_block_proposals_limit.as_ref()),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(path, values)| values.map(|values| (path, values.as_slice())))
+        .collect()
+    }
+
+    // All combinations of the grid, or the sampled ones, with the seed of the sampling.
+    pub(crate) fn combinations(&self) -> (Option<u64>, Vec<Combination>) {
+        let dimensions = self.dimensions();
+        if let Some(sessions) = self.sessions {
+            let seed = self.seed.unwrap_or_else(|| thread_rng().gen());
+            let mut rng = StdRng::seed_from_u64(seed);
+            let combinations = (0..sessions)
+                .map(|_| {
+                    dimensions
+                        .iter()
+                        .map(|(path, values)| {
+                            let value = values[rng.gen_range(0..values.len())];
+                            ((*path).to_owned(), value)
+                        })
+                        .collect()
+                })
+                .collect();
+            (Some(seed), combinations)
+        } else {
+            let mut combinations = vec![Combination::new()];
+            for (path, values) in &dimensions {
+                combinations = combinations
+                    .into_iter()
+                    .flat_map(|combination| {
+                        values.iter().map(move |value| {
+                            let mut combination = combination.clone();
+                            combination.insert((*path).to_owned(), *value);
+                            combination
+                        })
+                    })
+                    .collect();
+            }
+            (None, combinations)
+        }
+    }
+}
+
+// Override the configs by the values of a combination.
+pub(crate) fn apply_combination(
+    combination: &Combination,
+    meta_data: &mut MetaData,
+    run_env: &mut RunEnv,
+) {
+    for (path, value) in combination {
+        let value = *value;
+        let params = &mut meta_data.chain_spec.params;
+        match path.as_str() {
+            "tx_pool.max_mem_size" => tx_pool_options(run_env).max_mem_size = Some(value as usize),
+            "tx_pool.max_cycles" => tx_pool_options(run_env).max_cycles = Some(value),
+            "tx_pool.max_tx_verify_cycles" => {
+                tx_pool_options(run_env).max_tx_verify_cycles = Some(value)
+            }
+            "tx_pool.max_ancestors_count" => {
+                tx_pool_options(run_env).max_ancestors_count = Some(value as usize)
+            }
+            "params.max_block_cycles" => params.max_block_cycles = Some(value),
+            "params.max_block_bytes" => params.max_block_bytes = Some(value),
+            "params.max_block_proposals_limit" => params.max_block_proposals_limit = Some(value),
+            _ => unreachable!("the dimension {} is unknown", path),
+        }
+    }
+}
+
+fn tx_pool_options(run_env: &mut RunEnv) -> &mut TxPoolOptions {
+    run_env.tx_pool.get_or_insert_with(TxPoolOptions::default)
+}
+
+// Format a combination in one line, such as "tx_pool.max_cycles=1000, params.max_block_bytes=2".
+pub(crate) fn combination_summary(combination: &Combination) -> String {
+    combination
+        .iter()
+        .map(|(path, value)| format!("{}={}", path, value))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl MatrixReport {
+    pub(crate) fn add(&mut self, outcome: SessionOutcome) {
+        for (path, value) in &outcome.combination {
+            let stats = self
+                .by_value
+                .entry(format!("{}={}", path, value))
+                .or_default();
+            stats.sessions += 1;
+            match outcome.verdict {
+                Verdict::Passed => {}
+                Verdict::Mismatched => stats.mismatched += 1,
+                Verdict::Hung => stats.hung += 1,
+                Verdict::Failed => stats.failed += 1,
+            }
+        }
+        self.sessions.push(outcome);
+    }
+
+    // The count of the sessions which are not passed.
+    pub(crate) fn unpassed(&self) -> usize {
+        self.sessions
+            .iter()
+            .filter(|outcome| outcome.verdict != Verdict::Passed)
+            .count()
+    }
+}
+
+impl FromStr for MatrixSpec {
+    type Err = Problems;
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        let spec: Self = serde_yaml::from_str(s)?;
+        let mut problems = Problems::default();
+        spec.validate(&mut problems);
+        if problems.is_empty() {
+            Ok(spec)
+        } else {
+            Err(problems)
+        }
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Passed => "passed",
+            Self::Mismatched => "mismatched",
+            Self::Hung => "hung",
+            Self::Failed => "failed",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for MatrixReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        serde_yaml::to_string(self)
+            .map_err(|_| fmt::Error)
+            .and_then(|s| write!(f, "{}", s))
+    }
+}
@@ -1,11 +1,24 @@
-use std::{fmt, result::Result as StdResult, str::FromStr};
+use std::{fmt, path::PathBuf, result::Result as StdResult, str::FromStr};
 
 pub(crate) use ckb_chain_spec::Params;
+use ckb_pow::Pow;
+use ckb_resource::{Resource, AVAILABLE_SPECS};
+use ckb_types::H256;
 use serde::{Deserialize, Serialize};
 
+use super::Problems;
+use crate::error::{Error, Result};
+
+// The version of the meta data format, increase it when the format is changed, and add the
+// migration from the previous version.
+const META_DATA_VERSION: u32 = 2;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(deny_unknown_fields)]
-pub(crate) struct MetaData {
+pub struct MetaData {
+    // The meta data which are stored before the versioning are version 0.
+    // It's always the current version after parsing, since the older ones are migrated.
+    pub(crate) version: Option<u32>,
     pub(crate) chain_spec: ChainSpec,
 }
 
@@ -14,6 +27,11 @@ pub(crate) struct MetaData {
 pub(crate) struct ChainSpec {
     pub(crate) genesis: Genesis,
     pub(crate) params: Params,
+    // The dummy PoW by default.
+    pub(crate) pow: Option<PowEngineKind>,
+    // The chain is imported from the store of a CKB node, instead of starting from the genesis
+    // block which is built from this config.
+    pub(crate) imported: Option<ImportedChain>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -21,12 +39,230 @@ pub(crate) struct ChainSpec {
 pub(crate) struct Genesis {
     pub(crate) timestamp: u64,
     pub(crate) compact_target: u32,
+    // Deploy the secp256k1 lock, and keep a keyring of that many keys to sign the transactions.
+    pub(crate) secp256k1_keys: Option<u32>,
+    // Deploy the script binaries of the users, they are not mocked, so the transactions which
+    // use them are verified by the real code.
+    pub(crate) scripts: Option<Vec<ScriptBinary>>,
+    // Issue the cells in the genesis block, so the fuzzer starts with enough live cells instead
+    // of waiting for the cellbases.
+    pub(crate) issued_cells: Option<Vec<IssuedCells>>,
+}
+
+// A script binary which is provided by the user.
+//
+// The genesis block is rebuilt from the binaries each time the chain is loaded, so the binaries
+// should be kept, and the hash makes sure that they are not changed.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ScriptBinary {
+    pub(crate) path: PathBuf,
+    // The blake2b hash of the binary, which is the code hash in the data hash type.
+    pub(crate) hash: H256,
+    // Whether the script passes with empty args, the transactions which create the cells with
+    // the failed type scripts are expected to be rejected.
+    pub(crate) passes: bool,
+}
+
+// The blocks which are imported by `init --from-chain`, they are copied into the mocked chain,
+// so the source is only recorded for the reports.
+//
+// The mocked script and the issued cells are deployed by a bootstrap block just after the
+// imported blocks, since the genesis block of the node is kept.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ImportedChain {
+    pub(crate) source: PathBuf,
+    pub(crate) tip_number: u64,
+    pub(crate) bootstrap_block: H256,
+}
+
+// The cells in the same capacity and the same kind of locks.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct IssuedCells {
+    pub(crate) count: u32,
+    // In CKBytes.
+    pub(crate) capacity: u64,
+    pub(crate) lock: IssuedLock,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum IssuedLock {
+    // The mocked script which always passes.
+    Mocked,
+    // The secp256k1 locks of the keys in the keyring, the keys are used in turn.
+    Secp256k1,
+}
+
+// The PoW engine of the chain.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum PowEngineKind {
+    // Any nonce passes.
+    Dummy,
+    // The blocks are sealed by solving the real PoW, with the difficulty of the chain.
+    Eaglesong,
+}
+
+impl ChainSpec {
+    // Replace the consensus parameters by a standard CKB chain spec, which is a TOML file, or
+    // the name of a bundled one, such as "mainnet", "testnet", "staging" or "dev".
+    //
+    // The genesis timestamp, the compact target, the params and the PoW engine are imported.
+    // The genesis cells of the spec are not, since the fuzzer deploys its own scripts and could
+    // not spend the cells which are locked by others, so the genesis block is still built from
+    // this config.
+    pub(crate) fn import(&mut self, spec: &str) -> Result<()> {
+        let resource = if AVAILABLE_SPECS.contains(&spec) {
+            Resource::bundled(format!("specs/{}.toml", spec))
+        } else {
+            Resource::file_system(PathBuf::from(spec))
+        };
+        let imported = ckb_chain_spec::ChainSpec::load_from(&resource).map_err(|err| {
+            let errmsg = format!("failed to load the chain spec {} since {}", spec, err);
+            Error::config(errmsg)
+        })?;
+        let pow = match imported.pow {
+            Pow::Dummy => PowEngineKind::Dummy,
+            Pow::Eaglesong => PowEngineKind::Eaglesong,
+            ref pow => {
+                let errmsg = format!(
+                    "the PoW engine {:?} of the chain spec {} is not supported",
+                    pow, spec
+                );
+                return Err(Error::config(errmsg));
+            }
+        };
+        log::info!(
+            "Import the chain spec {} ({}), {} system cells and {} issued cells are ignored",
+            imported.name,
+            spec,
+            imported.genesis.system_cells.len(),
+            imported.genesis.issued_cells.len()
+        );
+        self.genesis.timestamp = imported.genesis.timestamp;
+        self.genesis.compact_target = imported.genesis.compact_target;
+        self.params = imported.params;
+        self.pow = Some(pow);
+        Ok(())
+    }
+}
+
+impl MetaData {
+    pub(crate) fn validate(&self, problems: &mut Problems) {
+        problems.check(
+            self.chain_spec.genesis.compact_target > 0,
+            "chain_spec.genesis.compact_target",
+            "should be positive",
+        );
+        if let Some(keys) = self.chain_spec.genesis.secp256k1_keys {
+            problems.check(
+                keys > 0,
+                "chain_spec.genesis.secp256k1_keys",
+                "should be positive",
+            );
+        }
+        if let Some(ref scripts) = self.chain_spec.genesis.scripts {
+            problems.check(
+                !scripts.is_empty(),
+                "chain_spec.genesis.scripts",
+                "should not be empty",
+            );
+        }
+        if self.chain_spec.imported.is_some() {
+            // The genesis block of the node doesn't deploy them.
+            problems.check(
+                self.chain_spec.genesis.secp256k1_keys.is_none(),
+                "chain_spec.genesis.secp256k1_keys",
+                "should not be set since the chain is imported",
+            );
+            problems.check(
+                self.chain_spec.genesis.scripts.is_none(),
+                "chain_spec.genesis.scripts",
+                "should not be set since the chain is imported",
+            );
+        }
+        if let Some(ref issued_cells) = self.chain_spec.genesis.issued_cells {
+            for (index, cells) in issued_cells.iter().enumerate() {
+                let path = format!("chain_spec.genesis.issued_cells[{}]", index);
+                problems.check(
+                    cells.count > 0,
+                    &format!("{}.count", path),
+                    "should be positive",
+                );
+                problems.check(
+                    cells.capacity > 0,
+                    &format!("{}.capacity", path),
+                    "should be positive",
+                );
+                problems.check(
+                    cells.lock != IssuedLock::Secp256k1
+                        || self.chain_spec.genesis.secp256k1_keys.is_some(),
+                    &format!("{}.lock", path),
+                    "should not be secp256k1 since secp256k1_keys is not set",
+                );
+            }
+        }
+    }
+}
+
+// Migrate the meta data from an older version to the current version, step by step.
+fn migrate(value: &mut serde_yaml::Value, from: u32) -> StdResult<(), String> {
+    for version in from..META_DATA_VERSION {
+        match version {
+            // Version 1: add the version, all other fields are unchanged.
+            0 => {}
+            // Version 2: add the optional imported chain.
+            1 => {}
+            _ => return Err(format!("no migration for version {}", version)),
+        }
+        log::debug!("[MetaData] migrate to version {}", version + 1);
+    }
+    if let serde_yaml::Value::Mapping(ref mut mapping) = value {
+        mapping.insert("version".into(), META_DATA_VERSION.into());
+    }
+    Ok(())
 }
 
 impl FromStr for MetaData {
-    type Err = serde_yaml::Error;
+    type Err = Problems;
     fn from_str(s: &str) -> StdResult<Self, Self::Err> {
-        serde_yaml::from_str(s)
+        // Check the version before the other fields, so a newer format is not reported as
+        // obscure unknown fields.
+        let mut value: serde_yaml::Value = serde_yaml::from_str(s)?;
+        let mut problems = Problems::default();
+        let version = match value.get("version") {
+            None | Some(serde_yaml::Value::Null) => 0,
+            Some(version) => {
+                if let Some(version) = version.as_u64().filter(|v| *v <= u64::from(u32::MAX)) {
+                    version as u32
+                } else {
+                    problems.add("version", "should be an unsigned integer");
+                    return Err(problems);
+                }
+            }
+        };
+        if version > META_DATA_VERSION {
+            let reason = format!(
+                "{} is newer than the supported version {}, please upgrade the fuzzer",
+                version, META_DATA_VERSION
+            );
+            problems.add("version", reason);
+            return Err(problems);
+        }
+        if let Err(reason) = migrate(&mut value, version) {
+            problems.add("version", reason);
+            return Err(problems);
+        }
+        let meta_data: Self = serde_yaml::from_value(value)?;
+        meta_data.validate(&mut problems);
+        if problems.is_empty() {
+            Ok(meta_data)
+        } else {
+            Err(problems)
+        }
     }
 }
 
@@ -1,11 +1,47 @@
+mod baseline;
+mod bias;
+mod block_delta;
 mod cache;
 mod chain;
+mod checkpoint;
+mod export;
+mod graph;
+mod history;
+mod keyring;
+mod ledger;
+mod matrix;
 mod meta_data;
+mod profile;
+mod proposals;
 mod random;
+mod report;
 mod run_env;
+mod run_record;
+mod script;
+mod tx_seq_id;
+mod validation;
+mod workload;
 
+pub(crate) use baseline::*;
+pub(crate) use bias::*;
+pub use block_delta::*;
 pub(crate) use cache::*;
 pub(crate) use chain::*;
-pub(crate) use meta_data::*;
-pub(crate) use random::*;
-pub(crate) use run_env::*;
+pub(crate) use checkpoint::*;
+pub(crate) use export::*;
+pub(crate) use graph::*;
+pub(crate) use history::*;
+pub(crate) use keyring::*;
+pub(crate) use ledger::*;
+pub(crate) use matrix::*;
+pub use meta_data::*;
+pub(crate) use profile::*;
+pub(crate) use proposals::*;
+pub use random::*;
+pub(crate) use report::*;
+pub use run_env::*;
+pub(crate) use run_record::*;
+pub use script::*;
+pub(crate) use tx_seq_id::*;
+pub use validation::*;
+pub(crate) use workload::*;
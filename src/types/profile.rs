@@ -0,0 +1,83 @@
+use std::{fmt, result::Result as StdResult, str::FromStr};
+
+// The annotated example of the initialization config.
+pub(crate) const INIT_CONFIG_SAMPLE: &str = include_str!("../../configs/init.yaml.sample");
+
+// A pre-tuned run config for a kind of runs.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Profile {
+    // The annotated example with all options.
+    Default,
+    QuickSmoke,
+    Soak,
+    Stress,
+    Differential,
+    // The short epochs, with the init config of its own.
+    Epochs,
+}
+
+impl Profile {
+    pub(crate) const ALL: &'static [Self] = &[
+        Self::Default,
+        Self::QuickSmoke,
+        Self::Soak,
+        Self::Stress,
+        Self::Differential,
+        Self::Epochs,
+    ];
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::QuickSmoke => "quick-smoke",
+            Self::Soak => "soak",
+            Self::Stress => "stress",
+            Self::Differential => "differential",
+            Self::Epochs => "epochs",
+        }
+    }
+
+    // The content of the run config.
+    pub(crate) fn run_config(self) -> &'static str {
+        match self {
+            Self::Default => include_str!("../../configs/run.yaml.sample"),
+            Self::QuickSmoke => include_str!("../../configs/profiles/quick-smoke.yaml"),
+            Self::Soak => include_str!("../../configs/profiles/soak.yaml"),
+            Self::Stress => include_str!("../../configs/profiles/stress.yaml"),
+            Self::Differential => include_str!("../../configs/profiles/differential.yaml"),
+            Self::Epochs => include_str!("../../configs/profiles/epochs.yaml"),
+        }
+    }
+
+    // The content of the initialization config, most profiles share the annotated example.
+    pub(crate) fn init_config(self) -> &'static str {
+        match self {
+            Self::Epochs => include_str!("../../configs/profiles/epochs-init.yaml"),
+            _ => INIT_CONFIG_SAMPLE,
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|profile| profile.name() == s)
+            .copied()
+            .ok_or_else(|| {
+                let available = Self::ALL
+                    .iter()
+                    .map(|profile| profile.name())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("profile {} is unknown (available: {})", s, available)
+            })
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
@@ -0,0 +1,104 @@
+use std::fmt;
+
+use ckb_chain_spec::consensus::ProposalWindow;
+use ckb_types::core::BlockNumber;
+
+use crate::error::{Error, Result};
+
+// The stages of a transaction in the tx-pool before it's committed, by the proposal window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TxStage {
+    // Not proposed, or all proposals are expired.
+    Pending,
+    // Proposed, but the proposal is too close to be committed.
+    Gap,
+    // Proposed, and it could be committed.
+    Proposed,
+}
+
+// The numbers of the blocks which propose a transaction, in ascending order.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Proposals {
+    numbers: Vec<BlockNumber>,
+}
+
+impl fmt::Display for TxStage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Pending => write!(f, "pending"),
+            Self::Gap => write!(f, "gap"),
+            Self::Proposed => write!(f, "proposed"),
+        }
+    }
+}
+
+impl Proposals {
+    const NAME: &'static str = "Proposals";
+
+    // Add a proposal, and prune the proposals which are expired for the next block.
+    pub(crate) fn add(&mut self, number: BlockNumber, window: &ProposalWindow) {
+        self.numbers
+            .retain(|proposed| number + 1 - proposed <= window.farthest());
+        if !self.numbers.contains(&number) {
+            self.numbers.push(number);
+        }
+    }
+
+    // The stage of the transaction when the block of the number is built.
+    pub(crate) fn stage(&self, number: BlockNumber, window: &ProposalWindow) -> TxStage {
+        let distances = self
+            .numbers
+            .iter()
+            .filter(|proposed| **proposed < number)
+            .map(|proposed| number - proposed);
+        let mut stage = TxStage::Pending;
+        for distance in distances {
+            if distance >= window.closest() && distance <= window.farthest() {
+                return TxStage::Proposed;
+            }
+            if distance < window.closest() {
+                stage = TxStage::Gap;
+            }
+        }
+        stage
+    }
+
+    pub(crate) fn from_slice(slice: &[u8]) -> Result<Self> {
+        if slice.len() % 8 != 0 {
+            let reason = format!(
+                "incorrect data size (expect: a multiple of 8, actual: {})",
+                slice.len()
+            );
+            return Err(Error::broken_since(Self::NAME, &reason));
+        }
+        let numbers = slice
+            .chunks(8)
+            .map(|chunk| {
+                let mut bytes = [0u8; 8];
+                bytes.copy_from_slice(chunk);
+                u64::from_le_bytes(bytes)
+            })
+            .collect();
+        Ok(Self { numbers })
+    }
+
+    pub(crate) fn to_vec(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.numbers.len() * 8);
+        for number in &self.numbers {
+            bytes.extend_from_slice(&number.to_le_bytes());
+        }
+        bytes
+    }
+}
+
+impl fmt::Display for Proposals {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let numbers = self
+            .numbers
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(f, "[{}]", numbers)
+    }
+}
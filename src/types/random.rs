@@ -1,40 +1,314 @@
 use std::{
     cell::{RefCell, RefMut},
     ops::DerefMut as _,
+    result::Result as StdResult,
 };
 
-use rand::{rngs::ThreadRng, thread_rng, Rng as _};
-use rand_distr::{Distribution as _, Normal};
+use ckb_types::{core, packed};
+use rand::{rngs::StdRng, thread_rng, Rng as _, RngCore, SeedableRng as _};
+use rand_distr::{Distribution as _, Normal, Pareto};
 
 use crate::{
     error::{Error, Result},
-    types::RunEnv,
+    types::{
+        Branch, Burst, DataSize, FeeRateDistribution, Generation, InputSelection, Interleave,
+        LargeCycles, LongChain, OutputsCountDistribution, OutputsShape, PartialCommit,
+        RemoteSubmit, RunEnv, SignatureFault, TemplateParams, Withhold, Witnesses, WorkloadProfile,
+    },
 };
 
-pub(crate) struct RandomGenerator {
-    rng: RefCell<ThreadRng>,
+pub struct RandomGenerator {
+    seed: u64,
+    rng: RefCell<Entropy>,
     block_interval: Normal<f64>,
+    partial_commit: Option<PartialCommit>,
+    withhold: Option<Withhold>,
+    template_params: Option<TemplateParams>,
+    min_batch_size: usize,
+    long_chain: Option<LongChain>,
+    remote_submit: Option<RemoteSubmit>,
+    large_cycles: Option<LargeCycles>,
+    interleave: Option<Interleave>,
+    burst: Burst,
+    generation: Generation,
+    input_selection: InputSelection,
+    fee_rate: Option<FeeRateSampler>,
+    outputs_count: Option<OutputsCountSampler>,
+    data_size: Option<DataSize>,
+    witnesses: Option<Witnesses>,
+    workload: Option<WorkloadProfile>,
+    // The branches which are exercised since last taken.
+    branches: RefCell<Vec<Branch>>,
+    // The multiples of the chances of the branches, indexed by the branches.
+    boosts: RefCell<Vec<u32>>,
+}
+
+// The sampler of the configured fee rate distribution.
+enum FeeRateSampler {
+    Uniform(u64, u64),
+    Normal(Normal<f64>),
+    // The scale is kept, since the distribution doesn't expose it.
+    Pareto(f64, Pareto<f64>),
+}
+
+// The sampler of the configured outputs count distribution.
+enum OutputsCountSampler {
+    Uniform(usize, usize),
+    Normal(Normal<f64>),
+}
+
+// How to change the args of a mocked script.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ArgsShape {
+    Exact,
+    // Append some bytes.
+    Longer(usize),
+    // Append a lot of bytes, to put pressure on the sizes and the capacities.
+    Huge(usize),
+    // Truncate the args to the length.
+    Shorter(usize),
+    // Replace all bytes with random bytes.
+    Garbage,
+}
+
+// How to break the type ID of a new cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TypeIdFault {
+    // The type ID is not calculated from the first input.
+    WrongId,
+    // More than one output have the same type ID.
+    Duplicate,
+}
+
+// The limits to request a block template with, none means the consensus limit.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct TemplateLimits {
+    pub(crate) bytes_limit: Option<u64>,
+    pub(crate) proposals_limit: Option<u64>,
+    pub(crate) max_version: Option<core::Version>,
+}
+
+// The source of all random decisions.
+enum Entropy {
+    Prng(StdRng),
+    Bytes(ByteStream),
+}
+
+// Consume the decisions from a byte buffer instead of a PRNG, so the fuzzer could be driven by
+// coverage-guided fuzzers.
+// When all bytes are consumed, zeros are returned, which always end the loops.
+struct ByteStream {
+    data: Vec<u8>,
+    offset: usize,
+}
+
+impl RngCore for Entropy {
+    fn next_u32(&mut self) -> u32 {
+        match self {
+            Self::Prng(ref mut inner) => inner.next_u32(),
+            Self::Bytes(ref mut inner) => inner.next_u32(),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match self {
+            Self::Prng(ref mut inner) => inner.next_u64(),
+            Self::Bytes(ref mut inner) => inner.next_u64(),
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match self {
+            Self::Prng(ref mut inner) => inner.fill_bytes(dest),
+            Self::Bytes(ref mut inner) => inner.fill_bytes(dest),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> StdResult<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl RngCore for ByteStream {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        let rest = &self.data[self.offset..];
+        let len = rest.len().min(dest.len());
+        dest[..len].copy_from_slice(&rest[..len]);
+        for byte in &mut dest[len..] {
+            *byte = 0;
+        }
+        self.offset += len;
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> StdResult<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
 }
 
 impl RandomGenerator {
-    pub(crate) fn new(run_env: &RunEnv) -> Result<Self> {
-        let rng = RefCell::new(thread_rng());
+    // The seed from a checkpoint takes precedence over the seed in the configuration.
+    pub fn new(run_env: &RunEnv, resumed_seed: Option<u64>) -> Result<Self> {
+        let seed = if let Some(seed) = resumed_seed {
+            log::info!("[Random] resumed seed: {}", seed);
+            seed
+        } else {
+            let seed = run_env.seed.unwrap_or_else(|| thread_rng().gen());
+            log::info!("[Random] seed: {}", seed);
+            seed
+        };
+        let entropy = Entropy::Prng(StdRng::seed_from_u64(seed));
+        Self::with_entropy(run_env, seed, entropy)
+    }
+
+    // Consume all decisions from the bytes, the seed is meaningless.
+    pub fn from_bytes(run_env: &RunEnv, data: Vec<u8>) -> Result<Self> {
+        log::info!("[Random] use a byte stream of {} bytes", data.len());
+        let entropy = Entropy::Bytes(ByteStream { data, offset: 0 });
+        Self::with_entropy(run_env, 0, entropy)
+    }
+
+    fn with_entropy(run_env: &RunEnv, seed: u64, entropy: Entropy) -> Result<Self> {
+        let rng = RefCell::new(entropy);
         let block_interval = {
             let mean = f64::from(run_env.block_interval);
             let std_dev = mean / 4.0;
             Normal::new(mean, std_dev).map_err(Error::runtime)
         }?;
+        let partial_commit = run_env.partial_commit.clone();
+        let withhold = run_env.withhold.clone();
+        let template_params = run_env.template_params.clone();
+        let min_batch_size = run_env
+            .eviction
+            .as_ref()
+            .map(|cfg| cfg.batch_size)
+            .unwrap_or(0);
+        let long_chain = run_env.long_chain.clone();
+        let remote_submit = run_env.remote_submit.clone();
+        let large_cycles = run_env.large_cycles.clone();
+        let interleave = run_env.interleave.clone();
+        let burst = run_env.burst.clone().unwrap_or_default();
+        let generation = run_env.generation.clone().unwrap_or_default();
+        let input_selection = run_env.input_selection.unwrap_or_default();
+        let fee_rate = run_env
+            .fee_rate
+            .map(|distribution| match distribution {
+                FeeRateDistribution::Uniform { min, max } => Ok(FeeRateSampler::Uniform(min, max)),
+                FeeRateDistribution::Normal { mean, std_dev } => Normal::new(mean, std_dev)
+                    .map(FeeRateSampler::Normal)
+                    .map_err(Error::runtime),
+                FeeRateDistribution::Pareto { scale, shape } => Pareto::new(scale, shape)
+                    .map(|inner| FeeRateSampler::Pareto(scale, inner))
+                    .map_err(Error::runtime),
+            })
+            .transpose()?;
+        let outputs_count = run_env
+            .outputs_count
+            .map(|distribution| match distribution {
+                OutputsCountDistribution::Uniform { min, max } => {
+                    Ok(OutputsCountSampler::Uniform(min, max))
+                }
+                OutputsCountDistribution::Normal { mean, std_dev } => Normal::new(mean, std_dev)
+                    .map(OutputsCountSampler::Normal)
+                    .map_err(Error::runtime),
+            })
+            .transpose()?;
+        let data_size = run_env.data_size.clone();
+        let witnesses = run_env.witnesses.clone();
+        let workload = run_env.profile;
         Ok(Self {
+            seed,
             rng,
             block_interval,
+            partial_commit,
+            withhold,
+            template_params,
+            min_batch_size,
+            long_chain,
+            remote_submit,
+            large_cycles,
+            interleave,
+            burst,
+            generation,
+            input_selection,
+            fee_rate,
+            outputs_count,
+            data_size,
+            witnesses,
+            workload,
+            branches: RefCell::new(Vec::new()),
+            boosts: RefCell::new(vec![1; Branch::ALL.len()]),
         })
     }
 
-    fn rng(&self) -> RefMut<ThreadRng> {
+    fn rng(&self) -> RefMut<Entropy> {
         self.rng.borrow_mut()
     }
 
+    fn hit(&self, branch: Branch, exercised: bool) -> bool {
+        if exercised {
+            self.branches.borrow_mut().push(branch);
+        }
+        exercised
+    }
+
+    // The boosted chance of a branch, but never more than a half of the total.
+    fn boosted(&self, branch: Branch, chance: u32, total: u32) -> u32 {
+        let boost = self.boosts.borrow()[branch as usize];
+        chance.saturating_mul(boost).min(total / 2)
+    }
+
+    pub(crate) fn boost(&self, branch: Branch) -> u32 {
+        self.boosts.borrow()[branch as usize]
+    }
+
+    pub(crate) fn set_boost(&self, branch: Branch, boost: u32) {
+        self.boosts.borrow_mut()[branch as usize] = boost;
+    }
+
+    // Take the branches which are exercised since last taken.
+    pub(crate) fn take_branches(&self) -> Vec<Branch> {
+        self.branches.replace(Vec::new())
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // Reseed the random generator with a new seed and return it, so the state of the random
+    // generator could be persisted.
+    // The byte stream is not reseeded, the checkpoint is useless for it.
+    pub(crate) fn checkpoint_seed(&self) -> u64 {
+        match self.rng().deref_mut() {
+            Entropy::Prng(ref mut inner) => {
+                let seed: u64 = inner.gen();
+                *inner = StdRng::seed_from_u64(seed);
+                seed
+            }
+            Entropy::Bytes(_) => self.seed,
+        }
+    }
+
     pub(crate) fn block_interval(&self) -> u32 {
+        let is_byte_stream = matches!(*self.rng(), Entropy::Bytes(_));
+        if is_byte_stream {
+            // The normal distribution may never end with a byte stream full of zeros.
+            let mean = self.block_interval.mean() as u64;
+            return self.u64_between(mean / 2, mean * 3 / 2 + 1) as u32;
+        }
         let mut ret;
         loop {
             ret = self.block_interval.sample(self.rng().deref_mut());
@@ -45,10 +319,23 @@ impl RandomGenerator {
         ret.ceil() as u32
     }
 
-    pub(crate) fn random_hash(&self) -> [u8; 32] {
-        let mut hash = [0u8; 32];
-        self.rng().deref_mut().fill(&mut hash[..]);
-        hash
+    // The fee rate of the next random transaction, in shannons per 1000 bytes.
+    // Returns nothing if no fee rate distribution is configured, then a constant fee is paid.
+    pub(crate) fn fee_rate(&self) -> Option<core::FeeRate> {
+        let sampler = self.fee_rate.as_ref()?;
+        let is_byte_stream = matches!(*self.rng(), Entropy::Bytes(_));
+        let shannons = match sampler {
+            FeeRateSampler::Uniform(min, max) => self.rng().deref_mut().gen_range(*min..=*max),
+            _ if is_byte_stream => {
+                // The distributions may never end with a byte stream full of zeros.
+                let typical = sampler.typical() as u64;
+                self.u64_between(0, typical.saturating_mul(2).saturating_add(1))
+            }
+            FeeRateSampler::Normal(inner) => inner.sample(self.rng().deref_mut()).max(0.0) as u64,
+            // The conversion saturates for the huge samples from the long tail.
+            FeeRateSampler::Pareto(_, inner) => inner.sample(self.rng().deref_mut()) as u64,
+        };
+        Some(core::FeeRate::from_u64(shannons))
     }
 
     // 9/10 chance to add another tx.
@@ -56,54 +343,216 @@ impl RandomGenerator {
         self.rng().deref_mut().gen_range::<u32, _>(0..10) > 0
     }
 
+    // The min count of transactions for each step.
+    pub(crate) fn min_batch_size(&self) -> usize {
+        self.min_batch_size
+    }
+
     // 1/1000 chance to generate an empty inputs transaction.
     pub(crate) fn no_inputs(&self) -> bool {
-        self.rng().deref_mut().gen_range::<u32, _>(0..1000) == 0
+        let chance = self.boosted(Branch::NoInputs, 1, 1000);
+        let exercised = self.rng().deref_mut().gen_range::<u32, _>(0..1000) < chance;
+        self.hit(Branch::NoInputs, exercised)
     }
 
     // 1/1000 chance to generate an empty outputs transaction.
     pub(crate) fn no_outputs(&self) -> bool {
-        self.rng().deref_mut().gen_range::<u32, _>(0..1000) == 0
+        let chance = self.boosted(Branch::NoOutputs, 1, 1000);
+        let exercised = self.rng().deref_mut().gen_range::<u32, _>(0..1000) < chance;
+        self.hit(Branch::NoOutputs, exercised)
     }
 
     // 1/1000 chance to overflow the total capacity
     pub(crate) fn allow_capacity_overflow(&self) -> bool {
-        self.rng().deref_mut().gen_range::<u32, _>(0..1000) == 0
+        let chance = self.boosted(Branch::CapacityOverflow, 1, 1000);
+        let exercised = self.rng().deref_mut().gen_range::<u32, _>(0..1000) < chance;
+        self.hit(Branch::CapacityOverflow, exercised)
+    }
+
+    // 1/1000 chance to declare less capacity than the occupied capacity of an output.
+    pub(crate) fn break_occupied_capacity(&self) -> bool {
+        let chance = self.boosted(Branch::InsufficientCapacity, 1, 1000);
+        let exercised = self.rng().deref_mut().gen_range::<u32, _>(0..1000) < chance;
+        self.hit(Branch::InsufficientCapacity, exercised)
     }
 
-    // 7/8 chance to add another input cell.
+    // 7/8 chance to add another input cell, or the chance of the workload profile.
     pub(crate) fn has_next_input(&self) -> bool {
+        if let Some(workload) = self.workload {
+            let chance = workload.next_input_chance();
+            return self.rng().deref_mut().gen_range::<u32, _>(0..100) < chance;
+        }
         self.rng().deref_mut().gen_range::<u32, _>(0..7) > 0
     }
 
+    // The target count of the outputs of the next random transaction.
+    // Returns nothing if no outputs count distribution is configured, then the count is decided
+    // by splitting the capacity.
+    pub(crate) fn outputs_count(&self) -> Option<usize> {
+        let sampler = self.outputs_count.as_ref()?;
+        let is_byte_stream = matches!(*self.rng(), Entropy::Bytes(_));
+        let count = match sampler {
+            OutputsCountSampler::Uniform(min, max) => self.rng().deref_mut().gen_range(*min..=*max),
+            OutputsCountSampler::Normal(inner) => {
+                if is_byte_stream {
+                    // The normal distribution may never end with a byte stream full of zeros.
+                    let mean = inner.mean() as usize;
+                    self.usize_less_than(mean * 2) + 1
+                } else {
+                    inner.sample(self.rng().deref_mut()).round() as usize
+                }
+            }
+        };
+        Some(count.max(1))
+    }
+
+    // The capacity of the next output cell, split randomly, or by the shape of the workload
+    // profile.
+    // If there is a target count of the outputs, the capacity is split into that many outputs,
+    // the caller should make sure that all of them could have the smallest capacity.
+    // The remain capacity should not be less than the smallest capacity.
+    pub(crate) fn output_shannons(
+        &self,
+        smallest: u64,
+        remain: u64,
+        outputs_count: usize,
+        target_count: Option<usize>,
+    ) -> u64 {
+        if let Some(target_count) = target_count {
+            let left = target_count.saturating_sub(outputs_count) as u64;
+            if left <= 1 {
+                return remain;
+            }
+            // Keep the smallest capacity for each of the rest outputs.
+            let max = remain - smallest * (left - 1);
+            let upper = (remain / left * 2).min(max);
+            if upper <= smallest {
+                return smallest;
+            }
+            return self.u64_between(smallest, upper + 1);
+        }
+        let shape = self
+            .workload
+            .map(WorkloadProfile::outputs_shape)
+            .unwrap_or(OutputsShape::Random);
+        let is_change = shape
+            .max_small_outputs()
+            .map(|max| outputs_count >= max)
+            .unwrap_or(false);
+        if remain == smallest || is_change {
+            return remain;
+        }
+        match shape {
+            OutputsShape::Random => self.u64_between(smallest, remain),
+            OutputsShape::Payouts => self.u64_between(smallest, remain.min(smallest * 10)),
+            OutputsShape::Dust => smallest,
+            OutputsShape::Single => remain,
+        }
+    }
+
     // 1/200 chance to add a burned cell as input.
     pub(crate) fn could_has_burned_input(&self) -> bool {
-        self.rng().deref_mut().gen_range::<u32, _>(0..200) == 0
+        let chance = self.boosted(Branch::BurnedInput, 1, 200);
+        let exercised = self.rng().deref_mut().gen_range::<u32, _>(0..200) < chance;
+        self.hit(Branch::BurnedInput, exercised)
     }
 
     // 1/200 chance to add a dead cell as input.
     pub(crate) fn could_has_dead_input(&self) -> bool {
-        self.rng().deref_mut().gen_range::<u32, _>(0..200) == 0
+        let chance = self.boosted(Branch::DeadInput, 1, 200);
+        let exercised = self.rng().deref_mut().gen_range::<u32, _>(0..200) < chance;
+        self.hit(Branch::DeadInput, exercised)
     }
 
     // 1/200 chance to add a cell from a failed transaction.
     pub(crate) fn could_be_from_failed_tx(&self) -> bool {
-        self.rng().deref_mut().gen_range::<u32, _>(0..200) == 0
+        let chance = self.boosted(Branch::FailedTxInput, 1, 200);
+        let exercised = self.rng().deref_mut().gen_range::<u32, _>(0..200) < chance;
+        self.hit(Branch::FailedTxInput, exercised)
+    }
+
+    // 1/1000 chance to add a cell whose index is out of the range of the outputs.
+    pub(crate) fn could_has_out_of_range_input(&self) -> bool {
+        let chance = self.boosted(Branch::OutOfRangeInput, 1, 1000);
+        let exercised = self.rng().deref_mut().gen_range::<u32, _>(0..1000) < chance;
+        self.hit(Branch::OutOfRangeInput, exercised)
+    }
+
+    // 1/200 chance to add a cell from an immature cellbase.
+    pub(crate) fn could_has_immature_input(&self) -> bool {
+        let chance = self.boosted(Branch::ImmatureInput, 1, 200);
+        let exercised = self.rng().deref_mut().gen_range::<u32, _>(0..200) < chance;
+        self.hit(Branch::ImmatureInput, exercised)
+    }
+
+    // 1/1000 chance to use a version other than the default version, which is zero.
+    pub(crate) fn wrong_version(&self) -> Option<core::Version> {
+        let chance = self.boosted(Branch::WrongVersion, 1, 1000);
+        let exercised = self.rng().deref_mut().gen_range::<u32, _>(0..1000) < chance;
+        if self.hit(Branch::WrongVersion, exercised) {
+            Some(self.rng().deref_mut().gen_range(1..=core::Version::MAX))
+        } else {
+            None
+        }
+    }
+
+    // 1/100 chance to add an empty witness for each input.
+    pub(crate) fn has_empty_witnesses(&self) -> bool {
+        let exercised = self.rng().deref_mut().gen_range::<u32, _>(0..100) == 0;
+        self.hit(Branch::EmptyWitnesses, exercised)
+    }
+
+    // 1/100 chance to add some witnesses which are more than the inputs.
+    pub(crate) fn has_extra_witnesses(&self) -> bool {
+        let exercised = self.rng().deref_mut().gen_range::<u32, _>(0..100) == 0;
+        self.hit(Branch::ExtraWitnesses, exercised)
+    }
+
+    // The sizes of the random witnesses of a transaction, by the chances in the configuration.
+    // Usually there is one witness for each input, as the signatures.
+    pub(crate) fn witness_sizes(&self, inputs_count: usize) -> Option<Vec<usize>> {
+        let witnesses = self.witnesses.as_ref()?;
+        if self.rng().deref_mut().gen_range::<u32, _>(0..100) >= witnesses.chance {
+            return None;
+        }
+        let mismatched =
+            self.rng().deref_mut().gen_range::<u32, _>(0..100) < witnesses.mismatch_chance;
+        let count = if !mismatched {
+            inputs_count
+        } else if inputs_count > 0 && self.rng().deref_mut().gen() {
+            self.usize_less_than(inputs_count)
+        } else {
+            inputs_count + 1 + self.usize_less_than(3)
+        };
+        let sizes = (0..count)
+            .map(|_| self.usize_less_than(witnesses.max_size + 1))
+            .collect();
+        Some(sizes)
+    }
+
+    pub(crate) fn fill_bytes(&self, dest: &mut [u8]) {
+        self.rng().deref_mut().fill_bytes(dest);
     }
 
     // 1/200 chance to allow duplicated cell.
     pub(crate) fn allow_duplicated(&self) -> bool {
-        self.rng().deref_mut().gen_range::<u32, _>(0..200) == 0
+        let chance = self.boosted(Branch::DuplicatedInput, 1, 200);
+        let exercised = self.rng().deref_mut().gen_range::<u32, _>(0..200) < chance;
+        self.hit(Branch::DuplicatedInput, exercised)
     }
 
     // Lock Script:
     // - 1/100 chance: no lock script
     // - 10/100 chance: failed lock script
     pub(crate) fn lock_status(&self) -> Option<bool> {
+        let no_lock = self.boosted(Branch::NoLockScript, 1, 100);
+        let failed_lock = self.boosted(Branch::FailedLockScript, 9, 100);
         let tmp = self.rng().deref_mut().gen_range::<u32, _>(0..100);
-        if tmp == 0 {
+        if tmp < no_lock {
+            self.hit(Branch::NoLockScript, true);
             None
-        } else if tmp < 10 {
+        } else if tmp < no_lock + failed_lock {
+            self.hit(Branch::FailedLockScript, true);
             Some(false)
         } else {
             Some(true)
@@ -116,8 +565,10 @@ impl RandomGenerator {
     pub(crate) fn type_status(&self) -> Option<bool> {
         let tmp = self.rng().deref_mut().gen_range::<u32, _>(0..100);
         if tmp < 40 {
+            self.hit(Branch::NoTypeScript, true);
             None
         } else if tmp < 10 {
+            self.hit(Branch::FailedTypeScript, true);
             Some(false)
         } else {
             Some(true)
@@ -127,7 +578,318 @@ impl RandomGenerator {
     // 40/100 chance: data hash-type
     // 60/100 chance: type hash-type
     pub(crate) fn is_data_hash_type(&self) -> bool {
-        self.rng().deref_mut().gen_range::<u32, _>(0..100) < 40
+        let is_data = self.rng().deref_mut().gen_range::<u32, _>(0..100) < 40;
+        self.hit(Branch::DataHashType, is_data);
+        self.hit(Branch::TypeHashType, !is_data);
+        is_data
+    }
+
+    // 1/10 chance to use the data1 hash type instead of the data hash type.
+    pub(crate) fn is_data1_hash_type(&self) -> bool {
+        let is_data1 = self.rng().deref_mut().gen_range::<u32, _>(0..10) == 0;
+        self.hit(Branch::Data1HashType, is_data1)
+    }
+
+    // 1/4 chance to use a script binary of the users as the type script, if any is deployed.
+    pub(crate) fn is_custom_type_script(&self) -> bool {
+        let is_custom = self.rng().deref_mut().gen_range::<u32, _>(0..4) == 0;
+        self.hit(Branch::CustomTypeScript, is_custom)
+    }
+
+    // 1/2 chance to lock a cell by a key of the keyring, if the secp256k1 lock is deployed.
+    pub(crate) fn is_secp256k1_lock(&self) -> bool {
+        let is_secp256k1 = self.rng().deref_mut().gen_range::<u32, _>(0..2) == 0;
+        self.hit(Branch::Secp256k1Lock, is_secp256k1)
+    }
+
+    // 1/4 chance to lock a secp256k1 cell by a multisig of the keys.
+    pub(crate) fn is_multisig_lock(&self) -> bool {
+        let is_multisig = self.rng().deref_mut().gen_range::<u32, _>(0..4) == 0;
+        self.hit(Branch::MultisigLock, is_multisig)
+    }
+
+    // - 1/100 chance: wrong signatures
+    // - 1/100 chance: short signatures, only if there are multisig locks
+    pub(crate) fn signature_fault(&self, has_multisig: bool) -> Option<SignatureFault> {
+        let wrong = self.boosted(Branch::WrongSignature, 1, 100);
+        let short = if has_multisig {
+            self.boosted(Branch::ShortSignatures, 1, 100)
+        } else {
+            0
+        };
+        let tmp = self.rng().deref_mut().gen_range::<u32, _>(0..100);
+        if tmp < wrong {
+            self.hit(Branch::WrongSignature, true);
+            Some(SignatureFault::Wrong)
+        } else if tmp < wrong + short {
+            self.hit(Branch::ShortSignatures, true);
+            Some(SignatureFault::Short)
+        } else {
+            None
+        }
+    }
+
+    // - 1/10 chance: wrong type ID
+    // - 1/10 chance: duplicate type IDs
+    pub(crate) fn type_id_fault(&self) -> Option<TypeIdFault> {
+        let tmp = self.rng().deref_mut().gen_range::<u32, _>(0..10);
+        if tmp == 0 {
+            self.hit(Branch::WrongTypeId, true);
+            Some(TypeIdFault::WrongId)
+        } else if tmp == 1 {
+            self.hit(Branch::DuplicateTypeId, true);
+            Some(TypeIdFault::Duplicate)
+        } else {
+            None
+        }
+    }
+
+    // 10/1000 chance: longer args
+    // 10/1000 chance: shorter args
+    // 10/1000 chance: garbage args
+    //  3/1000 chance: huge args
+    pub(crate) fn args_shape(&self, args_len: usize) -> ArgsShape {
+        let point = self.rng().deref_mut().gen_range::<u32, _>(0..1000);
+        let shape = match point {
+            0..=9 => ArgsShape::Longer(self.usize_less_than(32) + 1),
+            10..=19 => ArgsShape::Shorter(self.usize_less_than(args_len)),
+            20..=29 => ArgsShape::Garbage,
+            30..=32 => ArgsShape::Huge(self.usize_less_than(100_000) + 1_000),
+            _ => ArgsShape::Exact,
+        };
+        self.hit(Branch::FuzzedArgs, shape != ArgsShape::Exact);
+        shape
+    }
+
+    // Configurable chance to keep a transaction when commit partially, 1/2 chance by default.
+    pub(crate) fn keep_committed_tx(&self) -> bool {
+        let keep_chance = self
+            .partial_commit
+            .as_ref()
+            .map(|cfg| cfg.keep_chance)
+            .unwrap_or(50);
+        self.rng().deref_mut().gen_range::<u32, _>(0..100) < keep_chance
+    }
+
+    // Configurable chance to request the block template with random limits, the consensus
+    // limits are used by default.
+    //
+    // For each limit:
+    // 4/8 chance: the consensus limit
+    // 2/8 chance: within the consensus limit
+    // 1/8 chance: tight, within one percent of the consensus limit
+    // 1/8 chance: beyond the consensus limit
+    pub(crate) fn template_limits(
+        &self,
+        max_block_bytes: u64,
+        max_proposals: u64,
+        block_version: core::Version,
+    ) -> Option<TemplateLimits> {
+        let chance = self.template_params.as_ref()?.chance;
+        if self.rng().deref_mut().gen_range::<u32, _>(0..100) >= chance {
+            return None;
+        }
+        let max_version = if self.rng().deref_mut().gen_range::<u32, _>(0..2) == 0 {
+            None
+        } else {
+            Some(
+                self.rng()
+                    .deref_mut()
+                    .gen_range(0..=block_version.saturating_add(1)),
+            )
+        };
+        Some(TemplateLimits {
+            bytes_limit: self.random_limit(max_block_bytes),
+            proposals_limit: self.random_limit(max_proposals),
+            max_version,
+        })
+    }
+
+    fn random_limit(&self, limit: u64) -> Option<u64> {
+        let point = self.rng().deref_mut().gen_range::<u32, _>(0..8);
+        match point {
+            0..=3 => None,
+            4..=5 => Some(self.rng().deref_mut().gen_range(0..=limit)),
+            6 => Some(self.rng().deref_mut().gen_range(0..=limit / 100)),
+            _ => {
+                let beyond = limit.saturating_add(1);
+                Some(
+                    self.rng()
+                        .deref_mut()
+                        .gen_range(beyond..=beyond.saturating_mul(2)),
+                )
+            }
+        }
+    }
+
+    // 1/2 chance to keep a proposal when drop proposals.
+    pub(crate) fn keep_proposal(&self) -> bool {
+        self.rng().deref_mut().gen_range::<u32, _>(0..2) == 0
+    }
+
+    // Configurable chance to withhold a transaction, 1/2 chance by default.
+    // It's decided by the transaction hash and the seed instead of the random generator, so the
+    // same transactions are withheld each time until the run is resumed.
+    pub(crate) fn is_withheld(&self, tx_hash: &packed::Byte32) -> bool {
+        let tx_chance = self
+            .withhold
+            .as_ref()
+            .map(|cfg| cfg.tx_chance)
+            .unwrap_or(50);
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&tx_hash.as_slice()[..8]);
+        (u64::from_le_bytes(bytes) ^ self.seed) % 100 < u64::from(tx_chance)
+    }
+
+    // Configurable chance to build a long chain of unconfirmed transactions.
+    pub(crate) fn build_long_chain(&self) -> bool {
+        self.long_chain
+            .as_ref()
+            .map(|cfg| self.rng().deref_mut().gen_range::<u32, _>(0..100) < cfg.chance)
+            .unwrap_or(false)
+    }
+
+    // How many transactions beyond the max ancestors count in a long chain.
+    pub(crate) fn long_chain_extra_depth(&self) -> usize {
+        self.long_chain
+            .as_ref()
+            .map(|cfg| cfg.extra_depth)
+            .unwrap_or(0)
+    }
+
+    // 1/2 chance to race against a transaction in the batch, instead of building both of the
+    // rivals.
+    pub(crate) fn race_in_batch(&self) -> bool {
+        self.rng().deref_mut().gen_range::<u32, _>(0..2) == 0
+    }
+
+    // Configurable chance to submit a transaction as a remote transaction.
+    pub(crate) fn is_remote_submit(&self) -> bool {
+        self.remote_submit
+            .as_ref()
+            .map(|cfg| self.rng().deref_mut().gen_range::<u32, _>(0..100) < cfg.tx_chance)
+            .unwrap_or(false)
+    }
+
+    // Configurable chance to declare wrong cycles for a remote transaction.
+    pub(crate) fn declare_wrong_cycles(&self) -> bool {
+        self.remote_submit
+            .as_ref()
+            .map(|cfg| self.rng().deref_mut().gen_range::<u32, _>(0..100) < cfg.wrong_cycles_chance)
+            .unwrap_or(false)
+    }
+
+    // Wrong cycles which are different from the actual cycles, at most twice of them.
+    pub(crate) fn wrong_cycles(&self, cycles: u64) -> u64 {
+        let delta = self.u64_between(1, cycles.max(1) + 1);
+        if cycles > 0 && self.rng().deref_mut().gen::<bool>() {
+            cycles.saturating_sub(delta)
+        } else {
+            cycles.saturating_add(delta)
+        }
+    }
+
+    // The cycles of a mocked script, configurable chance to exceed the chunk limit.
+    pub(crate) fn script_cycles(&self) -> u64 {
+        if let Some(ref cfg) = self.large_cycles {
+            if self.rng().deref_mut().gen_range::<u32, _>(0..100) < cfg.chance {
+                return self.u64_between(cfg.chunk_limit + 1, cfg.chunk_limit * 2);
+            }
+        }
+        self.u64_between(500, LargeCycles::MIN_CHUNK_LIMIT)
+    }
+
+    // The index of the transaction in a batch before which the block template is built, the
+    // template is built after all transactions by default.
+    pub(crate) fn template_index(&self, count: usize) -> usize {
+        match self.interleave {
+            Some(ref cfg)
+                if count > 0
+                    && self.rng().deref_mut().gen_range::<u32, _>(0..100) < cfg.template_chance =>
+            {
+                self.usize_less_than(count)
+            }
+            _ => count,
+        }
+    }
+
+    // Configurable chance to advance the time before a transaction, in milliseconds.
+    pub(crate) fn time_jump(&self) -> Option<u32> {
+        let cfg = self.interleave.as_ref()?;
+        if self.rng().deref_mut().gen_range::<u32, _>(0..100) < cfg.time_jump_chance {
+            Some(
+                self.rng()
+                    .deref_mut()
+                    .gen_range(1..=cfg.max_time_jump_millis),
+            )
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn burst_size(&self) -> usize {
+        self.burst.size
+    }
+
+    pub(crate) fn burst_identical_fee_rates(&self) -> bool {
+        self.burst.identical_fee_rates
+    }
+
+    pub(crate) fn min_live_cells(&self) -> usize {
+        self.generation.min_live_cells
+    }
+
+    pub(crate) fn smallest_shannons(&self) -> u64 {
+        core::Capacity::bytes(self.generation.smallest_capacity as usize)
+            .map(|capacity| capacity.as_u64())
+            .unwrap_or(u64::MAX)
+    }
+
+    pub(crate) fn retries(&self) -> usize {
+        self.generation.retries
+    }
+
+    pub(crate) fn input_selection(&self) -> InputSelection {
+        self.input_selection
+    }
+
+    // Choose an index by the weights, the sum of the weights should be greater than zero.
+    pub(crate) fn weighted_index(&self, weights: &[u32]) -> usize {
+        let total = weights.iter().map(|weight| u64::from(*weight)).sum::<u64>();
+        let mut point = self.rng().deref_mut().gen_range::<u64, _>(0..total);
+        for (index, weight) in weights.iter().enumerate() {
+            let weight = u64::from(*weight);
+            if point < weight {
+                return index;
+            }
+            point -= weight;
+        }
+        weights.len() - 1
+    }
+
+    // The size of the data of an output, which is not greater than the free bytes.
+    pub(crate) fn data_size(&self, free_bytes: usize) -> usize {
+        let max = if let Some(ref data_size) = self.data_size {
+            let max = data_size
+                .limit
+                .map(|limit| limit.min(free_bytes))
+                .unwrap_or(free_bytes);
+            let point = self.rng().deref_mut().gen_range::<u32, _>(0..100);
+            if point < data_size.zero_chance {
+                return 0;
+            }
+            if point < data_size.zero_chance + data_size.max_chance {
+                return max;
+            }
+            max
+        } else {
+            free_bytes
+        };
+        if max > 0 {
+            self.usize_less_than(max)
+        } else {
+            0
+        }
     }
 
     pub(crate) fn usize_less_than(&self, limit: usize) -> usize {
@@ -138,3 +900,15 @@ impl RandomGenerator {
         self.rng().deref_mut().gen_range(smallest..limit)
     }
 }
+
+impl FeeRateSampler {
+    // The most common fee rate of the distribution.
+    fn typical(&self) -> f64 {
+        match self {
+            Self::Uniform(min, max) => (*min as f64 + *max as f64) / 2.0,
+            Self::Normal(inner) => inner.mean().max(0.0),
+            // The scale is the mode of a pareto distribution.
+            Self::Pareto(scale, _) => *scale,
+        }
+    }
+}
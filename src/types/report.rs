@@ -0,0 +1,385 @@
+use std::{collections::BTreeMap, fmt, result::Result as StdResult, str::FromStr};
+
+use ckb_types::core::{tx_pool::Reject, BlockNumber};
+use serde::{Deserialize, Serialize};
+
+use super::{CacheStats, GenerationBias};
+
+// A machine-readable summary of a run.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RunReport {
+    pub(crate) seed: u64,
+    pub(crate) runtime_millis: u64,
+    pub(crate) start_number: BlockNumber,
+    pub(crate) tip_number: BlockNumber,
+    pub(crate) blocks_mined: u64,
+    pub(crate) txs: TxOutcomes,
+    pub(crate) reject_reasons: BTreeMap<String, u64>,
+    pub(crate) mismatches: Mismatches,
+    pub(crate) storage: StorageStats,
+    // Reports before it was added don't have it.
+    #[serde(default)]
+    pub(crate) bias: GenerationBias,
+    // The significant shifts from the latest baseline of the same profile.
+    #[serde(default)]
+    pub(crate) baseline_shifts: Vec<String>,
+    // The mismatch which stopped the run by a fatal policy.
+    #[serde(default)]
+    pub(crate) divergence: Option<String>,
+    // The peaks of the large batches, for example, the bursts.
+    #[serde(default)]
+    pub(crate) throughput: Throughput,
+    // The latencies of the operations, by the names of the operations.
+    #[serde(default)]
+    pub(crate) latencies: BTreeMap<String, LatencyPercentiles>,
+}
+
+// The totals of the instances which run in parallel, with the report of each instance.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ParallelReport {
+    pub(crate) blocks_mined: u64,
+    pub(crate) txs: TxOutcomes,
+    pub(crate) reject_reasons: BTreeMap<String, u64>,
+    pub(crate) mismatches: Mismatches,
+    // By the indexes of the instances, the instances which stop before saving the reports are
+    // not included.
+    pub(crate) instances: BTreeMap<u32, RunReport>,
+    // Why the instances failed, by their indexes, for example, the fatal mismatches.
+    #[serde(default)]
+    pub(crate) failures: BTreeMap<u32, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TxOutcomes {
+    pub(crate) generated: u64,
+    pub(crate) accepted: u64,
+    pub(crate) rejected: u64,
+    // Generated but not submitted, for example, the tx-pool is full.
+    pub(crate) discarded: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Mismatches {
+    pub(crate) expect_passed_but_failed: u64,
+    pub(crate) expect_failed_but_passed: u64,
+    // Reports before it was added don't have it.
+    #[serde(default)]
+    pub(crate) package_skipped: u64,
+    // Double spends which are rejected by other reasons than the conflicts.
+    #[serde(default)]
+    pub(crate) double_spend_misjudged: u64,
+    // Pending transactions which are not committed for too many blocks.
+    #[serde(default)]
+    pub(crate) stuck_txs: u64,
+    // Rejects which are different from the expected reasons.
+    #[serde(default)]
+    pub(crate) reject_reason_mismatched: u64,
+    // Transactions which are rejected by resolving but not in the recent rejects.
+    #[serde(default)]
+    pub(crate) recent_reject_missed: u64,
+    // Proposals in the block templates which are not pending or already proposed.
+    #[serde(default)]
+    pub(crate) stale_proposals: u64,
+    // Remote transactions whose declared cycles are misjudged, or which are not pending or
+    // committed after they are verified in chunks.
+    #[serde(default)]
+    pub(crate) remote_submit: u64,
+    // Transactions which are not relayed or penalized as their submissions are.
+    #[serde(default)]
+    pub(crate) relay_mismatched: u64,
+    // Transactions which are lost, unexpected or moved after the tx-pool is reloaded from its
+    // persisted data.
+    #[serde(default)]
+    pub(crate) pool_reload: u64,
+    // Transactions which are evicted while a transaction has a lower eviction key in the pool.
+    #[serde(default)]
+    pub(crate) eviction_misordered: u64,
+    // The failures which the run couldn't go on after, they are always fatal.
+    #[serde(default)]
+    pub(crate) script_expect_failed: u64,
+    // Block templates which are broken by the assembler of the tx-pool.
+    #[serde(default)]
+    pub(crate) template_broken: u64,
+    // Blocks which are broken by the producers which edit the templates.
+    #[serde(default)]
+    pub(crate) produced_block_broken: u64,
+    #[serde(default)]
+    pub(crate) block_confirm_failed: u64,
+    #[serde(default)]
+    pub(crate) hook_failed: u64,
+    #[serde(default)]
+    pub(crate) storage_diverged: u64,
+    #[serde(default)]
+    pub(crate) ledger_mismatched: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Throughput {
+    pub(crate) batches: u64,
+    pub(crate) peak_txs_per_sec: u64,
+    // The total size of the transactions in the tx-pool, in bytes.
+    pub(crate) peak_pool_tx_size: usize,
+}
+
+// The upper bounds of the latency buckets of the percentiles.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct LatencyPercentiles {
+    pub(crate) p50_micros: u64,
+    pub(crate) p95_micros: u64,
+    pub(crate) p99_micros: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct StorageStats {
+    pub(crate) tx_pending: usize,
+    // Reports before the statuses were added don't have them.
+    #[serde(default)]
+    pub(crate) tx_proposed: usize,
+    pub(crate) tx_committed: usize,
+    #[serde(default)]
+    pub(crate) tx_orphan: usize,
+    #[serde(default)]
+    pub(crate) tx_evicted: usize,
+    #[serde(default)]
+    pub(crate) tx_expired: usize,
+    pub(crate) tx_failed: usize,
+    pub(crate) cell_live: usize,
+    // The counts of all rejected transactions in the storage, by the reasons.
+    // Reports before it was added don't have it.
+    #[serde(default)]
+    pub(crate) rejects: BTreeMap<String, u64>,
+}
+
+impl RunReport {
+    pub(crate) fn add_reject(&mut self, reject: &Reject) {
+        *self
+            .reject_reasons
+            .entry(reject_reason(reject))
+            .or_default() += 1;
+    }
+}
+
+impl ParallelReport {
+    pub(crate) fn add(&mut self, index: u32, report: RunReport) {
+        self.blocks_mined += report.blocks_mined;
+        self.txs.add(&report.txs);
+        for (reason, count) in &report.reject_reasons {
+            *self.reject_reasons.entry(reason.clone()).or_default() += count;
+        }
+        self.mismatches.add(&report.mismatches);
+        self.instances.insert(index, report);
+    }
+}
+
+impl TxOutcomes {
+    pub(crate) fn add(&mut self, other: &Self) {
+        self.generated += other.generated;
+        self.accepted += other.accepted;
+        self.rejected += other.rejected;
+        self.discarded += other.discarded;
+    }
+}
+
+// The categories of the mismatches, each of them has a policy and a count in the report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MismatchKind {
+    ExpectFailedButPassed,
+    ExpectPassedButFailed,
+    // A double spend is accepted, it's counted as expect_failed_but_passed.
+    DoubleSpendAccepted,
+    RejectReasonMismatched,
+    DoubleSpendMisjudged,
+    PackageSkipped,
+    StuckTxs,
+    RecentRejectMissed,
+    StaleProposals,
+    RemoteSubmit,
+    RelayMismatched,
+    PoolReload,
+    EvictionMisordered,
+    // The run couldn't go on after the kinds below, so they are always fatal.
+    ScriptExpectFailed,
+    // A block is broken by the assembler of the tx-pool.
+    TemplateBroken,
+    // A block is broken by the producer which edits the template.
+    ProducedBlockBroken,
+    BlockConfirmFailed,
+    HookFailed,
+    StorageDiverged,
+    LedgerMismatched,
+}
+
+impl MismatchKind {
+    // The same as the keys of the policies.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::ExpectFailedButPassed => "expect_failed_but_passed",
+            Self::ExpectPassedButFailed => "expect_passed_but_failed",
+            Self::DoubleSpendAccepted => "double_spend_accepted",
+            Self::RejectReasonMismatched => "reject_reason_mismatched",
+            Self::DoubleSpendMisjudged => "double_spend_misjudged",
+            Self::PackageSkipped => "package_skipped",
+            Self::StuckTxs => "stuck_txs",
+            Self::RecentRejectMissed => "recent_reject_missed",
+            Self::StaleProposals => "stale_proposals",
+            Self::RemoteSubmit => "remote_submit",
+            Self::RelayMismatched => "relay_mismatched",
+            Self::PoolReload => "pool_reload",
+            Self::EvictionMisordered => "eviction_misordered",
+            Self::ScriptExpectFailed => "script_expect_failed",
+            Self::TemplateBroken => "template_broken",
+            Self::ProducedBlockBroken => "produced_block_broken",
+            Self::BlockConfirmFailed => "block_confirm_failed",
+            Self::HookFailed => "hook_failed",
+            Self::StorageDiverged => "storage_diverged",
+            Self::LedgerMismatched => "ledger_mismatched",
+        }
+    }
+}
+
+impl Mismatches {
+    pub(crate) fn count(&mut self, kind: MismatchKind) {
+        let count = match kind {
+            MismatchKind::ExpectFailedButPassed | MismatchKind::DoubleSpendAccepted => {
+                &mut self.expect_failed_but_passed
+            }
+            MismatchKind::ExpectPassedButFailed => &mut self.expect_passed_but_failed,
+            MismatchKind::RejectReasonMismatched => &mut self.reject_reason_mismatched,
+            MismatchKind::DoubleSpendMisjudged => &mut self.double_spend_misjudged,
+            MismatchKind::PackageSkipped => &mut self.package_skipped,
+            MismatchKind::StuckTxs => &mut self.stuck_txs,
+            MismatchKind::RecentRejectMissed => &mut self.recent_reject_missed,
+            MismatchKind::StaleProposals => &mut self.stale_proposals,
+            MismatchKind::RemoteSubmit => &mut self.remote_submit,
+            MismatchKind::RelayMismatched => &mut self.relay_mismatched,
+            MismatchKind::PoolReload => &mut self.pool_reload,
+            MismatchKind::EvictionMisordered => &mut self.eviction_misordered,
+            MismatchKind::ScriptExpectFailed => &mut self.script_expect_failed,
+            MismatchKind::TemplateBroken => &mut self.template_broken,
+            MismatchKind::ProducedBlockBroken => &mut self.produced_block_broken,
+            MismatchKind::BlockConfirmFailed => &mut self.block_confirm_failed,
+            MismatchKind::HookFailed => &mut self.hook_failed,
+            MismatchKind::StorageDiverged => &mut self.storage_diverged,
+            MismatchKind::LedgerMismatched => &mut self.ledger_mismatched,
+        };
+        *count += 1;
+    }
+
+    pub(crate) fn add(&mut self, other: &Self) {
+        self.expect_passed_but_failed += other.expect_passed_but_failed;
+        self.expect_failed_but_passed += other.expect_failed_but_passed;
+        self.package_skipped += other.package_skipped;
+        self.double_spend_misjudged += other.double_spend_misjudged;
+        self.stuck_txs += other.stuck_txs;
+        self.reject_reason_mismatched += other.reject_reason_mismatched;
+        self.recent_reject_missed += other.recent_reject_missed;
+        self.stale_proposals += other.stale_proposals;
+        self.remote_submit += other.remote_submit;
+        self.relay_mismatched += other.relay_mismatched;
+        self.pool_reload += other.pool_reload;
+        self.eviction_misordered += other.eviction_misordered;
+        self.script_expect_failed += other.script_expect_failed;
+        self.template_broken += other.template_broken;
+        self.produced_block_broken += other.produced_block_broken;
+        self.block_confirm_failed += other.block_confirm_failed;
+        self.hook_failed += other.hook_failed;
+        self.storage_diverged += other.storage_diverged;
+        self.ledger_mismatched += other.ledger_mismatched;
+    }
+
+    pub(crate) fn total(&self) -> u64 {
+        self.expect_passed_but_failed
+            + self.expect_failed_but_passed
+            + self.package_skipped
+            + self.double_spend_misjudged
+            + self.stuck_txs
+            + self.reject_reason_mismatched
+            + self.recent_reject_missed
+            + self.stale_proposals
+            + self.remote_submit
+            + self.relay_mismatched
+            + self.pool_reload
+            + self.eviction_misordered
+            + self.script_expect_failed
+            + self.template_broken
+            + self.produced_block_broken
+            + self.block_confirm_failed
+            + self.hook_failed
+            + self.storage_diverged
+            + self.ledger_mismatched
+    }
+}
+
+impl Throughput {
+    pub(crate) fn observe(&mut self, txs_per_sec: u64, pool_tx_size: usize) {
+        self.batches += 1;
+        self.peak_txs_per_sec = self.peak_txs_per_sec.max(txs_per_sec);
+        self.peak_pool_tx_size = self.peak_pool_tx_size.max(pool_tx_size);
+    }
+}
+
+impl From<[u64; 3]> for LatencyPercentiles {
+    fn from([p50_micros, p95_micros, p99_micros]: [u64; 3]) -> Self {
+        Self {
+            p50_micros,
+            p95_micros,
+            p99_micros,
+        }
+    }
+}
+
+impl From<&CacheStats> for StorageStats {
+    fn from(stats: &CacheStats) -> Self {
+        Self {
+            tx_pending: stats.tx_pending_cnt(),
+            tx_proposed: stats.tx_proposed_cnt(),
+            tx_committed: stats.tx_committed_cnt(),
+            tx_orphan: stats.tx_orphan_cnt(),
+            tx_evicted: stats.tx_evicted_cnt(),
+            tx_expired: stats.tx_expired_cnt(),
+            tx_failed: stats.tx_failed_cnt(),
+            cell_live: stats.cell_live_cnt(),
+            rejects: stats.rejects().clone(),
+        }
+    }
+}
+
+// The name of the variant, without the details.
+pub(crate) fn reject_reason(reject: &Reject) -> String {
+    let debug = format!("{:?}", reject);
+    debug
+        .split(|c: char| !c.is_alphanumeric())
+        .next()
+        .unwrap_or_default()
+        .to_owned()
+}
+
+impl FromStr for RunReport {
+    type Err = serde_yaml::Error;
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        serde_yaml::from_str(s)
+    }
+}
+
+impl fmt::Display for RunReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        serde_yaml::to_string(self)
+            .map_err(|_| fmt::Error)
+            .and_then(|s| write!(f, "{}", s))
+    }
+}
+
+impl fmt::Display for ParallelReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        serde_yaml::to_string(self)
+            .map_err(|_| fmt::Error)
+            .and_then(|s| write!(f, "{}", s))
+    }
+}
@@ -1,22 +1,839 @@
 // TODO Add more configurations for running.
 
-use std::{fmt, result::Result as StdResult, str::FromStr};
+use std::{collections::HashSet, fmt, result::Result as StdResult, str::FromStr};
 
-use ckb_types::core::BlockNumber;
+use ckb_types::core::{BlockNumber, Cycle};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+use super::{MismatchKind, Problems, WorkloadProfile};
+use crate::fuzzer::{hook_names, producer_names, strategy_names};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 #[serde(deny_unknown_fields)]
-pub(crate) struct RunEnv {
+pub struct RunEnv {
     pub(crate) chain_blocks: BlockNumber,
+    pub(crate) max_duration_secs: Option<u64>,
+    pub(crate) max_txs: Option<u64>,
     pub(crate) step_interval: u64,
     pub(crate) block_interval: u32,
+    pub(crate) max_generation_millis: Option<u64>,
+    pub(crate) slow_call_millis: Option<u64>,
+    pub(crate) request_timeout_millis: Option<u64>,
+    pub(crate) cache_capacity: Option<usize>,
+    pub(crate) seed: Option<u64>,
+    pub(crate) profile: Option<WorkloadProfile>,
+    pub(crate) verify_every_n_blocks: Option<BlockNumber>,
+    pub(crate) verify_ledger_every_n_blocks: Option<BlockNumber>,
+    pub(crate) bias_report_every_n_blocks: Option<BlockNumber>,
+    pub(crate) prune_every_n_blocks: Option<BlockNumber>,
+    pub(crate) reload_pool_every_n_blocks: Option<BlockNumber>,
+    pub(crate) stuck_after_n_blocks: Option<BlockNumber>,
+    pub(crate) fail_on_stuck_txs: Option<bool>,
+    pub(crate) strict: Option<bool>,
+    pub(crate) mismatch_policies: Option<MismatchPolicies>,
+    pub(crate) adaptive: Option<Adaptive>,
+    pub(crate) post_block_hooks: Option<Vec<String>>,
+    pub(crate) strategies: Option<Vec<WeightedStrategy>>,
+    pub(crate) block_producers: Option<Vec<WeightedStrategy>>,
+    pub(crate) partial_commit: Option<PartialCommit>,
+    pub(crate) withhold: Option<Withhold>,
+    pub(crate) template_params: Option<TemplateParams>,
+    pub(crate) eviction: Option<Eviction>,
+    pub(crate) long_chain: Option<LongChain>,
+    pub(crate) storage_faults: Option<StorageFaults>,
+    pub(crate) remote_submit: Option<RemoteSubmit>,
+    pub(crate) large_cycles: Option<LargeCycles>,
+    pub(crate) interleave: Option<Interleave>,
+    pub(crate) burst: Option<Burst>,
+    pub(crate) input_selection: Option<InputSelection>,
+    pub(crate) fee_rate: Option<FeeRateDistribution>,
+    pub(crate) outputs_count: Option<OutputsCountDistribution>,
+    pub(crate) data_size: Option<DataSize>,
+    pub(crate) tx_size_limit: Option<u64>,
+    pub(crate) witnesses: Option<Witnesses>,
+    pub(crate) live_cells: Option<LiveCells>,
+    pub(crate) generation: Option<Generation>,
+    pub(crate) tx_pool: Option<TxPoolOptions>,
+}
+
+// How to pick the live cells as the inputs of the random transactions.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum InputSelection {
+    // All live cells are equally likely.
+    Uniform,
+    // The cells with more capacity are more likely.
+    Capacity,
+    // The cells which are committed earlier are more likely.
+    Age,
+}
+
+// The distribution of the fee rates of the random transactions, in shannons per 1000 bytes.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub(crate) enum FeeRateDistribution {
+    // Both bounds are inclusive.
+    Uniform { min: u64, max: u64 },
+    // The negative samples are treated as zero.
+    Normal { mean: f64, std_dev: f64 },
+    // Most transactions pay about the scale, and a few pay much more.
+    Pareto { scale: f64, shape: f64 },
+}
+
+// The distribution of the outputs count of the random transactions.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub(crate) enum OutputsCountDistribution {
+    // Both bounds are inclusive.
+    Uniform { min: usize, max: usize },
+    // The samples which are less than one are treated as one.
+    Normal { mean: f64, std_dev: f64 },
+}
+
+// The sizes of the data of the outputs of the random transactions, the rest of the outputs
+// take a size uniformly from their free capacity.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct DataSize {
+    // The chance (in percent) of an empty data.
+    pub(crate) zero_chance: u32,
+    // The chance (in percent) of a data which takes all free capacity of the output.
+    pub(crate) max_chance: u32,
+    // The max size of a data, in bytes.
+    pub(crate) limit: Option<usize>,
+}
+
+// The random witnesses of the random transactions, which are not checked by the mocked scripts
+// but are counted in the sizes.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Witnesses {
+    // The chance (in percent) of a transaction to have the random witnesses.
+    pub(crate) chance: u32,
+    // The chance (in percent) that the count of the witnesses differs from the count of the
+    // inputs.
+    pub(crate) mismatch_chance: u32,
+    // The max size of a witness, in bytes.
+    pub(crate) max_size: usize,
+}
+
+// Keep the count of the live cells in a range, by splitting a cell when there are too few live
+// cells, and consolidating the cells when there are too many.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct LiveCells {
+    pub(crate) floor: usize,
+    pub(crate) ceiling: usize,
+}
+
+// How to treat a category of mismatches.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum MismatchPolicy {
+    // Count it and log a warning.
+    Warn,
+    // Only count it in the report.
+    Record,
+    // Stop the run with the report.
+    Fatal,
+}
+
+// The policies for the directions of the mismatches, the unset ones keep the default behaviors.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct MismatchPolicies {
+    // Accepted but expected to be rejected, warn by default, fatal in the strict mode.
+    pub(crate) expect_failed_but_passed: Option<MismatchPolicy>,
+    // Rejected but expected to be accepted, fatal by default.
+    pub(crate) expect_passed_but_failed: Option<MismatchPolicy>,
+    // Rejected as expected, but by an unexpected reason, warn by default.
+    pub(crate) reject_reason_mismatched: Option<MismatchPolicy>,
+    // A double spend is rejected, but not as a conflict, warn by default.
+    pub(crate) double_spend_misjudged: Option<MismatchPolicy>,
+    // A double spend is accepted, fatal by default.
+    pub(crate) double_spend_accepted: Option<MismatchPolicy>,
+    // A package is not packaged into the block template as a whole, warn by default.
+    pub(crate) package_skipped: Option<MismatchPolicy>,
+    // Pending transactions which are not committed for too many blocks, fatal by default if
+    // fail_on_stuck_txs is set, otherwise warn by default.
+    pub(crate) stuck_txs: Option<MismatchPolicy>,
+    // Rejected by resolving but not in the recent rejects, warn by default.
+    pub(crate) recent_reject_missed: Option<MismatchPolicy>,
+    // Proposals which are not pending or already proposed, warn by default.
+    pub(crate) stale_proposals: Option<MismatchPolicy>,
+    // The remote submissions are misjudged, warn by default.
+    pub(crate) remote_submit: Option<MismatchPolicy>,
+    // Not relayed or penalized as the submissions are, warn by default.
+    pub(crate) relay_mismatched: Option<MismatchPolicy>,
+    // Lost, unexpected or moved after the tx-pool is reloaded, warn by default.
+    pub(crate) pool_reload: Option<MismatchPolicy>,
+    // Evicted while a transaction has a lower eviction key in the pool, warn by default.
+    pub(crate) eviction_misordered: Option<MismatchPolicy>,
+}
+
+// Boost the chances of the rare generator branches which were rarely exercised recently.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Adaptive {
+    // Adapt the chances every how many blocks.
+    pub(crate) every_n_blocks: BlockNumber,
+    // A branch is under-exercised if it's exercised fewer times since the last adaption.
+    pub(crate) min_hits: u64,
+    // The max multiple of the original chance of a branch.
+    pub(crate) max_boost: u32,
+}
+
+// A strategy to generate transactions, or a producer to produce blocks, and how often it's
+// chosen.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct WeightedStrategy {
+    pub(crate) name: String,
+    pub(crate) weight: u32,
+}
+
+// Commit only a random subset of the block template's transactions.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct PartialCommit {
+    // The chance (in percent) to commit partially for each block.
+    pub(crate) block_chance: u32,
+    // The chance (in percent) to keep each transaction when commit partially.
+    pub(crate) keep_chance: u32,
+}
+
+// Never commit some proposed transactions by the withholding producer.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Withhold {
+    // The chance (in percent) that a transaction is withheld.
+    pub(crate) tx_chance: u32,
+}
+
+// Request the block templates with random limits, within and beyond the consensus limits.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TemplateParams {
+    // The chance (in percent) to request the block template with random limits for each block.
+    pub(crate) chance: u32,
+}
+
+// Overfill a small tx-pool to test the eviction.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Eviction {
+    // The max memory size of the tx-pool, in bytes.
+    pub(crate) max_mem_size: usize,
+    // The min count of transactions for each step.
+    pub(crate) batch_size: usize,
+}
+
+// Override the config of the tx-pool, the options which are not set keep the defaults of CKB.
+//
+// The min fee rate is always zero, since the fees of the generated transactions are not
+// checked against it.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TxPoolOptions {
+    // The max memory size of the tx-pool, in bytes.
+    pub(crate) max_mem_size: Option<usize>,
+    // The max cycles of all transactions in the tx-pool.
+    pub(crate) max_cycles: Option<Cycle>,
+    // The max cycles to verify a transaction at once.
+    pub(crate) max_tx_verify_cycles: Option<Cycle>,
+    pub(crate) max_ancestors_count: Option<usize>,
+    // How long and how many the hashes of the rejected transactions are kept.
+    pub(crate) keep_rejected_tx_hashes_days: Option<u8>,
+    pub(crate) keep_rejected_tx_hashes_count: Option<u64>,
+}
+
+// Build chains of unconfirmed transactions deeper than the max ancestors count.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct LongChain {
+    // The chance (in percent) to build a long chain for each step.
+    pub(crate) chance: u32,
+    // How many transactions beyond the max ancestors count.
+    pub(crate) extra_depth: usize,
+}
+
+// Submit some transactions as they are relayed from peers, with the declared cycles.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RemoteSubmit {
+    // The chance (in percent) that a transaction is submitted as a remote transaction.
+    pub(crate) tx_chance: u32,
+    // The chance (in percent) that the declared cycles of a remote transaction are wrong.
+    pub(crate) wrong_cycles_chance: u32,
+}
+
+// Generate scripts with large cycles, so the transactions are verified in chunks.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct LargeCycles {
+    // The chance (in percent) that a generated script has large cycles.
+    pub(crate) chance: u32,
+    // The max cycles to verify a transaction at once, the transactions with more cycles are
+    // suspended and verified in chunks.
+    pub(crate) chunk_limit: u64,
+}
+
+impl LargeCycles {
+    pub(crate) const MIN_CHUNK_LIMIT: u64 = 1_000_000;
+}
+
+// Interleave the submissions with the block building and the time.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Interleave {
+    // The chance (in percent) to build the block template in the middle of a batch, so the rest
+    // transactions arrive while the block is being built.
+    pub(crate) template_chance: u32,
+    // The chance (in percent) to advance the time before each transaction.
+    pub(crate) time_jump_chance: u32,
+    // The max milliseconds of a time jump.
+    pub(crate) max_time_jump_millis: u32,
+}
+
+// The transactions which are generated by the burst strategy.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Burst {
+    // The count of the tiny transactions in a burst.
+    pub(crate) size: usize,
+    // Whether all tiny transactions pay the same fee rate.
+    pub(crate) identical_fee_rates: bool,
+}
+
+impl Default for InputSelection {
+    fn default() -> Self {
+        Self::Uniform
+    }
+}
+
+impl FeeRateDistribution {
+    fn validate(&self, problems: &mut Problems) {
+        match *self {
+            Self::Uniform { min, max } => {
+                problems.check(
+                    min <= max,
+                    "fee_rate.min",
+                    format!("{} should not be greater than fee_rate.max ({})", min, max),
+                );
+            }
+            Self::Normal { mean, std_dev } => {
+                problems.check(
+                    mean.is_finite() && mean >= 0.0,
+                    "fee_rate.mean",
+                    "should not be negative",
+                );
+                problems.check(
+                    std_dev.is_finite() && std_dev >= 0.0,
+                    "fee_rate.std_dev",
+                    "should not be negative",
+                );
+            }
+            Self::Pareto { scale, shape } => {
+                problems.check(
+                    scale.is_finite() && scale > 0.0,
+                    "fee_rate.scale",
+                    "should be positive",
+                );
+                problems.check(
+                    shape.is_finite() && shape > 0.0,
+                    "fee_rate.shape",
+                    "should be positive",
+                );
+            }
+        }
+    }
+}
+
+impl OutputsCountDistribution {
+    fn validate(&self, problems: &mut Problems) {
+        match *self {
+            Self::Uniform { min, max } => {
+                problems.check(min > 0, "outputs_count.min", "should be positive");
+                problems.check(
+                    min <= max,
+                    "outputs_count.min",
+                    format!(
+                        "{} should not be greater than outputs_count.max ({})",
+                        min, max
+                    ),
+                );
+            }
+            Self::Normal { mean, std_dev } => {
+                problems.check(
+                    mean.is_finite() && mean >= 1.0,
+                    "outputs_count.mean",
+                    "should be at least 1",
+                );
+                problems.check(
+                    std_dev.is_finite() && std_dev >= 0.0,
+                    "outputs_count.std_dev",
+                    "should not be negative",
+                );
+            }
+        }
+    }
+}
+
+impl Burst {
+    pub(crate) const MAX_SIZE: usize = 4_000;
+}
+
+// The thresholds of the transaction generation.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Generation {
+    // The transactions are not generated until there are that many live cells.
+    pub(crate) min_live_cells: usize,
+    // The smallest capacity of a generated output, in CKBytes.
+    pub(crate) smallest_capacity: u64,
+    // How many times to sample the live cells or the transactions, for an input which meets the
+    // requirements, such as a cell which could not be spent.
+    pub(crate) retries: usize,
+}
+
+impl Generation {
+    // The occupied capacity of a cell which is locked by a mocked script, without a type script
+    // or any data, in CKBytes.
+    pub(crate) const MIN_SMALLEST_CAPACITY: u64 = 73;
+}
+
+impl Default for Generation {
+    fn default() -> Self {
+        Self {
+            min_live_cells: 1_000,
+            smallest_capacity: 138,
+            retries: 30,
+        }
+    }
+}
+
+impl Default for Burst {
+    fn default() -> Self {
+        Self {
+            size: 1_000,
+            identical_fee_rates: true,
+        }
+    }
+}
+
+// Fail or delay the write operations of the storage, only for testing the fuzzer itself.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct StorageFaults {
+    // Fail one in how many write operations, on average.
+    pub(crate) fail_one_in: u64,
+    // The chance (in percent) to delay each write operation.
+    pub(crate) delay_chance: u32,
+    // The max delay of a write operation, in milliseconds.
+    pub(crate) max_delay_millis: u64,
+}
+
+impl RunEnv {
+    // Check the ranges of the fields and the constraints between them.
+    pub(crate) fn validate(&self, problems: &mut Problems) {
+        problems.check(
+            self.block_interval > 0,
+            "block_interval",
+            "should be positive",
+        );
+        problems.check(
+            self.step_interval <= u64::from(self.block_interval),
+            "step_interval",
+            format!(
+                "{} should not be greater than block_interval ({}), \
+                the real pause of a step is longer than the simulated block interval",
+                self.step_interval, self.block_interval
+            ),
+        );
+        if let Some(secs) = self.max_duration_secs {
+            problems.check(
+                secs > 0,
+                "max_duration_secs",
+                "should be positive, remove it for no limit",
+            );
+        }
+        if let Some(count) = self.max_txs {
+            problems.check(
+                count > 0,
+                "max_txs",
+                "should be positive, remove it for no limit",
+            );
+        }
+        if let Some(millis) = self.max_generation_millis {
+            problems.check(
+                millis > 0,
+                "max_generation_millis",
+                "should be positive, remove it for no limit",
+            );
+        }
+        if let Some(millis) = self.slow_call_millis {
+            problems.check(
+                millis > 0,
+                "slow_call_millis",
+                "should be positive, remove it to never warn",
+            );
+        }
+        if let Some(millis) = self.request_timeout_millis {
+            problems.check(
+                millis > 0,
+                "request_timeout_millis",
+                "should be positive, remove it to never time out",
+            );
+        }
+        problems.check_interval(self.verify_every_n_blocks, "verify_every_n_blocks");
+        problems.check_interval(
+            self.verify_ledger_every_n_blocks,
+            "verify_ledger_every_n_blocks",
+        );
+        problems.check_interval(
+            self.bias_report_every_n_blocks,
+            "bias_report_every_n_blocks",
+        );
+        problems.check_interval(self.prune_every_n_blocks, "prune_every_n_blocks");
+        problems.check_interval(
+            self.reload_pool_every_n_blocks,
+            "reload_pool_every_n_blocks",
+        );
+        problems.check_interval(self.stuck_after_n_blocks, "stuck_after_n_blocks");
+        problems.check(
+            self.fail_on_stuck_txs.is_none() || self.stuck_after_n_blocks.is_some(),
+            "fail_on_stuck_txs",
+            "requires stuck_after_n_blocks",
+        );
+        if let Some(ref adaptive) = self.adaptive {
+            problems.check(
+                adaptive.every_n_blocks > 0,
+                "adaptive.every_n_blocks",
+                "should be positive",
+            );
+            problems.check(
+                adaptive.min_hits > 0,
+                "adaptive.min_hits",
+                "should be positive",
+            );
+            problems.check(
+                adaptive.max_boost > 0,
+                "adaptive.max_boost",
+                "should be positive",
+            );
+        }
+        if let Some(ref hooks) = self.post_block_hooks {
+            let available = hook_names();
+            for (index, name) in hooks.iter().enumerate() {
+                let path = format!("post_block_hooks[{}]", index);
+                problems.check_name(name, &available, &path);
+            }
+        }
+        if let Some(ref policies) = self.mismatch_policies {
+            problems.check(
+                !self.strict.unwrap_or(false)
+                    || matches!(
+                        policies.expect_failed_but_passed,
+                        None | Some(MismatchPolicy::Fatal)
+                    ),
+                "mismatch_policies.expect_failed_but_passed",
+                "should be fatal in the strict mode",
+            );
+        }
+        if let Some(ref strategies) = self.strategies {
+            validate_weighted(problems, "strategies", strategies, &strategy_names());
+        }
+        if let Some(ref producers) = self.block_producers {
+            validate_weighted(problems, "block_producers", producers, &producer_names());
+        }
+        if let Some(ref partial_commit) = self.partial_commit {
+            problems.check_percent(partial_commit.block_chance, "partial_commit.block_chance");
+            problems.check_percent(partial_commit.keep_chance, "partial_commit.keep_chance");
+        }
+        if let Some(ref withhold) = self.withhold {
+            problems.check_percent(withhold.tx_chance, "withhold.tx_chance");
+        }
+        if let Some(ref template_params) = self.template_params {
+            problems.check_percent(template_params.chance, "template_params.chance");
+        }
+        if let Some(ref eviction) = self.eviction {
+            problems.check(
+                eviction.max_mem_size > 0,
+                "eviction.max_mem_size",
+                "should be positive",
+            );
+        }
+        if let Some(ref long_chain) = self.long_chain {
+            problems.check_percent(long_chain.chance, "long_chain.chance");
+        }
+        if let Some(ref remote_submit) = self.remote_submit {
+            problems.check_percent(remote_submit.tx_chance, "remote_submit.tx_chance");
+            problems.check_percent(
+                remote_submit.wrong_cycles_chance,
+                "remote_submit.wrong_cycles_chance",
+            );
+        }
+        if let Some(ref large_cycles) = self.large_cycles {
+            problems.check_percent(large_cycles.chance, "large_cycles.chance");
+            problems.check(
+                large_cycles.chunk_limit >= LargeCycles::MIN_CHUNK_LIMIT,
+                "large_cycles.chunk_limit",
+                format!(
+                    "should be at least {}, the max cycles of normal scripts",
+                    LargeCycles::MIN_CHUNK_LIMIT
+                ),
+            );
+        }
+        if let Some(ref interleave) = self.interleave {
+            problems.check_percent(interleave.template_chance, "interleave.template_chance");
+            problems.check_percent(interleave.time_jump_chance, "interleave.time_jump_chance");
+            problems.check(
+                interleave.max_time_jump_millis > 0,
+                "interleave.max_time_jump_millis",
+                "should be positive",
+            );
+        }
+        if let Some(ref burst) = self.burst {
+            problems.check(
+                burst.size > 0 && burst.size <= Burst::MAX_SIZE,
+                "burst.size",
+                format!("should be in [1, {}]", Burst::MAX_SIZE),
+            );
+        }
+        if let Some(distribution) = self.fee_rate {
+            distribution.validate(problems);
+        }
+        if let Some(distribution) = self.outputs_count {
+            distribution.validate(problems);
+        }
+        if let Some(ref data_size) = self.data_size {
+            problems.check_percent(data_size.zero_chance, "data_size.zero_chance");
+            problems.check_percent(data_size.max_chance, "data_size.max_chance");
+            problems.check(
+                data_size.zero_chance + data_size.max_chance <= 100,
+                "data_size.max_chance",
+                "the sum of zero_chance and max_chance should not be greater than 100",
+            );
+        }
+        if let Some(limit) = self.tx_size_limit {
+            problems.check(limit > 0, "tx_size_limit", "should be positive");
+        }
+        if let Some(ref witnesses) = self.witnesses {
+            problems.check_percent(witnesses.chance, "witnesses.chance");
+            problems.check_percent(witnesses.mismatch_chance, "witnesses.mismatch_chance");
+        }
+        if let Some(ref tx_pool) = self.tx_pool {
+            if let Some(size) = tx_pool.max_mem_size {
+                problems.check(size > 0, "tx_pool.max_mem_size", "should be positive");
+                problems.check(
+                    self.eviction.is_none(),
+                    "tx_pool.max_mem_size",
+                    "should not be set with eviction.max_mem_size",
+                );
+            }
+            if let Some(cycles) = tx_pool.max_tx_verify_cycles {
+                problems.check(
+                    cycles >= LargeCycles::MIN_CHUNK_LIMIT,
+                    "tx_pool.max_tx_verify_cycles",
+                    format!("should be {} at least", LargeCycles::MIN_CHUNK_LIMIT),
+                );
+                problems.check(
+                    self.large_cycles.is_none(),
+                    "tx_pool.max_tx_verify_cycles",
+                    "should not be set with large_cycles.chunk_limit",
+                );
+            }
+            if let Some(count) = tx_pool.max_ancestors_count {
+                problems.check(
+                    count > 0,
+                    "tx_pool.max_ancestors_count",
+                    "should be positive",
+                );
+            }
+        }
+        if let Some(ref generation) = self.generation {
+            problems.check(
+                generation.smallest_capacity >= Generation::MIN_SMALLEST_CAPACITY,
+                "generation.smallest_capacity",
+                format!("should be {} at least", Generation::MIN_SMALLEST_CAPACITY),
+            );
+            problems.check(
+                generation.retries > 0,
+                "generation.retries",
+                "should be positive",
+            );
+        }
+        if let Some(ref live_cells) = self.live_cells {
+            problems.check(
+                live_cells.floor < live_cells.ceiling,
+                "live_cells.ceiling",
+                format!(
+                    "{} should be greater than live_cells.floor ({})",
+                    live_cells.ceiling, live_cells.floor
+                ),
+            );
+        }
+        if let Some(ref faults) = self.storage_faults {
+            problems.check(
+                faults.fail_one_in > 0,
+                "storage_faults.fail_one_in",
+                "should be positive",
+            );
+            problems.check_percent(faults.delay_chance, "storage_faults.delay_chance");
+        }
+    }
+}
+
+impl RunEnv {
+    // The resolved policies, the strict mode makes the accepted invalid transactions fatal.
+    pub(crate) fn resolved_mismatch_policies(&self) -> ResolvedMismatchPolicies {
+        ResolvedMismatchPolicies {
+            policies: self.mismatch_policies.clone().unwrap_or_default(),
+            strict: self.strict.unwrap_or(false),
+            fail_on_stuck_txs: self.fail_on_stuck_txs.unwrap_or(false),
+        }
+    }
+}
+
+// The policies which are applied in a run.
+#[derive(Debug, Clone)]
+pub(crate) struct ResolvedMismatchPolicies {
+    policies: MismatchPolicies,
+    strict: bool,
+    fail_on_stuck_txs: bool,
+}
+
+impl ResolvedMismatchPolicies {
+    // The configured policy of the kind, or its default.
+    pub(crate) fn get(&self, kind: MismatchKind) -> MismatchPolicy {
+        let fatal_if = |fatal: bool| {
+            if fatal {
+                MismatchPolicy::Fatal
+            } else {
+                MismatchPolicy::Warn
+            }
+        };
+        let policies = &self.policies;
+        let (policy, default) = match kind {
+            MismatchKind::ExpectFailedButPassed => {
+                (policies.expect_failed_but_passed, fatal_if(self.strict))
+            }
+            MismatchKind::ExpectPassedButFailed => {
+                (policies.expect_passed_but_failed, MismatchPolicy::Fatal)
+            }
+            MismatchKind::DoubleSpendAccepted => {
+                (policies.double_spend_accepted, MismatchPolicy::Fatal)
+            }
+            MismatchKind::RejectReasonMismatched => {
+                (policies.reject_reason_mismatched, MismatchPolicy::Warn)
+            }
+            MismatchKind::DoubleSpendMisjudged => {
+                (policies.double_spend_misjudged, MismatchPolicy::Warn)
+            }
+            MismatchKind::PackageSkipped => (policies.package_skipped, MismatchPolicy::Warn),
+            MismatchKind::StuckTxs => (policies.stuck_txs, fatal_if(self.fail_on_stuck_txs)),
+            MismatchKind::RecentRejectMissed => {
+                (policies.recent_reject_missed, MismatchPolicy::Warn)
+            }
+            MismatchKind::StaleProposals => (policies.stale_proposals, MismatchPolicy::Warn),
+            MismatchKind::RemoteSubmit => (policies.remote_submit, MismatchPolicy::Warn),
+            MismatchKind::RelayMismatched => (policies.relay_mismatched, MismatchPolicy::Warn),
+            MismatchKind::PoolReload => (policies.pool_reload, MismatchPolicy::Warn),
+            MismatchKind::EvictionMisordered => {
+                (policies.eviction_misordered, MismatchPolicy::Warn)
+            }
+            MismatchKind::ScriptExpectFailed
+            | MismatchKind::TemplateBroken
+            | MismatchKind::ProducedBlockBroken
+            | MismatchKind::BlockConfirmFailed
+            | MismatchKind::HookFailed
+            | MismatchKind::StorageDiverged
+            | MismatchKind::LedgerMismatched => return MismatchPolicy::Fatal,
+        };
+        policy.unwrap_or(default)
+    }
+}
+
+impl MismatchPolicy {
+    // Log the mismatch as the policy, returns whether the run should be stopped.
+    pub(crate) fn log(self, msg: &str) -> bool {
+        match self {
+            Self::Warn => log::warn!("{}", msg),
+            Self::Record => log::trace!("{}", msg),
+            Self::Fatal => log::error!("{}", msg),
+        }
+        self == Self::Fatal
+    }
+}
+
+impl RunEnv {
+    // The fake time of the tx-pools is shared by the parallel instances, it's moved forward by
+    // the fastest clock of them, so the strategies and the producers which expect the tx-pool to
+    // see the clock of their own chain are refused.
+    pub(crate) fn validate_parallel(&self, problems: &mut Problems) {
+        let time_dependent = [
+            ("strategies", &self.strategies, "time_lock"),
+            (
+                "block_producers",
+                &self.block_producers,
+                "timestamp_anomaly",
+            ),
+        ];
+        for (path, items, refused) in time_dependent {
+            let items = if let Some(items) = items {
+                items
+            } else {
+                continue;
+            };
+            for (index, item) in items.iter().enumerate() {
+                problems.check(
+                    item.name != refused || item.weight == 0,
+                    &format!("{}[{}].name", path, index),
+                    format!(
+                        "{} depends on the fake time, which is shared by the parallel instances",
+                        refused
+                    ),
+                );
+            }
+        }
+    }
+}
+
+// Check the names and the weights of the items which are chosen by the weights.
+fn validate_weighted(
+    problems: &mut Problems,
+    path: &str,
+    items: &[WeightedStrategy],
+    available: &[&str],
+) {
+    let mut names = HashSet::new();
+    for (index, item) in items.iter().enumerate() {
+        let name_path = format!("{}[{}].name", path, index);
+        problems.check_name(&item.name, available, &name_path);
+        problems.check(
+            names.insert(item.name.as_str()),
+            &name_path,
+            format!("{} is duplicated", item.name),
+        );
+    }
+    problems.check(
+        items.iter().any(|item| item.weight > 0),
+        path,
+        "at least one item should have a positive weight",
+    );
 }
 
 impl FromStr for RunEnv {
-    type Err = serde_yaml::Error;
+    type Err = Problems;
     fn from_str(s: &str) -> StdResult<Self, Self::Err> {
-        serde_yaml::from_str(s)
+        let run_env: Self = serde_yaml::from_str(s)?;
+        let mut problems = Problems::default();
+        run_env.validate(&mut problems);
+        if problems.is_empty() {
+            Ok(run_env)
+        } else {
+            Err(problems)
+        }
     }
 }
 
@@ -0,0 +1,89 @@
+use std::{fmt, result::Result as StdResult, str::FromStr, time};
+
+use serde::{Deserialize, Serialize};
+
+use super::RunEnv;
+
+// The revision of the linked CKB crates, keep it the same as the revision in `Cargo.toml`.
+const CKB_REVISION: &str = "yangby-cryptape/ckb@352427b";
+
+// What a run is, and how it ends, to find out which run produced a stored divergence.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RunRecord {
+    pub(crate) run_id: u32,
+    // The wall-clock time in seconds since the UNIX epoch, not the fake time of the chain.
+    pub(crate) started_at: u64,
+    pub(crate) finished_at: Option<u64>,
+    pub(crate) version: String,
+    pub(crate) ckb_revision: String,
+    pub(crate) seed: u64,
+    pub(crate) resumed: bool,
+    pub(crate) run_env: RunEnv,
+    // None if the run is still running, or it's killed or crashed.
+    pub(crate) termination: Option<String>,
+}
+
+impl RunRecord {
+    pub(crate) fn start(run_id: u32, seed: u64, resumed: bool, run_env: &RunEnv) -> Self {
+        Self {
+            run_id,
+            started_at: unix_secs(),
+            finished_at: None,
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            ckb_revision: CKB_REVISION.to_owned(),
+            seed,
+            resumed,
+            run_env: run_env.clone(),
+            termination: None,
+        }
+    }
+
+    pub(crate) fn finish(&mut self, termination: &str) {
+        self.finished_at = Some(unix_secs());
+        self.termination = Some(termination.to_owned());
+    }
+
+    // A one-line summary for listing.
+    pub(crate) fn summary(&self) -> String {
+        let duration = self
+            .finished_at
+            .map(|finished_at| format!("{}s", finished_at.saturating_sub(self.started_at)))
+            .unwrap_or_else(|| "-".to_owned());
+        format!(
+            "run {}: started at {}, lasted {}, version {} ({}), seed {}{}, {}",
+            self.run_id,
+            self.started_at,
+            duration,
+            self.version,
+            self.ckb_revision,
+            self.seed,
+            if self.resumed { " (resumed)" } else { "" },
+            self.termination
+                .as_deref()
+                .unwrap_or("not finished (still running, killed or crashed)"),
+        )
+    }
+}
+
+fn unix_secs() -> u64 {
+    time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+impl FromStr for RunRecord {
+    type Err = serde_yaml::Error;
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        serde_yaml::from_str(s)
+    }
+}
+
+impl fmt::Display for RunRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        serde_yaml::to_string(self)
+            .map_err(|_| fmt::Error)
+            .and_then(|s| write!(f, "{}", s))
+    }
+}
@@ -0,0 +1,258 @@
+use std::{collections::HashMap, fmt, mem, result::Result as StdResult, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use super::Problems;
+use crate::fuzzer::strategy_names;
+
+// A sequence of actions to run instead of purely random steps.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Script {
+    pub(crate) actions: Vec<Action>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Action {
+    // Run some random steps, each step mines a block.
+    Random(u64),
+    // Submit transactions which are generated by a strategy, in the next mined block.
+    Submit(Submit),
+    // Mine some blocks, the first block includes the submitted transactions.
+    Mine(u64),
+    // Check the tx-pool after the last mined block, the run fails if it's not satisfied.
+    Expect(Expect),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Submit {
+    // The name to refer the transactions in the expectations.
+    pub(crate) name: Option<String>,
+    pub(crate) strategy: String,
+    // The min count of transactions, a strategy could generate several transactions at once.
+    pub(crate) count: usize,
+}
+
+// An assertion which is checked against both the storage and the tx-pool.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Expect {
+    // The count of the pending transactions, including the transactions in the gap.
+    PoolPending(usize),
+    // The count of the proposed transactions.
+    PoolProposed(usize),
+    // The status of the transactions from a named submit action.
+    TxStatus(ExpectTxStatus),
+    // The reason why the transactions from a named submit action are rejected.
+    RejectReason(ExpectRejectReason),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ExpectTxStatus {
+    pub(crate) submit: String,
+    // The index of the transaction in the submit action, all transactions by default.
+    pub(crate) index: Option<usize>,
+    pub(crate) status: ExpectedStatus,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ExpectRejectReason {
+    pub(crate) submit: String,
+    // The index of the transaction in the submit action, all transactions by default.
+    pub(crate) index: Option<usize>,
+    // The name of the variant of the reject, for example, "Resolve".
+    pub(crate) reason: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ExpectedStatus {
+    Pending,
+    Gap,
+    Proposed,
+    Committed,
+    Rejected,
+}
+
+// The actions of a step, each step mines a block except the expectations.
+#[derive(Debug, Clone)]
+pub(crate) enum ScriptStep {
+    Random,
+    Submit(Vec<Submit>),
+    Expect(Expect),
+}
+
+impl fmt::Display for ExpectedStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Pending => write!(f, "pending"),
+            Self::Gap => write!(f, "gap"),
+            Self::Proposed => write!(f, "proposed"),
+            Self::Committed => write!(f, "committed"),
+            Self::Rejected => write!(f, "rejected"),
+        }
+    }
+}
+
+impl fmt::Display for Expect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::PoolPending(count) => write!(f, "pool pending == {}", count),
+            Self::PoolProposed(count) => write!(f, "pool proposed == {}", count),
+            Self::TxStatus(inner) => write!(
+                f,
+                "tx {} status == {}",
+                display_tx_ref(&inner.submit, inner.index),
+                inner.status
+            ),
+            Self::RejectReason(inner) => write!(
+                f,
+                "tx {} reject reason == {}",
+                display_tx_ref(&inner.submit, inner.index),
+                inner.reason
+            ),
+        }
+    }
+}
+
+fn display_tx_ref(submit: &str, index: Option<usize>) -> String {
+    if let Some(index) = index {
+        format!("{}[{}]", submit, index)
+    } else {
+        format!("{}[*]", submit)
+    }
+}
+
+impl Script {
+    // Expand the actions into steps, the submitted transactions without a following mine
+    // action are mined in an extra step.
+    pub(crate) fn steps(&self) -> Vec<ScriptStep> {
+        let mut steps = Vec::new();
+        let mut submits = Vec::new();
+        for action in &self.actions {
+            match action {
+                Action::Random(count) => {
+                    if !submits.is_empty() {
+                        steps.push(ScriptStep::Submit(mem::take(&mut submits)));
+                    }
+                    for _ in 0..*count {
+                        steps.push(ScriptStep::Random);
+                    }
+                }
+                Action::Submit(submit) => {
+                    submits.push(submit.clone());
+                }
+                Action::Mine(count) => {
+                    for _ in 0..*count {
+                        steps.push(ScriptStep::Submit(mem::take(&mut submits)));
+                    }
+                }
+                Action::Expect(expect) => {
+                    if !submits.is_empty() {
+                        steps.push(ScriptStep::Submit(mem::take(&mut submits)));
+                    }
+                    steps.push(ScriptStep::Expect(expect.clone()));
+                }
+            }
+        }
+        if !submits.is_empty() {
+            steps.push(ScriptStep::Submit(submits));
+        }
+        steps
+    }
+
+    pub(crate) fn validate(&self, problems: &mut Problems) {
+        problems.check(!self.actions.is_empty(), "actions", "should not be empty");
+        let available = strategy_names();
+        // The counts of the named submit actions before.
+        let mut named = HashMap::new();
+        for (index, action) in self.actions.iter().enumerate() {
+            match action {
+                Action::Random(count) => {
+                    let path = format!("actions[{}].random", index);
+                    problems.check(*count > 0, &path, "should be positive");
+                }
+                Action::Submit(submit) => {
+                    let path = format!("actions[{}].submit.strategy", index);
+                    problems.check_name(&submit.strategy, &available, &path);
+                    let path = format!("actions[{}].submit.count", index);
+                    problems.check(submit.count > 0, &path, "should be positive");
+                    if let Some(ref name) = submit.name {
+                        let path = format!("actions[{}].submit.name", index);
+                        problems.check(
+                            named.insert(name.as_str(), submit.count).is_none(),
+                            &path,
+                            format!("{} is duplicated", name),
+                        );
+                    }
+                }
+                Action::Mine(count) => {
+                    let path = format!("actions[{}].mine", index);
+                    problems.check(*count > 0, &path, "should be positive");
+                }
+                Action::Expect(Expect::TxStatus(inner)) => {
+                    let path = format!("actions[{}].expect.tx_status", index);
+                    check_tx_ref(problems, &named, &inner.submit, inner.index, &path);
+                }
+                Action::Expect(Expect::RejectReason(inner)) => {
+                    let path = format!("actions[{}].expect.reject_reason", index);
+                    check_tx_ref(problems, &named, &inner.submit, inner.index, &path);
+                }
+                Action::Expect(_) => {}
+            }
+        }
+    }
+
+    // All strategies which are used in the script.
+    pub(crate) fn strategies(&self) -> Vec<&str> {
+        self.actions
+            .iter()
+            .filter_map(|action| match action {
+                Action::Submit(submit) => Some(submit.strategy.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+// The transactions should be submitted by a named submit action before.
+fn check_tx_ref(
+    problems: &mut Problems,
+    named: &HashMap<&str, usize>,
+    submit: &str,
+    index: Option<usize>,
+    path: &str,
+) {
+    if let Some(count) = named.get(submit) {
+        if let Some(index) = index {
+            problems.check(
+                index < *count,
+                &format!("{}.index", path),
+                format!("{} is out of the count of {} ({})", index, submit, count),
+            );
+        }
+    } else {
+        problems.add(
+            &format!("{}.submit", path),
+            format!("{} is not a named submit action before", submit),
+        );
+    }
+}
+
+impl FromStr for Script {
+    type Err = Problems;
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        let script: Self = serde_yaml::from_str(s)?;
+        let mut problems = Problems::default();
+        script.validate(&mut problems);
+        if problems.is_empty() {
+            Ok(script)
+        } else {
+            Err(problems)
+        }
+    }
+}
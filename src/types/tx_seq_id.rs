@@ -0,0 +1,55 @@
+use std::fmt;
+
+use crate::error::{Error, Result};
+
+// The position of a generated transaction in the sequence, which is independent of its hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TxSeqId {
+    // The count of runs on the data directory, starts from 1.
+    pub(crate) run: u32,
+    // The step in the run, one block is committed for each step.
+    pub(crate) step: u64,
+    // The index of the transaction in the step.
+    pub(crate) index: u32,
+}
+
+impl fmt::Display for TxSeqId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}-{}", self.run, self.step, self.index)
+    }
+}
+
+impl TxSeqId {
+    const NAME: &'static str = "TxSeqId";
+    const SIZE: usize = 4 + 8 + 4;
+
+    pub(crate) fn from_slice(slice: &[u8]) -> Result<Self> {
+        if slice.len() != Self::SIZE {
+            let reason = format!(
+                "incorrect data size (expect: {}, actual: {})",
+                Self::SIZE,
+                slice.len()
+            );
+            return Err(Error::broken_since(Self::NAME, &reason));
+        }
+        let mut run_bytes = [0u8; 4];
+        run_bytes.copy_from_slice(&slice[..4]);
+        let mut step_bytes = [0u8; 8];
+        step_bytes.copy_from_slice(&slice[4..12]);
+        let mut index_bytes = [0u8; 4];
+        index_bytes.copy_from_slice(&slice[12..]);
+        Ok(Self {
+            run: u32::from_le_bytes(run_bytes),
+            step: u64::from_le_bytes(step_bytes),
+            index: u32::from_le_bytes(index_bytes),
+        })
+    }
+
+    pub(crate) fn to_vec(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::SIZE);
+        bytes.extend_from_slice(&self.run.to_le_bytes());
+        bytes.extend_from_slice(&self.step.to_le_bytes());
+        bytes.extend_from_slice(&self.index.to_le_bytes());
+        bytes
+    }
+}
@@ -0,0 +1,66 @@
+use std::fmt;
+
+// The problems of a configuration, all of them are reported at once.
+#[derive(Debug, Default)]
+pub struct Problems {
+    inner: Vec<String>,
+}
+
+impl Problems {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    // Add a problem of a field, the path is like "adaptive.max_boost" or "strategies[1].name".
+    pub(crate) fn add<T: fmt::Display>(&mut self, path: &str, reason: T) {
+        self.inner.push(format!("{}: {}", path, reason));
+    }
+
+    pub(crate) fn check<T: fmt::Display>(&mut self, ok: bool, path: &str, reason: T) {
+        if !ok {
+            self.add(path, reason);
+        }
+    }
+
+    // Check that a chance (in percent) is not greater than 100.
+    pub(crate) fn check_percent(&mut self, value: u32, path: &str) {
+        self.check(
+            value <= 100,
+            path,
+            format!("{} is not a chance in percent, should be 0 to 100", value),
+        );
+    }
+
+    // Check that an optional interval is positive, zero disables it silently.
+    pub(crate) fn check_interval(&mut self, value: Option<u64>, path: &str) {
+        if let Some(value) = value {
+            self.check(value > 0, path, "should be positive, remove it to disable");
+        }
+    }
+
+    // Check that a name is one of the available names.
+    pub(crate) fn check_name(&mut self, name: &str, available: &[&str], path: &str) {
+        if !available.contains(&name) {
+            let reason = format!("{} is unknown (available: {})", name, available.join(", "));
+            self.add(path, reason);
+        }
+    }
+}
+
+impl From<serde_yaml::Error> for Problems {
+    fn from(err: serde_yaml::Error) -> Self {
+        Self {
+            inner: vec![err.to_string()],
+        }
+    }
+}
+
+impl fmt::Display for Problems {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} problem(s) found", self.inner.len())?;
+        for problem in &self.inner {
+            write!(f, "\n  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::WeightedStrategy;
+
+// The max count of the small outputs in a transaction, the rest capacity is in a change output.
+const MAX_PAYOUTS: usize = 16;
+const MAX_DUST_OUTPUTS: usize = 64;
+
+// A named preset of the strategies and the shapes of transactions, to generate a realistic
+// workload without tuning the options one by one.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum WorkloadProfile {
+    // Pay to many users from a few cells, and bump the stuck payouts by CPFP.
+    ExchangeHotWallet,
+    // Mint many small cells in bursts, and chain the mints one after another.
+    NftMintStorm,
+    // Split the cells into cells with the smallest capacity.
+    DustAttack,
+    // Merge many cells into one.
+    ConsolidationDay,
+}
+
+// How the capacity of the inputs is split into the outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OutputsShape {
+    // Split randomly.
+    Random,
+    // Several small outputs and a change output.
+    Payouts,
+    // Many outputs with the smallest capacity and a change output.
+    Dust,
+    // Only one output.
+    Single,
+}
+
+impl WorkloadProfile {
+    pub(crate) fn strategies(self) -> Vec<WeightedStrategy> {
+        let weighted = match self {
+            Self::ExchangeHotWallet => &[("random", 80), ("cpfp", 15), ("double_spend", 5)][..],
+            Self::NftMintStorm => &[("random", 70), ("long_chain", 30)][..],
+            Self::DustAttack => &[("random", 100)][..],
+            Self::ConsolidationDay => &[("random", 90), ("cpfp", 10)][..],
+        };
+        weighted
+            .iter()
+            .map(|(name, weight)| WeightedStrategy {
+                name: (*name).to_owned(),
+                weight: *weight,
+            })
+            .collect()
+    }
+
+    // The chance (in percent) to add another input cell.
+    pub(crate) fn next_input_chance(self) -> u32 {
+        match self {
+            Self::ExchangeHotWallet | Self::NftMintStorm | Self::DustAttack => 30,
+            Self::ConsolidationDay => 97,
+        }
+    }
+
+    pub(crate) fn outputs_shape(self) -> OutputsShape {
+        match self {
+            Self::ExchangeHotWallet | Self::NftMintStorm => OutputsShape::Payouts,
+            Self::DustAttack => OutputsShape::Dust,
+            Self::ConsolidationDay => OutputsShape::Single,
+        }
+    }
+}
+
+impl OutputsShape {
+    // The max count of the outputs before the change output, no limit if it's none.
+    pub(crate) fn max_small_outputs(self) -> Option<usize> {
+        match self {
+            Self::Random => None,
+            Self::Payouts => Some(MAX_PAYOUTS),
+            Self::Dust => Some(MAX_DUST_OUTPUTS),
+            Self::Single => Some(0),
+        }
+    }
+}
+
+impl fmt::Display for WorkloadProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ExchangeHotWallet => write!(f, "exchange_hot_wallet"),
+            Self::NftMintStorm => write!(f, "nft_mint_storm"),
+            Self::DustAttack => write!(f, "dust_attack"),
+            Self::ConsolidationDay => write!(f, "consolidation_day"),
+        }
+    }
+}
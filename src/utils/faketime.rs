@@ -1,8 +1,38 @@
-use std::env;
+// The time of the tx-pool is controlled by the `faketime` crate through the env "FAKETIME".
+//
+// The blocks are stamped by the clock of each mocked chain, the fake time only follows the
+// clocks for the tx-pool. It's shared by all instances in the process, so it's enabled only
+// once, and it never goes backwards: a clock only moves it forward, and the updates are
+// serialized, so the tx-pools which run in parallel just see the time passes faster.
+// There is no offset for each instance, since the tx-pool reads the time of the process, so the
+// checks which depend on the time of a tx-pool are disabled in the parallel runs.
+//
+// When the feature "system-time" is enabled, the real system time is used instead, for the
+// platforms which the fake time doesn't work on. It's a degraded mode: the block intervals
+// are still simulated by the clocks, but the tx-pool only sees the wall clock.
 
-use crate::error::{Error, Result};
+#[cfg(not(feature = "system-time"))]
+use std::{
+    env,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+};
 
+#[cfg(not(feature = "system-time"))]
+use crate::error::Error;
+use crate::error::Result;
+
+#[cfg(not(feature = "system-time"))]
+static ENABLED: AtomicBool = AtomicBool::new(false);
+// Held while the fake time is updated.
+#[cfg(not(feature = "system-time"))]
+static UPDATING: AtomicBool = AtomicBool::new(false);
+
+#[cfg(not(feature = "system-time"))]
 pub(crate) fn enable() -> Result<()> {
+    if ENABLED.swap(true, Ordering::SeqCst) {
+        return Ok(());
+    }
     let faketime_file = faketime::millis_tempfile(0).map_err(|err| {
         let errmsg = format!("failed to create faketime tempfile since {}", err);
         Error::Runtime(errmsg)
@@ -12,7 +42,33 @@ pub(crate) fn enable() -> Result<()> {
     Ok(())
 }
 
+// Move the fake time forward to the timestamp, it's never moved backwards.
+#[cfg(not(feature = "system-time"))]
 pub(crate) fn update(timestamp_millis: u64) -> Result<()> {
+    serialized(|| {
+        if timestamp_millis > faketime::unix_time_as_millis() {
+            write(timestamp_millis)
+        } else {
+            Ok(())
+        }
+    })
+}
+
+#[cfg(not(feature = "system-time"))]
+fn serialized<F: FnOnce() -> Result<()>>(f: F) -> Result<()> {
+    while UPDATING
+        .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        thread::yield_now();
+    }
+    let result = f();
+    UPDATING.store(false, Ordering::Release);
+    result
+}
+
+#[cfg(not(feature = "system-time"))]
+fn write(timestamp_millis: u64) -> Result<()> {
     env::var("FAKETIME")
         .map_err(|err| {
             let errmsg = format!("failed to read env \"FAKETIME\" since {}", err);
@@ -26,7 +82,21 @@ pub(crate) fn update(timestamp_millis: u64) -> Result<()> {
         })
 }
 
-pub(crate) fn increase(millis: u32) -> Result<()> {
-    let prev_timestamp_millis = faketime::unix_time_as_millis();
-    update(prev_timestamp_millis + u64::from(millis))
+#[cfg(feature = "system-time")]
+pub(crate) fn enable() -> Result<()> {
+    log::warn!("[Time] use the system time, the tx-pool doesn't see the block intervals");
+    Ok(())
+}
+
+#[cfg(feature = "system-time")]
+pub(crate) fn update(timestamp_millis: u64) -> Result<()> {
+    let now = faketime::unix_time_as_millis();
+    if timestamp_millis > now {
+        log::trace!(
+            "[Time] the timestamp {} is in the future (now: {}) for the tx-pool",
+            timestamp_millis,
+            now
+        );
+    }
+    Ok(())
 }
@@ -42,3 +42,74 @@ pub(crate) fn need_directory<P: AsRef<Path>>(path: P) -> Result<()> {
     }
     Ok(())
 }
+
+pub(crate) fn write_file<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> Result<()> {
+    let path = path.as_ref();
+    fs::write(path, contents).map_err(|err| {
+        let errmsg = format!("failed to write file {} since {}", path.display(), err);
+        Error::runtime(errmsg)
+    })
+}
+
+// Read a file, none if it doesn't exist.
+pub(crate) fn read_file_opt<P: AsRef<Path>>(path: P) -> Result<Option<String>> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Ok(None);
+    }
+    fs::read_to_string(path).map(Some).map_err(|err| {
+        let errmsg = format!("failed to read file {} since {}", path.display(), err);
+        Error::runtime(errmsg)
+    })
+}
+
+pub(crate) fn read_binary<P: AsRef<Path>>(path: P) -> Result<Vec<u8>> {
+    let path = path.as_ref();
+    fs::read(path).map_err(|err| {
+        let errmsg = format!("failed to read file {} since {}", path.display(), err);
+        Error::runtime(errmsg)
+    })
+}
+
+pub(crate) fn remove_directory<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref();
+    fs::remove_dir_all(path).map_err(|err| {
+        let errmsg = format!(
+            "failed to remove directory {} since {}",
+            path.display(),
+            err
+        );
+        Error::runtime(errmsg)
+    })
+}
+
+// Copy a directory recursively, the target should not exist.
+pub(crate) fn copy_directory<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> Result<()> {
+    let from = from.as_ref();
+    let to = to.as_ref();
+    check_directory(to, false)?;
+    create_directory(to)?;
+    let entries = fs::read_dir(from).map_err(|err| {
+        let errmsg = format!("failed to read directory {} since {}", from.display(), err);
+        Error::runtime(errmsg)
+    })?;
+    for entry in entries {
+        let entry = entry.map_err(Error::runtime)?;
+        let source = entry.path();
+        let target = to.join(entry.file_name());
+        if source.is_dir() {
+            copy_directory(&source, &target)?;
+        } else {
+            fs::copy(&source, &target).map_err(|err| {
+                let errmsg = format!(
+                    "failed to copy {} to {} since {}",
+                    source.display(),
+                    target.display(),
+                    err
+                );
+                Error::runtime(errmsg)
+            })?;
+        }
+    }
+    Ok(())
+}
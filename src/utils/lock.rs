@@ -0,0 +1,81 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{Read as _, Seek as _, SeekFrom, Write as _},
+    path::{Path, PathBuf},
+    process,
+};
+
+use fs2::FileExt as _;
+
+use crate::error::{Error, Result};
+
+// The lock file in the data directory.
+const LOCK_FILE: &str = "fuzzer.lock";
+
+// An exclusive lock of a data directory, so two processes never write the same data directory.
+//
+// It's an advisory lock by `flock`, which is released when the process exits, even if it's
+// killed. The lock file keeps the ID of the process which holds the lock, for the error message.
+pub struct DataDirLock {
+    path: PathBuf,
+    file: File,
+}
+
+impl DataDirLock {
+    // Lock the data directory, or fail if it's locked by another process.
+    //
+    // With `force`, the lock file is replaced, for the stale locks, for example, the data
+    // directory is on a network file system which doesn't release the locks.
+    pub fn acquire<P: AsRef<Path>>(data_dir: P, force: bool) -> Result<Self> {
+        let path = data_dir.as_ref().join(LOCK_FILE);
+        if force && path.exists() {
+            log::warn!("[Lock] force to unlock {}", path.display());
+            fs::remove_file(&path).map_err(|err| {
+                let errmsg = format!("failed to remove {} since {}", path.display(), err);
+                Error::runtime(errmsg)
+            })?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(&path)
+            .map_err(|err| {
+                let errmsg = format!("failed to open {} since {}", path.display(), err);
+                Error::runtime(errmsg)
+            })?;
+        if file.try_lock_exclusive().is_err() {
+            let mut holder = String::new();
+            let _ = file.read_to_string(&mut holder);
+            let errmsg = format!(
+                "the data directory {} is used by another process (pid: {}), \
+                use --force-unlock if the lock is stale",
+                data_dir.as_ref().display(),
+                holder.trim()
+            );
+            return Err(Error::config(errmsg));
+        }
+        file.set_len(0)
+            .and_then(|()| file.seek(SeekFrom::Start(0)))
+            .and_then(|_| write!(file, "{}", process::id()))
+            .and_then(|()| file.sync_all())
+            .map_err(|err| {
+                let errmsg = format!("failed to write {} since {}", path.display(), err);
+                Error::runtime(errmsg)
+            })?;
+        log::trace!("[Lock] lock {}", path.display());
+        Ok(Self { path, file })
+    }
+}
+
+impl Drop for DataDirLock {
+    fn drop(&mut self) {
+        if let Err(err) = self.file.unlock() {
+            log::warn!(
+                "[Lock] failed to unlock {} since {}",
+                self.path.display(),
+                err
+            );
+        }
+    }
+}
@@ -0,0 +1,408 @@
+use std::{
+    fmt::Write as _,
+    io::{Read as _, Write as _},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::atomic::{AtomicU64, Ordering},
+    thread, time,
+};
+
+use crate::error::{Error, Result};
+
+const PREFIX: &str = "ckb_txpool_fuzzer";
+
+const LATENCY_BUCKETS_COUNT: usize = 14;
+// The upper bounds of the latency buckets, in microseconds.
+const LATENCY_BUCKETS: [u64; LATENCY_BUCKETS_COUNT] = [
+    500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000, 250_000, 500_000, 1_000_000,
+    2_500_000, 5_000_000, 10_000_000,
+];
+
+// The quantiles of the latencies in the trace lines and the reports.
+pub(crate) const LATENCY_QUANTILES: [(&str, f64); 3] = [("p50", 0.5), ("p95", 0.95), ("p99", 0.99)];
+
+static METRICS: Metrics = Metrics::new();
+
+// The operations whose latencies are measured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Operation {
+    // Submit a transaction into the tx-pool.
+    Submit,
+    // Get a block template from the tx-pool.
+    Template,
+    // Update the tx-pool with a new block.
+    Reorg,
+    // Write the results into the storage.
+    StorageWrite,
+}
+
+pub(crate) struct Metrics {
+    // Counters.
+    txs_generated: AtomicU64,
+    txs_accepted: AtomicU64,
+    txs_rejected: AtomicU64,
+    oracle_mismatches: AtomicU64,
+    blocks_mined: AtomicU64,
+    generation_timeouts: AtomicU64,
+    // Gauges.
+    pool_pending: AtomicU64,
+    pool_proposed: AtomicU64,
+    live_cells: AtomicU64,
+    // Histograms, in the order of the operations.
+    latencies: [Histogram; Operation::COUNT],
+    // The latencies since the last block, in the order of the operations.
+    block_latencies: [Histogram; Operation::COUNT],
+    // The calls which are slower than it are warned, zero means never.
+    slow_call_micros: AtomicU64,
+}
+
+pub(crate) struct Histogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS_COUNT],
+    sum_micros: AtomicU64,
+    count: AtomicU64,
+}
+
+pub(crate) fn global() -> &'static Metrics {
+    &METRICS
+}
+
+// Serve the metrics in the Prometheus text format in a background thread.
+pub(crate) fn serve(listen: SocketAddr) -> Result<()> {
+    let listener = TcpListener::bind(listen).map_err(|err| {
+        let errmsg = format!("failed to listen metrics on {} since {}", listen, err);
+        Error::runtime(errmsg)
+    })?;
+    log::info!("[Metrics] listen on {}", listen);
+    thread::Builder::new()
+        .name("metrics".to_owned())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        if let Err(err) = respond(stream) {
+                            log::warn!("[Metrics] failed to respond since {}", err);
+                        }
+                    }
+                    Err(err) => {
+                        log::warn!("[Metrics] failed to accept since {}", err);
+                    }
+                }
+            }
+        })
+        .map_err(|err| {
+            let errmsg = format!("failed to spawn metrics thread since {}", err);
+            Error::runtime(errmsg)
+        })?;
+    Ok(())
+}
+
+fn respond(mut stream: TcpStream) -> std::io::Result<()> {
+    // Only the request head is required, the request itself is ignored.
+    let mut buffer = [0u8; 1024];
+    let _ = stream.read(&mut buffer)?;
+    let body = global().render();
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\n\
+        Content-Type: text/plain; version=0.0.4\r\n\
+        Content-Length: {}\r\n\
+        Connection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Self {
+            txs_generated: AtomicU64::new(0),
+            txs_accepted: AtomicU64::new(0),
+            txs_rejected: AtomicU64::new(0),
+            oracle_mismatches: AtomicU64::new(0),
+            blocks_mined: AtomicU64::new(0),
+            generation_timeouts: AtomicU64::new(0),
+            pool_pending: AtomicU64::new(0),
+            pool_proposed: AtomicU64::new(0),
+            live_cells: AtomicU64::new(0),
+            latencies: [
+                Histogram::new(),
+                Histogram::new(),
+                Histogram::new(),
+                Histogram::new(),
+            ],
+            block_latencies: [
+                Histogram::new(),
+                Histogram::new(),
+                Histogram::new(),
+                Histogram::new(),
+            ],
+            slow_call_micros: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn add_txs_generated(&self, count: usize) {
+        self.txs_generated
+            .fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn inc_txs_accepted(&self) {
+        self.txs_accepted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn inc_txs_rejected(&self) {
+        self.txs_rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn inc_oracle_mismatches(&self) {
+        self.oracle_mismatches.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn inc_blocks_mined(&self) {
+        self.blocks_mined.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn inc_generation_timeouts(&self) {
+        self.generation_timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn set_pool_sizes(&self, pending: usize, proposed: usize) {
+        self.pool_pending.store(pending as u64, Ordering::Relaxed);
+        self.pool_proposed.store(proposed as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn set_live_cells(&self, count: usize) {
+        self.live_cells.store(count as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn set_slow_call_threshold(&self, threshold: time::Duration) {
+        self.slow_call_micros
+            .store(threshold.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    // Returns whether the call is slower than the threshold, the callers should warn about it
+    // with the details.
+    pub(crate) fn observe_latency(&self, operation: Operation, elapsed: time::Duration) -> bool {
+        self.latencies[operation.index()].observe(elapsed);
+        self.block_latencies[operation.index()].observe(elapsed);
+        let threshold = self.slow_call_micros.load(Ordering::Relaxed);
+        threshold > 0 && elapsed.as_micros() as u64 > threshold
+    }
+
+    // The upper bounds of the latency buckets of the quantiles, in microseconds.
+    pub(crate) fn submit_latency_percentiles<const N: usize>(
+        &self,
+        quantiles: [f64; N],
+    ) -> Option<[u64; N]> {
+        self.latencies[Operation::Submit.index()].percentiles(quantiles)
+    }
+
+    // The percentiles of all operations which are observed since the start.
+    pub(crate) fn latency_percentiles(&self) -> Vec<(Operation, [u64; 3])> {
+        Operation::ALL
+            .iter()
+            .filter_map(|operation| {
+                self.latencies[operation.index()]
+                    .percentiles(LATENCY_QUANTILES.map(|(_, quantile)| quantile))
+                    .map(|percentiles| (*operation, percentiles))
+            })
+            .collect()
+    }
+
+    // The percentiles of all operations which are observed since the last call.
+    pub(crate) fn take_block_latency_percentiles(&self) -> Vec<(Operation, [u64; 3])> {
+        Operation::ALL
+            .iter()
+            .filter_map(|operation| {
+                let histogram = &self.block_latencies[operation.index()];
+                let percentiles =
+                    histogram.percentiles(LATENCY_QUANTILES.map(|(_, quantile)| quantile));
+                histogram.reset();
+                percentiles.map(|percentiles| (*operation, percentiles))
+            })
+            .collect()
+    }
+
+    fn render(&self) -> String {
+        let mut output = String::new();
+        let counters = [
+            (
+                "txs_generated",
+                "Transactions generated.",
+                &self.txs_generated,
+            ),
+            (
+                "txs_accepted",
+                "Transactions accepted by the tx-pool.",
+                &self.txs_accepted,
+            ),
+            (
+                "txs_rejected",
+                "Transactions rejected by the tx-pool.",
+                &self.txs_rejected,
+            ),
+            (
+                "oracle_mismatches",
+                "Submit results which are different from the expectations.",
+                &self.oracle_mismatches,
+            ),
+            ("blocks_mined", "Blocks mined.", &self.blocks_mined),
+            (
+                "generation_timeouts",
+                "Steps which reach the time limit of generating transactions.",
+                &self.generation_timeouts,
+            ),
+        ];
+        for (name, help, value) in counters {
+            render_single(&mut output, name, help, "counter", value);
+        }
+        let gauges = [
+            (
+                "pool_pending",
+                "Pending transactions in the tx-pool.",
+                &self.pool_pending,
+            ),
+            (
+                "pool_proposed",
+                "Proposed transactions in the tx-pool.",
+                &self.pool_proposed,
+            ),
+            ("live_cells", "Live cells in the storage.", &self.live_cells),
+        ];
+        for (name, help, value) in gauges {
+            render_single(&mut output, name, help, "gauge", value);
+        }
+        for operation in Operation::ALL {
+            let name = format!("{}_latency_seconds", operation.name());
+            self.latencies[operation.index()].render(&mut output, &name, operation.help());
+        }
+        output
+    }
+}
+
+impl Operation {
+    const COUNT: usize = 4;
+
+    pub(crate) const ALL: [Self; Self::COUNT] = [
+        Self::Submit,
+        Self::Template,
+        Self::Reorg,
+        Self::StorageWrite,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Submit => "submit",
+            Self::Template => "template",
+            Self::Reorg => "reorg",
+            Self::StorageWrite => "storage_write",
+        }
+    }
+
+    fn help(self) -> &'static str {
+        match self {
+            Self::Submit => "The latency of submitting a transaction to the tx-pool.",
+            Self::Template => "The latency of getting a block template from the tx-pool.",
+            Self::Reorg => "The latency of updating the tx-pool with a new block.",
+            Self::StorageWrite => "The latency of writing the results into the storage.",
+        }
+    }
+}
+
+impl Histogram {
+    const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: AtomicU64 = AtomicU64::new(0);
+        Self {
+            buckets: [ZERO; LATENCY_BUCKETS_COUNT],
+            sum_micros: AtomicU64::new(0),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    fn observe(&self, elapsed: time::Duration) {
+        let micros = elapsed.as_micros() as u64;
+        if let Some(index) = LATENCY_BUCKETS.iter().position(|bound| micros <= *bound) {
+            self.buckets[index].fetch_add(1, Ordering::Relaxed);
+        }
+        self.sum_micros.fetch_add(micros, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // The upper bound of the bucket which the quantile falls in, none if nothing is observed.
+    // The latencies which are greater than all bounds are counted into the last bucket.
+    fn percentile(&self, quantile: f64) -> Option<u64> {
+        let count = self.count.load(Ordering::Relaxed);
+        if count == 0 {
+            return None;
+        }
+        let rank = (count as f64 * quantile).ceil() as u64;
+        let mut cumulative = 0;
+        for (bound, bucket) in LATENCY_BUCKETS.iter().zip(self.buckets.iter()) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            if cumulative >= rank {
+                return Some(*bound);
+            }
+        }
+        LATENCY_BUCKETS.last().copied()
+    }
+
+    fn percentiles<const N: usize>(&self, quantiles: [f64; N]) -> Option<[u64; N]> {
+        let mut percentiles = [0; N];
+        for (percentile, quantile) in percentiles.iter_mut().zip(quantiles) {
+            *percentile = self.percentile(quantile)?;
+        }
+        Some(percentiles)
+    }
+
+    // The observations between the loads and the stores could be lost, it's fine for the
+    // per-block windows.
+    fn reset(&self) {
+        for bucket in &self.buckets {
+            bucket.store(0, Ordering::Relaxed);
+        }
+        self.sum_micros.store(0, Ordering::Relaxed);
+        self.count.store(0, Ordering::Relaxed);
+    }
+
+    fn render(&self, output: &mut String, name: &str, help: &str) {
+        let _ = writeln!(output, "# HELP {}_{} {}", PREFIX, name, help);
+        let _ = writeln!(output, "# TYPE {}_{} histogram", PREFIX, name);
+        let mut cumulative = 0;
+        for (bound, bucket) in LATENCY_BUCKETS.iter().zip(self.buckets.iter()) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(
+                output,
+                "{}_{}_bucket{{le=\"{}\"}} {}",
+                PREFIX,
+                name,
+                *bound as f64 / 1_000_000.0,
+                cumulative
+            );
+        }
+        let count = self.count.load(Ordering::Relaxed);
+        let sum = self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(
+            output,
+            "{}_{}_bucket{{le=\"+Inf\"}} {}",
+            PREFIX, name, count
+        );
+        let _ = writeln!(output, "{}_{}_sum {}", PREFIX, name, sum);
+        let _ = writeln!(output, "{}_{}_count {}", PREFIX, name, count);
+    }
+}
+
+fn render_single(output: &mut String, name: &str, help: &str, kind: &str, value: &AtomicU64) {
+    let _ = writeln!(output, "# HELP {}_{} {}", PREFIX, name, help);
+    let _ = writeln!(output, "# TYPE {}_{} {}", PREFIX, name, kind);
+    let _ = writeln!(
+        output,
+        "{}_{} {}",
+        PREFIX,
+        name,
+        value.load(Ordering::Relaxed)
+    );
+}
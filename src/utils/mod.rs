@@ -1,3 +1,6 @@
-pub(crate) mod ctrlc;
 pub(crate) mod faketime;
 pub(crate) mod fs;
+pub(crate) mod lock;
+pub(crate) mod metrics;
+pub(crate) mod rpc;
+pub(crate) mod signals;
@@ -0,0 +1,106 @@
+use std::{
+    io::{Read as _, Write as _},
+    net::{SocketAddr, TcpStream},
+    time,
+};
+
+use serde_json::{json, Value};
+
+use crate::error::{Error, Result};
+
+// Long enough for a node to verify a full block.
+const RPC_TIMEOUT: time::Duration = time::Duration::from_secs(60);
+
+// A minimal JSON-RPC client over HTTP/1.1, each call opens a new connection.
+pub(crate) struct RpcClient {
+    addr: SocketAddr,
+    next_id: u64,
+}
+
+impl RpcClient {
+    pub(crate) fn new(addr: SocketAddr) -> Self {
+        Self { addr, next_id: 0 }
+    }
+
+    pub(crate) fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    // Returns the result of the call, or the error which the node responds as an error.
+    pub(crate) fn call(&mut self, method: &str, params: Value) -> Result<Value> {
+        self.next_id += 1;
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": self.next_id,
+            "method": method,
+            "params": params,
+        })
+        .to_string();
+        let response = self.post(&request).map_err(|err| {
+            let errmsg = format!("failed to call {} on {} since {}", method, self.addr, err);
+            Error::runtime(errmsg)
+        })?;
+        let mut response: Value = serde_json::from_str(&response).map_err(|err| {
+            let errmsg = format!("failed to parse the response of {} since {}", method, err);
+            Error::runtime(errmsg)
+        })?;
+        if let Some(error) = response.get("error") {
+            let errmsg = format!("{} is failed since {}", method, error);
+            return Err(Error::runtime(errmsg));
+        }
+        Ok(response
+            .get_mut("result")
+            .map(Value::take)
+            .unwrap_or(Value::Null))
+    }
+
+    fn post(&self, request: &str) -> std::io::Result<String> {
+        let mut stream = TcpStream::connect_timeout(&self.addr, RPC_TIMEOUT)?;
+        stream.set_read_timeout(Some(RPC_TIMEOUT))?;
+        write!(
+            stream,
+            "POST / HTTP/1.1\r\n\
+            Host: {}\r\n\
+            Content-Type: application/json\r\n\
+            Content-Length: {}\r\n\
+            Connection: close\r\n\r\n{}",
+            self.addr,
+            request.len(),
+            request
+        )?;
+        stream.flush()?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response)?;
+        let invalid = |errmsg: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, errmsg);
+        let head_len = response
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .ok_or_else(|| invalid("no HTTP body"))?;
+        let head = String::from_utf8_lossy(&response[..head_len]).to_ascii_lowercase();
+        let body = &response[head_len + 4..];
+        let body = if head.contains("transfer-encoding: chunked") {
+            decode_chunked(body)
+        } else {
+            body.to_vec()
+        };
+        String::from_utf8(body).map_err(|_| invalid("the body is not UTF-8"))
+    }
+}
+
+// Join the chunks of a chunked body, each chunk is prefixed with its size in hex.
+fn decode_chunked(mut body: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::new();
+    while let Some(line_len) = body.windows(2).position(|window| window == b"\r\n") {
+        let size = std::str::from_utf8(&body[..line_len])
+            .ok()
+            .and_then(|size| usize::from_str_radix(size.trim(), 16).ok())
+            .unwrap_or(0);
+        let rest = &body[line_len + 2..];
+        if size == 0 || rest.len() < size {
+            break;
+        }
+        decoded.extend_from_slice(&rest[..size]);
+        body = rest[size..].strip_prefix(b"\r\n").unwrap_or(&rest[size..]);
+    }
+    decoded
+}
@@ -0,0 +1,66 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use once_cell::sync::OnceCell;
+
+use crate::error::{Error, Result};
+
+// The handlers could only be installed once in a process, so all captures share the flags.
+static CAPTURED: OnceCell<Signals> = OnceCell::new();
+
+// The flags which are set by the signals, they are checked between the steps, so the tx-pool
+// and the storage are always saved before exit.
+#[derive(Clone, Default)]
+pub(crate) struct Signals {
+    // Set by Ctrl-C (SIGINT), and SIGTERM for the containers and CI.
+    shutdown: Arc<AtomicBool>,
+    // Set by SIGHUP, to dump the states without stopping.
+    dump: Arc<AtomicBool>,
+}
+
+impl Signals {
+    // Install the handlers at the first time, and clear the flags which are set before, so each
+    // run only sees the signals during it.
+    pub(crate) fn capture() -> Result<Self> {
+        let signals = CAPTURED.get_or_try_init(Self::install)?.clone();
+        signals.shutdown.store(false, Ordering::SeqCst);
+        signals.dump.store(false, Ordering::SeqCst);
+        Ok(signals)
+    }
+
+    fn install() -> Result<Self> {
+        let signals = Self::default();
+        let shutdown = Arc::clone(&signals.shutdown);
+        ctrlc::set_handler(move || {
+            shutdown.store(true, Ordering::SeqCst);
+        })
+        .map_err(|err| {
+            let errmsg = format!("failed to set Ctrl-C handler since {}", err);
+            Error::runtime(errmsg)
+        })?;
+        #[cfg(unix)]
+        {
+            use signal_hook::{consts, flag};
+            flag::register(consts::SIGTERM, Arc::clone(&signals.shutdown)).map_err(|err| {
+                let errmsg = format!("failed to set SIGTERM handler since {}", err);
+                Error::runtime(errmsg)
+            })?;
+            flag::register(consts::SIGHUP, Arc::clone(&signals.dump)).map_err(|err| {
+                let errmsg = format!("failed to set SIGHUP handler since {}", err);
+                Error::runtime(errmsg)
+            })?;
+        }
+        Ok(signals)
+    }
+
+    pub(crate) fn is_shutdown(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+
+    // Whether a dump is requested since the last call.
+    pub(crate) fn take_dump(&self) -> bool {
+        self.dump.swap(false, Ordering::SeqCst)
+    }
+}
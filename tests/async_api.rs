@@ -0,0 +1,11 @@
+#![cfg(feature = "async-api")]
+
+// The steppers are moved into the blocking threads of the tokio runtimes, so it fails to build if
+// anything in them is not sendable.
+#[test]
+fn steppers_are_sendable() {
+    fn assert_send<T: Send>() {}
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send::<ckb_txpool_fuzzer::Stepper>();
+    assert_send_sync::<ckb_txpool_fuzzer::AsyncStepper>();
+}